@@ -1,9 +1,20 @@
+use std::time::Instant;
+
+use crate::events::EventHandler;
+use crate::input::InputState;
 use crate::layers::Layer;
 use crate::logger;
+use crate::renderer::capture::FrameCapture;
+use crate::renderer::debug::set_object_label;
+use crate::renderer::debug_draw::{self, DebugRenderer};
 use crate::renderer::shader::{OpenGLShader, Shader};
+use crate::renderer::{profiler, state};
 use crate::window::{ApplicationWindow, Window, WindowProps};
-use crate::{events::Event, renderer::context::RenderingContext};
-use crate::{log_info, log_trace};
+use crate::{
+    events::Event,
+    renderer::context::{ContextConfig, GpuInfo, RenderingContext},
+};
+use crate::{gl_check, log_info, log_trace};
 
 static VERTICES: [f32; 6] = [-0.5, -0.5, 0.5, -0.5, 0.0, 0.5];
 
@@ -27,26 +38,43 @@ static FRAGMENT_SHADER: &'static str = "
     }
 ";
 
+// Fields are dropped in declaration order, the ones owning GL objects have to
+// come before `window`, which destroys the context.
 pub struct Application {
     is_initialized: bool,
     is_running: bool,
     event_queue: Vec<Event>,
     layer_stack: Vec<Box<dyn Layer>>,
+    frame_capture: Option<FrameCapture>,
+    debug_renderer: Option<DebugRenderer>,
     window: ApplicationWindow,
+    input: InputState,
+    gpu_info: Option<GpuInfo>,
 }
 
 impl Application {
     pub fn new() -> Self {
+        Self::with_window_props(WindowProps {
+            title: "Simulacra".to_string(),
+            width: 1024,
+            height: 728,
+            context: ContextConfig::default(),
+        })
+    }
+
+    // the context config has to be known up front, it is fixed once the
+    // window exists
+    pub fn with_window_props(props: WindowProps) -> Self {
         Self {
             is_initialized: false,
             is_running: false,
             event_queue: Vec::new(),
             layer_stack: Vec::new(),
-            window: ApplicationWindow::new(WindowProps {
-                title: "Simulacra".to_string(),
-                width: 1024,
-                height: 728,
-            }),
+            frame_capture: None,
+            debug_renderer: None,
+            window: ApplicationWindow::new(props),
+            input: InputState::default(),
+            gpu_info: None,
         }
     }
 
@@ -54,7 +82,9 @@ impl Application {
         logger::init();
         log_info!("App Starting");
 
-        self.window.init();
+        self.gpu_info = Some(self.window.init());
+        self.frame_capture = Some(FrameCapture::new());
+        self.debug_renderer = Some(DebugRenderer::new());
 
         self.is_initialized = true;
         self.is_running = true;
@@ -66,32 +96,97 @@ impl Application {
         }
 
         let (vao, _) = gl_test_setup();
+        let mut last_frame = Instant::now();
 
         while self.is_running {
+            let now = Instant::now();
+            let dt = (now - last_frame).as_secs_f32();
+            last_frame = now;
+            profiler::begin_frame();
+
             unsafe {
                 gl::ClearColor(0.1, 0.1, 0.1, 1.0);
                 gl::Clear(gl::COLOR_BUFFER_BIT);
 
-                gl::BindVertexArray(vao);
+                state::bind_vertex_array(vao);
                 gl::DrawArrays(gl::TRIANGLES, 0, 3);
             }
+            profiler::record_draw(gl::TRIANGLES, 3, 1);
+
+            for layer in self.layer_stack.iter_mut() {
+                layer.on_update(dt, &self.input);
+            }
+
+            let (width, height) = self.window.get_framebuffer_size();
+            if let Some(debug_renderer) = &mut self.debug_renderer {
+                unsafe {
+                    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                }
+                state::set_viewport(0, 0, width as i32, height as i32);
+                debug_renderer.render(&debug_draw::get_view_projection(), width, height);
+                debug_renderer.end_frame(dt);
+            }
 
-            for layer in self.layer_stack.iter_mut().rev() {
-                (*layer).handle_events(&mut self.event_queue);
+            if let Some(capture) = &mut self.frame_capture {
+                capture.capture_frame(width, height);
             }
 
+            profiler::end_frame();
             self.window.on_update(&mut self.event_queue);
             self.handle_events();
         }
     }
 
+    pub fn push_layer(&mut self, mut layer: Box<dyn Layer>) {
+        layer.on_attach();
+        self.layer_stack.push(layer);
+    }
+
+    pub fn pop_layer(&mut self) -> Option<Box<dyn Layer>> {
+        let mut layer = self.layer_stack.pop()?;
+        layer.on_detach();
+        Some(layer)
+    }
+
+    pub fn get_input(&self) -> &InputState {
+        &self.input
+    }
+
+    // available after `init`
+    pub fn get_gpu_info(&self) -> Option<&GpuInfo> {
+        self.gpu_info.as_ref()
+    }
+
+    pub fn get_frame_capture(&mut self) -> Option<&mut FrameCapture> {
+        self.frame_capture.as_mut()
+    }
+
+    // draws the `debug_draw` queue each frame, available after `init`
+    pub fn get_debug_renderer(&mut self) -> Option<&mut DebugRenderer> {
+        self.debug_renderer.as_mut()
+    }
+
+    // dispatches the events polled by the window, the top layer first
     fn handle_events(&mut self) {
+        self.input.handle_events(&mut self.event_queue);
+
+        for layer in self.layer_stack.iter_mut().rev() {
+            layer.handle_events(&mut self.event_queue);
+        }
+
+        if let Some(capture) = &mut self.frame_capture {
+            capture.handle_events(&mut self.event_queue);
+        }
+
         for e in self.event_queue.iter() {
             match e {
                 Event::WindowClose => {
                     log_info!("App Stopping");
                     self.is_running = false;
                 }
+                Event::FramebufferResize { width, height } => {
+                    state::set_viewport(0, 0, *width as i32, *height as i32);
+                }
                 _ => (),
             };
         }
@@ -108,44 +203,47 @@ pub fn gl_test_setup() -> (u32, u32) {
     let mut vbo = 0;
 
     let shader = OpenGLShader::new(VERTEX_SHADER, FRAGMENT_SHADER);
+    shader.set_label("Test triangle");
 
     unsafe {
         // Create Vertex Array Object
-        gl::GenVertexArrays(1, &mut vao);
-        gl::BindVertexArray(vao);
+        gl_check!(gl::GenVertexArrays(1, &mut vao));
+        gl_check!(state::bind_vertex_array(vao));
+        set_object_label(gl::VERTEX_ARRAY, vao, "Test triangle");
 
         // Create a Vertex Buffer Object and copy the vertex data to it
-        gl::GenBuffers(1, &mut vbo);
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(
+        gl_check!(gl::GenBuffers(1, &mut vbo));
+        gl_check!(state::bind_buffer(gl::ARRAY_BUFFER, vbo));
+        set_object_label(gl::BUFFER, vbo, "Test triangle vertices");
+        gl_check!(gl::BufferData(
             gl::ARRAY_BUFFER,
             (VERTICES.len() * mem::size_of::<GLfloat>()) as GLsizeiptr,
             mem::transmute(&VERTICES[0]),
             gl::STATIC_DRAW,
-        );
+        ));
 
         // Use shader program
         shader.bind();
-        gl::BindFragDataLocation(
+        gl_check!(gl::BindFragDataLocation(
             shader.get_program_id(),
             0,
             CString::new("color").unwrap().as_ptr(),
-        );
+        ));
 
         // Specify the layout of the vertex data
-        let pos_attr = gl::GetAttribLocation(
+        let pos_attr = gl_check!(gl::GetAttribLocation(
             shader.get_program_id(),
             CString::new("position").unwrap().as_ptr(),
-        );
-        gl::EnableVertexAttribArray(pos_attr as GLuint);
-        gl::VertexAttribPointer(
+        ));
+        gl_check!(gl::EnableVertexAttribArray(pos_attr as GLuint));
+        gl_check!(gl::VertexAttribPointer(
             pos_attr as GLuint,
             2,
             gl::FLOAT,
             gl::FALSE as GLboolean,
             0,
             ptr::null(),
-        );
+        ));
     }
 
     (vao, vbo)
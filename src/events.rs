@@ -17,7 +17,10 @@ impl Category {
 #[derive(Debug)]
 pub enum Event {
     WindowClose,
+    // window size in screen coordinates, the unit of mouse positions
     WindowResize { width: u32, height: u32 },
+    // default framebuffer size in pixels, differs on high DPI displays
+    FramebufferResize { width: u32, height: u32 },
     WindowFocus,
     WindowLostFocus,
     WindowMoved { x: u32, y: u32 },
@@ -28,8 +31,8 @@ pub enum Event {
     KeyReleased { key: Key },
     MouseButtonPressed { btn: MouseButton },
     MouseButtonReleased { btn: MouseButton },
-    MouseMoved { x: u64, y: u64 },
-    MouseScrolled { x: u64, y: u64 },
+    MouseMoved { x: f64, y: f64 },
+    MouseScrolled { x: f64, y: f64 },
 }
 
 impl Event {
@@ -37,6 +40,7 @@ impl Event {
         match &self {
             Event::WindowClose => Category::APPLICATION | Category::INPUT,
             Event::WindowResize { .. } => Category::APPLICATION | Category::INPUT,
+            Event::FramebufferResize { .. } => Category::APPLICATION | Category::INPUT,
             Event::WindowFocus => Category::APPLICATION | Category::INPUT,
             Event::WindowLostFocus => Category::APPLICATION | Category::INPUT,
             Event::WindowMoved { .. } => Category::APPLICATION | Category::INPUT,
@@ -60,7 +64,7 @@ impl Event {
 pub trait EventHandler {
     fn handle_events(&mut self, events: &mut Vec<Event>);
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Key {
     Space,
     Apostrophe,
@@ -184,7 +188,7 @@ pub enum Key {
     Menu,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MouseButton {
     Left,
     Right,
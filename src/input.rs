@@ -1,5 +1,77 @@
-use crate::application::Application;
+use std::collections::HashSet;
 
-pub trait InputHandler {}
+use crate::{
+    application::Application,
+    events::{Event, EventHandler, Key, MouseButton},
+};
 
-impl InputHandler for Application {}
+pub trait InputHandler {
+    fn is_key_pressed(&self, key: Key) -> bool;
+    fn is_mouse_button_pressed(&self, btn: MouseButton) -> bool;
+    // window coordinates, origin at the top left
+    fn get_mouse_position(&self) -> (f64, f64);
+}
+
+// Input state tracked from the event queue.
+#[derive(Debug, Default)]
+pub struct InputState {
+    pressed_keys: HashSet<Key>,
+    pressed_buttons: HashSet<MouseButton>,
+    mouse_position: (f64, f64),
+}
+
+impl EventHandler for InputState {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        for e in events.iter() {
+            match e {
+                Event::KeyPressed { key } => {
+                    self.pressed_keys.insert(*key);
+                }
+                Event::KeyReleased { key } => {
+                    self.pressed_keys.remove(key);
+                }
+                Event::MouseButtonPressed { btn } => {
+                    self.pressed_buttons.insert(*btn);
+                }
+                Event::MouseButtonReleased { btn } => {
+                    self.pressed_buttons.remove(btn);
+                }
+                Event::MouseMoved { x, y } => self.mouse_position = (*x, *y),
+                // releases are not reported to unfocused windows
+                Event::WindowLostFocus => {
+                    self.pressed_keys.clear();
+                    self.pressed_buttons.clear();
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl InputHandler for InputState {
+    fn is_key_pressed(&self, key: Key) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    fn is_mouse_button_pressed(&self, btn: MouseButton) -> bool {
+        self.pressed_buttons.contains(&btn)
+    }
+
+    fn get_mouse_position(&self) -> (f64, f64) {
+        self.mouse_position
+    }
+}
+
+impl InputHandler for Application {
+    fn is_key_pressed(&self, key: Key) -> bool {
+        self.get_input().is_key_pressed(key)
+    }
+
+    fn is_mouse_button_pressed(&self, btn: MouseButton) -> bool {
+        self.get_input().is_mouse_button_pressed(btn)
+    }
+
+    fn get_mouse_position(&self) -> (f64, f64) {
+        self.get_input().get_mouse_position()
+    }
+}
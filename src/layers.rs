@@ -1,6 +1,10 @@
-use crate::events::EventHandler;
+use crate::{events::EventHandler, input::InputHandler};
 
 pub trait Layer: EventHandler {
     fn on_attach(&mut self);
     fn on_detach(&mut self);
+
+    // called once per frame, `dt` in seconds since the previous frame, after
+    // the events of the previous frame were handled
+    fn on_update(&mut self, _dt: f32, _input: &dyn InputHandler) {}
 }
@@ -6,6 +6,7 @@ mod events;
 mod input;
 mod layers;
 mod logger;
+mod math;
 mod renderer;
 mod window;
 
@@ -0,0 +1,11 @@
+mod geometry;
+mod matrix;
+mod quat;
+mod transform;
+mod vector;
+
+pub use geometry::{Aabb, Frustum, Ray};
+pub use matrix::{Mat3, Mat4};
+pub use quat::Quat;
+pub use transform::Transform;
+pub use vector::{Vec2, Vec3, Vec4};
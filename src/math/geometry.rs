@@ -0,0 +1,445 @@
+use crate::math::{Mat4, Vec3, Vec4};
+
+// Axis-aligned bounding box, empty while `min` > `max`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3 {
+            x: f32::INFINITY,
+            y: f32::INFINITY,
+            z: f32::INFINITY,
+        },
+        max: Vec3 {
+            x: f32::NEG_INFINITY,
+            y: f32::NEG_INFINITY,
+            z: f32::NEG_INFINITY,
+        },
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_center_half_extents(center: Vec3, half_extents: Vec3) -> Self {
+        Self::new(center - half_extents, center + half_extents)
+    }
+
+    pub fn from_points<I: IntoIterator<Item = Vec3>>(points: I) -> Self {
+        points.into_iter().fold(Self::EMPTY, |b, p| b.including(p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn get_center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn get_half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    pub fn get_size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn get_corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z),
+            Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z),
+            Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z),
+            Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z),
+            Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+
+    pub fn including(&self, p: Vec3) -> Self {
+        Self::new(self.min.min(p), self.max.max(p))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn contains_point(&self, p: Vec3) -> bool {
+        p.x >= self.min.x
+            && p.x <= self.max.x
+            && p.y >= self.min.y
+            && p.y <= self.max.y
+            && p.z >= self.min.z
+            && p.z <= self.max.z
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    // bounds of the transformed box
+    pub fn transformed(&self, m: &Mat4) -> Self {
+        if self.is_empty() {
+            return *self;
+        }
+        Self::from_points(self.get_corners().iter().map(|c| m.transform_point(*c)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    // normalized, so hit distances are in world units
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, direction: Vec3) -> Self {
+        Self {
+            origin,
+            direction: direction.normalize(),
+        }
+    }
+
+    // ray through a point given in normalized device coordinates
+    pub fn from_ndc(x: f32, y: f32, inverse_view_projection: &Mat4) -> Self {
+        let near = inverse_view_projection.transform_point(Vec3::new(x, y, -1.0));
+        let far = inverse_view_projection.transform_point(Vec3::new(x, y, 1.0));
+        Self::new(near, far - near)
+    }
+
+    pub fn at(&self, t: f32) -> Vec3 {
+        self.origin + self.direction * t
+    }
+
+    // distance to the entry point, zero when starting inside
+    pub fn intersect_aabb(&self, aabb: &Aabb) -> Option<f32> {
+        let mut t_min = 0.0f32;
+        let mut t_max = f32::INFINITY;
+
+        for axis in 0..3 {
+            let inv = 1.0 / self.direction[axis];
+            let mut t0 = (aabb.min[axis] - self.origin[axis]) * inv;
+            let mut t1 = (aabb.max[axis] - self.origin[axis]) * inv;
+            if inv < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // NaN from 0 * inf when parallel and on a slab boundary is ignored
+            if t0.is_nan() || t1.is_nan() {
+                continue;
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max < t_min {
+                return None;
+            }
+        }
+
+        Some(t_min)
+    }
+
+    pub fn intersect_plane(&self, plane: &Plane) -> Option<f32> {
+        let denom = plane.normal.dot(self.direction);
+        if denom.abs() < 1e-6 {
+            return None;
+        }
+        let t = -plane.signed_distance(self.origin) / denom;
+        if t >= 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    pub fn intersect_sphere(&self, center: Vec3, radius: f32) -> Option<f32> {
+        let oc = self.origin - center;
+        let b = oc.dot(self.direction);
+        let c = oc.dot(oc) - radius * radius;
+        let discriminant = b * b - c;
+        if discriminant < 0.0 {
+            return None;
+        }
+
+        let sqrt_d = discriminant.sqrt();
+        let t = if -b - sqrt_d >= 0.0 {
+            -b - sqrt_d
+        } else {
+            -b + sqrt_d
+        };
+        if t >= 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    // Moller-Trumbore, both windings hit
+    pub fn intersect_triangle(&self, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+        let e1 = b - a;
+        let e2 = c - a;
+        let p = self.direction.cross(e2);
+        let det = e1.dot(p);
+        if det.abs() < 1e-8 {
+            return None;
+        }
+
+        let inv_det = 1.0 / det;
+        let s = self.origin - a;
+        let u = s.dot(p) * inv_det;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+
+        let q = s.cross(e1);
+        let v = self.direction.dot(q) * inv_det;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+
+        let t = e2.dot(q) * inv_det;
+        if t >= 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+// Planes of a view volume, normals point inwards.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    // left, right, bottom, top, near, far
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    // Gribb-Hartmann extraction from a view-projection matrix
+    pub fn from_view_projection(m: &Mat4) -> Self {
+        let row = |i: usize| Vec4::new(m.cols[0][i], m.cols[1][i], m.cols[2][i], m.cols[3][i]);
+        let (r0, r1, r2, r3) = (row(0), row(1), row(2), row(3));
+        let plane = |v: Vec4| Plane::new(v.truncate(), v.w).normalize();
+
+        Self {
+            planes: [
+                plane(r3 + r0),
+                plane(r3 - r0),
+                plane(r3 + r1),
+                plane(r3 - r1),
+                plane(r3 + r2),
+                plane(r3 - r2),
+            ],
+        }
+    }
+
+    pub fn contains_point(&self, p: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(p) >= 0.0)
+    }
+
+    // conservative, boxes near the corners may be reported as visible
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|plane| {
+            // corner furthest along the normal
+            let p = Vec3::new(
+                if plane.normal.x >= 0.0 {
+                    aabb.max.x
+                } else {
+                    aabb.min.x
+                },
+                if plane.normal.y >= 0.0 {
+                    aabb.max.y
+                } else {
+                    aabb.min.y
+                },
+                if plane.normal.z >= 0.0 {
+                    aabb.max.z
+                } else {
+                    aabb.min.z
+                },
+            );
+            plane.signed_distance(p) >= 0.0
+        })
+    }
+
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.signed_distance(center) >= -radius)
+    }
+}
+
+// Points p on the plane satisfy normal.dot(p) + d == 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub d: f32,
+}
+
+impl Plane {
+    pub fn new(normal: Vec3, d: f32) -> Self {
+        Self { normal, d }
+    }
+
+    pub fn from_point_normal(point: Vec3, normal: Vec3) -> Self {
+        let normal = normal.normalize();
+        Self::new(normal, -normal.dot(point))
+    }
+
+    // counter-clockwise points face the normal
+    pub fn from_points(a: Vec3, b: Vec3, c: Vec3) -> Self {
+        Self::from_point_normal(a, (b - a).cross(c - a))
+    }
+
+    // rescales so that the normal has unit length
+    pub fn normalize(&self) -> Self {
+        let len = self.normal.length();
+        if len > 0.0 {
+            Self::new(self.normal / len, self.d / len)
+        } else {
+            *self
+        }
+    }
+
+    // positive on the side the normal points to
+    pub fn signed_distance(&self, p: Vec3) -> f32 {
+        self.normal.dot(p) + self.d
+    }
+
+    pub fn project_point(&self, p: Vec3) -> Vec3 {
+        p - self.normal * self.signed_distance(p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vec3::splat(-1.0), Vec3::ONE)
+    }
+
+    #[test]
+    fn frustum_from_orthographic() {
+        let frustum =
+            Frustum::from_view_projection(&Mat4::orthographic(-1.0, 1.0, -1.0, 1.0, 0.0, 10.0));
+
+        assert!(frustum.contains_point(Vec3::new(0.5, -0.5, -5.0)));
+        assert!(!frustum.contains_point(Vec3::new(2.0, 0.0, -5.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -11.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn frustum_from_perspective() {
+        let view_projection = Mat4::perspective(90f32.to_radians(), 1.0, 0.1, 100.0)
+            * Mat4::look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let frustum = Frustum::from_view_projection(&view_projection);
+
+        for plane in frustum.planes.iter() {
+            assert!((plane.normal.length() - 1.0).abs() < 1e-5);
+        }
+        assert!(frustum.intersects_aabb(&unit_box()));
+        assert!(frustum.intersects_sphere(Vec3::ZERO, 0.5));
+        // behind the camera
+        assert!(!frustum.intersects_aabb(&Aabb::from_center_half_extents(
+            Vec3::new(0.0, 0.0, 10.0),
+            Vec3::ONE
+        )));
+        // outside the 90 degree cone at the target's depth
+        assert!(!frustum.intersects_sphere(Vec3::new(20.0, 0.0, 0.0), 1.0));
+    }
+
+    #[test]
+    fn ray_hits_aabb() {
+        let ray = Ray::new(Vec3::new(-5.0, 0.0, 0.0), Vec3::X);
+        assert_eq!(ray.intersect_aabb(&unit_box()), Some(4.0));
+
+        let inside = Ray::new(Vec3::ZERO, Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(inside.intersect_aabb(&unit_box()), Some(0.0));
+
+        let away = Ray::new(Vec3::new(-5.0, 0.0, 0.0), -Vec3::X);
+        assert_eq!(away.intersect_aabb(&unit_box()), None);
+
+        // parallel to a slab and outside of it
+        let parallel = Ray::new(Vec3::new(-5.0, 2.0, 0.0), Vec3::X);
+        assert_eq!(parallel.intersect_aabb(&unit_box()), None);
+    }
+
+    #[test]
+    fn ray_hits_plane() {
+        let plane = Plane::from_point_normal(Vec3::new(0.0, 2.0, 0.0), Vec3::Y);
+
+        let down = Ray::new(Vec3::new(1.0, 5.0, 1.0), -Vec3::Y);
+        assert_eq!(down.intersect_plane(&plane), Some(3.0));
+
+        let up = Ray::new(Vec3::new(1.0, 5.0, 1.0), Vec3::Y);
+        assert_eq!(up.intersect_plane(&plane), None);
+
+        let along = Ray::new(Vec3::new(1.0, 5.0, 1.0), Vec3::X);
+        assert_eq!(along.intersect_plane(&plane), None);
+    }
+
+    #[test]
+    fn ray_hits_sphere() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 10.0), -Vec3::Z);
+        assert_eq!(ray.intersect_sphere(Vec3::ZERO, 2.0), Some(8.0));
+
+        // starting inside gives the exit point
+        let inside = Ray::new(Vec3::ZERO, -Vec3::Z);
+        assert_eq!(inside.intersect_sphere(Vec3::ZERO, 2.0), Some(2.0));
+
+        let miss = Ray::new(Vec3::new(3.0, 0.0, 10.0), -Vec3::Z);
+        assert_eq!(miss.intersect_sphere(Vec3::ZERO, 2.0), None);
+    }
+
+    #[test]
+    fn ray_hits_triangle_from_both_sides() {
+        let (a, b, c) = (
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+
+        let front = Ray::new(Vec3::new(0.0, 0.0, 3.0), -Vec3::Z);
+        assert_eq!(front.intersect_triangle(a, b, c), Some(3.0));
+
+        let back = Ray::new(Vec3::new(0.0, 0.0, -2.0), Vec3::Z);
+        assert_eq!(back.intersect_triangle(a, b, c), Some(2.0));
+
+        let miss = Ray::new(Vec3::new(1.0, 1.0, 3.0), -Vec3::Z);
+        assert_eq!(miss.intersect_triangle(a, b, c), None);
+    }
+
+    #[test]
+    fn ray_from_ndc_center_looks_forward() {
+        let view_projection = Mat4::perspective(1.0, 1.0, 0.1, 100.0)
+            * Mat4::look_at(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let ray = Ray::from_ndc(0.0, 0.0, &view_projection.inverse().unwrap());
+
+        assert!((ray.direction - -Vec3::Z).length() < 1e-4);
+        assert!((ray.origin - Vec3::new(0.0, 0.0, 4.9)).length() < 1e-3);
+    }
+
+    #[test]
+    fn transformed_aabb_bounds_corners() {
+        let aabb = unit_box().transformed(&Mat4::rotation_z(45f32.to_radians()));
+        let extent = 2f32.sqrt();
+
+        assert!((aabb.max.x - extent).abs() < 1e-5);
+        assert!((aabb.min.y + extent).abs() < 1e-5);
+        assert!(Aabb::EMPTY.transformed(&Mat4::IDENTITY).is_empty());
+    }
+}
@@ -0,0 +1,472 @@
+use std::ops::Mul;
+
+use crate::math::{Quat, Vec3, Vec4};
+
+// Column-major like OpenGL, `cols[c][r]` is the element at row r, column c.
+// Vectors are columns and transforms compose right to left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat3 {
+    pub cols: [[f32; 3]; 3],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub cols: [[f32; 4]; 4],
+}
+
+impl Mat3 {
+    pub const IDENTITY: Mat3 = Mat3 {
+        cols: [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    };
+
+    pub fn from_cols(x: Vec3, y: Vec3, z: Vec3) -> Self {
+        Self {
+            cols: [x.to_array(), y.to_array(), z.to_array()],
+        }
+    }
+
+    pub fn from_quat(q: Quat) -> Self {
+        Self::from_cols(q * Vec3::X, q * Vec3::Y, q * Vec3::Z)
+    }
+
+    pub fn scale(s: Vec3) -> Self {
+        Self {
+            cols: [[s.x, 0.0, 0.0], [0.0, s.y, 0.0], [0.0, 0.0, s.z]],
+        }
+    }
+
+    // upper left 3x3 block
+    pub fn from_mat4(m: &Mat4) -> Self {
+        let c = &m.cols;
+        Self {
+            cols: [
+                [c[0][0], c[0][1], c[0][2]],
+                [c[1][0], c[1][1], c[1][2]],
+                [c[2][0], c[2][1], c[2][2]],
+            ],
+        }
+    }
+
+    // inverse transpose of the upper left block, transforms normals
+    pub fn normal_matrix(m: &Mat4) -> Self {
+        Self::from_mat4(m)
+            .inverse()
+            .map(|inv| inv.transpose())
+            .unwrap_or(Self::IDENTITY)
+    }
+
+    pub fn get_col(&self, index: usize) -> Vec3 {
+        Vec3::from(self.cols[index])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = Self::IDENTITY;
+        for c in 0..3 {
+            for r in 0..3 {
+                m.cols[c][r] = self.cols[r][c];
+            }
+        }
+        m
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.get_col(0).dot(self.get_col(1).cross(self.get_col(2)))
+    }
+
+    pub fn inverse(&self) -> Option<Self> {
+        let (x, y, z) = (self.get_col(0), self.get_col(1), self.get_col(2));
+        let det = self.determinant();
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+
+        // rows of the inverse are the cross products of the columns
+        let rows = Self::from_cols(y.cross(z) / det, z.cross(x) / det, x.cross(y) / det);
+        Some(rows.transpose())
+    }
+
+    pub fn as_ptr(&self) -> *const f32 {
+        self.cols[0].as_ptr()
+    }
+}
+
+impl Default for Mat3 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, rhs: Mat3) -> Mat3 {
+        let mut out = [[0.0; 3]; 3];
+        for (c, col) in out.iter_mut().enumerate() {
+            for (r, value) in col.iter_mut().enumerate() {
+                *value = (0..3).map(|k| self.cols[k][r] * rhs.cols[c][k]).sum();
+            }
+        }
+        Mat3 { cols: out }
+    }
+}
+
+impl Mul<Vec3> for Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.get_col(0) * v.x + self.get_col(1) * v.y + self.get_col(2) * v.z
+    }
+}
+
+impl Mat4 {
+    pub const IDENTITY: Mat4 = Mat4 {
+        cols: [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    };
+
+    pub fn from_cols(cols: [[f32; 4]; 4]) -> Self {
+        Self { cols }
+    }
+
+    pub fn translation(t: Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        m.cols[3] = [t.x, t.y, t.z, 1.0];
+        m
+    }
+
+    pub fn scale(s: Vec3) -> Self {
+        let mut m = Self::IDENTITY;
+        m.cols[0][0] = s.x;
+        m.cols[1][1] = s.y;
+        m.cols[2][2] = s.z;
+        m
+    }
+
+    pub fn rotation_x(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        let mut m = Self::IDENTITY;
+        m.cols[1] = [0.0, c, s, 0.0];
+        m.cols[2] = [0.0, -s, c, 0.0];
+        m
+    }
+
+    pub fn rotation_y(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        let mut m = Self::IDENTITY;
+        m.cols[0] = [c, 0.0, -s, 0.0];
+        m.cols[2] = [s, 0.0, c, 0.0];
+        m
+    }
+
+    pub fn rotation_z(angle: f32) -> Self {
+        let (s, c) = angle.sin_cos();
+        let mut m = Self::IDENTITY;
+        m.cols[0] = [c, s, 0.0, 0.0];
+        m.cols[1] = [-s, c, 0.0, 0.0];
+        m
+    }
+
+    pub fn from_quat(q: Quat) -> Self {
+        Self::from_mat3(&Mat3::from_quat(q))
+    }
+
+    pub fn from_mat3(m: &Mat3) -> Self {
+        let c = &m.cols;
+        Self {
+            cols: [
+                [c[0][0], c[0][1], c[0][2], 0.0],
+                [c[1][0], c[1][1], c[1][2], 0.0],
+                [c[2][0], c[2][1], c[2][2], 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    // scale first, then rotation, then translation
+    pub fn from_scale_rotation_translation(scale: Vec3, rotation: Quat, translation: Vec3) -> Self {
+        let r = Mat3::from_quat(rotation);
+        let x = r.get_col(0) * scale.x;
+        let y = r.get_col(1) * scale.y;
+        let z = r.get_col(2) * scale.z;
+
+        Self {
+            cols: [
+                x.extend(0.0).to_array(),
+                y.extend(0.0).to_array(),
+                z.extend(0.0).to_array(),
+                translation.extend(1.0).to_array(),
+            ],
+        }
+    }
+
+    pub fn get_col(&self, index: usize) -> Vec4 {
+        Vec4::from(self.cols[index])
+    }
+
+    pub fn get_translation(&self) -> Vec3 {
+        self.get_col(3).truncate()
+    }
+
+    // OpenGL clip space, depth mapped to [-1, 1]
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let mut m = Self::IDENTITY;
+        m.cols[0][0] = 2.0 / (right - left);
+        m.cols[1][1] = 2.0 / (top - bottom);
+        m.cols[2][2] = -2.0 / (far - near);
+        m.cols[3] = [
+            -(right + left) / (right - left),
+            -(top + bottom) / (top - bottom),
+            -(far + near) / (far - near),
+            1.0,
+        ];
+        m
+    }
+
+    // right-handed, looking down -Z, `fov_y` in radians
+    pub fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let f = 1.0 / (fov_y * 0.5).tan();
+        Self {
+            cols: [
+                [f / aspect, 0.0, 0.0, 0.0],
+                [0.0, f, 0.0, 0.0],
+                [0.0, 0.0, (far + near) / (near - far), -1.0],
+                [0.0, 0.0, (2.0 * far * near) / (near - far), 0.0],
+            ],
+        }
+    }
+
+    // view matrix of an eye looking at `target`
+    pub fn look_at(eye: Vec3, target: Vec3, up: Vec3) -> Self {
+        let f = (target - eye).normalize();
+        let s = f.cross(up).normalize();
+        let u = s.cross(f);
+
+        Self {
+            cols: [
+                [s.x, u.x, -f.x, 0.0],
+                [s.y, u.y, -f.y, 0.0],
+                [s.z, u.z, -f.z, 0.0],
+                [-s.dot(eye), -u.dot(eye), f.dot(eye), 1.0],
+            ],
+        }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut m = Self::IDENTITY;
+        for c in 0..4 {
+            for r in 0..4 {
+                m.cols[c][r] = self.cols[r][c];
+            }
+        }
+        m
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let m = &self.cols;
+        let a2323 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let a1323 = m[1][2] * m[3][3] - m[3][2] * m[1][3];
+        let a1223 = m[1][2] * m[2][3] - m[2][2] * m[1][3];
+        let a0323 = m[0][2] * m[3][3] - m[3][2] * m[0][3];
+        let a0223 = m[0][2] * m[2][3] - m[2][2] * m[0][3];
+        let a0123 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+
+        m[0][0] * (m[1][1] * a2323 - m[2][1] * a1323 + m[3][1] * a1223)
+            - m[1][0] * (m[0][1] * a2323 - m[2][1] * a0323 + m[3][1] * a0223)
+            + m[2][0] * (m[0][1] * a1323 - m[1][1] * a0323 + m[3][1] * a0123)
+            - m[3][0] * (m[0][1] * a1223 - m[1][1] * a0223 + m[2][1] * a0123)
+    }
+
+    // general inverse by cofactor expansion, None for singular matrices
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.cols;
+        let a2323 = m[2][2] * m[3][3] - m[3][2] * m[2][3];
+        let a1323 = m[1][2] * m[3][3] - m[3][2] * m[1][3];
+        let a1223 = m[1][2] * m[2][3] - m[2][2] * m[1][3];
+        let a0323 = m[0][2] * m[3][3] - m[3][2] * m[0][3];
+        let a0223 = m[0][2] * m[2][3] - m[2][2] * m[0][3];
+        let a0123 = m[0][2] * m[1][3] - m[1][2] * m[0][3];
+        let a2313 = m[2][1] * m[3][3] - m[3][1] * m[2][3];
+        let a1313 = m[1][1] * m[3][3] - m[3][1] * m[1][3];
+        let a1213 = m[1][1] * m[2][3] - m[2][1] * m[1][3];
+        let a2312 = m[2][1] * m[3][2] - m[3][1] * m[2][2];
+        let a1312 = m[1][1] * m[3][2] - m[3][1] * m[1][2];
+        let a1212 = m[1][1] * m[2][2] - m[2][1] * m[1][2];
+        let a0313 = m[0][1] * m[3][3] - m[3][1] * m[0][3];
+        let a0213 = m[0][1] * m[2][3] - m[2][1] * m[0][3];
+        let a0312 = m[0][1] * m[3][2] - m[3][1] * m[0][2];
+        let a0212 = m[0][1] * m[2][2] - m[2][1] * m[0][2];
+        let a0113 = m[0][1] * m[1][3] - m[1][1] * m[0][3];
+        let a0112 = m[0][1] * m[1][2] - m[1][1] * m[0][2];
+
+        let det = m[0][0] * (m[1][1] * a2323 - m[2][1] * a1323 + m[3][1] * a1223)
+            - m[1][0] * (m[0][1] * a2323 - m[2][1] * a0323 + m[3][1] * a0223)
+            + m[2][0] * (m[0][1] * a1323 - m[1][1] * a0323 + m[3][1] * a0123)
+            - m[3][0] * (m[0][1] * a1223 - m[1][1] * a0223 + m[2][1] * a0123);
+
+        if det.abs() < f32::EPSILON {
+            return None;
+        }
+        let inv = 1.0 / det;
+
+        Some(Self {
+            cols: [
+                [
+                    inv * (m[1][1] * a2323 - m[2][1] * a1323 + m[3][1] * a1223),
+                    inv * -(m[0][1] * a2323 - m[2][1] * a0323 + m[3][1] * a0223),
+                    inv * (m[0][1] * a1323 - m[1][1] * a0323 + m[3][1] * a0123),
+                    inv * -(m[0][1] * a1223 - m[1][1] * a0223 + m[2][1] * a0123),
+                ],
+                [
+                    inv * -(m[1][0] * a2323 - m[2][0] * a1323 + m[3][0] * a1223),
+                    inv * (m[0][0] * a2323 - m[2][0] * a0323 + m[3][0] * a0223),
+                    inv * -(m[0][0] * a1323 - m[1][0] * a0323 + m[3][0] * a0123),
+                    inv * (m[0][0] * a1223 - m[1][0] * a0223 + m[2][0] * a0123),
+                ],
+                [
+                    inv * (m[1][0] * a2313 - m[2][0] * a1313 + m[3][0] * a1213),
+                    inv * -(m[0][0] * a2313 - m[2][0] * a0313 + m[3][0] * a0213),
+                    inv * (m[0][0] * a1313 - m[1][0] * a0313 + m[3][0] * a0113),
+                    inv * -(m[0][0] * a1213 - m[1][0] * a0213 + m[2][0] * a0113),
+                ],
+                [
+                    inv * -(m[1][0] * a2312 - m[2][0] * a1312 + m[3][0] * a1212),
+                    inv * (m[0][0] * a2312 - m[2][0] * a0312 + m[3][0] * a0212),
+                    inv * -(m[0][0] * a1312 - m[1][0] * a0312 + m[3][0] * a0112),
+                    inv * (m[0][0] * a1212 - m[1][0] * a0212 + m[2][0] * a0112),
+                ],
+            ],
+        })
+    }
+
+    // applies the full transform including the perspective divide
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        let m = &self.cols;
+        let x = m[0][0] * p.x + m[1][0] * p.y + m[2][0] * p.z + m[3][0];
+        let y = m[0][1] * p.x + m[1][1] * p.y + m[2][1] * p.z + m[3][1];
+        let z = m[0][2] * p.x + m[1][2] * p.y + m[2][2] * p.z + m[3][2];
+        let w = m[0][3] * p.x + m[1][3] * p.y + m[2][3] * p.z + m[3][3];
+        Vec3::new(x / w, y / w, z / w)
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        let m = &self.cols;
+        Vec3::new(
+            m[0][0] * v.x + m[1][0] * v.y + m[2][0] * v.z,
+            m[0][1] * v.x + m[1][1] * v.y + m[2][1] * v.z,
+            m[0][2] * v.x + m[1][2] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    pub fn as_ptr(&self) -> *const f32 {
+        self.cols[0].as_ptr()
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (c, col) in out.iter_mut().enumerate() {
+            for (r, value) in col.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.cols[k][r] * rhs.cols[c][k]).sum();
+            }
+        }
+        Mat4 { cols: out }
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, v: Vec4) -> Vec4 {
+        self.get_col(0) * v.x
+            + self.get_col(1) * v.y
+            + self.get_col(2) * v.z
+            + self.get_col(3) * v.w
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mat4_eq(a: &Mat4, b: &Mat4) {
+        for c in 0..4 {
+            for r in 0..4 {
+                assert!(
+                    (a.cols[c][r] - b.cols[c][r]).abs() < 1e-4,
+                    "{:?} != {:?}",
+                    a,
+                    b
+                );
+            }
+        }
+    }
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn mat4_inverse_of_affine() {
+        let m = Mat4::from_scale_rotation_translation(
+            Vec3::new(2.0, 3.0, 0.5),
+            Quat::from_axis_angle(Vec3::new(1.0, 1.0, 0.0).normalize(), 0.7),
+            Vec3::new(1.0, -2.0, 3.0),
+        );
+        let inverse = m.inverse().unwrap();
+
+        assert_mat4_eq(&(m * inverse), &Mat4::IDENTITY);
+        assert_mat4_eq(&(inverse * m), &Mat4::IDENTITY);
+        let p = Vec3::new(0.3, -4.0, 2.0);
+        assert_vec3_eq(inverse.transform_point(m.transform_point(p)), p);
+    }
+
+    #[test]
+    fn mat4_inverse_of_projection() {
+        let m = Mat4::perspective(1.0, 1.5, 0.1, 100.0)
+            * Mat4::look_at(Vec3::new(0.0, 2.0, 5.0), Vec3::ZERO, Vec3::Y);
+        let inverse = m.inverse().unwrap();
+
+        assert_mat4_eq(&(m * inverse), &Mat4::IDENTITY);
+    }
+
+    #[test]
+    fn mat4_singular_has_no_inverse() {
+        assert!(Mat4::scale(Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+    }
+
+    #[test]
+    fn mat4_transform_point_and_vector() {
+        let m = Mat4::translation(Vec3::new(1.0, 2.0, 3.0)) * Mat4::rotation_z(90f32.to_radians());
+
+        assert_vec3_eq(m.transform_point(Vec3::X), Vec3::new(1.0, 3.0, 3.0));
+        assert_vec3_eq(m.transform_vector(Vec3::X), Vec3::Y);
+    }
+
+    #[test]
+    fn mat3_inverse() {
+        let m = Mat3::from_cols(
+            Vec3::new(2.0, 0.0, 1.0),
+            Vec3::new(1.0, 3.0, 0.0),
+            Vec3::new(0.0, 1.0, 4.0),
+        );
+        let product = m * m.inverse().unwrap();
+
+        for c in 0..3 {
+            assert_vec3_eq(product.get_col(c), Mat3::IDENTITY.get_col(c));
+        }
+        assert!(Mat3::scale(Vec3::new(1.0, 1.0, 0.0)).inverse().is_none());
+    }
+}
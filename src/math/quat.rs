@@ -0,0 +1,282 @@
+use std::ops::{Mul, MulAssign};
+
+use crate::math::{Mat3, Vec3};
+
+// Unit quaternion rotation, `w` is the scalar part.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Quat = Quat {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    pub fn from_xyzw(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    // `axis` must be normalized, `angle` in radians
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let (s, c) = (angle * 0.5).sin_cos();
+        Self::from_xyzw(axis.x * s, axis.y * s, axis.z * s, c)
+    }
+
+    pub fn from_rotation_x(angle: f32) -> Self {
+        Self::from_axis_angle(Vec3::X, angle)
+    }
+
+    pub fn from_rotation_y(angle: f32) -> Self {
+        Self::from_axis_angle(Vec3::Y, angle)
+    }
+
+    pub fn from_rotation_z(angle: f32) -> Self {
+        Self::from_axis_angle(Vec3::Z, angle)
+    }
+
+    // yaw around Y, then pitch around X, then roll around Z, in the
+    // rotating frame, matching the camera conventions
+    pub fn from_euler(yaw: f32, pitch: f32, roll: f32) -> Self {
+        Self::from_rotation_y(yaw) * Self::from_rotation_x(pitch) * Self::from_rotation_z(roll)
+    }
+
+    // shortest rotation taking the normalized vector `from` onto `to`
+    pub fn from_rotation_arc(from: Vec3, to: Vec3) -> Self {
+        let d = from.dot(to);
+        if d < -1.0 + 1e-6 {
+            // opposite vectors, any perpendicular axis works
+            return Self::from_axis_angle(from.any_orthogonal(), std::f32::consts::PI);
+        }
+
+        let c = from.cross(to);
+        Self::from_xyzw(c.x, c.y, c.z, 1.0 + d).normalize()
+    }
+
+    // `m` must be a pure rotation
+    pub fn from_mat3(m: &Mat3) -> Self {
+        let c = &m.cols;
+        let trace = c[0][0] + c[1][1] + c[2][2];
+
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Self::from_xyzw(
+                (c[1][2] - c[2][1]) / s,
+                (c[2][0] - c[0][2]) / s,
+                (c[0][1] - c[1][0]) / s,
+                0.25 * s,
+            )
+        } else if c[0][0] > c[1][1] && c[0][0] > c[2][2] {
+            let s = (1.0 + c[0][0] - c[1][1] - c[2][2]).sqrt() * 2.0;
+            Self::from_xyzw(
+                0.25 * s,
+                (c[1][0] + c[0][1]) / s,
+                (c[2][0] + c[0][2]) / s,
+                (c[1][2] - c[2][1]) / s,
+            )
+        } else if c[1][1] > c[2][2] {
+            let s = (1.0 + c[1][1] - c[0][0] - c[2][2]).sqrt() * 2.0;
+            Self::from_xyzw(
+                (c[1][0] + c[0][1]) / s,
+                0.25 * s,
+                (c[2][1] + c[1][2]) / s,
+                (c[2][0] - c[0][2]) / s,
+            )
+        } else {
+            let s = (1.0 + c[2][2] - c[0][0] - c[1][1]).sqrt() * 2.0;
+            Self::from_xyzw(
+                (c[2][0] + c[0][2]) / s,
+                (c[2][1] + c[1][2]) / s,
+                0.25 * s,
+                (c[0][1] - c[1][0]) / s,
+            )
+        };
+
+        q.normalize()
+    }
+
+    // rotation whose -Z axis points along `forward`
+    pub fn look_rotation(forward: Vec3, up: Vec3) -> Self {
+        let f = forward.normalize();
+        let r = f.cross(up).normalize();
+        let u = r.cross(f);
+        Self::from_mat3(&Mat3::from_cols(r, u, -f))
+    }
+
+    pub fn dot(self, other: Quat) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    pub fn normalize(self) -> Self {
+        let len = self.length();
+        if len > 0.0 {
+            Self::from_xyzw(self.x / len, self.y / len, self.z / len, self.w / len)
+        } else {
+            Self::IDENTITY
+        }
+    }
+
+    pub fn conjugate(self) -> Self {
+        Self::from_xyzw(-self.x, -self.y, -self.z, self.w)
+    }
+
+    // for unit quaternions the inverse is the conjugate
+    pub fn inverse(self) -> Self {
+        let len_sq = self.dot(self);
+        let c = self.conjugate();
+        Self::from_xyzw(c.x / len_sq, c.y / len_sq, c.z / len_sq, c.w / len_sq)
+    }
+
+    // (axis, angle in radians)
+    pub fn to_axis_angle(self) -> (Vec3, f32) {
+        let q = self.normalize();
+        let angle = 2.0 * q.w.clamp(-1.0, 1.0).acos();
+        let s = (1.0 - q.w * q.w).max(0.0).sqrt();
+        if s < 1e-6 {
+            (Vec3::X, 0.0)
+        } else {
+            (Vec3::new(q.x / s, q.y / s, q.z / s), angle)
+        }
+    }
+
+    pub fn slerp(self, end: Quat, t: f32) -> Self {
+        let mut end = end;
+        let mut d = self.dot(end);
+        // take the short way around
+        if d < 0.0 {
+            end = Self::from_xyzw(-end.x, -end.y, -end.z, -end.w);
+            d = -d;
+        }
+
+        // nearly parallel, fall back to normalized lerp
+        if d > 0.9995 {
+            return Self::from_xyzw(
+                self.x + (end.x - self.x) * t,
+                self.y + (end.y - self.y) * t,
+                self.z + (end.z - self.z) * t,
+                self.w + (end.w - self.w) * t,
+            )
+            .normalize();
+        }
+
+        let theta = d.acos();
+        let sin_theta = theta.sin();
+        let a = ((1.0 - t) * theta).sin() / sin_theta;
+        let b = (t * theta).sin() / sin_theta;
+
+        Self::from_xyzw(
+            self.x * a + end.x * b,
+            self.y * a + end.y * b,
+            self.z * a + end.z * b,
+            self.w * a + end.w * b,
+        )
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+// composition, `a * b` applies b first
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat::from_xyzw(
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+        )
+    }
+}
+
+impl MulAssign for Quat {
+    fn mul_assign(&mut self, rhs: Quat) {
+        *self = *self * rhs;
+    }
+}
+
+// rotates a vector
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.0;
+        v + t * self.w + u.cross(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_quat_eq(a: Quat, b: Quat) {
+        // q and -q are the same rotation
+        assert!(a.dot(b).abs() > 1.0 - 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn from_mat3_round_trips() {
+        // one rotation per branch of the extraction, including half turns
+        let rotations = [
+            Quat::from_axis_angle(Vec3::new(1.0, 2.0, 3.0).normalize(), 0.5),
+            Quat::from_rotation_x(3.0),
+            Quat::from_rotation_y(3.0),
+            Quat::from_rotation_z(3.0),
+            Quat::from_rotation_x(std::f32::consts::PI),
+        ];
+        for q in rotations.iter() {
+            assert_quat_eq(Quat::from_mat3(&Mat3::from_quat(*q)), *q);
+        }
+    }
+
+    #[test]
+    fn slerp_endpoints_and_midpoint() {
+        let a = Quat::IDENTITY;
+        let b = Quat::from_rotation_z(std::f32::consts::FRAC_PI_2);
+
+        assert_quat_eq(a.slerp(b, 0.0), a);
+        assert_quat_eq(a.slerp(b, 1.0), b);
+        assert_quat_eq(
+            a.slerp(b, 0.5),
+            Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+        );
+        assert!((a.slerp(b, 0.3).length() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn slerp_takes_the_short_way() {
+        let a = Quat::from_rotation_z(0.1);
+        let b = Quat::from_rotation_z(-0.1);
+        let negated = Quat::from_xyzw(-b.x, -b.y, -b.z, -b.w);
+
+        assert_quat_eq(a.slerp(negated, 0.5), Quat::IDENTITY);
+    }
+
+    #[test]
+    fn rotates_vectors() {
+        let q = Quat::from_rotation_y(std::f32::consts::FRAC_PI_2);
+
+        assert_vec3_eq(q * Vec3::X, -Vec3::Z);
+        assert_vec3_eq(q.inverse() * (q * Vec3::Y), Vec3::Y);
+    }
+}
@@ -0,0 +1,151 @@
+use std::ops::Mul;
+
+use crate::math::{Mat4, Quat, Vec3};
+
+// Translation, rotation and scale applied in scale, rotate, translate order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Transform = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Self {
+            rotation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn from_scale(scale: Vec3) -> Self {
+        Self {
+            scale,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn to_mat4(self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    pub fn transform_point(&self, p: Vec3) -> Vec3 {
+        self.rotation * (p * self.scale) + self.translation
+    }
+
+    pub fn transform_vector(&self, v: Vec3) -> Vec3 {
+        self.rotation * (v * self.scale)
+    }
+
+    pub fn get_forward(&self) -> Vec3 {
+        self.rotation * -Vec3::Z
+    }
+
+    pub fn get_right(&self) -> Vec3 {
+        self.rotation * Vec3::X
+    }
+
+    pub fn get_up(&self) -> Vec3 {
+        self.rotation * Vec3::Y
+    }
+
+    pub fn look_at(&mut self, target: Vec3, up: Vec3) {
+        self.rotation = Quat::look_rotation(target - self.translation, up);
+    }
+
+    // Exact for uniform scale. A rotated non-uniform scale has no TRS inverse,
+    // use `to_mat4().inverse()` when that matters.
+    pub fn inverse(&self) -> Self {
+        let scale = Vec3::new(1.0 / self.scale.x, 1.0 / self.scale.y, 1.0 / self.scale.z);
+        let rotation = self.rotation.inverse();
+        let translation = rotation * -self.translation * scale;
+
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+// `parent * child` gives the child's transform in the parent's space, exact
+// unless the parent combines rotation with non-uniform scale
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, child: Transform) -> Transform {
+        Transform {
+            translation: self.transform_point(child.translation),
+            rotation: self.rotation * child.rotation,
+            scale: self.scale * child.scale,
+        }
+    }
+}
+
+impl From<Transform> for Mat4 {
+    fn from(t: Transform) -> Mat4 {
+        t.to_mat4()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn inverse_undoes_uniform_scale() {
+        let t = Transform::new(
+            Vec3::new(1.0, -2.0, 3.0),
+            Quat::from_axis_angle(Vec3::new(0.0, 1.0, 1.0).normalize(), 1.2),
+            Vec3::splat(2.5),
+        );
+        let inverse = t.inverse();
+        let p = Vec3::new(0.5, 4.0, -1.0);
+
+        assert_vec3_eq(inverse.transform_point(t.transform_point(p)), p);
+        assert_vec3_eq((t * inverse).transform_point(p), p);
+    }
+
+    #[test]
+    fn matches_matrix() {
+        let t = Transform::new(
+            Vec3::new(3.0, 0.0, -1.0),
+            Quat::from_rotation_x(0.4),
+            Vec3::new(1.0, 2.0, 3.0),
+        );
+        let p = Vec3::new(1.0, 1.0, 1.0);
+
+        assert_vec3_eq(t.to_mat4().transform_point(p), t.transform_point(p));
+    }
+}
@@ -0,0 +1,302 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec2 {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Vec4 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Vec2 {
+    pub const ZERO: Vec2 = Vec2 { x: 0.0, y: 0.0 };
+    pub const ONE: Vec2 = Vec2 { x: 1.0, y: 1.0 };
+    pub const X: Vec2 = Vec2 { x: 1.0, y: 0.0 };
+    pub const Y: Vec2 = Vec2 { x: 0.0, y: 1.0 };
+
+    pub fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    pub fn splat(v: f32) -> Self {
+        Self::new(v, v)
+    }
+
+    pub fn extend(self, z: f32) -> Vec3 {
+        Vec3::new(self.x, self.y, z)
+    }
+
+    // z component of the 3D cross product, positive when `other` is
+    // counter-clockwise from `self`
+    pub fn perp_dot(self, other: Vec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+
+    // rotated a quarter turn counter-clockwise
+    pub fn perp(self) -> Vec2 {
+        Vec2::new(-self.y, self.x)
+    }
+
+    pub fn to_array(self) -> [f32; 2] {
+        [self.x, self.y]
+    }
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    pub const ONE: Vec3 = Vec3 {
+        x: 1.0,
+        y: 1.0,
+        z: 1.0,
+    };
+    pub const X: Vec3 = Vec3 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+    };
+    pub const Y: Vec3 = Vec3 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+    };
+    pub const Z: Vec3 = Vec3 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    pub fn splat(v: f32) -> Self {
+        Self::new(v, v, v)
+    }
+
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+
+    pub fn truncate(self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    // any unit vector perpendicular to `self`, which must be normalized
+    pub fn any_orthogonal(self) -> Vec3 {
+        let other = if self.x.abs() < 0.9 { Vec3::X } else { Vec3::Y };
+        self.cross(other).normalize()
+    }
+
+    pub fn to_array(self) -> [f32; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+impl Vec4 {
+    pub const ZERO: Vec4 = Vec4 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 0.0,
+    };
+    pub const ONE: Vec4 = Vec4 {
+        x: 1.0,
+        y: 1.0,
+        z: 1.0,
+        w: 1.0,
+    };
+
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    pub fn splat(v: f32) -> Self {
+        Self::new(v, v, v, v)
+    }
+
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+
+    pub fn to_array(self) -> [f32; 4] {
+        [self.x, self.y, self.z, self.w]
+    }
+}
+
+macro_rules! impl_vec {
+    ($t:ident, $n:expr, { $($f:ident),+ }) => {
+        impl $t {
+            pub fn dot(self, other: $t) -> f32 {
+                0.0 $(+ self.$f * other.$f)+
+            }
+
+            pub fn length_squared(self) -> f32 {
+                self.dot(self)
+            }
+
+            pub fn length(self) -> f32 {
+                self.dot(self).sqrt()
+            }
+
+            pub fn distance(self, other: $t) -> f32 {
+                (self - other).length()
+            }
+
+            // zero vectors are returned unchanged
+            pub fn normalize(self) -> $t {
+                let len = self.length();
+                if len > 0.0 {
+                    self / len
+                } else {
+                    self
+                }
+            }
+
+            pub fn lerp(self, other: $t, t: f32) -> $t {
+                self + (other - self) * t
+            }
+
+            pub fn min(self, other: $t) -> $t {
+                $t { $($f: self.$f.min(other.$f)),+ }
+            }
+
+            pub fn max(self, other: $t) -> $t {
+                $t { $($f: self.$f.max(other.$f)),+ }
+            }
+
+            pub fn abs(self) -> $t {
+                $t { $($f: self.$f.abs()),+ }
+            }
+
+            pub fn abs_diff_eq(self, other: $t, epsilon: f32) -> bool {
+                true $(&& (self.$f - other.$f).abs() <= epsilon)+
+            }
+        }
+
+        impl From<[f32; $n]> for $t {
+            fn from(a: [f32; $n]) -> $t {
+                let mut i = 0..$n;
+                $t { $($f: a[i.next().unwrap()]),+ }
+            }
+        }
+
+        impl From<$t> for [f32; $n] {
+            fn from(v: $t) -> [f32; $n] {
+                v.to_array()
+            }
+        }
+
+        impl Index<usize> for $t {
+            type Output = f32;
+            fn index(&self, index: usize) -> &f32 {
+                [$(&self.$f),+][index]
+            }
+        }
+
+        impl Add for $t {
+            type Output = $t;
+            fn add(self, rhs: $t) -> $t {
+                $t { $($f: self.$f + rhs.$f),+ }
+            }
+        }
+
+        impl Sub for $t {
+            type Output = $t;
+            fn sub(self, rhs: $t) -> $t {
+                $t { $($f: self.$f - rhs.$f),+ }
+            }
+        }
+
+        // component-wise
+        impl Mul for $t {
+            type Output = $t;
+            fn mul(self, rhs: $t) -> $t {
+                $t { $($f: self.$f * rhs.$f),+ }
+            }
+        }
+
+        impl Mul<f32> for $t {
+            type Output = $t;
+            fn mul(self, rhs: f32) -> $t {
+                $t { $($f: self.$f * rhs),+ }
+            }
+        }
+
+        impl Mul<$t> for f32 {
+            type Output = $t;
+            fn mul(self, rhs: $t) -> $t {
+                rhs * self
+            }
+        }
+
+        impl Div<f32> for $t {
+            type Output = $t;
+            fn div(self, rhs: f32) -> $t {
+                $t { $($f: self.$f / rhs),+ }
+            }
+        }
+
+        impl Neg for $t {
+            type Output = $t;
+            fn neg(self) -> $t {
+                $t { $($f: -self.$f),+ }
+            }
+        }
+
+        impl AddAssign for $t {
+            fn add_assign(&mut self, rhs: $t) {
+                $(self.$f += rhs.$f;)+
+            }
+        }
+
+        impl SubAssign for $t {
+            fn sub_assign(&mut self, rhs: $t) {
+                $(self.$f -= rhs.$f;)+
+            }
+        }
+
+        impl MulAssign<f32> for $t {
+            fn mul_assign(&mut self, rhs: f32) {
+                $(self.$f *= rhs;)+
+            }
+        }
+
+        impl DivAssign<f32> for $t {
+            fn div_assign(&mut self, rhs: f32) {
+                $(self.$f /= rhs;)+
+            }
+        }
+    };
+}
+
+impl_vec!(Vec2, 2, { x, y });
+impl_vec!(Vec3, 3, { x, y, z });
+impl_vec!(Vec4, 4, { x, y, z, w });
@@ -1,2 +1,31 @@
+pub mod backend;
+pub mod bitmap;
+pub mod buffer;
+pub mod camera;
+pub mod camera_controller;
+pub mod capture;
+pub mod command;
 pub mod context;
+pub mod debug;
+pub mod debug_draw;
+pub mod environment;
+pub mod font;
+pub mod framebuffer;
+pub mod instancing;
+pub mod lighting;
+pub mod material;
+pub mod mesh;
+pub mod model;
+pub mod particles;
+pub mod picking;
+pub mod postprocess;
+pub mod profiler;
+pub mod render_graph;
+pub mod renderer2d;
 pub mod shader;
+pub mod shadow;
+pub mod software;
+pub mod sprite;
+pub mod state;
+pub mod texture;
+pub mod tilemap;
@@ -0,0 +1,202 @@
+use std::{mem, ptr};
+
+use crate::math::Vec4;
+use crate::renderer::{
+    command::{CommandBuffer, RenderCommand, Shading, Vertex},
+    profiler,
+    shader::{OpenGLShader, Shader},
+    state,
+};
+
+static VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_Position;
+    layout(location = 1) in vec4 a_Color;
+
+    uniform vec4 u_Color;
+    uniform int u_UseVertexColor;
+
+    out vec4 v_Color;
+
+    void main() {
+        v_Color = u_UseVertexColor != 0 ? a_Color : u_Color;
+        gl_Position = vec4(a_Position, 1.0);
+    }
+";
+
+static FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    in vec4 v_Color;
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        color = v_Color;
+    }
+";
+
+pub trait RenderBackend {
+    fn execute(&mut self, commands: &CommandBuffer);
+}
+
+pub struct OpenGLBackend {
+    shader: OpenGLShader,
+    vao: u32,
+    vbo: u32,
+    ibo: u32,
+}
+
+impl OpenGLBackend {
+    // requires a current OpenGL context
+    pub fn new() -> Self {
+        use gl::types::*;
+
+        let shader = OpenGLShader::new(VERTEX_SHADER, FRAGMENT_SHADER);
+
+        let mut vao = 0;
+        let mut vbo = 0;
+        let mut ibo = 0;
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            state::bind_vertex_array(vao);
+
+            gl::GenBuffers(1, &mut vbo);
+            state::bind_buffer(gl::ARRAY_BUFFER, vbo);
+
+            gl::GenBuffers(1, &mut ibo);
+            state::bind_buffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
+
+            let stride = mem::size_of::<Vertex>() as GLsizei;
+            gl::EnableVertexAttribArray(0);
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, stride, ptr::null());
+            gl::EnableVertexAttribArray(1);
+            gl::VertexAttribPointer(
+                1,
+                4,
+                gl::FLOAT,
+                gl::FALSE,
+                stride,
+                (3 * mem::size_of::<GLfloat>()) as *const _,
+            );
+
+            state::bind_vertex_array(0);
+        }
+
+        Self {
+            shader,
+            vao,
+            vbo,
+            ibo,
+        }
+    }
+
+    fn draw_triangles(&self, vertices: &[Vertex], indices: Option<&[u32]>, shading: Shading) {
+        use gl::types::*;
+
+        let (color, use_vertex_color) = match shading {
+            Shading::Flat(color) => (color, 0),
+            Shading::VertexColor => (Vec4::ONE, 1),
+        };
+
+        self.shader.bind();
+        self.shader.set_float4("u_Color", color);
+        self.shader.set_int("u_UseVertexColor", use_vertex_color);
+
+        unsafe {
+            state::bind_vertex_array(self.vao);
+            state::bind_buffer(gl::ARRAY_BUFFER, self.vbo);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(vertices) as GLsizeiptr,
+                vertices.as_ptr() as *const _,
+                gl::STREAM_DRAW,
+            );
+            profiler::record_upload(mem::size_of_val(vertices));
+
+            match indices {
+                Some(indices) => {
+                    state::bind_buffer(gl::ELEMENT_ARRAY_BUFFER, self.ibo);
+                    gl::BufferData(
+                        gl::ELEMENT_ARRAY_BUFFER,
+                        mem::size_of_val(indices) as GLsizeiptr,
+                        indices.as_ptr() as *const _,
+                        gl::STREAM_DRAW,
+                    );
+                    profiler::record_upload(mem::size_of_val(indices));
+                    gl::DrawElements(
+                        gl::TRIANGLES,
+                        indices.len() as GLsizei,
+                        gl::UNSIGNED_INT,
+                        ptr::null(),
+                    );
+                }
+                None => gl::DrawArrays(gl::TRIANGLES, 0, vertices.len() as GLsizei),
+            }
+            let vertex_count = indices.map_or(vertices.len(), |indices| indices.len());
+            profiler::record_draw(gl::TRIANGLES, vertex_count as u32, 1);
+
+            state::bind_vertex_array(0);
+        }
+    }
+}
+
+impl RenderBackend for OpenGLBackend {
+    fn execute(&mut self, commands: &CommandBuffer) {
+        for command in commands.get_commands() {
+            match command {
+                RenderCommand::SetViewport {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => state::set_viewport(*x, *y, *width as i32, *height as i32),
+
+                RenderCommand::SetDepthTest(enabled) => {
+                    if *enabled {
+                        state::enable(gl::DEPTH_TEST);
+                        state::set_depth_func(gl::LESS);
+                    } else {
+                        state::disable(gl::DEPTH_TEST);
+                    }
+                }
+
+                RenderCommand::Clear { color, depth } => unsafe {
+                    let mut mask = 0;
+                    if let Some(c) = color {
+                        gl::ClearColor(c.x, c.y, c.z, c.w);
+                        mask |= gl::COLOR_BUFFER_BIT;
+                    }
+                    if let Some(d) = depth {
+                        gl::ClearDepth(*d as f64);
+                        mask |= gl::DEPTH_BUFFER_BIT;
+                    }
+                    if mask != 0 {
+                        gl::Clear(mask);
+                    }
+                },
+
+                RenderCommand::DrawTriangles {
+                    vertices,
+                    indices,
+                    shading,
+                } => self.draw_triangles(vertices, indices.as_deref(), *shading),
+            }
+        }
+    }
+}
+
+impl Drop for OpenGLBackend {
+    fn drop(&mut self) {
+        state::forget_buffer(self.ibo);
+        state::forget_buffer(self.vbo);
+        state::forget_vertex_array(self.vao);
+        unsafe {
+            gl::DeleteBuffers(1, &self.ibo);
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
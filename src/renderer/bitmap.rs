@@ -0,0 +1,177 @@
+use std::path::Path;
+
+// 8-bit RGBA pixels, rows stored top to bottom
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bitmap {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+}
+
+impl Bitmap {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    pub fn from_raw(width: u32, height: u32, data: Vec<u8>) -> Self {
+        assert_eq!(
+            data.len(),
+            (width * height * 4) as usize,
+            "Bitmap data does not match its dimensions."
+        );
+
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    // any format `image` is built with (PNG, JPEG, TGA, HDR), picked from
+    // the extension and contents, converted to 8-bit RGBA
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let img = image::open(path.as_ref())
+            .map_err(|e| format!("Failed to load {:?}: {}", path.as_ref(), e))?
+            .into_rgba8();
+        let (width, height) = img.dimensions();
+
+        Ok(Self::from_raw(width, height, img.into_raw()))
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        image::save_buffer(
+            path.as_ref(),
+            &self.data,
+            self.width,
+            self.height,
+            image::ColorType::Rgba8,
+        )
+        .map_err(|e| format!("Failed to save {:?}: {}", path.as_ref(), e))
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let i = self.index(x, y);
+        [
+            self.data[i],
+            self.data[i + 1],
+            self.data[i + 2],
+            self.data[i + 3],
+        ]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        let i = self.index(x, y);
+        self.data[i..i + 4].copy_from_slice(&pixel);
+    }
+
+    pub fn fill(&mut self, pixel: [u8; 4]) {
+        for chunk in self.data.chunks_exact_mut(4) {
+            chunk.copy_from_slice(&pixel);
+        }
+    }
+
+    // converts between top-down rows and OpenGL's bottom-up rows
+    pub fn flip_vertical(&mut self) {
+        let row_size = (self.width * 4) as usize;
+        let height = self.height as usize;
+
+        for y in 0..height / 2 {
+            let (top, bottom) = self.data.split_at_mut((height - 1 - y) * row_size);
+            top[y * row_size..(y + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+        }
+    }
+
+    // number of pixels where any channel differs by more than `tolerance`,
+    // bitmaps of different sizes never match
+    pub fn count_mismatches(&self, other: &Bitmap, tolerance: u8) -> usize {
+        if self.width != other.width || self.height != other.height {
+            return (self.width * self.height).max(other.width * other.height) as usize;
+        }
+
+        self.data
+            .chunks_exact(4)
+            .zip(other.data.chunks_exact(4))
+            .filter(|(a, b)| {
+                a.iter()
+                    .zip(b.iter())
+                    .any(|(x, y)| (*x as i16 - *y as i16).abs() > tolerance as i16)
+            })
+            .count()
+    }
+
+    pub fn matches(&self, other: &Bitmap, tolerance: u8) -> bool {
+        self.count_mismatches(other, tolerance) == 0
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        assert!(
+            x < self.width && y < self.height,
+            "Pixel ({}, {}) out of bounds.",
+            x,
+            y
+        );
+        ((y * self.width + x) * 4) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filled(width: u32, height: u32, pixel: [u8; 4]) -> Bitmap {
+        let mut bitmap = Bitmap::new(width, height);
+        bitmap.fill(pixel);
+        bitmap
+    }
+
+    #[test]
+    fn mismatches_respect_tolerance() {
+        let golden = filled(2, 2, [100, 100, 100, 255]);
+        let mut image = golden.clone();
+        image.set_pixel(0, 0, [102, 100, 100, 255]);
+        image.set_pixel(1, 1, [100, 100, 97, 255]);
+
+        assert_eq!(image.count_mismatches(&golden, 0), 2);
+        assert_eq!(image.count_mismatches(&golden, 2), 1);
+        assert!(image.matches(&golden, 3));
+        assert!(!image.matches(&golden, 2));
+    }
+
+    #[test]
+    fn different_sizes_never_match() {
+        let a = filled(2, 2, [0; 4]);
+        let b = filled(4, 1, [0; 4]);
+
+        assert_eq!(a.count_mismatches(&b, 255), 4);
+        assert!(!a.matches(&b, 255));
+    }
+
+    #[test]
+    fn flip_vertical_reverses_rows() {
+        let mut bitmap = Bitmap::new(1, 3);
+        for y in 0..3 {
+            bitmap.set_pixel(0, y, [y as u8; 4]);
+        }
+        bitmap.flip_vertical();
+
+        assert_eq!(bitmap.get_pixel(0, 0), [2; 4]);
+        assert_eq!(bitmap.get_pixel(0, 1), [1; 4]);
+        assert_eq!(bitmap.get_pixel(0, 2), [0; 4]);
+    }
+}
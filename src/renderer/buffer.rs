@@ -0,0 +1,521 @@
+use std::{mem, ptr, rc::Rc};
+
+use crate::renderer::{debug::set_object_label, profiler, state};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderDataType {
+    Float,
+    Float2,
+    Float3,
+    Float4,
+    Int,
+    Int2,
+    Int3,
+    Int4,
+    // four normalized bytes, e.g. packed colors
+    UByte4,
+    // column major, one attribute location per column
+    Mat3,
+    Mat4,
+}
+
+impl ShaderDataType {
+    pub fn get_size(&self) -> usize {
+        self.get_component_count() * self.get_component_size()
+    }
+
+    pub fn get_component_count(&self) -> usize {
+        match self {
+            ShaderDataType::Float | ShaderDataType::Int => 1,
+            ShaderDataType::Float2 | ShaderDataType::Int2 => 2,
+            ShaderDataType::Float3 | ShaderDataType::Int3 => 3,
+            ShaderDataType::Float4 | ShaderDataType::Int4 | ShaderDataType::UByte4 => 4,
+            ShaderDataType::Mat3 => 3 * 3,
+            ShaderDataType::Mat4 => 4 * 4,
+        }
+    }
+
+    pub fn get_location_count(&self) -> usize {
+        match self {
+            ShaderDataType::Mat3 => 3,
+            ShaderDataType::Mat4 => 4,
+            _ => 1,
+        }
+    }
+
+    fn get_component_size(&self) -> usize {
+        match self {
+            ShaderDataType::UByte4 => 1,
+            _ => 4,
+        }
+    }
+
+    fn get_gl_type(&self) -> u32 {
+        match self {
+            ShaderDataType::Float
+            | ShaderDataType::Float2
+            | ShaderDataType::Float3
+            | ShaderDataType::Float4
+            | ShaderDataType::Mat3
+            | ShaderDataType::Mat4 => gl::FLOAT,
+            ShaderDataType::Int
+            | ShaderDataType::Int2
+            | ShaderDataType::Int3
+            | ShaderDataType::Int4 => gl::INT,
+            ShaderDataType::UByte4 => gl::UNSIGNED_BYTE,
+        }
+    }
+
+    fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            ShaderDataType::Int
+                | ShaderDataType::Int2
+                | ShaderDataType::Int3
+                | ShaderDataType::Int4
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferElement {
+    pub name: String,
+    pub data_type: ShaderDataType,
+    pub normalized: bool,
+    pub offset: usize,
+}
+
+impl BufferElement {
+    pub fn new(name: &str, data_type: ShaderDataType) -> Self {
+        Self {
+            name: name.to_string(),
+            data_type,
+            normalized: data_type == ShaderDataType::UByte4,
+            offset: 0,
+        }
+    }
+}
+
+// Interleaved vertex layout. Elements map to attribute locations in order,
+// offsets and the stride are computed from their sizes without padding, so
+// the matching vertex struct must be `#[repr(C)]` with the same field order.
+// A non-zero divisor makes the attributes advance once per that many
+// instances instead of once per vertex.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BufferLayout {
+    elements: Vec<BufferElement>,
+    stride: usize,
+    divisor: u32,
+}
+
+impl BufferLayout {
+    pub fn new(elements: Vec<BufferElement>) -> Self {
+        let mut elements = elements;
+        let mut offset = 0;
+        for element in elements.iter_mut() {
+            element.offset = offset;
+            offset += element.data_type.get_size();
+        }
+
+        Self {
+            elements,
+            stride: offset,
+            divisor: 0,
+        }
+    }
+
+    pub fn with_divisor(mut self, divisor: u32) -> Self {
+        self.divisor = divisor;
+        self
+    }
+
+    pub fn get_elements(&self) -> &[BufferElement] {
+        &self.elements
+    }
+
+    pub fn get_stride(&self) -> usize {
+        self.stride
+    }
+
+    pub fn get_divisor(&self) -> u32 {
+        self.divisor
+    }
+
+    pub fn is_instanced(&self) -> bool {
+        self.divisor > 0
+    }
+}
+
+pub struct VertexBuffer {
+    renderer_id: u32,
+    size: usize,
+    layout: BufferLayout,
+}
+
+impl VertexBuffer {
+    // dynamic buffer of `size` bytes, filled later with `set_data`
+    pub fn new(size: usize, layout: BufferLayout) -> Self {
+        Self::create(size, ptr::null(), gl::DYNAMIC_DRAW, layout)
+    }
+
+    pub fn from_data<T>(data: &[T], layout: BufferLayout) -> Self {
+        Self::create(
+            mem::size_of_val(data),
+            data.as_ptr() as *const _,
+            gl::STATIC_DRAW,
+            layout,
+        )
+    }
+
+    fn create(
+        size: usize,
+        data: *const std::ffi::c_void,
+        usage: u32,
+        layout: BufferLayout,
+    ) -> Self {
+        let mut renderer_id = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut renderer_id);
+            state::bind_buffer(gl::ARRAY_BUFFER, renderer_id);
+            gl::BufferData(gl::ARRAY_BUFFER, size as isize, data, usage);
+        }
+        if !data.is_null() {
+            profiler::record_upload(size);
+        }
+
+        Self {
+            renderer_id,
+            size,
+            layout,
+        }
+    }
+
+    pub fn bind(&self) {
+        state::bind_buffer(gl::ARRAY_BUFFER, self.renderer_id);
+    }
+
+    pub fn unbind(&self) {
+        state::bind_buffer(gl::ARRAY_BUFFER, 0);
+    }
+
+    // overwrites the start of the buffer
+    pub fn set_data<T>(&self, data: &[T]) {
+        let size = mem::size_of_val(data);
+        assert!(size <= self.size, "Vertex data exceeds the buffer size.");
+
+        unsafe {
+            state::bind_buffer(gl::ARRAY_BUFFER, self.renderer_id);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                size as isize,
+                data.as_ptr() as *const _,
+            );
+        }
+        profiler::record_upload(size);
+    }
+
+    // Replaces the whole content, detaching the old storage first so that
+    // the driver does not wait for draws still reading it. Meant for buffers
+    // rewritten every frame.
+    pub fn stream_data<T>(&self, data: &[T]) {
+        let size = mem::size_of_val(data);
+        assert!(size <= self.size, "Vertex data exceeds the buffer size.");
+
+        unsafe {
+            state::bind_buffer(gl::ARRAY_BUFFER, self.renderer_id);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                self.size as isize,
+                ptr::null(),
+                gl::STREAM_DRAW,
+            );
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                0,
+                size as isize,
+                data.as_ptr() as *const _,
+            );
+        }
+        profiler::record_upload(size);
+    }
+
+    pub fn get_layout(&self) -> &BufferLayout {
+        &self.layout
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get_renderer_id(&self) -> u32 {
+        self.renderer_id
+    }
+
+    // names the buffer in debug output and frame captures
+    pub fn set_label(&self, label: &str) {
+        set_object_label(gl::BUFFER, self.renderer_id, label);
+    }
+}
+
+impl Drop for VertexBuffer {
+    fn drop(&mut self) {
+        state::forget_buffer(self.renderer_id);
+        unsafe {
+            gl::DeleteBuffers(1, &self.renderer_id);
+        }
+    }
+}
+
+pub struct IndexBuffer {
+    renderer_id: u32,
+    count: usize,
+}
+
+impl IndexBuffer {
+    pub fn new(indices: &[u32]) -> Self {
+        let mut renderer_id = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut renderer_id);
+            // bound as an array buffer so that it can be filled without
+            // disturbing the element binding of the current vertex array
+            state::bind_buffer(gl::ARRAY_BUFFER, renderer_id);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(indices) as isize,
+                indices.as_ptr() as *const _,
+                gl::STATIC_DRAW,
+            );
+        }
+        profiler::record_upload(mem::size_of_val(indices));
+
+        Self {
+            renderer_id,
+            count: indices.len(),
+        }
+    }
+
+    pub fn bind(&self) {
+        state::bind_buffer(gl::ELEMENT_ARRAY_BUFFER, self.renderer_id);
+    }
+
+    pub fn unbind(&self) {
+        state::bind_buffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+    }
+
+    pub fn get_count(&self) -> usize {
+        self.count
+    }
+
+    // names the buffer in debug output and frame captures
+    pub fn set_label(&self, label: &str) {
+        set_object_label(gl::BUFFER, self.renderer_id, label);
+    }
+}
+
+impl Drop for IndexBuffer {
+    fn drop(&mut self) {
+        state::forget_buffer(self.renderer_id);
+        unsafe {
+            gl::DeleteBuffers(1, &self.renderer_id);
+        }
+    }
+}
+
+// Backs a std140 uniform block, bound to a fixed binding point that shaders
+// link their block to with `OpenGLShader::set_uniform_block_binding`.
+pub struct UniformBuffer {
+    renderer_id: u32,
+    size: usize,
+    binding: u32,
+}
+
+impl UniformBuffer {
+    pub fn new(size: usize, binding: u32) -> Self {
+        let mut renderer_id = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut renderer_id);
+            state::bind_buffer(gl::UNIFORM_BUFFER, renderer_id);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                size as isize,
+                ptr::null(),
+                gl::DYNAMIC_DRAW,
+            );
+            state::bind_buffer_base(gl::UNIFORM_BUFFER, binding, renderer_id);
+        }
+
+        Self {
+            renderer_id,
+            size,
+            binding,
+        }
+    }
+
+    // attaches the buffer to its binding point again
+    pub fn bind(&self) {
+        state::bind_buffer_base(gl::UNIFORM_BUFFER, self.binding, self.renderer_id);
+    }
+
+    // overwrites the start of the buffer
+    pub fn set_data<T>(&self, data: &[T]) {
+        let size = mem::size_of_val(data);
+        assert!(size <= self.size, "Uniform data exceeds the buffer size.");
+
+        unsafe {
+            state::bind_buffer(gl::UNIFORM_BUFFER, self.renderer_id);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                size as isize,
+                data.as_ptr() as *const _,
+            );
+        }
+        profiler::record_upload(size);
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    pub fn get_binding(&self) -> u32 {
+        self.binding
+    }
+
+    pub fn get_renderer_id(&self) -> u32 {
+        self.renderer_id
+    }
+
+    // names the buffer in debug output and frame captures
+    pub fn set_label(&self, label: &str) {
+        set_object_label(gl::BUFFER, self.renderer_id, label);
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        state::forget_buffer(self.renderer_id);
+        unsafe {
+            gl::DeleteBuffers(1, &self.renderer_id);
+        }
+    }
+}
+
+// Buffers are shared so that several vertex arrays can use the same index
+// buffer, e.g. the quad indices of different batch types.
+pub struct VertexArray {
+    renderer_id: u32,
+    vertex_buffers: Vec<Rc<VertexBuffer>>,
+    index_buffer: Option<Rc<IndexBuffer>>,
+    attribute_count: u32,
+}
+
+impl VertexArray {
+    pub fn new() -> Self {
+        let mut renderer_id = 0;
+
+        unsafe {
+            gl::GenVertexArrays(1, &mut renderer_id);
+        }
+
+        Self {
+            renderer_id,
+            vertex_buffers: Vec::new(),
+            index_buffer: None,
+            attribute_count: 0,
+        }
+    }
+
+    pub fn bind(&self) {
+        state::bind_vertex_array(self.renderer_id);
+    }
+
+    pub fn unbind(&self) {
+        state::bind_vertex_array(0);
+    }
+
+    // attributes continue from the locations used by previous buffers
+    pub fn add_vertex_buffer(&mut self, vertex_buffer: Rc<VertexBuffer>) {
+        let layout = vertex_buffer.get_layout();
+        assert!(
+            !layout.get_elements().is_empty(),
+            "Vertex buffer has no layout."
+        );
+
+        self.bind();
+        vertex_buffer.bind();
+
+        let stride = layout.get_stride() as i32;
+        let divisor = layout.get_divisor();
+        for element in layout.get_elements() {
+            let locations = element.data_type.get_location_count();
+            let count = (element.data_type.get_component_count() / locations) as i32;
+            let column_size = element.data_type.get_size() / locations;
+            let ty = element.data_type.get_gl_type();
+
+            for column in 0..locations {
+                let location = self.attribute_count;
+                let offset = (element.offset + column * column_size) as *const _;
+
+                unsafe {
+                    gl::EnableVertexAttribArray(location);
+                    if element.data_type.is_integer() {
+                        gl::VertexAttribIPointer(location, count, ty, stride, offset);
+                    } else {
+                        gl::VertexAttribPointer(
+                            location,
+                            count,
+                            ty,
+                            element.normalized as u8,
+                            stride,
+                            offset,
+                        );
+                    }
+                    if divisor > 0 {
+                        gl::VertexAttribDivisor(location, divisor);
+                    }
+                }
+                self.attribute_count += 1;
+            }
+        }
+
+        self.unbind();
+        self.vertex_buffers.push(vertex_buffer);
+    }
+
+    pub fn set_index_buffer(&mut self, index_buffer: Rc<IndexBuffer>) {
+        self.bind();
+        index_buffer.bind();
+        self.unbind();
+        self.index_buffer = Some(index_buffer);
+    }
+
+    pub fn get_vertex_buffers(&self) -> &[Rc<VertexBuffer>] {
+        &self.vertex_buffers
+    }
+
+    pub fn get_index_buffer(&self) -> Option<&Rc<IndexBuffer>> {
+        self.index_buffer.as_ref()
+    }
+
+    pub fn get_attribute_count(&self) -> u32 {
+        self.attribute_count
+    }
+
+    // names the vertex array in debug output and frame captures
+    pub fn set_label(&self, label: &str) {
+        set_object_label(gl::VERTEX_ARRAY, self.renderer_id, label);
+    }
+}
+
+impl Drop for VertexArray {
+    fn drop(&mut self) {
+        state::forget_vertex_array(self.renderer_id);
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.renderer_id);
+        }
+    }
+}
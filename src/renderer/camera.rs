@@ -0,0 +1,229 @@
+use crate::math::{Frustum, Mat4, Quat, Ray, Transform, Vec3};
+
+const MAX_PITCH: f32 = 89.0 * std::f32::consts::PI / 180.0;
+
+pub trait Camera {
+    fn get_projection(&self) -> &Mat4;
+    fn get_view(&self) -> &Mat4;
+    fn get_view_projection(&self) -> &Mat4;
+    fn get_position(&self) -> Vec3;
+
+    fn get_frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.get_view_projection())
+    }
+
+    // world space ray through a point in normalized device coordinates
+    fn get_ray(&self, ndc_x: f32, ndc_y: f32) -> Ray {
+        let inverse = self
+            .get_view_projection()
+            .inverse()
+            .unwrap_or(Mat4::IDENTITY);
+        Ray::from_ndc(ndc_x, ndc_y, &inverse)
+    }
+}
+
+pub struct OrthographicCamera {
+    projection: Mat4,
+    view: Mat4,
+    view_projection: Mat4,
+    position: Vec3,
+    // around the Z axis, radians
+    rotation: f32,
+}
+
+impl OrthographicCamera {
+    pub fn new(left: f32, right: f32, bottom: f32, top: f32) -> Self {
+        let projection = Mat4::orthographic(left, right, bottom, top, -1.0, 1.0);
+
+        Self {
+            projection,
+            view: Mat4::IDENTITY,
+            view_projection: projection,
+            position: Vec3::ZERO,
+            rotation: 0.0,
+        }
+    }
+
+    pub fn set_projection(&mut self, left: f32, right: f32, bottom: f32, top: f32) {
+        self.projection = Mat4::orthographic(left, right, bottom, top, -1.0, 1.0);
+        self.view_projection = self.projection * self.view;
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+        self.recalculate_view();
+    }
+
+    pub fn get_rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+        self.recalculate_view();
+    }
+
+    fn recalculate_view(&mut self) {
+        let transform = Transform::new(
+            self.position,
+            Quat::from_rotation_z(self.rotation),
+            Vec3::ONE,
+        );
+        self.view = transform.inverse().to_mat4();
+        self.view_projection = self.projection * self.view;
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_projection(&self) -> &Mat4 {
+        &self.projection
+    }
+
+    fn get_view(&self) -> &Mat4 {
+        &self.view
+    }
+
+    fn get_view_projection(&self) -> &Mat4 {
+        &self.view_projection
+    }
+
+    fn get_position(&self) -> Vec3 {
+        self.position
+    }
+}
+
+// Orientation is given by yaw around +Y and pitch around the camera's right
+// axis, both in radians. Zero yaw and pitch look down -Z.
+pub struct PerspectiveCamera {
+    fov_y: f32,
+    aspect_ratio: f32,
+    near: f32,
+    far: f32,
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+    projection: Mat4,
+    view: Mat4,
+    view_projection: Mat4,
+}
+
+impl PerspectiveCamera {
+    pub fn new(fov_y: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        let mut camera = Self {
+            fov_y,
+            aspect_ratio,
+            near,
+            far,
+            position: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            projection: Mat4::IDENTITY,
+            view: Mat4::IDENTITY,
+            view_projection: Mat4::IDENTITY,
+        };
+        camera.recalculate_projection();
+        camera.recalculate_view();
+        camera
+    }
+
+    pub fn get_fov(&self) -> f32 {
+        self.fov_y
+    }
+
+    pub fn set_fov(&mut self, fov_y: f32) {
+        self.fov_y = fov_y;
+        self.recalculate_projection();
+    }
+
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.aspect_ratio = aspect_ratio;
+        self.recalculate_projection();
+    }
+
+    pub fn get_clip_planes(&self) -> (f32, f32) {
+        (self.near, self.far)
+    }
+
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far;
+        self.recalculate_projection();
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+        self.recalculate_view();
+    }
+
+    pub fn get_yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    // pitch is clamped short of straight up or down
+    pub fn set_rotation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+        self.recalculate_view();
+    }
+
+    pub fn look_at(&mut self, target: Vec3) {
+        let dir = (target - self.position).normalize();
+        self.set_rotation((-dir.x).atan2(-dir.z), dir.y.asin());
+    }
+
+    pub fn get_forward(&self) -> Vec3 {
+        forward_from_angles(self.yaw, self.pitch)
+    }
+
+    pub fn get_right(&self) -> Vec3 {
+        self.get_forward().cross(Vec3::Y).normalize()
+    }
+
+    pub fn get_up(&self) -> Vec3 {
+        self.get_right().cross(self.get_forward())
+    }
+
+    fn recalculate_projection(&mut self) {
+        self.projection = Mat4::perspective(self.fov_y, self.aspect_ratio, self.near, self.far);
+        self.view_projection = self.projection * self.view;
+    }
+
+    fn recalculate_view(&mut self) {
+        self.view = Mat4::look_at(self.position, self.position + self.get_forward(), Vec3::Y);
+        self.view_projection = self.projection * self.view;
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_projection(&self) -> &Mat4 {
+        &self.projection
+    }
+
+    fn get_view(&self) -> &Mat4 {
+        &self.view
+    }
+
+    fn get_view_projection(&self) -> &Mat4 {
+        &self.view_projection
+    }
+
+    fn get_position(&self) -> Vec3 {
+        self.position
+    }
+}
+
+pub fn forward_from_angles(yaw: f32, pitch: f32) -> Vec3 {
+    Vec3::new(
+        -yaw.sin() * pitch.cos(),
+        pitch.sin(),
+        -yaw.cos() * pitch.cos(),
+    )
+}
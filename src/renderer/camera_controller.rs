@@ -0,0 +1,567 @@
+use crate::{
+    events::{Event, EventHandler, Key, MouseButton},
+    input::InputHandler,
+    math::Vec3,
+    renderer::camera::{forward_from_angles, Camera, OrthographicCamera, PerspectiveCamera},
+};
+
+pub trait CameraController: EventHandler {
+    // `dt` in seconds
+    fn on_update(&mut self, dt: f32, input: &dyn InputHandler);
+}
+
+// Cursor movement since the previous update while `active` holds, so that
+// drags do not jump when a button is first pressed.
+fn mouse_delta(
+    last: &mut Option<(f64, f64)>,
+    input: &dyn InputHandler,
+    active: bool,
+) -> (f32, f32) {
+    let current = input.get_mouse_position();
+    let delta = match (*last, active) {
+        (Some((x, y)), true) => ((current.0 - x) as f32, (current.1 - y) as f32),
+        _ => (0.0, 0.0),
+    };
+    *last = if active { Some(current) } else { None };
+    delta
+}
+
+// Factor for a scroll of `y` wheel steps, positive when scrolling up. Steps
+// compound, so high resolution wheels reporting large or fractional deltas
+// scale as smoothly as a step at a time and never reach zero.
+fn scroll_scale(y: f64, speed: f32) -> f32 {
+    (-(y as f32) * speed).exp()
+}
+
+// 2D camera, WASD or dragging with the middle/right button pans, the mouse
+// wheel zooms and Q/E rotate when rotation is enabled.
+pub struct OrthographicCameraController {
+    camera: OrthographicCamera,
+    aspect_ratio: f32,
+    zoom_level: f32,
+    position: Vec3,
+    rotation: f32,
+    rotation_enabled: bool,
+    viewport_height: f32,
+    last_mouse_position: Option<(f64, f64)>,
+    pub pan_speed: f32,
+    pub rotation_speed: f32,
+    pub zoom_speed: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+}
+
+impl OrthographicCameraController {
+    pub fn new(viewport_width: u32, viewport_height: u32, rotation_enabled: bool) -> Self {
+        let aspect_ratio = viewport_width as f32 / viewport_height.max(1) as f32;
+        let zoom_level = 1.0;
+
+        Self {
+            camera: OrthographicCamera::new(
+                -aspect_ratio * zoom_level,
+                aspect_ratio * zoom_level,
+                -zoom_level,
+                zoom_level,
+            ),
+            aspect_ratio,
+            zoom_level,
+            position: Vec3::ZERO,
+            rotation: 0.0,
+            rotation_enabled,
+            viewport_height: viewport_height.max(1) as f32,
+            last_mouse_position: None,
+            pan_speed: 1.0,
+            rotation_speed: std::f32::consts::PI,
+            zoom_speed: 0.1,
+            min_zoom: 0.01,
+            max_zoom: 1000.0,
+        }
+    }
+
+    pub fn get_camera(&self) -> &OrthographicCamera {
+        &self.camera
+    }
+
+    pub fn get_zoom_level(&self) -> f32 {
+        self.zoom_level
+    }
+
+    // half the visible height in world units
+    pub fn set_zoom_level(&mut self, zoom_level: f32) {
+        self.zoom_level = zoom_level.clamp(self.min_zoom, self.max_zoom);
+        self.update_projection();
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+        self.camera.set_position(position);
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        self.aspect_ratio = width as f32 / height as f32;
+        self.viewport_height = height as f32;
+        self.update_projection();
+    }
+
+    fn update_projection(&mut self) {
+        let (w, h) = (self.aspect_ratio * self.zoom_level, self.zoom_level);
+        self.camera.set_projection(-w, w, -h, h);
+    }
+}
+
+impl CameraController for OrthographicCameraController {
+    fn on_update(&mut self, dt: f32, input: &dyn InputHandler) {
+        let (sin, cos) = self.rotation.sin_cos();
+        let right = Vec3::new(cos, sin, 0.0);
+        let up = Vec3::new(-sin, cos, 0.0);
+        // pan speed follows the zoom so it feels the same at every scale
+        let speed = self.pan_speed * self.zoom_level * dt;
+
+        let mut movement = Vec3::ZERO;
+        if input.is_key_pressed(Key::A) {
+            movement -= right;
+        }
+        if input.is_key_pressed(Key::D) {
+            movement += right;
+        }
+        if input.is_key_pressed(Key::S) {
+            movement -= up;
+        }
+        if input.is_key_pressed(Key::W) {
+            movement += up;
+        }
+        self.position += movement * speed;
+
+        let dragging = input.is_mouse_button_pressed(MouseButton::Middle)
+            || input.is_mouse_button_pressed(MouseButton::Right);
+        let (dx, dy) = mouse_delta(&mut self.last_mouse_position, input, dragging);
+        let world_per_pixel = 2.0 * self.zoom_level / self.viewport_height;
+        // window y points down
+        self.position -= (right * dx - up * dy) * world_per_pixel;
+
+        if self.rotation_enabled {
+            if input.is_key_pressed(Key::Q) {
+                self.rotation += self.rotation_speed * dt;
+            }
+            if input.is_key_pressed(Key::E) {
+                self.rotation -= self.rotation_speed * dt;
+            }
+            self.camera.set_rotation(self.rotation);
+        }
+
+        self.camera.set_position(self.position);
+    }
+}
+
+impl EventHandler for OrthographicCameraController {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        for e in events.iter() {
+            match e {
+                Event::MouseScrolled { y, .. } => {
+                    let zoom = self.zoom_level * scroll_scale(*y, self.zoom_speed);
+                    self.set_zoom_level(zoom);
+                }
+                Event::WindowResize { width, height } => self.resize(*width, *height),
+                _ => (),
+            }
+        }
+    }
+}
+
+// Orbits a target point, left drag rotates, middle/right drag pans the
+// target and the mouse wheel moves closer or further away.
+pub struct OrbitCameraController {
+    camera: PerspectiveCamera,
+    target: Vec3,
+    distance: f32,
+    yaw: f32,
+    pitch: f32,
+    viewport_height: f32,
+    last_mouse_position: Option<(f64, f64)>,
+    // radians per pixel
+    pub rotation_sensitivity: f32,
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+}
+
+impl OrbitCameraController {
+    pub fn new(
+        camera: PerspectiveCamera,
+        target: Vec3,
+        distance: f32,
+        viewport_height: u32,
+    ) -> Self {
+        let mut controller = Self {
+            yaw: camera.get_yaw(),
+            pitch: camera.get_pitch(),
+            camera,
+            target,
+            distance,
+            viewport_height: viewport_height.max(1) as f32,
+            last_mouse_position: None,
+            rotation_sensitivity: 0.005,
+            zoom_speed: 0.1,
+            min_distance: 0.01,
+            max_distance: 10000.0,
+        };
+        controller.update_camera();
+        controller
+    }
+
+    pub fn get_camera(&self) -> &PerspectiveCamera {
+        &self.camera
+    }
+
+    pub fn get_target(&self) -> Vec3 {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = target;
+        self.update_camera();
+    }
+
+    pub fn get_distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn set_distance(&mut self, distance: f32) {
+        self.distance = distance.clamp(self.min_distance, self.max_distance);
+        self.update_camera();
+    }
+
+    fn update_camera(&mut self) {
+        self.camera.set_rotation(self.yaw, self.pitch);
+        // read back the clamped pitch
+        self.pitch = self.camera.get_pitch();
+        let forward = forward_from_angles(self.yaw, self.pitch);
+        self.camera
+            .set_position(self.target - forward * self.distance);
+    }
+}
+
+impl CameraController for OrbitCameraController {
+    fn on_update(&mut self, _dt: f32, input: &dyn InputHandler) {
+        let rotating = input.is_mouse_button_pressed(MouseButton::Left);
+        let panning = input.is_mouse_button_pressed(MouseButton::Middle)
+            || input.is_mouse_button_pressed(MouseButton::Right);
+        let (dx, dy) = mouse_delta(&mut self.last_mouse_position, input, rotating || panning);
+
+        if rotating {
+            self.yaw -= dx * self.rotation_sensitivity;
+            self.pitch -= dy * self.rotation_sensitivity;
+        } else if panning {
+            // keep the point under the cursor under the cursor
+            let half_fov = self.camera.get_fov() * 0.5;
+            let world_per_pixel = 2.0 * self.distance * half_fov.tan() / self.viewport_height;
+            let offset = self.camera.get_right() * -dx + self.camera.get_up() * dy;
+            self.target += offset * world_per_pixel;
+        }
+
+        self.update_camera();
+    }
+}
+
+impl EventHandler for OrbitCameraController {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        for e in events.iter() {
+            match e {
+                Event::MouseScrolled { y, .. } => {
+                    let distance = self.distance * scroll_scale(*y, self.zoom_speed);
+                    self.set_distance(distance);
+                }
+                Event::WindowResize { width, height } if *width > 0 && *height > 0 => {
+                    self.camera.set_aspect_ratio(*width as f32 / *height as f32);
+                    self.viewport_height = *height as f32;
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+// First person camera, WASD moves, E/Q or Space/LeftControl move up and
+// down, holding the right button looks around and LeftShift speeds up.
+// The mouse wheel changes the movement speed.
+pub struct FlyCameraController {
+    camera: PerspectiveCamera,
+    last_mouse_position: Option<(f64, f64)>,
+    // world units per second
+    pub speed: f32,
+    pub fast_multiplier: f32,
+    // radians per pixel
+    pub look_sensitivity: f32,
+}
+
+impl FlyCameraController {
+    pub fn new(camera: PerspectiveCamera) -> Self {
+        Self {
+            camera,
+            last_mouse_position: None,
+            speed: 5.0,
+            fast_multiplier: 4.0,
+            look_sensitivity: 0.003,
+        }
+    }
+
+    pub fn get_camera(&self) -> &PerspectiveCamera {
+        &self.camera
+    }
+
+    pub fn get_camera_mut(&mut self) -> &mut PerspectiveCamera {
+        &mut self.camera
+    }
+}
+
+impl CameraController for FlyCameraController {
+    fn on_update(&mut self, dt: f32, input: &dyn InputHandler) {
+        let looking = input.is_mouse_button_pressed(MouseButton::Right);
+        let (dx, dy) = mouse_delta(&mut self.last_mouse_position, input, looking);
+        if looking {
+            self.camera.set_rotation(
+                self.camera.get_yaw() - dx * self.look_sensitivity,
+                self.camera.get_pitch() - dy * self.look_sensitivity,
+            );
+        }
+
+        let forward = self.camera.get_forward();
+        let right = self.camera.get_right();
+
+        let mut movement = Vec3::ZERO;
+        if input.is_key_pressed(Key::W) {
+            movement += forward;
+        }
+        if input.is_key_pressed(Key::S) {
+            movement -= forward;
+        }
+        if input.is_key_pressed(Key::D) {
+            movement += right;
+        }
+        if input.is_key_pressed(Key::A) {
+            movement -= right;
+        }
+        if input.is_key_pressed(Key::E) || input.is_key_pressed(Key::Space) {
+            movement += Vec3::Y;
+        }
+        if input.is_key_pressed(Key::Q) || input.is_key_pressed(Key::LeftControl) {
+            movement -= Vec3::Y;
+        }
+
+        if movement != Vec3::ZERO {
+            let mut speed = self.speed;
+            if input.is_key_pressed(Key::LeftShift) {
+                speed *= self.fast_multiplier;
+            }
+            let position = self.camera.get_position() + movement.normalize() * speed * dt;
+            self.camera.set_position(position);
+        }
+    }
+}
+
+impl EventHandler for FlyCameraController {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        for e in events.iter() {
+            match e {
+                Event::MouseScrolled { y, .. } => {
+                    self.speed = (self.speed / scroll_scale(*y, 0.1)).max(0.01);
+                }
+                Event::WindowResize { width, height } if *width > 0 && *height > 0 => {
+                    self.camera.set_aspect_ratio(*width as f32 / *height as f32);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputState;
+
+    fn send(handler: &mut dyn EventHandler, events: Vec<Event>) {
+        let mut events = events;
+        handler.handle_events(&mut events);
+    }
+
+    fn scroll(y: f64) -> Vec<Event> {
+        vec![Event::MouseScrolled { x: 0.0, y }]
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn perspective() -> PerspectiveCamera {
+        PerspectiveCamera::new(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0)
+    }
+
+    #[test]
+    fn scrolling_zooms_smoothly() {
+        let mut controller = OrthographicCameraController::new(200, 100, false);
+        send(&mut controller, scroll(1.0));
+        assert_near(controller.get_zoom_level(), (-0.1f32).exp());
+        send(&mut controller, scroll(-1.0));
+        assert_near(controller.get_zoom_level(), 1.0);
+
+        // one large delta zooms as far as as many single steps
+        send(&mut controller, scroll(20.0));
+        let large = controller.get_zoom_level();
+        controller.set_zoom_level(1.0);
+        for _ in 0..20 {
+            send(&mut controller, scroll(1.0));
+        }
+        assert_near(controller.get_zoom_level(), large);
+        assert!(large > controller.min_zoom);
+
+        send(&mut controller, scroll(1000.0));
+        assert_eq!(controller.get_zoom_level(), controller.min_zoom);
+    }
+
+    #[test]
+    fn orthographic_keys_pan_with_the_zoom() {
+        let mut input = InputState::default();
+        send(&mut input, vec![Event::KeyPressed { key: Key::D }]);
+
+        let mut controller = OrthographicCameraController::new(200, 100, false);
+        controller.on_update(0.5, &input);
+        assert_vec3_eq(
+            controller.get_camera().get_position(),
+            Vec3::new(0.5, 0.0, 0.0),
+        );
+
+        controller.set_zoom_level(4.0);
+        controller.on_update(0.5, &input);
+        assert_vec3_eq(
+            controller.get_camera().get_position(),
+            Vec3::new(2.5, 0.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn orthographic_drags_follow_the_cursor() {
+        let mut input = InputState::default();
+        let mut controller = OrthographicCameraController::new(200, 100, false);
+
+        send(
+            &mut input,
+            vec![
+                Event::MouseMoved { x: 50.0, y: 50.0 },
+                Event::MouseButtonPressed {
+                    btn: MouseButton::Right,
+                },
+            ],
+        );
+        // the press does not move the camera
+        controller.on_update(0.016, &input);
+        assert_vec3_eq(controller.get_camera().get_position(), Vec3::ZERO);
+
+        // 100 pixels are the visible height of 2 units, the view moves with
+        // the cursor, so the camera against it, window y points down
+        send(&mut input, vec![Event::MouseMoved { x: 60.0, y: 30.0 }]);
+        controller.on_update(0.016, &input);
+        assert_vec3_eq(
+            controller.get_camera().get_position(),
+            Vec3::new(-0.2, -0.4, 0.0),
+        );
+    }
+
+    #[test]
+    fn orbit_keeps_its_distance_to_the_target() {
+        let target = Vec3::new(1.0, 2.0, 3.0);
+        let mut controller = OrbitCameraController::new(perspective(), target, 5.0, 100);
+        assert_vec3_eq(
+            controller.get_camera().get_position(),
+            target + Vec3::new(0.0, 0.0, 5.0),
+        );
+
+        let mut input = InputState::default();
+        send(
+            &mut input,
+            vec![Event::MouseButtonPressed {
+                btn: MouseButton::Left,
+            }],
+        );
+        controller.on_update(0.016, &input);
+        send(&mut input, vec![Event::MouseMoved { x: 100.0, y: -50.0 }]);
+        controller.on_update(0.016, &input);
+
+        let camera = controller.get_camera();
+        assert_near(camera.get_yaw(), -0.5);
+        assert_near(camera.get_pitch(), 0.25);
+        assert_near((camera.get_position() - target).length(), 5.0);
+        assert_vec3_eq(camera.get_position() + camera.get_forward() * 5.0, target);
+    }
+
+    #[test]
+    fn orbit_scrolling_scales_the_distance() {
+        let mut controller = OrbitCameraController::new(perspective(), Vec3::ZERO, 10.0, 100);
+        send(&mut controller, scroll(2.0));
+        assert_near(controller.get_distance(), 10.0 * (-0.2f32).exp());
+        send(&mut controller, scroll(100.0));
+        assert!(controller.get_distance() > 0.0);
+    }
+
+    #[test]
+    fn orbit_panning_moves_the_target() {
+        let mut controller = OrbitCameraController::new(perspective(), Vec3::ZERO, 1.0, 100);
+        let mut input = InputState::default();
+        send(
+            &mut input,
+            vec![Event::MouseButtonPressed {
+                btn: MouseButton::Middle,
+            }],
+        );
+        controller.on_update(0.016, &input);
+
+        // at a 90 degree fov the view is 2 units high at distance 1
+        send(&mut input, vec![Event::MouseMoved { x: -50.0, y: 0.0 }]);
+        controller.on_update(0.016, &input);
+        assert_vec3_eq(controller.get_target(), Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn fly_moves_along_the_view() {
+        let mut controller = FlyCameraController::new(perspective());
+        let mut input = InputState::default();
+        send(&mut input, vec![Event::KeyPressed { key: Key::W }]);
+        controller.on_update(0.5, &input);
+        assert_vec3_eq(
+            controller.get_camera().get_position(),
+            Vec3::new(0.0, 0.0, -2.5),
+        );
+
+        send(
+            &mut input,
+            vec![
+                Event::KeyReleased { key: Key::W },
+                Event::KeyPressed { key: Key::D },
+                Event::KeyPressed {
+                    key: Key::LeftShift,
+                },
+            ],
+        );
+        controller.on_update(0.5, &input);
+        assert_vec3_eq(
+            controller.get_camera().get_position(),
+            Vec3::new(10.0, 0.0, -2.5),
+        );
+    }
+
+    #[test]
+    fn fly_scrolling_changes_the_speed() {
+        let mut controller = FlyCameraController::new(perspective());
+        send(&mut controller, scroll(1.0));
+        assert_near(controller.speed, 5.0 * 0.1f32.exp());
+        send(&mut controller, scroll(-1000.0));
+        assert_eq!(controller.speed, 0.01);
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Sender},
+    thread::{self, JoinHandle},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    events::{Event, EventHandler, Key},
+    log_error, log_info,
+    renderer::{bitmap::Bitmap, framebuffer::check_status, state},
+};
+
+// Readbacks are read into a ring of pixel buffers and mapped this many frames
+// later, by then the GPU is done with them and mapping does not stall.
+const PBO_COUNT: usize = 3;
+
+// decoded frame and the files it is written to
+type WriteJob = (Bitmap, Vec<PathBuf>);
+
+struct PendingReadback {
+    pbo: u32,
+    frame: u64,
+    width: u32,
+    height: u32,
+    targets: Vec<PathBuf>,
+}
+
+struct FrameSequence {
+    directory: PathBuf,
+    frame_interval: f64,
+    accumulated_time: f64,
+    next_frame: u32,
+}
+
+impl FrameSequence {
+    fn new(directory: PathBuf, frame_rate: f64) -> Self {
+        Self {
+            directory,
+            frame_interval: 1.0 / frame_rate,
+            // the first frame is taken right away
+            accumulated_time: 1.0 / frame_rate,
+            next_frame: 0,
+        }
+    }
+
+    // Numbers of the frames due for the time accumulated so far, several when
+    // the simulation stepped further than one interval, none when it stepped
+    // less. Sums of steps rarely add up to an interval exactly, the tolerance
+    // keeps e.g. two steps of half an interval from dropping a frame.
+    fn take_due_frames(&mut self) -> Range<u32> {
+        let first = self.next_frame;
+        let tolerance = self.frame_interval * 1e-6;
+        while self.accumulated_time + tolerance >= self.frame_interval {
+            self.accumulated_time -= self.frame_interval;
+            self.next_frame += 1;
+        }
+        first..self.next_frame
+    }
+
+    fn get_frame_path(&self, frame: u32) -> PathBuf {
+        self.directory.join(format!("frame_{:06}.png", frame))
+    }
+}
+
+// Captures the default framebuffer into PNG files, either as single
+// screenshots or as numbered frame sequences advanced by simulated time.
+// Encoding happens on a background thread.
+pub struct FrameCapture {
+    pbos: [u32; PBO_COUNT],
+    pbo_sizes: [usize; PBO_COUNT],
+    next_pbo: usize,
+    frame: u64,
+    pending: VecDeque<PendingReadback>,
+    screenshot_requests: Vec<PathBuf>,
+    screenshot_directory: PathBuf,
+    screenshot_key: Option<Key>,
+    screenshot_count: u32,
+    sequence: Option<FrameSequence>,
+    // simulated seconds added each captured frame, for simulations that do
+    // not call `advance` themselves
+    fixed_step: Option<f64>,
+    // single sampled copy of the default framebuffer, which may be
+    // multisampled and can then not be read directly
+    resolve_framebuffer: u32,
+    resolve_renderbuffer: u32,
+    resolve_size: (u32, u32),
+    writer: Option<(Sender<WriteJob>, JoinHandle<()>)>,
+}
+
+impl FrameCapture {
+    // requires a current OpenGL context
+    pub fn new() -> Self {
+        let mut pbos = [0; PBO_COUNT];
+        let mut resolve_framebuffer = 0;
+        let mut resolve_renderbuffer = 0;
+        unsafe {
+            gl::GenBuffers(PBO_COUNT as i32, pbos.as_mut_ptr());
+            gl::GenFramebuffers(1, &mut resolve_framebuffer);
+            gl::GenRenderbuffers(1, &mut resolve_renderbuffer);
+        }
+
+        let (sender, receiver) = mpsc::channel::<WriteJob>();
+        let handle = thread::spawn(move || {
+            for (bitmap, targets) in receiver {
+                for target in targets {
+                    if let Err(e) = bitmap.save_png(&target) {
+                        log_error!("{}", e);
+                    }
+                }
+            }
+        });
+
+        Self {
+            pbos,
+            pbo_sizes: [0; PBO_COUNT],
+            next_pbo: 0,
+            frame: 0,
+            pending: VecDeque::new(),
+            screenshot_requests: Vec::new(),
+            screenshot_directory: PathBuf::from("."),
+            screenshot_key: Some(Key::F12),
+            screenshot_count: 0,
+            sequence: None,
+            fixed_step: None,
+            resolve_framebuffer,
+            resolve_renderbuffer,
+            resolve_size: (0, 0),
+            writer: Some((sender, handle)),
+        }
+    }
+
+    pub fn set_screenshot_key(&mut self, key: Option<Key>) {
+        self.screenshot_key = key;
+    }
+
+    pub fn set_screenshot_directory<P: AsRef<Path>>(&mut self, directory: P) {
+        self.screenshot_directory = directory.as_ref().to_path_buf();
+    }
+
+    // saved once the current frame has been captured
+    pub fn screenshot<P: AsRef<Path>>(&mut self, path: P) {
+        self.screenshot_requests.push(path.as_ref().to_path_buf());
+    }
+
+    pub fn start_sequence<P: AsRef<Path>>(&mut self, directory: P, frame_rate: f64) {
+        assert!(frame_rate > 0.0, "Frame rate must be positive.");
+
+        if let Err(e) = std::fs::create_dir_all(directory.as_ref()) {
+            log_error!("Failed to create {:?}: {}", directory.as_ref(), e);
+            return;
+        }
+
+        log_info!(
+            "Recording frames to {:?} at {} fps",
+            directory.as_ref(),
+            frame_rate
+        );
+
+        self.sequence = Some(FrameSequence::new(
+            directory.as_ref().to_path_buf(),
+            frame_rate,
+        ));
+    }
+
+    pub fn stop_sequence(&mut self) {
+        if let Some(sequence) = self.sequence.take() {
+            log_info!("Recorded {} frames", sequence.next_frame);
+        }
+        self.flush();
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    // Advances the recording clock by simulated time. A sequence frame is
+    // written every 1 / frame_rate simulated seconds: frames are repeated when
+    // the simulation steps further than that in a rendered frame and skipped
+    // when it steps less, so playback speed never depends on wall-clock time.
+    pub fn advance(&mut self, simulated_dt: f64) {
+        if let Some(sequence) = &mut self.sequence {
+            sequence.accumulated_time += simulated_dt;
+        }
+    }
+
+    // Advances the recording clock by `step` simulated seconds every captured
+    // frame, e.g. 1 / frame_rate to record every rendered frame. Only for
+    // simulations stepping by a fixed amount per rendered frame without
+    // calling `advance`.
+    pub fn set_fixed_step(&mut self, step: Option<f64>) {
+        assert!(
+            step.is_none_or(|step| step > 0.0),
+            "Fixed step must be positive."
+        );
+        self.fixed_step = step;
+    }
+
+    pub fn get_fixed_step(&self) -> Option<f64> {
+        self.fixed_step
+    }
+
+    // Call once the frame is rendered, before the buffers are swapped.
+    pub fn capture_frame(&mut self, width: u32, height: u32) {
+        let mut targets: Vec<PathBuf> = self.screenshot_requests.drain(..).collect();
+
+        if let Some(sequence) = &mut self.sequence {
+            sequence.accumulated_time += self.fixed_step.unwrap_or(0.0);
+            for frame in sequence.take_due_frames() {
+                targets.push(sequence.get_frame_path(frame));
+            }
+        }
+
+        if !targets.is_empty() && width > 0 && height > 0 {
+            // all buffers in flight, wait for the oldest
+            if self.pending.len() == PBO_COUNT {
+                self.complete_oldest();
+            }
+            self.read_pixels(width, height, targets);
+        }
+
+        // read back everything that had time to finish
+        while let Some(oldest) = self.pending.front() {
+            if self.frame - oldest.frame < (PBO_COUNT - 1) as u64 {
+                break;
+            }
+            self.complete_oldest();
+        }
+
+        self.frame += 1;
+    }
+
+    // completes all pending readbacks, stalling until the GPU is done
+    pub fn flush(&mut self) {
+        while !self.pending.is_empty() {
+            self.complete_oldest();
+        }
+    }
+
+    fn read_pixels(&mut self, width: u32, height: u32, targets: Vec<PathBuf>) {
+        let index = self.next_pbo;
+        let pbo = self.pbos[index];
+        let size = (width * height * 4) as usize;
+
+        self.resolve(width, height);
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.resolve_framebuffer);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            state::bind_buffer(gl::PIXEL_PACK_BUFFER, pbo);
+            if self.pbo_sizes[index] != size {
+                gl::BufferData(
+                    gl::PIXEL_PACK_BUFFER,
+                    size as isize,
+                    std::ptr::null(),
+                    gl::STREAM_READ,
+                );
+                self.pbo_sizes[index] = size;
+            }
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::ReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                gl::RGBA,
+                gl::UNSIGNED_BYTE,
+                std::ptr::null_mut(),
+            );
+            state::bind_buffer(gl::PIXEL_PACK_BUFFER, 0);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+
+        self.pending.push_back(PendingReadback {
+            pbo,
+            frame: self.frame,
+            width,
+            height,
+            targets,
+        });
+        self.next_pbo = (self.next_pbo + 1) % PBO_COUNT;
+    }
+
+    // copies the back buffer into the resolve framebuffer, resolving samples
+    fn resolve(&mut self, width: u32, height: u32) {
+        unsafe {
+            if self.resolve_size != (width, height) {
+                gl::BindRenderbuffer(gl::RENDERBUFFER, self.resolve_renderbuffer);
+                gl::RenderbufferStorage(gl::RENDERBUFFER, gl::RGBA8, width as i32, height as i32);
+                gl::BindFramebuffer(gl::FRAMEBUFFER, self.resolve_framebuffer);
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::RENDERBUFFER,
+                    self.resolve_renderbuffer,
+                );
+                check_status("Capture framebuffer");
+                self.resolve_size = (width, height);
+            }
+
+            let (w, h) = (width as i32, height as i32);
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+            gl::ReadBuffer(gl::BACK);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.resolve_framebuffer);
+            gl::BlitFramebuffer(0, 0, w, h, 0, 0, w, h, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+        }
+    }
+
+    fn complete_oldest(&mut self) {
+        let readback = match self.pending.pop_front() {
+            Some(r) => r,
+            None => return,
+        };
+
+        let size = (readback.width * readback.height * 4) as usize;
+        let mut data = vec![0u8; size];
+
+        unsafe {
+            state::bind_buffer(gl::PIXEL_PACK_BUFFER, readback.pbo);
+            let ptr = gl::MapBuffer(gl::PIXEL_PACK_BUFFER, gl::READ_ONLY) as *const u8;
+            if ptr.is_null() {
+                log_error!("Failed to map capture pixel buffer");
+            } else {
+                std::ptr::copy_nonoverlapping(ptr, data.as_mut_ptr(), size);
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            }
+            state::bind_buffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        let mut bitmap = Bitmap::from_raw(readback.width, readback.height, data);
+        bitmap.flip_vertical();
+
+        if let Some((sender, _)) = &self.writer {
+            sender.send((bitmap, readback.targets)).ok();
+        }
+    }
+
+    fn next_screenshot_path(&mut self) -> PathBuf {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.screenshot_count += 1;
+
+        self.screenshot_directory.join(format!(
+            "screenshot_{}_{}.png",
+            timestamp, self.screenshot_count
+        ))
+    }
+}
+
+impl EventHandler for FrameCapture {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        let key = match self.screenshot_key {
+            Some(k) => k,
+            None => return,
+        };
+
+        for e in events.iter() {
+            if let Event::KeyPressed { key: pressed } = e {
+                if *pressed == key {
+                    let path = self.next_screenshot_path();
+                    log_info!("Saving screenshot to {:?}", path);
+                    self.screenshot(path);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for FrameCapture {
+    fn drop(&mut self) {
+        self.flush();
+
+        for &pbo in self.pbos.iter() {
+            state::forget_buffer(pbo);
+        }
+        unsafe {
+            gl::DeleteBuffers(PBO_COUNT as i32, self.pbos.as_ptr());
+            gl::DeleteFramebuffers(1, &self.resolve_framebuffer);
+            gl::DeleteRenderbuffers(1, &self.resolve_renderbuffer);
+        }
+
+        // finish writing queued images
+        if let Some((sender, handle)) = self.writer.take() {
+            drop(sender);
+            handle.join().ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // frames written for each simulated step
+    fn record(frame_rate: f64, steps: &[f64]) -> Vec<usize> {
+        let mut sequence = FrameSequence::new(PathBuf::new(), frame_rate);
+        steps
+            .iter()
+            .map(|step| {
+                sequence.accumulated_time += step;
+                sequence.take_due_frames().len()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn first_frame_is_taken_right_away() {
+        assert_eq!(record(30.0, &[0.0, 0.0]), vec![1, 0]);
+    }
+
+    #[test]
+    fn matching_steps_write_one_frame_each() {
+        let frames = record(60.0, &[1.0 / 60.0; 1000]);
+        // the first step is one interval past the first frame
+        assert_eq!(frames[0], 2);
+        assert!(frames[1..].iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn long_steps_repeat_frames() {
+        assert_eq!(record(30.0, &[0.0, 0.1, 1.0 / 30.0]), vec![1, 3, 1]);
+    }
+
+    #[test]
+    fn short_steps_skip_frames() {
+        let frames = record(30.0, &[0.0, 1.0 / 60.0, 1.0 / 60.0, 1.0 / 60.0, 1.0 / 60.0]);
+        assert_eq!(frames, vec![1, 0, 1, 0, 1]);
+
+        // simulated seconds decide the frame count, not the number of steps
+        let frames = record(24.0, &[1.0 / 144.0; 1440]);
+        assert_eq!(frames.iter().sum::<usize>(), 1 + 240);
+    }
+
+    #[test]
+    fn frames_are_numbered_in_order() {
+        let mut sequence = FrameSequence::new(PathBuf::from("out"), 10.0);
+        assert_eq!(sequence.take_due_frames(), 0..1);
+        sequence.accumulated_time += 0.3;
+        assert_eq!(sequence.take_due_frames(), 1..4);
+        assert_eq!(
+            sequence.get_frame_path(3),
+            Path::new("out").join("frame_000003.png")
+        );
+    }
+}
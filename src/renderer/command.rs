@@ -0,0 +1,117 @@
+use crate::math::{Vec3, Vec4};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    // normalized device coordinates
+    pub position: Vec3,
+    pub color: Vec4,
+}
+
+impl Vertex {
+    pub fn new(position: Vec3, color: Vec4) -> Self {
+        Self { position, color }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shading {
+    Flat(Vec4),
+    VertexColor,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderCommand {
+    SetViewport {
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    SetDepthTest(bool),
+    Clear {
+        color: Option<Vec4>,
+        depth: Option<f32>,
+    },
+    DrawTriangles {
+        vertices: Vec<Vertex>,
+        indices: Option<Vec<u32>>,
+        shading: Shading,
+    },
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct CommandBuffer {
+    commands: Vec<RenderCommand>,
+}
+
+impl CommandBuffer {
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn set_viewport(&mut self, x: i32, y: i32, width: u32, height: u32) {
+        self.commands.push(RenderCommand::SetViewport {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    pub fn set_depth_test(&mut self, enabled: bool) {
+        self.commands.push(RenderCommand::SetDepthTest(enabled));
+    }
+
+    pub fn clear(&mut self, color: Option<Vec4>, depth: Option<f32>) {
+        self.commands.push(RenderCommand::Clear { color, depth });
+    }
+
+    pub fn draw_triangles(&mut self, vertices: &[Vertex], shading: Shading) {
+        assert!(
+            vertices.len().is_multiple_of(3),
+            "Triangle list vertex count must be a multiple of 3."
+        );
+
+        self.commands.push(RenderCommand::DrawTriangles {
+            vertices: vertices.to_vec(),
+            indices: None,
+            shading,
+        });
+    }
+
+    pub fn draw_indexed(&mut self, vertices: &[Vertex], indices: &[u32], shading: Shading) {
+        assert!(
+            indices.len().is_multiple_of(3),
+            "Triangle list index count must be a multiple of 3."
+        );
+        assert!(
+            indices.iter().all(|&i| (i as usize) < vertices.len()),
+            "Index out of range of the vertex list."
+        );
+
+        self.commands.push(RenderCommand::DrawTriangles {
+            vertices: vertices.to_vec(),
+            indices: Some(indices.to_vec()),
+            shading,
+        });
+    }
+
+    pub fn get_commands(&self) -> &[RenderCommand] {
+        &self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn reset(&mut self) {
+        self.commands.clear();
+    }
+}
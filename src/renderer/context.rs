@@ -1,26 +1,368 @@
+use std::{collections::HashSet, ffi::CStr, fmt};
+
+use gl::types::GLenum;
 use glfw::Context;
 
-use crate::{log_info, window::glfw::GLFWWindow};
+use crate::{
+    log_info, log_warn,
+    renderer::{debug::enable_debug_output, state},
+    window::glfw::GLFWWindow,
+};
+
+// Profiles, version queries and indexed extension strings used by `GpuInfo`
+// need OpenGL 3.2.
+pub const MIN_GL_VERSION: (u32, u32) = (3, 2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlProfile {
+    Core,
+    Compatibility,
+    // lets the driver pick
+    Any,
+}
+
+// The context requested from the window system. The driver may hand out a
+// newer version than asked for, what was actually created is in `GpuInfo`.
+#[derive(Debug, Clone)]
+pub struct ContextConfig {
+    pub version: (u32, u32),
+    pub profile: GlProfile,
+    // required for core profiles on macOS
+    pub forward_compatible: bool,
+    // MSAA samples of the default framebuffer, 0 disables multisampling
+    pub samples: u32,
+    // whether writes to the default framebuffer are converted to sRGB
+    pub srgb: bool,
+    pub depth_bits: u32,
+    pub stencil_bits: u32,
+    pub debug: bool,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            version: (3, 3),
+            profile: GlProfile::Core,
+            forward_compatible: true,
+            samples: 0,
+            srgb: false,
+            depth_bits: 24,
+            stencil_bits: 8,
+            debug: cfg!(debug_assertions),
+        }
+    }
+}
+
+impl ContextConfig {
+    pub fn with_version(mut self, major: u32, minor: u32) -> Self {
+        self.version = (major, minor);
+        self.validate();
+        self
+    }
+
+    pub fn with_profile(mut self, profile: GlProfile) -> Self {
+        self.profile = profile;
+        self
+    }
+
+    pub fn with_forward_compatible(mut self, forward_compatible: bool) -> Self {
+        self.forward_compatible = forward_compatible;
+        self
+    }
+
+    pub fn with_samples(mut self, samples: u32) -> Self {
+        self.samples = samples;
+        self
+    }
+
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn with_depth_stencil_bits(mut self, depth_bits: u32, stencil_bits: u32) -> Self {
+        self.depth_bits = depth_bits;
+        self.stencil_bits = stencil_bits;
+        self
+    }
+
+    pub fn with_debug(mut self, debug: bool) -> Self {
+        self.debug = debug;
+        self
+    }
+
+    // called before the context is created, as the fields are public
+    pub fn validate(&self) {
+        assert!(
+            self.version >= MIN_GL_VERSION,
+            "OpenGL {}.{} is not supported, the minimum is {}.{}.",
+            self.version.0,
+            self.version.1,
+            MIN_GL_VERSION.0,
+            MIN_GL_VERSION.1
+        );
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GpuLimits {
+    pub max_texture_size: i32,
+    pub max_cube_map_size: i32,
+    pub max_3d_texture_size: i32,
+    pub max_array_texture_layers: i32,
+    pub max_texture_units: i32,
+    pub max_combined_texture_units: i32,
+    pub max_vertex_attribs: i32,
+    pub max_uniform_block_size: i32,
+    pub max_uniform_buffer_bindings: i32,
+    pub max_color_attachments: i32,
+    pub max_draw_buffers: i32,
+    pub max_samples: i32,
+    pub max_renderbuffer_size: i32,
+    pub max_viewport_size: (i32, i32),
+}
+
+// What the driver reports about the created context, queried once by
+// `RenderingContext::init`.
+#[derive(Debug, Clone)]
+pub struct GpuInfo {
+    vendor: String,
+    renderer: String,
+    version: String,
+    glsl_version: String,
+    major_version: i32,
+    minor_version: i32,
+    profile: GlProfile,
+    samples: i32,
+    limits: GpuLimits,
+    extensions: HashSet<String>,
+}
+
+impl GpuInfo {
+    // requires a current OpenGL context
+    pub fn query() -> Self {
+        let mut major_version = 0;
+        let mut minor_version = 0;
+        let mut profile_mask = 0;
+        let mut samples = 0;
+        let mut extension_count = 0;
+        let mut max_viewport_size = [0; 2];
+        unsafe {
+            gl::GetIntegerv(gl::MAJOR_VERSION, &mut major_version);
+            gl::GetIntegerv(gl::MINOR_VERSION, &mut minor_version);
+            gl::GetIntegerv(gl::CONTEXT_PROFILE_MASK, &mut profile_mask);
+            gl::GetIntegerv(gl::SAMPLES, &mut samples);
+            gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut extension_count);
+            gl::GetIntegerv(gl::MAX_VIEWPORT_DIMS, max_viewport_size.as_mut_ptr());
+        }
+
+        let profile = if profile_mask as GLenum & gl::CONTEXT_CORE_PROFILE_BIT != 0 {
+            GlProfile::Core
+        } else if profile_mask as GLenum & gl::CONTEXT_COMPATIBILITY_PROFILE_BIT != 0 {
+            GlProfile::Compatibility
+        } else {
+            GlProfile::Any
+        };
+
+        let limits = GpuLimits {
+            max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+            max_cube_map_size: get_integer(gl::MAX_CUBE_MAP_TEXTURE_SIZE),
+            max_3d_texture_size: get_integer(gl::MAX_3D_TEXTURE_SIZE),
+            max_array_texture_layers: get_integer(gl::MAX_ARRAY_TEXTURE_LAYERS),
+            max_texture_units: get_integer(gl::MAX_TEXTURE_IMAGE_UNITS),
+            max_combined_texture_units: get_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS),
+            max_vertex_attribs: get_integer(gl::MAX_VERTEX_ATTRIBS),
+            max_uniform_block_size: get_integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+            max_uniform_buffer_bindings: get_integer(gl::MAX_UNIFORM_BUFFER_BINDINGS),
+            max_color_attachments: get_integer(gl::MAX_COLOR_ATTACHMENTS),
+            max_draw_buffers: get_integer(gl::MAX_DRAW_BUFFERS),
+            max_samples: get_integer(gl::MAX_SAMPLES),
+            max_renderbuffer_size: get_integer(gl::MAX_RENDERBUFFER_SIZE),
+            max_viewport_size: (max_viewport_size[0], max_viewport_size[1]),
+        };
+
+        let extensions = (0..extension_count.max(0) as u32)
+            .map(|i| unsafe { gl::GetStringi(gl::EXTENSIONS, i) })
+            .filter(|name| !name.is_null())
+            .map(|name| {
+                unsafe { CStr::from_ptr(name as *const _) }
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+
+        Self {
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            version: get_string(gl::VERSION),
+            glsl_version: get_string(gl::SHADING_LANGUAGE_VERSION),
+            major_version,
+            minor_version,
+            profile,
+            samples,
+            limits,
+            extensions,
+        }
+    }
+
+    pub fn get_vendor(&self) -> &str {
+        &self.vendor
+    }
+
+    pub fn get_renderer(&self) -> &str {
+        &self.renderer
+    }
+
+    // the full driver version string, e.g. "4.6.0 NVIDIA 535.54.03"
+    pub fn get_version_string(&self) -> &str {
+        &self.version
+    }
+
+    pub fn get_version(&self) -> (i32, i32) {
+        (self.major_version, self.minor_version)
+    }
+
+    pub fn get_glsl_version(&self) -> &str {
+        &self.glsl_version
+    }
+
+    pub fn get_profile(&self) -> GlProfile {
+        self.profile
+    }
+
+    pub fn get_samples(&self) -> i32 {
+        self.samples
+    }
+
+    pub fn get_limits(&self) -> &GpuLimits {
+        &self.limits
+    }
+
+    pub fn get_extensions(&self) -> &HashSet<String> {
+        &self.extensions
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    pub fn is_version_at_least(&self, major: i32, minor: i32) -> bool {
+        (self.major_version, self.minor_version) >= (major, minor)
+    }
+}
+
+impl fmt::Display for GpuInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Vendor: {}", self.vendor)?;
+        writeln!(f, "Renderer: {}", self.renderer)?;
+        writeln!(
+            f,
+            "OpenGL: {}.{} {:?} ({})",
+            self.major_version, self.minor_version, self.profile, self.version
+        )?;
+        writeln!(f, "GLSL: {}", self.glsl_version)?;
+        writeln!(f, "Samples: {}", self.samples)?;
+        writeln!(f, "Max texture size: {}", self.limits.max_texture_size)?;
+        writeln!(
+            f,
+            "Max texture units: {} ({} combined)",
+            self.limits.max_texture_units, self.limits.max_combined_texture_units
+        )?;
+        writeln!(
+            f,
+            "Max color attachments: {}",
+            self.limits.max_color_attachments
+        )?;
+        writeln!(f, "Max samples: {}", self.limits.max_samples)?;
+        write!(f, "Extensions: {}", self.extensions.len())
+    }
+}
+
+fn get_string(name: GLenum) -> String {
+    let string = unsafe { gl::GetString(name) };
+    if string.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(string as *const _) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn get_integer(name: GLenum) -> i32 {
+    let mut value = 0;
+    unsafe {
+        gl::GetIntegerv(name, &mut value);
+    }
+    value
+}
 
 pub trait RenderingContext {
-    fn init(&mut self);
+    fn init(&mut self) -> GpuInfo;
     fn swap_buffers(&mut self);
 }
 
 impl RenderingContext for GLFWWindow {
-    fn init(&mut self) {
+    fn init(&mut self) -> GpuInfo {
         self.window.make_current();
 
         gl::load_with(|s| self.window.get_proc_address(s) as *const _);
 
+        let info = GpuInfo::query();
         log_info!(
-            "OpenGL Version: {:?}.{:?}",
-            gl::MAJOR_VERSION,
-            gl::MINOR_VERSION
+            "OpenGL {}.{} {:?} profile",
+            info.major_version,
+            info.minor_version,
+            info.profile
         );
+        log_info!("  Vendor: {}", info.vendor);
+        log_info!("  Renderer: {}", info.renderer);
+        log_info!("  Version: {}", info.version);
+        log_info!("  GLSL: {}", info.glsl_version);
+
+        let config = self.get_context_config();
+        let (major, minor) = config.version;
+        if !info.is_version_at_least(major as i32, minor as i32) {
+            log_warn!(
+                "Requested OpenGL {}.{}, but the context is {}.{}",
+                major,
+                minor,
+                info.major_version,
+                info.minor_version
+            );
+        }
+
+        if config.samples > 0 {
+            state::enable(gl::MULTISAMPLE);
+        }
+        if config.srgb {
+            state::enable(gl::FRAMEBUFFER_SRGB);
+        }
+
+        if config.debug && !enable_debug_output() {
+            log_warn!("OpenGL debug output is not supported, use gl_check! to find errors");
+        }
+
+        info
     }
 
     fn swap_buffers(&mut self) {
         self.window.swap_buffers();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_supported() {
+        ContextConfig::default().validate();
+        ContextConfig::default().with_version(4, 6).validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "not supported")]
+    fn rejects_versions_before_3_2() {
+        ContextConfig::default().with_version(3, 1);
+    }
+}
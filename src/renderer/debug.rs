@@ -0,0 +1,223 @@
+use std::{ffi::CString, os::raw::c_void, ptr, slice, str};
+
+use gl::types::{GLchar, GLenum, GLsizei, GLuint};
+
+use crate::{log_error, log_info, log_trace, log_warn};
+
+// Wraps a GL call, in debug builds logs every error it raised together with
+// the call and its location. Meant for drivers without `KHR_debug`, where the
+// debug output cannot report errors as they happen.
+#[macro_export]
+macro_rules! gl_check {
+    ($call:expr) => {{
+        let result = $call;
+        if cfg!(debug_assertions) {
+            $crate::renderer::debug::check_errors(stringify!($call), file!(), line!());
+        }
+        result
+    }};
+}
+
+// Core in OpenGL 4.3, older contexts may expose it through `KHR_debug` or
+// `ARB_debug_output`.
+pub fn supports_debug_output() -> bool {
+    gl::DebugMessageCallback::is_loaded()
+}
+
+// Requires a current OpenGL context. Routes driver messages to the log,
+// high severity ones as errors down to notifications as traces. Most drivers
+// only report much for contexts created with the debug flag. Returns false
+// where debug output is not supported.
+pub fn enable_debug_output() -> bool {
+    if !supports_debug_output() {
+        return false;
+    }
+
+    unsafe {
+        let mut flags = 0;
+        gl::GetIntegerv(gl::CONTEXT_FLAGS, &mut flags);
+        if flags as u32 & gl::CONTEXT_FLAG_DEBUG_BIT == 0 {
+            log_warn!("OpenGL context is not a debug context, debug output may be limited");
+        }
+
+        gl::Enable(gl::DEBUG_OUTPUT);
+        // messages are reported from within the call causing them, so that a
+        // breakpoint in the callback shows the culprit
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_callback), ptr::null());
+        gl::DebugMessageControl(
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            gl::DONT_CARE,
+            0,
+            ptr::null(),
+            gl::TRUE,
+        );
+    }
+    true
+}
+
+pub fn disable_debug_output() {
+    if !supports_debug_output() {
+        return;
+    }
+
+    unsafe {
+        gl::Disable(gl::DEBUG_OUTPUT);
+        gl::Disable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(None, ptr::null());
+    }
+}
+
+// Logs and clears all pending errors, returns whether there were any. Called
+// by `gl_check!`.
+pub fn check_errors(call: &str, file: &str, line: u32) -> bool {
+    let mut has_errors = false;
+    loop {
+        let error = unsafe { gl::GetError() };
+        if error == gl::NO_ERROR {
+            return has_errors;
+        }
+        log_error!(
+            "OpenGL error {} in {} at {}:{}",
+            get_error_name(error),
+            call,
+            file,
+            line
+        );
+        has_errors = true;
+    }
+}
+
+pub fn get_error_name(error: GLenum) -> &'static str {
+    match error {
+        gl::NO_ERROR => "GL_NO_ERROR",
+        gl::INVALID_ENUM => "GL_INVALID_ENUM",
+        gl::INVALID_VALUE => "GL_INVALID_VALUE",
+        gl::INVALID_OPERATION => "GL_INVALID_OPERATION",
+        gl::INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+        gl::OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+        gl::STACK_UNDERFLOW => "GL_STACK_UNDERFLOW",
+        gl::STACK_OVERFLOW => "GL_STACK_OVERFLOW",
+        _ => "unknown error",
+    }
+}
+
+// Names an object in debug messages and frame captures, e.g. in RenderDoc.
+// `identifier` is the namespace of `name`, such as `gl::TEXTURE`, `gl::BUFFER`
+// or `gl::PROGRAM`. Does nothing without debug output support.
+pub fn set_object_label(identifier: GLenum, name: GLuint, label: &str) {
+    if !gl::ObjectLabel::is_loaded() {
+        return;
+    }
+
+    let label = CString::new(label.replace('\0', "")).unwrap();
+    unsafe {
+        gl::ObjectLabel(identifier, name, -1, label.as_ptr());
+    }
+}
+
+pub fn get_object_label(identifier: GLenum, name: GLuint) -> Option<String> {
+    if !gl::GetObjectLabel::is_loaded() {
+        return None;
+    }
+
+    let mut length = 0;
+    let mut buffer = [0u8; 256];
+    unsafe {
+        gl::GetObjectLabel(
+            identifier,
+            name,
+            buffer.len() as GLsizei,
+            &mut length,
+            buffer.as_mut_ptr() as *mut GLchar,
+        );
+    }
+    str::from_utf8(&buffer[..length as usize])
+        .ok()
+        .map(|label| label.to_string())
+}
+
+// Groups the calls made while alive under `name` in frame captures and debug
+// messages, e.g. one group per render pass.
+pub struct DebugGroup {
+    is_pushed: bool,
+}
+
+impl DebugGroup {
+    pub fn new(name: &str) -> Self {
+        if !gl::PushDebugGroup::is_loaded() {
+            return Self { is_pushed: false };
+        }
+
+        let name = CString::new(name.replace('\0', "")).unwrap();
+        unsafe {
+            gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, -1, name.as_ptr());
+        }
+        Self { is_pushed: true }
+    }
+}
+
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        if self.is_pushed {
+            unsafe {
+                gl::PopDebugGroup();
+            }
+        }
+    }
+}
+
+extern "system" fn debug_callback(
+    source: GLenum,
+    ty: GLenum,
+    id: GLuint,
+    severity: GLenum,
+    length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    // group markers would echo every `DebugGroup`
+    if ty == gl::DEBUG_TYPE_PUSH_GROUP || ty == gl::DEBUG_TYPE_POP_GROUP {
+        return;
+    }
+
+    let message = if message.is_null() || length < 0 {
+        String::new()
+    } else {
+        let bytes = unsafe { slice::from_raw_parts(message as *const u8, length as usize) };
+        String::from_utf8_lossy(bytes).trim_end().to_string()
+    };
+    let source = match source {
+        gl::DEBUG_SOURCE_API => "API",
+        gl::DEBUG_SOURCE_WINDOW_SYSTEM => "window system",
+        gl::DEBUG_SOURCE_SHADER_COMPILER => "shader compiler",
+        gl::DEBUG_SOURCE_THIRD_PARTY => "third party",
+        gl::DEBUG_SOURCE_APPLICATION => "application",
+        _ => "other",
+    };
+    let ty = match ty {
+        gl::DEBUG_TYPE_ERROR => "error",
+        gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => "deprecated behavior",
+        gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => "undefined behavior",
+        gl::DEBUG_TYPE_PORTABILITY => "portability",
+        gl::DEBUG_TYPE_PERFORMANCE => "performance",
+        gl::DEBUG_TYPE_MARKER => "marker",
+        _ => "other",
+    };
+
+    match severity {
+        gl::DEBUG_SEVERITY_HIGH => {
+            log_error!("OpenGL {} {} ({}): {}", source, ty, id, message);
+        }
+        gl::DEBUG_SEVERITY_MEDIUM => {
+            log_warn!("OpenGL {} {} ({}): {}", source, ty, id, message);
+        }
+        gl::DEBUG_SEVERITY_LOW => {
+            log_info!("OpenGL {} {} ({}): {}", source, ty, id, message);
+        }
+        _ => {
+            log_trace!("OpenGL {} {} ({}): {}", source, ty, id, message);
+        }
+    }
+}
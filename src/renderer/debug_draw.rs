@@ -0,0 +1,656 @@
+use std::{cell::RefCell, f32::consts::PI, rc::Rc};
+
+use crate::math::{Aabb, Mat4, Vec2, Vec3, Vec4};
+use crate::renderer::{
+    buffer::{BufferElement, BufferLayout, ShaderDataType, VertexArray, VertexBuffer},
+    profiler,
+    shader::{OpenGLShader, Shader},
+    state,
+};
+
+// Immediate mode gizmos. The free functions below can be called from any
+// code running on the render thread, primitives are queued until the
+// `DebugRenderer` owned by the `Application` draws them over the frame, after
+// the layers, with the camera passed to `set_view_projection`:
+//
+//     debug_draw::set_view_projection(&camera.get_view_projection());
+//     debug_draw::line(a, b, Vec4::new(1.0, 0.0, 0.0, 1.0));
+//     debug_draw::aabb(&bounds, DebugStyle::new(color).with_duration(2.0));
+
+const CIRCLE_SEGMENTS: usize = 32;
+const MAX_VERTICES: usize = 65536;
+// glyph proportions relative to the text size
+const GLYPH_WIDTH: f32 = 0.6;
+const GLYPH_ADVANCE: f32 = 0.9;
+const LINE_ADVANCE: f32 = 1.4;
+
+thread_local! {
+    static QUEUE: RefCell<DebugDrawQueue> = RefCell::new(DebugDrawQueue::default());
+}
+
+static VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_Position;
+    layout(location = 1) in vec4 a_Color;
+
+    uniform mat4 u_ViewProjection;
+
+    out vec4 v_Color;
+
+    void main() {
+        v_Color = a_Color;
+        gl_Position = u_ViewProjection * vec4(a_Position, 1.0);
+    }
+";
+
+static FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    in vec4 v_Color;
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        color = v_Color;
+    }
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugStyle {
+    pub color: Vec4,
+    // seconds to keep drawing, zero for the current frame only
+    pub duration: f32,
+    // hidden behind scene geometry instead of drawn on top
+    pub depth_test: bool,
+}
+
+impl DebugStyle {
+    pub fn new(color: Vec4) -> Self {
+        Self {
+            color,
+            duration: 0.0,
+            depth_test: false,
+        }
+    }
+
+    pub fn with_duration(mut self, duration: f32) -> Self {
+        self.duration = duration;
+        self
+    }
+
+    pub fn with_depth_test(mut self, depth_test: bool) -> Self {
+        self.depth_test = depth_test;
+        self
+    }
+}
+
+impl From<Vec4> for DebugStyle {
+    fn from(color: Vec4) -> Self {
+        Self::new(color)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DebugLine {
+    start: Vec3,
+    end: Vec3,
+    color: Vec4,
+    depth_test: bool,
+    remaining: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TextAnchor {
+    World(Vec3),
+    // pixels from the top left corner of the viewport
+    Screen(Vec2),
+}
+
+#[derive(Debug, Clone)]
+struct DebugText {
+    anchor: TextAnchor,
+    text: String,
+    // glyph height in pixels
+    size: f32,
+    color: Vec4,
+    remaining: f32,
+}
+
+#[derive(Default)]
+struct DebugDrawQueue {
+    lines: Vec<DebugLine>,
+    texts: Vec<DebugText>,
+    enabled: bool,
+    view_projection: Mat4,
+}
+
+impl DebugDrawQueue {
+    fn push_line(&mut self, start: Vec3, end: Vec3, style: &DebugStyle) {
+        self.lines.push(DebugLine {
+            start,
+            end,
+            color: style.color,
+            depth_test: style.depth_test,
+            remaining: style.duration,
+        });
+    }
+
+    fn push_circle(&mut self, center: Vec3, normal: Vec3, radius: f32, style: &DebugStyle) {
+        let normal = normal.normalize();
+        let u = normal.any_orthogonal().normalize();
+        let v = normal.cross(u);
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+
+        for i in 0..CIRCLE_SEGMENTS {
+            self.push_line(point(i), point(i + 1), style);
+        }
+    }
+}
+
+fn with_queue<F: FnOnce(&mut DebugDrawQueue)>(f: F) {
+    QUEUE.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        if queue.enabled {
+            f(&mut queue);
+        }
+    });
+}
+
+// disabled until a `DebugRenderer` exists, so that calls are free without one
+pub fn set_enabled(enabled: bool) {
+    QUEUE.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        queue.enabled = enabled;
+        if !enabled {
+            queue.lines.clear();
+            queue.texts.clear();
+        }
+    });
+}
+
+pub fn is_enabled() -> bool {
+    QUEUE.with(|queue| queue.borrow().enabled)
+}
+
+// camera the world space primitives are drawn with by the application, set
+// it each frame from the layer owning the camera
+pub fn set_view_projection(view_projection: &Mat4) {
+    QUEUE.with(|queue| queue.borrow_mut().view_projection = *view_projection);
+}
+
+pub fn get_view_projection() -> Mat4 {
+    QUEUE.with(|queue| queue.borrow().view_projection)
+}
+
+pub fn clear() {
+    QUEUE.with(|queue| {
+        let mut queue = queue.borrow_mut();
+        queue.lines.clear();
+        queue.texts.clear();
+    });
+}
+
+pub fn line<S: Into<DebugStyle>>(start: Vec3, end: Vec3, style: S) {
+    let style = style.into();
+    with_queue(|q| q.push_line(start, end, &style));
+}
+
+pub fn arrow<S: Into<DebugStyle>>(start: Vec3, end: Vec3, style: S) {
+    let style = style.into();
+    with_queue(|q| {
+        q.push_line(start, end, &style);
+
+        let dir = end - start;
+        let length = dir.length();
+        if length == 0.0 {
+            return;
+        }
+        let dir = dir / length;
+        let head = length.min(1.0) * 0.2;
+        let side = dir.any_orthogonal().normalize();
+        let up = dir.cross(side);
+        let base = end - dir * head;
+        for offset in [side, -side, up, -up].iter() {
+            q.push_line(end, base + *offset * (head * 0.5), &style);
+        }
+    });
+}
+
+pub fn aabb<S: Into<DebugStyle>>(aabb: &Aabb, style: S) {
+    box_corners(&aabb.get_corners(), style.into());
+}
+
+// box spanning the unit cube from -0.5 to 0.5 under `transform`
+pub fn oriented_box<S: Into<DebugStyle>>(transform: &Mat4, style: S) {
+    let unit = Aabb::from_center_half_extents(Vec3::ZERO, Vec3::splat(0.5));
+    let mut corners = unit.get_corners();
+    for corner in corners.iter_mut() {
+        *corner = transform.transform_point(*corner);
+    }
+    box_corners(&corners, style.into());
+}
+
+fn box_corners(c: &[Vec3; 8], style: DebugStyle) {
+    // corner index bits are x, y, z
+    const EDGES: [(usize, usize); 12] = [
+        (0, 1),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+        (0, 2),
+        (1, 3),
+        (4, 6),
+        (5, 7),
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+    ];
+    with_queue(|q| {
+        for (a, b) in EDGES.iter() {
+            q.push_line(c[*a], c[*b], &style);
+        }
+    });
+}
+
+pub fn circle<S: Into<DebugStyle>>(center: Vec3, normal: Vec3, radius: f32, style: S) {
+    let style = style.into();
+    with_queue(|q| q.push_circle(center, normal, radius, &style));
+}
+
+// drawn as three great circles
+pub fn sphere<S: Into<DebugStyle>>(center: Vec3, radius: f32, style: S) {
+    let style = style.into();
+    with_queue(|q| {
+        q.push_circle(center, Vec3::X, radius, &style);
+        q.push_circle(center, Vec3::Y, radius, &style);
+        q.push_circle(center, Vec3::Z, radius, &style);
+    });
+}
+
+// X, Y and Z axes of `transform` in red, green and blue, the style color is
+// ignored
+pub fn axes<S: Into<DebugStyle>>(transform: &Mat4, length: f32, style: S) {
+    let style = style.into();
+    let origin = transform.transform_point(Vec3::ZERO);
+    let colors = [
+        Vec4::new(1.0, 0.2, 0.2, 1.0),
+        Vec4::new(0.2, 1.0, 0.2, 1.0),
+        Vec4::new(0.2, 0.4, 1.0, 1.0),
+    ];
+
+    for (axis, color) in [Vec3::X, Vec3::Y, Vec3::Z].iter().zip(colors.iter()) {
+        let end = transform.transform_point(*axis * length);
+        arrow(
+            origin,
+            end,
+            DebugStyle {
+                color: *color,
+                ..style
+            },
+        );
+    }
+}
+
+// `cell_count` cells of `cell_size` along each side, centered on `center`
+// in the plane with the given normal, e.g. `Vec3::Y` for the ground or
+// `Vec3::Z` for 2D scenes
+pub fn grid<S: Into<DebugStyle>>(
+    center: Vec3,
+    normal: Vec3,
+    cell_size: f32,
+    cell_count: u32,
+    style: S,
+) {
+    let style = style.into();
+    let normal = normal.normalize();
+    // keep the grid axis aligned for the common planes
+    let u = if normal.y.abs() > 0.999 || normal.z.abs() > 0.999 {
+        Vec3::X
+    } else {
+        normal.any_orthogonal().normalize()
+    };
+    let v = normal.cross(u);
+    let half = cell_size * cell_count as f32 * 0.5;
+
+    with_queue(|q| {
+        for i in 0..=cell_count {
+            let offset = -half + i as f32 * cell_size;
+            q.push_line(
+                center + u * offset - v * half,
+                center + u * offset + v * half,
+                &style,
+            );
+            q.push_line(
+                center + v * offset - u * half,
+                center + v * offset + u * half,
+                &style,
+            );
+        }
+    });
+}
+
+// Screen aligned label anchored at a world position, `size` is the glyph
+// height in pixels. Labels are always drawn on top and use a built-in
+// segment font covering letters, digits and common punctuation.
+pub fn text<S: Into<DebugStyle>>(position: Vec3, text: &str, size: f32, style: S) {
+    let style = style.into();
+    with_queue(|q| {
+        q.texts.push(DebugText {
+            anchor: TextAnchor::World(position),
+            text: text.to_string(),
+            size,
+            color: style.color,
+            remaining: style.duration,
+        })
+    });
+}
+
+// Like `text`, but placed at `position` in pixels from the top left corner
+// of the viewport, e.g. for overlays.
+pub fn screen_text<S: Into<DebugStyle>>(position: Vec2, text: &str, size: f32, style: S) {
+    let style = style.into();
+    with_queue(|q| {
+        q.texts.push(DebugText {
+            anchor: TextAnchor::Screen(position),
+            text: text.to_string(),
+            size,
+            color: style.color,
+            remaining: style.duration,
+        })
+    });
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LineVertex {
+    position: Vec3,
+    color: Vec4,
+}
+
+// The application owns one. Others are only needed to draw the queue into
+// other framebuffers, note that dropping any of them disables the queue.
+pub struct DebugRenderer {
+    shader: OpenGLShader,
+    vertex_array: VertexArray,
+    vertex_buffer: Rc<VertexBuffer>,
+    vertices: Vec<LineVertex>,
+    line_width: f32,
+}
+
+impl DebugRenderer {
+    // requires a current OpenGL context, enables the debug draw queue
+    pub fn new() -> Self {
+        let vertex_buffer = Rc::new(VertexBuffer::new(
+            MAX_VERTICES * std::mem::size_of::<LineVertex>(),
+            BufferLayout::new(vec![
+                BufferElement::new("a_Position", ShaderDataType::Float3),
+                BufferElement::new("a_Color", ShaderDataType::Float4),
+            ]),
+        ));
+        let mut vertex_array = VertexArray::new();
+        vertex_array.add_vertex_buffer(vertex_buffer.clone());
+
+        set_enabled(true);
+
+        Self {
+            shader: OpenGLShader::new(VERTEX_SHADER, FRAGMENT_SHADER),
+            vertex_array,
+            vertex_buffer,
+            vertices: Vec::new(),
+            line_width: 1.0,
+        }
+    }
+
+    pub fn set_line_width(&mut self, width: f32) {
+        self.line_width = width;
+    }
+
+    // Draws the queued primitives into the bound framebuffer, `width` and
+    // `height` of the viewport are needed to size text labels. Can be called
+    // for several views before `end_frame`.
+    pub fn render(&mut self, view_projection: &Mat4, width: u32, height: u32) {
+        QUEUE.with(|queue| {
+            let queue = queue.borrow();
+
+            let mut depth_tested = Vec::new();
+            let mut on_top = Vec::new();
+            for line in queue.lines.iter() {
+                let vertices = if line.depth_test {
+                    &mut depth_tested
+                } else {
+                    &mut on_top
+                };
+                vertices.push(LineVertex {
+                    position: line.start,
+                    color: line.color,
+                });
+                vertices.push(LineVertex {
+                    position: line.end,
+                    color: line.color,
+                });
+            }
+
+            let mut labels = Vec::new();
+            for text in queue.texts.iter() {
+                push_text(&mut labels, text, view_projection, width, height);
+            }
+
+            let depth_enabled = state::is_enabled(gl::DEPTH_TEST);
+            let depth_func = state::get_depth_func();
+            let blend_enabled = state::is_enabled(gl::BLEND);
+            let blend_func = state::get_blend_func();
+
+            state::enable(gl::BLEND);
+            state::set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            unsafe {
+                gl::LineWidth(self.line_width);
+            }
+
+            state::enable(gl::DEPTH_TEST);
+            state::set_depth_func(gl::LEQUAL);
+            self.draw_lines(&depth_tested, view_projection);
+
+            state::disable(gl::DEPTH_TEST);
+            self.draw_lines(&on_top, view_projection);
+            // labels are built in normalized device coordinates
+            self.draw_lines(&labels, &Mat4::IDENTITY);
+
+            state::set_blend_func_separate(
+                blend_func[0],
+                blend_func[1],
+                blend_func[2],
+                blend_func[3],
+            );
+            state::set_enabled(gl::BLEND, blend_enabled);
+            state::set_depth_func(depth_func);
+            state::set_enabled(gl::DEPTH_TEST, depth_enabled);
+        });
+    }
+
+    // drops single frame primitives and ages timed ones by `dt` seconds
+    pub fn end_frame(&mut self, dt: f32) {
+        QUEUE.with(|queue| {
+            let mut queue = queue.borrow_mut();
+            for line in queue.lines.iter_mut() {
+                line.remaining -= dt;
+            }
+            for text in queue.texts.iter_mut() {
+                text.remaining -= dt;
+            }
+            queue.lines.retain(|l| l.remaining > 0.0);
+            queue.texts.retain(|t| t.remaining > 0.0);
+        });
+    }
+
+    fn draw_lines(&mut self, vertices: &[LineVertex], view_projection: &Mat4) {
+        if vertices.is_empty() {
+            return;
+        }
+
+        self.shader.bind();
+        self.shader.set_mat4("u_ViewProjection", view_projection);
+        self.vertex_array.bind();
+
+        // even chunk size keeps line pairs together
+        for chunk in vertices.chunks(MAX_VERTICES) {
+            self.vertices.clear();
+            self.vertices.extend_from_slice(chunk);
+            self.vertex_buffer.set_data(&self.vertices);
+            unsafe {
+                gl::DrawArrays(gl::LINES, 0, chunk.len() as i32);
+            }
+            profiler::record_draw(gl::LINES, chunk.len() as u32, 1);
+        }
+
+        self.vertex_array.unbind();
+    }
+}
+
+impl Drop for DebugRenderer {
+    fn drop(&mut self) {
+        set_enabled(false);
+    }
+}
+
+fn push_text(
+    vertices: &mut Vec<LineVertex>,
+    text: &DebugText,
+    view_projection: &Mat4,
+    width: u32,
+    height: u32,
+) {
+    // pixels to normalized device coordinates
+    let sx = text.size * 2.0 / width.max(1) as f32;
+    let sy = text.size * 2.0 / height.max(1) as f32;
+
+    let anchor = match text.anchor {
+        TextAnchor::World(position) => {
+            let clip = *view_projection * position.extend(1.0);
+            // behind the camera
+            if clip.w <= 0.0 {
+                return;
+            }
+            let anchor = Vec3::new(clip.x / clip.w, clip.y / clip.w, 0.0);
+            if anchor.x.abs() > 1.0 || anchor.y.abs() > 1.0 {
+                return;
+            }
+            anchor
+        }
+        // the first line hangs below the position
+        TextAnchor::Screen(position) => Vec3::new(
+            position.x * 2.0 / width.max(1) as f32 - 1.0,
+            1.0 - position.y * 2.0 / height.max(1) as f32 - sy,
+            0.0,
+        ),
+    };
+
+    let (mut x, mut y) = (0.0, 0.0);
+    for c in text.text.chars() {
+        if c == '\n' {
+            x = 0.0;
+            y -= LINE_ADVANCE;
+            continue;
+        }
+
+        for (a, b) in get_glyph_segments(c) {
+            for p in [a, b].iter() {
+                vertices.push(LineVertex {
+                    position: anchor + Vec3::new((x + p.0 * GLYPH_WIDTH) * sx, (y + p.1) * sy, 0.0),
+                    color: text.color,
+                });
+            }
+        }
+        x += GLYPH_ADVANCE;
+    }
+}
+
+// Sixteen segment display glyphs on a unit cell, segments are named
+// a/A top, b/c right, d/D bottom, e/f left, g/G middle, h i j upper
+// diagonals and center, k l m lower ones, p and q short ticks for
+// punctuation. Lowercase letters use the uppercase shapes.
+fn get_glyph_segments(c: char) -> impl Iterator<Item = ((f32, f32), (f32, f32))> {
+    let segments = match c.to_ascii_uppercase() {
+        '0' => "aAbcdDefjk",
+        '1' => "bcj",
+        '2' => "aAbgGedD",
+        '3' => "aAbcdDG",
+        '4' => "fgGbc",
+        '5' | 'S' => "aAfgGcdD",
+        '6' => "aAfedDcgG",
+        '7' => "aAbc",
+        '8' => "aAbcdDefgG",
+        '9' => "aAbcdDfgG",
+        'A' => "efaAbcgG",
+        'B' => "aAbcdDGil",
+        'C' => "aAfedD",
+        'D' => "aAbcdDil",
+        'E' => "aAfedDg",
+        'F' => "aAfeg",
+        'G' => "aAfedDcG",
+        'H' => "febcgG",
+        'I' => "aAdDil",
+        'J' => "bcdDe",
+        'K' => "fegjm",
+        'L' => "fedD",
+        'M' => "efhjbc",
+        'N' => "efhmcb",
+        'O' => "aAbcdDef",
+        'P' => "aAbfegG",
+        'Q' => "aAbcdDefm",
+        'R' => "aAbfegGm",
+        'T' => "aAil",
+        'U' => "fedDcb",
+        'V' => "fekj",
+        'W' => "fekmcb",
+        'X' => "hjkm",
+        'Y' => "hjl",
+        'Z' => "aAjkdD",
+        '-' => "gG",
+        '+' => "gGil",
+        '=' => "gGdD",
+        '_' => "dD",
+        '*' => "hijklmgG",
+        '/' => "jk",
+        '\\' => "hm",
+        '(' | '<' => "jm",
+        ')' | '>' => "hk",
+        '[' => "iAlD",
+        ']' => "iald",
+        '\'' => "i",
+        '"' => "if",
+        '.' | ',' => "p",
+        ':' => "pq",
+        '!' => "ip",
+        '?' => "aAbGp",
+        '%' => "jkfA",
+        '#' => "gGdDil",
+        _ => "",
+    };
+
+    segments.chars().map(|s| match s {
+        'a' => ((0.0, 1.0), (0.5, 1.0)),
+        'A' => ((0.5, 1.0), (1.0, 1.0)),
+        'b' => ((1.0, 1.0), (1.0, 0.5)),
+        'c' => ((1.0, 0.5), (1.0, 0.0)),
+        'd' => ((0.0, 0.0), (0.5, 0.0)),
+        'D' => ((0.5, 0.0), (1.0, 0.0)),
+        'e' => ((0.0, 0.0), (0.0, 0.5)),
+        'f' => ((0.0, 0.5), (0.0, 1.0)),
+        'g' => ((0.0, 0.5), (0.5, 0.5)),
+        'G' => ((0.5, 0.5), (1.0, 0.5)),
+        'h' => ((0.0, 1.0), (0.5, 0.5)),
+        'i' => ((0.5, 1.0), (0.5, 0.5)),
+        'j' => ((1.0, 1.0), (0.5, 0.5)),
+        'k' => ((0.0, 0.0), (0.5, 0.5)),
+        'l' => ((0.5, 0.0), (0.5, 0.5)),
+        'm' => ((1.0, 0.0), (0.5, 0.5)),
+        'p' => ((0.5, 0.0), (0.5, 0.12)),
+        'q' => ((0.5, 0.6), (0.5, 0.72)),
+        _ => unreachable!(),
+    })
+}
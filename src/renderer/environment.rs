@@ -0,0 +1,563 @@
+use std::{path::Path, rc::Rc};
+
+use crate::{
+    math::{Mat3, Mat4},
+    renderer::{
+        buffer::VertexArray,
+        camera::Camera,
+        framebuffer::{Framebuffer, FramebufferAttachment, FramebufferFormat, FramebufferSpec},
+        postprocess::FULLSCREEN_VERTEX_SHADER,
+        profiler,
+        shader::{OpenGLShader, Shader},
+        state,
+        texture::{
+            get_mip_level_count, ColorSpace, SamplerSettings, Texture, Texture2D, TextureCube,
+            TextureFormat, TextureWrap,
+        },
+    },
+};
+
+// Texture units of the irradiance map, the prefiltered map and the BRDF
+// lookup table, past the shadow maps and the post-processing inputs.
+pub const ENVIRONMENT_TEXTURE_SLOT: u32 = 26;
+
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+const PREFILTERED_LEVELS: u32 = 5;
+const BRDF_LUT_SIZE: u32 = 512;
+// the irradiance convolution reads a level of about this size
+const IRRADIANCE_SOURCE_SIZE: u32 = 64;
+
+// Renders into one cubemap face at a time, `get_direction` returns the
+// direction of the fragment's texel on face `u_Face`.
+static CUBE_FACE_HEADER: &'static str = "
+    #version 330 core
+
+    #define PI 3.14159265
+
+    in vec2 v_TexCoord;
+
+    uniform int u_Face;
+
+    layout(location = 0) out vec4 color;
+
+    // face orientations as in the OpenGL specification
+    vec3 get_direction() {
+        vec2 st = v_TexCoord * 2.0 - 1.0;
+        vec3 d;
+        if (u_Face == 0) {
+            d = vec3(1.0, -st.y, -st.x);
+        } else if (u_Face == 1) {
+            d = vec3(-1.0, -st.y, st.x);
+        } else if (u_Face == 2) {
+            d = vec3(st.x, 1.0, st.y);
+        } else if (u_Face == 3) {
+            d = vec3(st.x, -1.0, -st.y);
+        } else if (u_Face == 4) {
+            d = vec3(st.x, -st.y, 1.0);
+        } else {
+            d = vec3(-st.x, -st.y, -1.0);
+        }
+        return normalize(d);
+    }
+";
+
+static EQUIRECTANGULAR_SHADER: &'static str = "
+    uniform sampler2D u_Equirectangular;
+
+    void main() {
+        vec3 d = get_direction();
+        vec2 uv = vec2(atan(d.z, d.x) / (2.0 * PI), asin(clamp(d.y, -1.0, 1.0)) / PI) + 0.5;
+        color = vec4(texture(u_Equirectangular, uv).rgb, 1.0);
+    }
+";
+
+// cosine weighted integral over the hemisphere around each direction
+static IRRADIANCE_SHADER: &'static str = "
+    uniform samplerCube u_Environment;
+    uniform float u_SourceLod;
+
+    void main() {
+        vec3 n = get_direction();
+        vec3 up = abs(n.y) < 0.999 ? vec3(0.0, 1.0, 0.0) : vec3(0.0, 0.0, 1.0);
+        vec3 right = normalize(cross(up, n));
+        up = cross(n, right);
+
+        float delta = 0.025;
+        vec3 irradiance = vec3(0.0);
+        float sample_count = 0.0;
+        for (float phi = 0.0; phi < 2.0 * PI; phi += delta) {
+            for (float theta = 0.0; theta < 0.5 * PI; theta += delta) {
+                vec3 t = vec3(sin(theta) * cos(phi), sin(theta) * sin(phi), cos(theta));
+                vec3 direction = t.x * right + t.y * up + t.z * n;
+                irradiance += textureLod(u_Environment, direction, u_SourceLod).rgb * cos(theta) * sin(theta);
+                sample_count += 1.0;
+            }
+        }
+        color = vec4(PI * irradiance / sample_count, 1.0);
+    }
+";
+
+// GGX importance sampling shared by the prefilter and the BRDF integration
+static IMPORTANCE_SAMPLING: &'static str = "
+    #define SAMPLE_COUNT 1024u
+
+    float radical_inverse(uint bits) {
+        bits = (bits << 16u) | (bits >> 16u);
+        bits = ((bits & 0x55555555u) << 1u) | ((bits & 0xAAAAAAAAu) >> 1u);
+        bits = ((bits & 0x33333333u) << 2u) | ((bits & 0xCCCCCCCCu) >> 2u);
+        bits = ((bits & 0x0F0F0F0Fu) << 4u) | ((bits & 0xF0F0F0F0u) >> 4u);
+        bits = ((bits & 0x00FF00FFu) << 8u) | ((bits & 0xFF00FF00u) >> 8u);
+        return float(bits) * 2.3283064365386963e-10;
+    }
+
+    vec2 hammersley(uint i) {
+        return vec2(float(i) / float(SAMPLE_COUNT), radical_inverse(i));
+    }
+
+    // half vector around `n`, distributed as the GGX lobe of `roughness`
+    vec3 importance_sample_ggx(vec2 xi, vec3 n, float roughness) {
+        float a = roughness * roughness;
+        float phi = 2.0 * PI * xi.x;
+        float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a * a - 1.0) * xi.y));
+        float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+        vec3 up = abs(n.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+        vec3 tangent = normalize(cross(up, n));
+        vec3 bitangent = cross(n, tangent);
+        return normalize(tangent * h.x + bitangent * h.y + n * h.z);
+    }
+";
+
+static PREFILTER_SHADER: &'static str = "
+    uniform samplerCube u_Environment;
+    uniform float u_Roughness;
+    // face size and last mip level of the environment
+    uniform float u_EnvironmentSize;
+    uniform float u_EnvironmentMaxLod;
+
+    float distribution_ggx(float n_dot_h, float roughness) {
+        float a = roughness * roughness;
+        float a2 = a * a;
+        float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+        return a2 / (PI * d * d);
+    }
+
+    void main() {
+        // view and reflection direction are assumed to be the normal
+        vec3 n = get_direction();
+        vec3 v = n;
+
+        vec3 prefiltered = vec3(0.0);
+        float total_weight = 0.0;
+        for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+            vec3 h = importance_sample_ggx(hammersley(i), n, u_Roughness);
+            vec3 l = normalize(2.0 * dot(v, h) * h - v);
+            float n_dot_l = dot(n, l);
+            if (n_dot_l <= 0.0) {
+                continue;
+            }
+
+            // reads a blurrier level for samples covering more of the sphere
+            float n_dot_h = max(dot(n, h), 0.0);
+            float pdf = distribution_ggx(n_dot_h, u_Roughness) * 0.25 + 0.0001;
+            float texel_solid_angle = 4.0 * PI / (6.0 * u_EnvironmentSize * u_EnvironmentSize);
+            float sample_solid_angle = 1.0 / (float(SAMPLE_COUNT) * pdf + 0.0001);
+            float lod = u_Roughness == 0.0 ? 0.0 : 0.5 * log2(sample_solid_angle / texel_solid_angle);
+
+            prefiltered += textureLod(u_Environment, l, clamp(lod, 0.0, u_EnvironmentMaxLod)).rgb * n_dot_l;
+            total_weight += n_dot_l;
+        }
+        color = vec4(prefiltered / max(total_weight, 0.0001), 1.0);
+    }
+";
+
+// scale and bias to f0 of the specular BRDF, by n dot v and roughness
+static BRDF_LUT_SHADER: &'static str = "
+    float geometry_schlick_ggx(float n_dot_x, float roughness) {
+        float k = roughness * roughness / 2.0;
+        return n_dot_x / (n_dot_x * (1.0 - k) + k);
+    }
+
+    void main() {
+        float n_dot_v = v_TexCoord.x;
+        float roughness = v_TexCoord.y;
+        vec3 n = vec3(0.0, 0.0, 1.0);
+        vec3 v = vec3(sqrt(1.0 - n_dot_v * n_dot_v), 0.0, n_dot_v);
+
+        float scale = 0.0;
+        float bias = 0.0;
+        for (uint i = 0u; i < SAMPLE_COUNT; i++) {
+            vec3 h = importance_sample_ggx(hammersley(i), n, roughness);
+            vec3 l = normalize(2.0 * dot(v, h) * h - v);
+            float n_dot_l = max(l.z, 0.0);
+            if (n_dot_l <= 0.0) {
+                continue;
+            }
+
+            float n_dot_h = max(h.z, 0.0);
+            float v_dot_h = max(dot(v, h), 0.0);
+            float g = geometry_schlick_ggx(n_dot_v, roughness) * geometry_schlick_ggx(n_dot_l, roughness);
+            float visibility = g * v_dot_h / (n_dot_h * n_dot_v);
+            float fc = pow(1.0 - v_dot_h, 5.0);
+            scale += (1.0 - fc) * visibility;
+            bias += fc * visibility;
+        }
+        color = vec4(vec2(scale, bias) / float(SAMPLE_COUNT), 0.0, 1.0);
+    }
+";
+
+static SKYBOX_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    // of the view without translation
+    uniform mat4 u_InverseViewProjection;
+
+    out vec3 v_Direction;
+
+    void main() {
+        vec2 position = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+        vec4 far_point = u_InverseViewProjection * vec4(position, 1.0, 1.0);
+        v_Direction = far_point.xyz / far_point.w;
+        // on the far plane, behind everything drawn before
+        gl_Position = vec4(position, 1.0, 1.0);
+    }
+";
+
+static SKYBOX_FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    in vec3 v_Direction;
+
+    uniform samplerCube u_Environment;
+    uniform float u_Lod;
+    uniform float u_Intensity;
+    // set when a later pass does tone mapping and gamma
+    uniform int u_LinearOutput;
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        vec3 linear = textureLod(u_Environment, normalize(v_Direction), u_Lod).rgb * u_Intensity;
+        if (u_LinearOutput != 0) {
+            color = vec4(linear, 1.0);
+        } else {
+            color = vec4(pow(clamp(linear, 0.0, 1.0), vec3(1.0 / 2.2)), 1.0);
+        }
+    }
+";
+
+// Diffuse and specular image based lighting derived from an environment
+// cubemap, for `pbr_shader`:
+//
+//     let environment = EnvironmentMap::from_equirectangular("sky.hdr", 512)?;
+//     environment.apply(&pbr_shader);
+//     // every frame, before drawing lit meshes
+//     environment.bind();
+pub struct EnvironmentMap {
+    environment: Rc<TextureCube>,
+    irradiance: TextureCube,
+    prefiltered: TextureCube,
+    brdf_lut: Framebuffer,
+    intensity: f32,
+}
+
+impl EnvironmentMap {
+    // requires a current OpenGL context
+    pub fn from_cubemap(environment: Rc<TextureCube>) -> Self {
+        let vertex_array = VertexArray::new();
+        let environment_max_lod = (environment.get_mip_levels() - 1) as f32;
+
+        let irradiance = TextureCube::new(
+            IRRADIANCE_SIZE,
+            TextureFormat::Rgba16F,
+            ColorSpace::Linear,
+            1,
+        );
+        let shader = cube_face_shader(IRRADIANCE_SHADER);
+        let source_lod = (environment.get_size() as f32 / IRRADIANCE_SOURCE_SIZE as f32)
+            .log2()
+            .clamp(0.0, environment_max_lod);
+        shader.bind();
+        environment.bind(0);
+        shader.set_int("u_Environment", 0);
+        shader.set_float("u_SourceLod", source_lod);
+        render_faces(&shader, &irradiance, 0, &vertex_array);
+
+        let prefiltered = TextureCube::new(
+            PREFILTERED_SIZE,
+            TextureFormat::Rgba16F,
+            ColorSpace::Linear,
+            PREFILTERED_LEVELS,
+        );
+        let shader = cube_face_shader(&format!("{}{}", IMPORTANCE_SAMPLING, PREFILTER_SHADER));
+        shader.bind();
+        environment.bind(0);
+        shader.set_int("u_Environment", 0);
+        shader.set_float("u_EnvironmentSize", environment.get_size() as f32);
+        shader.set_float("u_EnvironmentMaxLod", environment_max_lod);
+        for level in 0..PREFILTERED_LEVELS {
+            let roughness = level as f32 / (PREFILTERED_LEVELS - 1) as f32;
+            shader.set_float("u_Roughness", roughness);
+            render_faces(&shader, &prefiltered, level, &vertex_array);
+        }
+
+        let brdf_lut = Framebuffer::new(FramebufferSpec {
+            width: BRDF_LUT_SIZE,
+            height: BRDF_LUT_SIZE,
+            samples: 1,
+            color_attachments: vec![FramebufferFormat::Rgba16F],
+            depth_attachment: None,
+            resize_with_window: false,
+        });
+        let shader = OpenGLShader::new(
+            FULLSCREEN_VERTEX_SHADER,
+            &format!(
+                "{}{}{}",
+                CUBE_FACE_HEADER, IMPORTANCE_SAMPLING, BRDF_LUT_SHADER
+            ),
+        );
+        with_offscreen_state(|| {
+            brdf_lut.bind();
+            shader.bind();
+            vertex_array.bind();
+            unsafe {
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+            profiler::record_draw(gl::TRIANGLES, 3, 1);
+            vertex_array.unbind();
+            brdf_lut.unbind();
+        });
+
+        Self {
+            environment,
+            irradiance,
+            prefiltered,
+            brdf_lut,
+            intensity: 1.0,
+        }
+    }
+
+    // Radiance HDR panorama, converted into a cubemap with faces of `size`
+    // pixels.
+    pub fn from_equirectangular<P: AsRef<Path>>(path: P, size: u32) -> Result<Self, String> {
+        let panorama = Texture2D::from_hdr_file(
+            path,
+            SamplerSettings {
+                mipmap_filter: None,
+                ..SamplerSettings::default().with_wrap(TextureWrap::ClampToEdge)
+            },
+        )?;
+        let environment = equirectangular_to_cubemap(&panorama, size);
+        Ok(Self::from_cubemap(Rc::new(environment)))
+    }
+
+    pub fn get_environment(&self) -> &Rc<TextureCube> {
+        &self.environment
+    }
+
+    pub fn get_irradiance(&self) -> &TextureCube {
+        &self.irradiance
+    }
+
+    pub fn get_prefiltered(&self) -> &TextureCube {
+        &self.prefiltered
+    }
+
+    pub fn get_brdf_lut(&self) -> &FramebufferAttachment {
+        self.brdf_lut.get_color_attachment(0)
+    }
+
+    pub fn get_intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    // scales the environment lighting, call `apply` again afterwards
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    // turns on image based lighting in a shader made by `pbr_shader`
+    pub fn apply(&self, shader: &OpenGLShader) {
+        shader.bind();
+        shader.set_int("u_HasEnvironment", 1);
+        shader.set_float("u_PrefilteredMaxLod", (PREFILTERED_LEVELS - 1) as f32);
+        shader.set_float("u_EnvironmentIntensity", self.intensity);
+        shader.unbind();
+    }
+
+    // back to the flat ambient term of the light environment
+    pub fn disable(shader: &OpenGLShader) {
+        shader.bind();
+        shader.set_int("u_HasEnvironment", 0);
+        shader.unbind();
+    }
+
+    // binds the maps to their fixed texture units
+    pub fn bind(&self) {
+        self.irradiance.bind(ENVIRONMENT_TEXTURE_SLOT);
+        self.prefiltered.bind(ENVIRONMENT_TEXTURE_SLOT + 1);
+        self.get_brdf_lut().bind(ENVIRONMENT_TEXTURE_SLOT + 2);
+    }
+}
+
+// Draws a cubemap behind the scene. Call it after the opaque geometry so that
+// covered pixels fail the depth test.
+pub struct Skybox {
+    shader: OpenGLShader,
+    vertex_array: VertexArray,
+    intensity: f32,
+    lod: f32,
+}
+
+impl Skybox {
+    // requires a current OpenGL context
+    pub fn new() -> Self {
+        Self {
+            shader: OpenGLShader::new(SKYBOX_VERTEX_SHADER, SKYBOX_FRAGMENT_SHADER),
+            vertex_array: VertexArray::new(),
+            intensity: 1.0,
+            lod: 0.0,
+        }
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity;
+    }
+
+    // mip level to sample, higher levels blur the background
+    pub fn set_lod(&mut self, lod: f32) {
+        self.lod = lod;
+    }
+
+    // same meaning as `u_LinearOutput` of the lit shaders
+    pub fn set_linear_output(&self, linear_output: bool) {
+        self.shader.bind();
+        self.shader.set_int("u_LinearOutput", linear_output as i32);
+        self.shader.unbind();
+    }
+
+    pub fn draw(&self, camera: &dyn Camera, cubemap: &TextureCube) {
+        let rotation = Mat4::from_mat3(&Mat3::from_mat4(camera.get_view()));
+        let inverse_view_projection = (*camera.get_projection() * rotation)
+            .inverse()
+            .unwrap_or(Mat4::IDENTITY);
+
+        let (depth_func, depth_mask) = (state::get_depth_func(), state::get_depth_mask());
+        state::set_depth_func(gl::LEQUAL);
+        state::set_depth_mask(false);
+
+        self.shader.bind();
+        cubemap.bind(0);
+        self.shader.set_int("u_Environment", 0);
+        self.shader
+            .set_mat4("u_InverseViewProjection", &inverse_view_projection);
+        self.shader.set_float("u_Lod", self.lod);
+        self.shader.set_float("u_Intensity", self.intensity);
+        self.vertex_array.bind();
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+        profiler::record_draw(gl::TRIANGLES, 3, 1);
+        self.vertex_array.unbind();
+        self.shader.unbind();
+
+        state::set_depth_func(depth_func);
+        state::set_depth_mask(depth_mask);
+    }
+}
+
+impl Default for Skybox {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Requires a current OpenGL context. The cubemap is half float with a full
+// mip chain.
+pub fn equirectangular_to_cubemap(panorama: &Texture2D, size: u32) -> TextureCube {
+    let cubemap = TextureCube::new(
+        size,
+        TextureFormat::Rgba16F,
+        ColorSpace::Linear,
+        get_mip_level_count(size),
+    );
+    let shader = cube_face_shader(EQUIRECTANGULAR_SHADER);
+    shader.bind();
+    panorama.bind(0);
+    shader.set_int("u_Equirectangular", 0);
+    render_faces(&shader, &cubemap, 0, &VertexArray::new());
+    cubemap.generate_mipmaps();
+    cubemap
+}
+
+fn cube_face_shader(fragment_shader: &str) -> OpenGLShader {
+    OpenGLShader::new(
+        FULLSCREEN_VERTEX_SHADER,
+        &format!("{}{}", CUBE_FACE_HEADER, fragment_shader),
+    )
+}
+
+// draws a fullscreen triangle into every face of `level`, expects `shader`
+// to be bound with its inputs set
+fn render_faces(
+    shader: &OpenGLShader,
+    target: &TextureCube,
+    level: u32,
+    vertex_array: &VertexArray,
+) {
+    let size = (target.get_size() >> level).max(1) as i32;
+
+    with_offscreen_state(|| {
+        let mut framebuffer = 0;
+        unsafe {
+            gl::GenFramebuffers(1, &mut framebuffer);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+        }
+        state::set_viewport(0, 0, size, size);
+        vertex_array.bind();
+
+        for face in 0..6 {
+            shader.set_int("u_Face", face as i32);
+            unsafe {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0,
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                    target.get_renderer_id(),
+                    level as i32,
+                );
+                gl::DrawArrays(gl::TRIANGLES, 0, 3);
+            }
+            profiler::record_draw(gl::TRIANGLES, 3, 1);
+        }
+
+        vertex_array.unbind();
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::DeleteFramebuffers(1, &framebuffer);
+        }
+    });
+}
+
+// runs `f` without depth test, blending and culling, restoring them and the
+// viewport afterwards
+fn with_offscreen_state<F: FnOnce()>(f: F) {
+    let viewport = state::get_viewport();
+    let capabilities = [gl::DEPTH_TEST, gl::BLEND, gl::CULL_FACE];
+    let enabled: Vec<bool> = capabilities
+        .iter()
+        .map(|&capability| state::is_enabled(capability))
+        .collect();
+    for &capability in capabilities.iter() {
+        state::disable(capability);
+    }
+
+    f();
+
+    for (&capability, &was_enabled) in capabilities.iter().zip(enabled.iter()) {
+        state::set_enabled(capability, was_enabled);
+    }
+    state::set_viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+}
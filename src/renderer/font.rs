@@ -0,0 +1,824 @@
+use std::{collections::HashMap, path::Path};
+
+use ab_glyph::{Font as _, FontVec, GlyphId, PxScale, ScaleFont};
+
+use crate::math::Vec2;
+use crate::renderer::texture::{SamplerSettings, Texture2D, TextureFormat, TextureSpec};
+
+const ATLAS_SIZES: [u32; 5] = [256, 512, 1024, 2048, 4096];
+// gap between glyphs in the atlas so that linear filtering does not bleed
+const ATLAS_PADDING: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontAtlasMode {
+    // coverage, crisp at the rasterized size
+    Bitmap,
+    // signed distance field, scales well above the rasterized size. `spread`
+    // is the distance in pixels encoded on each side of the outline.
+    Sdf { spread: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontSpec {
+    // pixel height the glyphs are rasterized at
+    pub size: f32,
+    pub mode: FontAtlasMode,
+    // characters baked into the atlas, others fall back to '?'
+    pub chars: Vec<char>,
+}
+
+impl FontSpec {
+    // printable ASCII and Latin-1
+    pub fn new(size: f32, mode: FontAtlasMode) -> Self {
+        Self {
+            size,
+            mode,
+            chars: (0x20u8..0x7f)
+                .chain(0xa0u8..=0xff)
+                .map(|c| c as char)
+                .collect(),
+        }
+    }
+}
+
+// Metrics in pixels at the font size, y up. `offset` goes from the pen
+// position on the baseline to the bottom left corner of the glyph quad.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GlyphInfo {
+    pub id: u16,
+    pub advance: f32,
+    pub offset: Vec2,
+    pub size: Vec2,
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOptions {
+    pub align: TextAlign,
+    // wraps at word boundaries, in pixels at the font size
+    pub max_width: Option<f32>,
+    // multiplier of the font line height
+    pub line_spacing: f32,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub glyph: GlyphInfo,
+    // bottom left corner of the quad
+    pub position: Vec2,
+}
+
+// Glyph quads in pixels at the font size, with the origin at the top left
+// of the text block and y up, so lines extend towards negative y.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TextLayout {
+    pub glyphs: Vec<PositionedGlyph>,
+    pub size: Vec2,
+    pub line_count: usize,
+}
+
+pub struct Font {
+    font: FontVec,
+    scale: PxScale,
+    spec: FontSpec,
+    glyphs: HashMap<char, GlyphInfo>,
+    atlas: Texture2D,
+    ascent: f32,
+    descent: f32,
+    line_gap: f32,
+}
+
+impl Font {
+    // requires a current OpenGL context
+    pub fn from_file<P: AsRef<Path>>(path: P, spec: FontSpec) -> Result<Self, String> {
+        let data = std::fs::read(path.as_ref())
+            .map_err(|e| format!("Failed to read font {:?}: {}", path.as_ref(), e))?;
+        Self::from_memory(data, spec)
+    }
+
+    pub fn from_memory(data: Vec<u8>, spec: FontSpec) -> Result<Self, String> {
+        let font = FontVec::try_from_vec(data).map_err(|e| format!("Invalid font: {}", e))?;
+        let scale = PxScale::from(spec.size);
+        let scaled = font.as_scaled(scale);
+
+        let mut rasterized = Vec::new();
+        let mut chars = spec.chars.clone();
+        if !chars.contains(&'?') {
+            chars.push('?');
+        }
+        for c in chars {
+            rasterized.push(rasterize_glyph(&font, scale, c, spec.mode));
+        }
+
+        let (width, height, placements) = pack_glyphs(&rasterized)?;
+        let mut pixels = vec![0u8; width as usize * height as usize];
+        let mut glyphs = HashMap::new();
+
+        for (glyph, (x, y)) in rasterized.iter().zip(placements) {
+            for row in 0..glyph.height {
+                let src = (row * glyph.width) as usize;
+                let dst = ((y + row) * width + x) as usize;
+                pixels[dst..dst + glyph.width as usize]
+                    .copy_from_slice(&glyph.pixels[src..src + glyph.width as usize]);
+            }
+
+            // atlas rows are uploaded top to bottom, so v grows downwards
+            let (w, h) = (width as f32, height as f32);
+            glyphs.insert(
+                glyph.c,
+                GlyphInfo {
+                    id: glyph.id.0,
+                    advance: scaled.h_advance(glyph.id),
+                    offset: glyph.offset,
+                    size: Vec2::new(glyph.width as f32, glyph.height as f32),
+                    uv_min: Vec2::new(x as f32 / w, (y + glyph.height) as f32 / h),
+                    uv_max: Vec2::new((x + glyph.width) as f32 / w, y as f32 / h),
+                },
+            );
+        }
+
+        let mut atlas = Texture2D::new(TextureSpec {
+            sampler: SamplerSettings::default(),
+            ..TextureSpec::new(width, height, TextureFormat::R8)
+        });
+        atlas.set_data(&pixels);
+
+        let (ascent, descent, line_gap) = (scaled.ascent(), scaled.descent(), scaled.line_gap());
+
+        Ok(Self {
+            font,
+            scale,
+            spec,
+            glyphs,
+            atlas,
+            ascent,
+            descent,
+            line_gap,
+        })
+    }
+
+    pub fn get_spec(&self) -> &FontSpec {
+        &self.spec
+    }
+
+    pub fn get_size(&self) -> f32 {
+        self.spec.size
+    }
+
+    pub fn get_mode(&self) -> FontAtlasMode {
+        self.spec.mode
+    }
+
+    pub fn get_atlas(&self) -> &Texture2D {
+        &self.atlas
+    }
+
+    pub fn get_ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    // negative, below the baseline
+    pub fn get_descent(&self) -> f32 {
+        self.descent
+    }
+
+    pub fn get_line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+
+    pub fn get_glyph(&self, c: char) -> Option<&GlyphInfo> {
+        self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+    }
+
+    pub fn get_kerning(&self, left: &GlyphInfo, right: &GlyphInfo) -> f32 {
+        self.font
+            .as_scaled(self.scale)
+            .kern(GlyphId(left.id), GlyphId(right.id))
+    }
+
+    // width of a single line in pixels at the font size
+    pub fn measure_line(&self, line: &str) -> f32 {
+        measure_line(self, line)
+    }
+
+    pub fn measure(&self, text: &str, options: &TextOptions) -> Vec2 {
+        self.layout(text, options).size
+    }
+
+    pub fn layout(&self, text: &str, options: &TextOptions) -> TextLayout {
+        layout_text(self, text, options)
+    }
+}
+
+impl GlyphMetrics for Font {
+    fn get_glyph(&self, c: char) -> Option<&GlyphInfo> {
+        Font::get_glyph(self, c)
+    }
+
+    fn get_kerning(&self, left: &GlyphInfo, right: &GlyphInfo) -> f32 {
+        Font::get_kerning(self, left, right)
+    }
+
+    fn get_ascent(&self) -> f32 {
+        self.ascent
+    }
+
+    fn get_descent(&self) -> f32 {
+        self.descent
+    }
+
+    fn get_line_height(&self) -> f32 {
+        Font::get_line_height(self)
+    }
+}
+
+// what laying out text needs of a font, apart from its atlas
+trait GlyphMetrics {
+    fn get_glyph(&self, c: char) -> Option<&GlyphInfo>;
+    fn get_kerning(&self, left: &GlyphInfo, right: &GlyphInfo) -> f32;
+    fn get_ascent(&self) -> f32;
+    // negative, below the baseline
+    fn get_descent(&self) -> f32;
+    fn get_line_height(&self) -> f32;
+}
+
+// width of a single line in pixels at the font size
+fn measure_line(metrics: &dyn GlyphMetrics, line: &str) -> f32 {
+    let mut width = 0.0;
+    let mut previous: Option<&GlyphInfo> = None;
+    for c in line.chars() {
+        if let Some(glyph) = metrics.get_glyph(c) {
+            if let Some(prev) = previous {
+                width += metrics.get_kerning(prev, glyph);
+            }
+            width += glyph.advance;
+            previous = Some(glyph);
+        }
+    }
+    width
+}
+
+fn layout_text(metrics: &dyn GlyphMetrics, text: &str, options: &TextOptions) -> TextLayout {
+    let lines = wrap(metrics, text, options.max_width);
+    let widths: Vec<f32> = lines.iter().map(|l| measure_line(metrics, l)).collect();
+    let block_width = options
+        .max_width
+        .unwrap_or_else(|| widths.iter().cloned().fold(0.0, f32::max));
+    let line_advance = metrics.get_line_height() * options.line_spacing;
+
+    let mut glyphs = Vec::new();
+    for (i, (line, width)) in lines.iter().zip(widths.iter()).enumerate() {
+        let mut x = match options.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => (block_width - width) * 0.5,
+            TextAlign::Right => block_width - width,
+        };
+        let baseline = -metrics.get_ascent() - i as f32 * line_advance;
+
+        let mut previous: Option<&GlyphInfo> = None;
+        for c in line.chars() {
+            let glyph = match metrics.get_glyph(c) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if let Some(prev) = previous {
+                x += metrics.get_kerning(prev, glyph);
+            }
+            if glyph.size.x > 0.0 && glyph.size.y > 0.0 {
+                glyphs.push(PositionedGlyph {
+                    glyph: *glyph,
+                    position: Vec2::new(x, baseline) + glyph.offset,
+                });
+            }
+            x += glyph.advance;
+            previous = Some(glyph);
+        }
+    }
+
+    let line_count = lines.len();
+    let height = if line_count == 0 {
+        0.0
+    } else {
+        (line_count - 1) as f32 * line_advance + metrics.get_ascent() - metrics.get_descent()
+    };
+
+    TextLayout {
+        glyphs,
+        size: Vec2::new(block_width, height),
+        line_count,
+    }
+}
+
+// splits at newlines and, with a maximum width, between words; words
+// wider than the maximum are split between characters
+fn wrap(metrics: &dyn GlyphMetrics, text: &str, max_width: Option<f32>) -> Vec<String> {
+    let mut lines = Vec::new();
+
+    for paragraph in text.split('\n') {
+        let max_width = match max_width {
+            Some(max_width) => max_width,
+            None => {
+                lines.push(paragraph.to_string());
+                continue;
+            }
+        };
+
+        let mut line = String::new();
+        for word in paragraph.split(' ') {
+            let candidate = if line.is_empty() {
+                word.to_string()
+            } else {
+                format!("{} {}", line, word)
+            };
+
+            if measure_line(metrics, &candidate) <= max_width || line.is_empty() {
+                line = candidate;
+            } else {
+                lines.push(line);
+                line = word.to_string();
+            }
+
+            while measure_line(metrics, &line) > max_width && line.chars().count() > 1 {
+                let mut split = line.len();
+                for (i, _) in line.char_indices().skip(1) {
+                    if measure_line(metrics, &line[..i]) > max_width {
+                        break;
+                    }
+                    split = i;
+                }
+                // always make progress, even if one character is too wide
+                if split == line.len() {
+                    split = line.char_indices().nth(1).map(|(i, _)| i).unwrap();
+                }
+                lines.push(line[..split].to_string());
+                line = line[split..].to_string();
+            }
+        }
+        lines.push(line);
+    }
+
+    lines
+}
+
+struct RasterizedGlyph {
+    c: char,
+    id: GlyphId,
+    width: u32,
+    height: u32,
+    offset: Vec2,
+    pixels: Vec<u8>,
+}
+
+fn rasterize_glyph(
+    font: &FontVec,
+    scale: PxScale,
+    c: char,
+    mode: FontAtlasMode,
+) -> RasterizedGlyph {
+    let id = font.glyph_id(c);
+    let glyph = id.with_scale_and_position(scale, ab_glyph::point(0.0, 0.0));
+
+    let outline = match font.outline_glyph(glyph) {
+        Some(outline) => outline,
+        // whitespace and missing glyphs only advance the pen
+        None => {
+            return RasterizedGlyph {
+                c,
+                id,
+                width: 0,
+                height: 0,
+                offset: Vec2::ZERO,
+                pixels: Vec::new(),
+            }
+        }
+    };
+
+    let bounds = outline.px_bounds();
+    let (w, h) = (bounds.width() as u32, bounds.height() as u32);
+    let pad = match mode {
+        FontAtlasMode::Bitmap => 0,
+        FontAtlasMode::Sdf { spread } => spread,
+    };
+    let (width, height) = (w + pad * 2, h + pad * 2);
+
+    let mut coverage = vec![0.0f32; (width * height) as usize];
+    outline.draw(|x, y, c| {
+        coverage[((y + pad) * width + x + pad) as usize] = c;
+    });
+
+    let pixels = match mode {
+        FontAtlasMode::Bitmap => coverage
+            .iter()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+            .collect(),
+        FontAtlasMode::Sdf { spread } => signed_distance_field(&coverage, width, height, spread),
+    };
+
+    // bounds are y down from the baseline
+    RasterizedGlyph {
+        c,
+        id,
+        width,
+        height,
+        offset: Vec2::new(bounds.min.x - pad as f32, -bounds.max.y - pad as f32),
+        pixels,
+    }
+}
+
+// 0.5 on the outline, increasing inwards, reaching 0 and 1 at `spread`
+// pixels outside and inside
+fn signed_distance_field(coverage: &[f32], width: u32, height: u32, spread: u32) -> Vec<u8> {
+    let (w, h) = (width as usize, height as usize);
+    let inside: Vec<bool> = coverage.iter().map(|c| *c >= 0.5).collect();
+
+    let mut to_inside: Vec<f32> = inside
+        .iter()
+        .map(|i| if *i { 0.0 } else { f32::INFINITY })
+        .collect();
+    let mut to_outside: Vec<f32> = inside
+        .iter()
+        .map(|i| if *i { f32::INFINITY } else { 0.0 })
+        .collect();
+    distance_transform(&mut to_inside, w, h);
+    distance_transform(&mut to_outside, w, h);
+
+    let spread = spread.max(1) as f32;
+    to_inside
+        .iter()
+        .zip(to_outside.iter())
+        .map(|(a, b)| {
+            // half a pixel puts the edge between inside and outside pixels
+            let d = if b.is_finite() && *b > 0.0 {
+                b.sqrt() - 0.5
+            } else {
+                -(a.sqrt() - 0.5)
+            };
+            ((0.5 + d / (2.0 * spread)).clamp(0.0, 1.0) * 255.0).round() as u8
+        })
+        .collect()
+}
+
+// Exact squared euclidean distance transform (Felzenszwalb and
+// Huttenlocher), `grid` holds 0 on features and infinity elsewhere.
+fn distance_transform(grid: &mut [f32], width: usize, height: usize) {
+    let n = width.max(height);
+    let mut f = vec![0.0f32; n];
+    let mut d = vec![0.0f32; n];
+    let mut v = vec![0usize; n];
+    let mut z = vec![0.0f32; n + 1];
+
+    for x in 0..width {
+        for y in 0..height {
+            f[y] = grid[y * width + x];
+        }
+        distance_transform_1d(&f[..height], &mut d, &mut v, &mut z);
+        for y in 0..height {
+            grid[y * width + x] = d[y];
+        }
+    }
+
+    for y in 0..height {
+        f[..width].copy_from_slice(&grid[y * width..(y + 1) * width]);
+        distance_transform_1d(&f[..width], &mut d, &mut v, &mut z);
+        grid[y * width..(y + 1) * width].copy_from_slice(&d[..width]);
+    }
+}
+
+fn distance_transform_1d(f: &[f32], d: &mut [f32], v: &mut [usize], z: &mut [f32]) {
+    let n = f.len();
+    // lower envelope of parabolas rooted at the finite samples
+    let mut k = 0;
+    let first = match f.iter().position(|x| x.is_finite()) {
+        Some(first) => first,
+        None => {
+            d[..n].iter_mut().for_each(|x| *x = f32::INFINITY);
+            return;
+        }
+    };
+    v[0] = first;
+    z[0] = f32::NEG_INFINITY;
+    z[1] = f32::INFINITY;
+
+    for q in first + 1..n {
+        if !f[q].is_finite() {
+            continue;
+        }
+        // z[0] is -inf, so k never drops below zero
+        loop {
+            let p = v[k];
+            let s = ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2.0 * (q - p) as f32);
+            if s <= z[k] {
+                k -= 1;
+                continue;
+            }
+            k += 1;
+            v[k] = q;
+            z[k] = s;
+            z[k + 1] = f32::INFINITY;
+            break;
+        }
+    }
+
+    k = 0;
+    for (q, out) in d[..n].iter_mut().enumerate() {
+        while z[k + 1] < q as f32 {
+            k += 1;
+        }
+        let p = v[k];
+        let dq = q as f32 - p as f32;
+        *out = dq * dq + f[p];
+    }
+}
+
+// atlas width, height and the top left corner of each glyph
+type AtlasPacking = (u32, u32, Vec<(u32, u32)>);
+
+// Shelf packing, tallest glyphs first, glyphs are placed in input order.
+fn pack_glyphs(glyphs: &[RasterizedGlyph]) -> Result<AtlasPacking, String> {
+    let mut order: Vec<usize> = (0..glyphs.len()).collect();
+    order.sort_by(|a, b| glyphs[*b].height.cmp(&glyphs[*a].height));
+
+    for &size in ATLAS_SIZES.iter() {
+        let mut placements = vec![(0, 0); glyphs.len()];
+        let (mut x, mut y, mut shelf_height) = (ATLAS_PADDING, ATLAS_PADDING, 0);
+        let mut fits = true;
+
+        for &i in order.iter() {
+            let glyph = &glyphs[i];
+            if glyph.width + ATLAS_PADDING * 2 > size {
+                fits = false;
+                break;
+            }
+            if x + glyph.width + ATLAS_PADDING > size {
+                x = ATLAS_PADDING;
+                y += shelf_height + ATLAS_PADDING;
+                shelf_height = 0;
+            }
+            placements[i] = (x, y);
+            x += glyph.width + ATLAS_PADDING;
+            shelf_height = shelf_height.max(glyph.height);
+        }
+
+        let height = (y + shelf_height + ATLAS_PADDING).next_power_of_two();
+        if fits && height <= size {
+            return Ok((size, height, placements));
+        }
+    }
+
+    Err(
+        "Font glyphs do not fit in the largest atlas, reduce the size or character set."
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every glyph advances 10 pixels, 'A' followed by 'V' is kerned by -3
+    struct TestMetrics {
+        glyphs: HashMap<char, GlyphInfo>,
+    }
+
+    impl TestMetrics {
+        fn new() -> Self {
+            let glyphs = "abcdefghAV? "
+                .chars()
+                .map(|c| {
+                    let size = if c == ' ' {
+                        Vec2::ZERO
+                    } else {
+                        Vec2::new(8.0, 10.0)
+                    };
+                    let glyph = GlyphInfo {
+                        id: c as u16,
+                        advance: 10.0,
+                        offset: Vec2::new(1.0, -2.0),
+                        size,
+                        uv_min: Vec2::ZERO,
+                        uv_max: Vec2::ZERO,
+                    };
+                    (c, glyph)
+                })
+                .collect();
+            Self { glyphs }
+        }
+    }
+
+    impl GlyphMetrics for TestMetrics {
+        fn get_glyph(&self, c: char) -> Option<&GlyphInfo> {
+            self.glyphs.get(&c).or_else(|| self.glyphs.get(&'?'))
+        }
+
+        fn get_kerning(&self, left: &GlyphInfo, right: &GlyphInfo) -> f32 {
+            if (left.id, right.id) == ('A' as u16, 'V' as u16) {
+                -3.0
+            } else {
+                0.0
+            }
+        }
+
+        fn get_ascent(&self) -> f32 {
+            8.0
+        }
+
+        fn get_descent(&self) -> f32 {
+            -2.0
+        }
+
+        fn get_line_height(&self) -> f32 {
+            12.0
+        }
+    }
+
+    fn glyph(width: u32, height: u32) -> RasterizedGlyph {
+        RasterizedGlyph {
+            c: 'a',
+            id: GlyphId(0),
+            width,
+            height,
+            offset: Vec2::ZERO,
+            pixels: vec![0; (width * height) as usize],
+        }
+    }
+
+    fn options(align: TextAlign, max_width: Option<f32>) -> TextOptions {
+        TextOptions {
+            align,
+            max_width,
+            ..TextOptions::default()
+        }
+    }
+
+    #[test]
+    fn wraps_at_word_boundaries() {
+        let metrics = TestMetrics::new();
+        assert_eq!(
+            wrap(&metrics, "ab cd ef gh", Some(50.0)),
+            vec!["ab cd", "ef gh"]
+        );
+        assert_eq!(
+            wrap(&metrics, "ab cd\nef", Some(100.0)),
+            vec!["ab cd", "ef"]
+        );
+        assert_eq!(wrap(&metrics, "ab cd ef", None), vec!["ab cd ef"]);
+    }
+
+    #[test]
+    fn splits_words_wider_than_the_line() {
+        let metrics = TestMetrics::new();
+        assert_eq!(
+            wrap(&metrics, "abcdefgh", Some(30.0)),
+            vec!["abc", "def", "gh"]
+        );
+        assert_eq!(
+            wrap(&metrics, "a bcdefgh", Some(30.0)),
+            vec!["a", "bcd", "efg", "h"]
+        );
+        // a single character wider than the line still makes progress
+        assert_eq!(wrap(&metrics, "abc", Some(5.0)), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn kerning_applies_between_pairs() {
+        let metrics = TestMetrics::new();
+        assert_eq!(measure_line(&metrics, "AV"), 17.0);
+        assert_eq!(measure_line(&metrics, "VA"), 20.0);
+        // unknown characters fall back to '?'
+        assert_eq!(measure_line(&metrics, "a\u{2603}"), 20.0);
+
+        let layout = layout_text(&metrics, "AVa", &TextOptions::default());
+        let x: Vec<f32> = layout.glyphs.iter().map(|g| g.position.x).collect();
+        assert_eq!(x, vec![1.0, 8.0, 18.0]);
+        assert_eq!(layout.size.x, 27.0);
+    }
+
+    #[test]
+    fn aligns_lines_within_the_block() {
+        let metrics = TestMetrics::new();
+        let first_x = |align| {
+            let layout = layout_text(&metrics, "ab", &options(align, Some(100.0)));
+            layout.glyphs[0].position.x
+        };
+        assert_eq!(first_x(TextAlign::Left), 1.0);
+        assert_eq!(first_x(TextAlign::Center), 41.0);
+        assert_eq!(first_x(TextAlign::Right), 81.0);
+
+        // without a maximum the widest line sets the block width
+        let layout = layout_text(&metrics, "abcd\nab", &options(TextAlign::Right, None));
+        assert_eq!(layout.size.x, 40.0);
+        assert_eq!(layout.glyphs[4].position.x, 21.0);
+    }
+
+    #[test]
+    fn lines_stack_downwards() {
+        let metrics = TestMetrics::new();
+        let layout = layout_text(&metrics, "a b", &options(TextAlign::Left, Some(10.0)));
+        assert_eq!(layout.line_count, 2);
+        // whitespace has no quad
+        assert_eq!(layout.glyphs.len(), 2);
+        assert_eq!(layout.glyphs[0].position, Vec2::new(1.0, -10.0));
+        assert_eq!(layout.glyphs[1].position, Vec2::new(1.0, -22.0));
+        assert_eq!(layout.size.y, 22.0);
+
+        let spaced = TextOptions {
+            line_spacing: 2.0,
+            ..options(TextAlign::Left, Some(10.0))
+        };
+        assert_eq!(layout_text(&metrics, "a b", &spaced).size.y, 34.0);
+        assert_eq!(
+            layout_text(&metrics, "", &TextOptions::default()).line_count,
+            1
+        );
+    }
+
+    #[test]
+    fn distance_field_is_signed_around_the_outline() {
+        // a 3x3 square in the middle of a 9x9 grid
+        let coverage: Vec<f32> = (0..81)
+            .map(|i| {
+                let (x, y) = (i % 9, i / 9);
+                if (3..6).contains(&x) && (3..6).contains(&y) {
+                    1.0
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let field = signed_distance_field(&coverage, 9, 9, 2);
+        let at = |x: usize, y: usize| field[y * 9 + x];
+
+        // half a pixel inside and outside of the edge, mirrored around 0.5
+        assert_eq!(at(3, 4), 159);
+        assert_eq!(at(2, 4), 96);
+        // the center is 1.5 pixels from the edge
+        assert_eq!(at(4, 4), 223);
+        // clamped beyond the spread
+        assert_eq!(at(0, 0), 0);
+        assert_eq!(at(0, 4), 0);
+
+        for y in 0..9 {
+            for x in 0..9 {
+                assert_eq!(at(x, y) >= 128, coverage[y * 9 + x] >= 0.5);
+            }
+        }
+    }
+
+    #[test]
+    fn packed_glyphs_never_overlap() {
+        let glyphs: Vec<RasterizedGlyph> = (0..200)
+            .map(|i| glyph(5 + i % 23, 7 + (i * 7) % 31))
+            .collect();
+        let (width, height, placements) = pack_glyphs(&glyphs).unwrap();
+        assert!(width.is_power_of_two() && height.is_power_of_two());
+
+        let rects: Vec<(u32, u32, u32, u32)> = glyphs
+            .iter()
+            .zip(placements.iter())
+            .map(|(glyph, (x, y))| (*x, *y, x + glyph.width, y + glyph.height))
+            .collect();
+        for (i, a) in rects.iter().enumerate() {
+            assert!(a.0 >= ATLAS_PADDING && a.1 >= ATLAS_PADDING);
+            assert!(a.2 + ATLAS_PADDING <= width && a.3 + ATLAS_PADDING <= height);
+            for b in rects[i + 1..].iter() {
+                // padding stays between neighbours
+                let apart = a.2 + ATLAS_PADDING <= b.0
+                    || b.2 + ATLAS_PADDING <= a.0
+                    || a.3 + ATLAS_PADDING <= b.1
+                    || b.3 + ATLAS_PADDING <= a.1;
+                assert!(apart, "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn packing_grows_the_atlas_and_fails_past_the_largest() {
+        let (width, _, _) = pack_glyphs(&[glyph(16, 16)]).unwrap();
+        assert_eq!(width, ATLAS_SIZES[0]);
+
+        let (width, _, _) = pack_glyphs(&[glyph(300, 16)]).unwrap();
+        assert_eq!(width, 512);
+
+        assert!(pack_glyphs(&[glyph(5000, 16)]).is_err());
+        let too_many: Vec<RasterizedGlyph> = (0..300).map(|_| glyph(1000, 1000)).collect();
+        assert!(pack_glyphs(&too_many).is_err());
+    }
+}
@@ -0,0 +1,675 @@
+use std::mem;
+
+use gl::types::GLsync;
+
+use crate::{
+    events::{Event, EventHandler},
+    log_warn,
+    renderer::{debug::set_object_label, state, texture::Texture},
+};
+
+const MAX_FRAMEBUFFER_SIZE: u32 = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramebufferFormat {
+    Rgba8,
+    Rgba16F,
+    Rgba32F,
+    R32I,
+    Depth24Stencil8,
+    Depth32F,
+}
+
+impl FramebufferFormat {
+    pub fn is_depth(&self) -> bool {
+        matches!(
+            self,
+            FramebufferFormat::Depth24Stencil8 | FramebufferFormat::Depth32F
+        )
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, FramebufferFormat::R32I)
+    }
+
+    // (internal format, pixel format, pixel type)
+    fn get_gl_formats(&self) -> (u32, u32, u32) {
+        match self {
+            FramebufferFormat::Rgba8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            FramebufferFormat::Rgba16F => (gl::RGBA16F, gl::RGBA, gl::FLOAT),
+            FramebufferFormat::Rgba32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+            FramebufferFormat::R32I => (gl::R32I, gl::RED_INTEGER, gl::INT),
+            FramebufferFormat::Depth24Stencil8 => (
+                gl::DEPTH24_STENCIL8,
+                gl::DEPTH_STENCIL,
+                gl::UNSIGNED_INT_24_8,
+            ),
+            FramebufferFormat::Depth32F => (gl::DEPTH_COMPONENT32F, gl::DEPTH_COMPONENT, gl::FLOAT),
+        }
+    }
+
+    pub(crate) fn get_gl_attachment_point(&self, index: usize) -> u32 {
+        match self {
+            FramebufferFormat::Depth24Stencil8 => gl::DEPTH_STENCIL_ATTACHMENT,
+            FramebufferFormat::Depth32F => gl::DEPTH_ATTACHMENT,
+            _ => gl::COLOR_ATTACHMENT0 + index as u32,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FramebufferSpec {
+    pub width: u32,
+    pub height: u32,
+    // 1 disables multisampling
+    pub samples: u32,
+    pub color_attachments: Vec<FramebufferFormat>,
+    pub depth_attachment: Option<FramebufferFormat>,
+    // follow Event::FramebufferResize, off for fixed size targets such as
+    // shadow maps
+    pub resize_with_window: bool,
+}
+
+impl FramebufferSpec {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            samples: 1,
+            color_attachments: vec![FramebufferFormat::Rgba8],
+            depth_attachment: Some(FramebufferFormat::Depth24Stencil8),
+            resize_with_window: true,
+        }
+    }
+}
+
+// Single sampled texture backing an attachment, can be bound for sampling.
+pub struct FramebufferAttachment {
+    renderer_id: u32,
+    width: u32,
+    height: u32,
+    format: FramebufferFormat,
+}
+
+impl FramebufferAttachment {
+    pub(crate) fn new(width: u32, height: u32, format: FramebufferFormat) -> Self {
+        let mut renderer_id = 0;
+        let (internal_format, pixel_format, ty) = format.get_gl_formats();
+
+        // integer textures cannot be filtered
+        let filter = if format.is_integer() {
+            gl::NEAREST
+        } else {
+            gl::LINEAR
+        };
+
+        unsafe {
+            gl::GenTextures(1, &mut renderer_id);
+            state::bind_texture(0, gl::TEXTURE_2D, renderer_id);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as i32,
+                width as i32,
+                height as i32,
+                0,
+                pixel_format,
+                ty,
+                std::ptr::null(),
+            );
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, filter as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, filter as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        }
+
+        Self {
+            renderer_id,
+            width,
+            height,
+            format,
+        }
+    }
+
+    pub fn get_format(&self) -> FramebufferFormat {
+        self.format
+    }
+
+    // Depth attachments with comparison enabled are sampled through
+    // `sampler2DShadow`, returning the filtered result of the depth test.
+    pub fn set_depth_compare(&self, enabled: bool) {
+        assert!(
+            self.format.is_depth(),
+            "Depth comparison requires a depth attachment."
+        );
+
+        let mode = if enabled {
+            gl::COMPARE_REF_TO_TEXTURE
+        } else {
+            gl::NONE
+        };
+
+        unsafe {
+            state::bind_texture(0, gl::TEXTURE_2D, self.renderer_id);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_MODE, mode as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as i32);
+        }
+    }
+}
+
+impl Texture for FramebufferAttachment {
+    fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    fn get_renderer_id(&self) -> u32 {
+        self.renderer_id
+    }
+
+    fn bind(&self, slot: u32) {
+        state::bind_texture(slot, gl::TEXTURE_2D, self.renderer_id);
+    }
+}
+
+impl Drop for FramebufferAttachment {
+    fn drop(&mut self) {
+        state::forget_texture(self.renderer_id);
+        unsafe {
+            gl::DeleteTextures(1, &self.renderer_id);
+        }
+    }
+}
+
+// Offscreen render target. Multisampled framebuffers render into renderbuffers
+// and are blitted into single sampled textures by `resolve`, which are the
+// ones returned by the attachment getters.
+pub struct Framebuffer {
+    spec: FramebufferSpec,
+    renderer_id: u32,
+    color_attachments: Vec<FramebufferAttachment>,
+    depth_attachment: Option<FramebufferAttachment>,
+    // multisampled renderbuffers and the framebuffer resolving into the textures
+    msaa_renderbuffers: Vec<u32>,
+    resolve_id: Option<u32>,
+    // reapplied to the recreated objects on resize
+    label: Option<String>,
+}
+
+impl Framebuffer {
+    pub fn new(spec: FramebufferSpec) -> Self {
+        let mut framebuffer = Self {
+            spec,
+            renderer_id: 0,
+            color_attachments: Vec::new(),
+            depth_attachment: None,
+            msaa_renderbuffers: Vec::new(),
+            resolve_id: None,
+            label: None,
+        };
+        framebuffer.invalidate();
+        framebuffer
+    }
+
+    // names the framebuffer and its attachments in debug output and captures
+    pub fn set_label(&mut self, label: &str) {
+        self.label = Some(label.to_string());
+        self.apply_label();
+    }
+
+    pub fn get_spec(&self) -> &FramebufferSpec {
+        &self.spec
+    }
+
+    pub fn get_renderer_id(&self) -> u32 {
+        self.renderer_id
+    }
+
+    pub fn is_multisampled(&self) -> bool {
+        self.spec.samples > 1
+    }
+
+    pub fn bind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.renderer_id);
+            state::set_viewport(0, 0, self.spec.width as i32, self.spec.height as i32);
+        }
+    }
+
+    pub fn unbind(&self) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0
+            || height == 0
+            || width > MAX_FRAMEBUFFER_SIZE
+            || height > MAX_FRAMEBUFFER_SIZE
+        {
+            log_warn!("Attempted to resize framebuffer to {}x{}", width, height);
+            return;
+        }
+        if width == self.spec.width && height == self.spec.height {
+            return;
+        }
+
+        self.spec.width = width;
+        self.spec.height = height;
+        self.invalidate();
+    }
+
+    pub fn get_color_attachment(&self, index: usize) -> &FramebufferAttachment {
+        &self.color_attachments[index]
+    }
+
+    pub fn get_color_attachment_count(&self) -> usize {
+        self.color_attachments.len()
+    }
+
+    pub fn get_depth_attachment(&self) -> Option<&FramebufferAttachment> {
+        self.depth_attachment.as_ref()
+    }
+
+    // copies the multisampled attachments into the sampleable textures,
+    // a no-op for single sampled framebuffers
+    pub fn resolve(&self) {
+        let resolve_id = match self.resolve_id {
+            Some(id) => id,
+            None => return,
+        };
+
+        let (w, h) = (self.spec.width as i32, self.spec.height as i32);
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.renderer_id);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, resolve_id);
+
+            for i in 0..self.color_attachments.len() {
+                let attachment = gl::COLOR_ATTACHMENT0 + i as u32;
+                gl::ReadBuffer(attachment);
+                gl::DrawBuffers(1, &attachment);
+                gl::BlitFramebuffer(0, 0, w, h, 0, 0, w, h, gl::COLOR_BUFFER_BIT, gl::NEAREST);
+            }
+
+            if let Some(depth) = &self.depth_attachment {
+                let mask = match depth.format {
+                    FramebufferFormat::Depth24Stencil8 => {
+                        gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT
+                    }
+                    _ => gl::DEPTH_BUFFER_BIT,
+                };
+                gl::BlitFramebuffer(0, 0, w, h, 0, 0, w, h, mask, gl::NEAREST);
+            }
+
+            set_draw_buffers(self.color_attachments.len());
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // expects the framebuffer to be bound
+    pub fn clear_attachment(&self, index: usize, value: [f32; 4]) {
+        let format = self.spec.color_attachments[index];
+
+        unsafe {
+            if format.is_integer() {
+                let int_value = [
+                    value[0] as i32,
+                    value[1] as i32,
+                    value[2] as i32,
+                    value[3] as i32,
+                ];
+                gl::ClearBufferiv(gl::COLOR, index as i32, int_value.as_ptr());
+            } else {
+                gl::ClearBufferfv(gl::COLOR, index as i32, value.as_ptr());
+            }
+        }
+    }
+
+    // reads a single pixel of an integer attachment, origin at the bottom left,
+    // waits for the GPU to finish rendering into it
+    pub fn read_pixel(&self, index: usize, x: u32, y: u32) -> i32 {
+        let mut value: i32 = 0;
+
+        self.bind_read_attachment(index);
+        unsafe {
+            gl::ReadPixels(
+                x as i32,
+                y as i32,
+                1,
+                1,
+                gl::RED_INTEGER,
+                gl::INT,
+                &mut value as *mut i32 as *mut _,
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+
+        value
+    }
+
+    // like `read_pixel`, but copies the pixel into `readback` on the GPU, the
+    // value can be taken from it once the rendering has finished
+    pub fn read_pixel_async(&self, index: usize, x: u32, y: u32, readback: &mut PixelReadback) {
+        self.bind_read_attachment(index);
+        state::bind_buffer(gl::PIXEL_PACK_BUFFER, readback.renderer_id);
+        unsafe {
+            gl::ReadPixels(
+                x as i32,
+                y as i32,
+                1,
+                1,
+                gl::RED_INTEGER,
+                gl::INT,
+                std::ptr::null_mut(),
+            );
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+        }
+        state::bind_buffer(gl::PIXEL_PACK_BUFFER, 0);
+        readback.set_fence();
+    }
+
+    fn bind_read_attachment(&self, index: usize) {
+        assert!(
+            self.spec.color_attachments[index].is_integer(),
+            "read_pixel requires an integer attachment."
+        );
+
+        let source = self.resolve_id.unwrap_or(self.renderer_id);
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, source);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0 + index as u32);
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.release();
+
+        let (width, height) = (self.spec.width, self.spec.height);
+
+        self.color_attachments = self
+            .spec
+            .color_attachments
+            .iter()
+            .map(|f| FramebufferAttachment::new(width, height, *f))
+            .collect();
+        self.depth_attachment = self
+            .spec
+            .depth_attachment
+            .map(|f| FramebufferAttachment::new(width, height, f));
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut self.renderer_id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.renderer_id);
+        }
+
+        if self.is_multisampled() {
+            self.attach_renderbuffers();
+            self.resolve_id = Some(self.create_resolve_framebuffer());
+            unsafe {
+                gl::BindFramebuffer(gl::FRAMEBUFFER, self.renderer_id);
+            }
+        } else {
+            attach_textures(&self.color_attachments, self.depth_attachment.as_ref());
+        }
+
+        unsafe {
+            set_draw_buffers(self.color_attachments.len());
+            check_status("Framebuffer");
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+        self.apply_label();
+    }
+
+    fn apply_label(&self) {
+        let label = match &self.label {
+            Some(label) => label,
+            None => return,
+        };
+
+        set_object_label(gl::FRAMEBUFFER, self.renderer_id, label);
+        if let Some(resolve_id) = self.resolve_id {
+            set_object_label(gl::FRAMEBUFFER, resolve_id, &format!("{} resolve", label));
+        }
+        for (i, attachment) in self.color_attachments.iter().enumerate() {
+            attachment.set_label(&format!("{} color {}", label, i));
+        }
+        if let Some(attachment) = &self.depth_attachment {
+            attachment.set_label(&format!("{} depth", label));
+        }
+    }
+
+    fn attach_renderbuffers(&mut self) {
+        let (width, height) = (self.spec.width as i32, self.spec.height as i32);
+        let samples = self.spec.samples as i32;
+        let formats = self
+            .spec
+            .color_attachments
+            .iter()
+            .enumerate()
+            .chain(self.spec.depth_attachment.iter().map(|f| (0, f)));
+
+        for (index, format) in formats {
+            let mut renderbuffer = 0;
+            let (internal_format, _, _) = format.get_gl_formats();
+
+            unsafe {
+                gl::GenRenderbuffers(1, &mut renderbuffer);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+                gl::RenderbufferStorageMultisample(
+                    gl::RENDERBUFFER,
+                    samples,
+                    internal_format,
+                    width,
+                    height,
+                );
+                gl::FramebufferRenderbuffer(
+                    gl::FRAMEBUFFER,
+                    format.get_gl_attachment_point(index),
+                    gl::RENDERBUFFER,
+                    renderbuffer,
+                );
+            }
+
+            self.msaa_renderbuffers.push(renderbuffer);
+        }
+    }
+
+    fn create_resolve_framebuffer(&self) -> u32 {
+        let mut resolve_id = 0;
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut resolve_id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, resolve_id);
+            attach_textures(&self.color_attachments, self.depth_attachment.as_ref());
+            set_draw_buffers(self.color_attachments.len());
+            check_status("Resolve framebuffer");
+        }
+
+        resolve_id
+    }
+
+    fn release(&mut self) {
+        unsafe {
+            if self.renderer_id != 0 {
+                gl::DeleteFramebuffers(1, &self.renderer_id);
+                self.renderer_id = 0;
+            }
+            if let Some(id) = self.resolve_id.take() {
+                gl::DeleteFramebuffers(1, &id);
+            }
+            if !self.msaa_renderbuffers.is_empty() {
+                gl::DeleteRenderbuffers(
+                    self.msaa_renderbuffers.len() as i32,
+                    self.msaa_renderbuffers.as_ptr(),
+                );
+                self.msaa_renderbuffers.clear();
+            }
+        }
+
+        self.color_attachments.clear();
+        self.depth_attachment = None;
+    }
+}
+
+impl EventHandler for Framebuffer {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        if !self.spec.resize_with_window {
+            return;
+        }
+
+        for e in events.iter() {
+            if let Event::FramebufferResize { width, height } = e {
+                self.resize(*width, *height);
+            }
+        }
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+// Receives a pixel of an integer attachment from `read_pixel_async` without
+// stalling on the GPU. Holds one read at a time, a new read replaces the
+// pending one.
+pub struct PixelReadback {
+    renderer_id: u32,
+    fence: Option<GLsync>,
+}
+
+impl PixelReadback {
+    // requires a current OpenGL context
+    pub fn new() -> Self {
+        let mut renderer_id = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut renderer_id);
+        }
+        state::bind_buffer(gl::PIXEL_PACK_BUFFER, renderer_id);
+        unsafe {
+            gl::BufferData(
+                gl::PIXEL_PACK_BUFFER,
+                mem::size_of::<i32>() as isize,
+                std::ptr::null(),
+                gl::STREAM_READ,
+            );
+        }
+        state::bind_buffer(gl::PIXEL_PACK_BUFFER, 0);
+
+        Self {
+            renderer_id,
+            fence: None,
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.fence.is_some()
+    }
+
+    // the pixel once the GPU has written it, None while the read is pending
+    // or when there is none
+    pub fn try_take(&mut self) -> Option<i32> {
+        let fence = self.fence?;
+        let status = unsafe { gl::ClientWaitSync(fence, 0, 0) };
+        if status != gl::ALREADY_SIGNALED && status != gl::CONDITION_SATISFIED {
+            return None;
+        }
+        self.delete_fence();
+
+        let mut value: i32 = 0;
+        state::bind_buffer(gl::PIXEL_PACK_BUFFER, self.renderer_id);
+        unsafe {
+            gl::GetBufferSubData(
+                gl::PIXEL_PACK_BUFFER,
+                0,
+                mem::size_of::<i32>() as isize,
+                &mut value as *mut i32 as *mut _,
+            );
+        }
+        state::bind_buffer(gl::PIXEL_PACK_BUFFER, 0);
+        Some(value)
+    }
+
+    fn set_fence(&mut self) {
+        self.delete_fence();
+        self.fence = Some(unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) });
+    }
+
+    fn delete_fence(&mut self) {
+        if let Some(fence) = self.fence.take() {
+            unsafe {
+                gl::DeleteSync(fence);
+            }
+        }
+    }
+}
+
+impl Default for PixelReadback {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PixelReadback {
+    fn drop(&mut self) {
+        self.delete_fence();
+        state::forget_buffer(self.renderer_id);
+        unsafe {
+            gl::DeleteBuffers(1, &self.renderer_id);
+        }
+    }
+}
+
+// expects the target framebuffer to be bound
+fn attach_textures(
+    color_attachments: &[FramebufferAttachment],
+    depth_attachment: Option<&FramebufferAttachment>,
+) {
+    let attachments = color_attachments
+        .iter()
+        .enumerate()
+        .chain(depth_attachment.into_iter().map(|a| (0, a)));
+
+    for (index, attachment) in attachments {
+        unsafe {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment.format.get_gl_attachment_point(index),
+                gl::TEXTURE_2D,
+                attachment.renderer_id,
+                0,
+            );
+        }
+    }
+}
+
+pub(crate) unsafe fn set_draw_buffers(count: usize) {
+    if count == 0 {
+        // depth only
+        gl::DrawBuffer(gl::NONE);
+        gl::ReadBuffer(gl::NONE);
+        return;
+    }
+
+    let buffers: Vec<u32> = (0..count as u32)
+        .map(|i| gl::COLOR_ATTACHMENT0 + i)
+        .collect();
+    gl::DrawBuffers(count as i32, buffers.as_ptr());
+}
+
+pub(crate) unsafe fn check_status(name: &str) {
+    let status = gl::CheckFramebufferStatus(gl::FRAMEBUFFER);
+    assert_eq!(
+        status,
+        gl::FRAMEBUFFER_COMPLETE,
+        "{} is incomplete: 0x{:x}",
+        name,
+        status
+    );
+}
@@ -0,0 +1,249 @@
+use std::{mem, ptr, rc::Rc, slice};
+
+use crate::math::{Mat4, Vec4};
+use crate::renderer::{
+    buffer::{BufferElement, BufferLayout, IndexBuffer, ShaderDataType, VertexArray, VertexBuffer},
+    mesh::{MeshData, MeshVertex},
+    profiler,
+};
+
+// upper bound for the vertices of one fallback batch
+const MAX_BATCH_VERTICES: usize = 1 << 18;
+
+// Default per instance attributes, for shaders that only need a transform
+// and a color.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InstanceData {
+    pub transform: Mat4,
+    pub color: Vec4,
+}
+
+impl InstanceData {
+    pub fn new(transform: Mat4, color: Vec4) -> Self {
+        Self { transform, color }
+    }
+
+    // attribute locations 4 to 8, following the `MeshVertex` attributes
+    pub fn get_layout() -> BufferLayout {
+        BufferLayout::new(vec![
+            BufferElement::new("a_InstanceTransform", ShaderDataType::Mat4),
+            BufferElement::new("a_InstanceColor", ShaderDataType::Float4),
+        ])
+        .with_divisor(1)
+    }
+}
+
+// Instanced draws and attribute divisors are core in OpenGL 3.3, older
+// contexts may still expose them through extensions.
+pub fn supports_instancing() -> bool {
+    gl::DrawElementsInstanced::is_loaded() && gl::VertexAttribDivisor::is_loaded()
+}
+
+enum DrawPath {
+    Instanced,
+    // The mesh is copied `batch_size` times into one buffer and the instance
+    // attributes are repeated for every vertex of their copy, so the same
+    // shader works with plain indexed draws.
+    Batched { batch_size: usize, scratch: Vec<u8> },
+}
+
+// A mesh drawn many times per call with per instance attributes streamed
+// from the CPU every frame.
+pub struct InstancedMesh {
+    vertex_array: VertexArray,
+    instance_buffer: Rc<VertexBuffer>,
+    instance_layout: BufferLayout,
+    index_count: usize,
+    vertex_count: usize,
+    max_instances: usize,
+    path: DrawPath,
+}
+
+impl InstancedMesh {
+    // Requires a current OpenGL context. The instance attributes follow the
+    // `MeshVertex` ones, `instance_layout` needs a non-zero divisor. Draws
+    // with more than `max_instances` instances are split into several calls.
+    pub fn new(data: &MeshData, instance_layout: BufferLayout, max_instances: usize) -> Self {
+        assert!(
+            instance_layout.is_instanced(),
+            "Instance layout has no divisor."
+        );
+        assert!(
+            max_instances > 0,
+            "Instanced mesh needs at least one instance."
+        );
+
+        if supports_instancing() {
+            Self::new_instanced(data, instance_layout, max_instances)
+        } else {
+            Self::new_batched(data, instance_layout, max_instances)
+        }
+    }
+
+    fn new_instanced(data: &MeshData, instance_layout: BufferLayout, max_instances: usize) -> Self {
+        let divisor = instance_layout.get_divisor() as usize;
+        let element_count = max_instances.div_ceil(divisor);
+        let instance_buffer = Rc::new(VertexBuffer::new(
+            element_count * instance_layout.get_stride(),
+            instance_layout.clone(),
+        ));
+
+        let mut vertex_array = VertexArray::new();
+        vertex_array.add_vertex_buffer(Rc::new(VertexBuffer::from_data(
+            &data.vertices,
+            MeshVertex::get_layout(),
+        )));
+        vertex_array.add_vertex_buffer(instance_buffer.clone());
+        vertex_array.set_index_buffer(Rc::new(IndexBuffer::new(&data.indices)));
+
+        Self {
+            vertex_array,
+            instance_buffer,
+            instance_layout,
+            index_count: data.indices.len(),
+            vertex_count: data.vertices.len(),
+            max_instances: element_count * divisor,
+            path: DrawPath::Instanced,
+        }
+    }
+
+    fn new_batched(data: &MeshData, instance_layout: BufferLayout, max_instances: usize) -> Self {
+        let vertex_count = data.vertices.len().max(1);
+        let batch_size = (MAX_BATCH_VERTICES / vertex_count)
+            .max(1)
+            .min(max_instances);
+
+        let mut vertices = Vec::with_capacity(batch_size * data.vertices.len());
+        let mut indices = Vec::with_capacity(batch_size * data.indices.len());
+        for copy in 0..batch_size {
+            let base = (copy * data.vertices.len()) as u32;
+            vertices.extend_from_slice(&data.vertices);
+            indices.extend(data.indices.iter().map(|index| base + index));
+        }
+
+        // same attributes and locations, advancing per vertex
+        let per_vertex_layout = instance_layout.clone().with_divisor(0);
+        let instance_buffer = Rc::new(VertexBuffer::new(
+            batch_size * data.vertices.len() * per_vertex_layout.get_stride(),
+            per_vertex_layout,
+        ));
+
+        let mut vertex_array = VertexArray::new();
+        vertex_array.add_vertex_buffer(Rc::new(VertexBuffer::from_data(
+            &vertices,
+            MeshVertex::get_layout(),
+        )));
+        vertex_array.add_vertex_buffer(instance_buffer.clone());
+        vertex_array.set_index_buffer(Rc::new(IndexBuffer::new(&indices)));
+
+        Self {
+            vertex_array,
+            instance_buffer,
+            instance_layout,
+            index_count: data.indices.len(),
+            vertex_count: data.vertices.len(),
+            max_instances,
+            path: DrawPath::Batched {
+                batch_size,
+                scratch: Vec::new(),
+            },
+        }
+    }
+
+    // Draws with the currently bound shader. `T` must match the instance
+    // layout, each element covers `divisor` consecutive instances.
+    pub fn draw<T: Copy>(&mut self, instances: &[T]) {
+        let stride = self.instance_layout.get_stride();
+        assert_eq!(
+            mem::size_of::<T>(),
+            stride,
+            "Instance data does not match the instance layout."
+        );
+        if instances.is_empty() || self.index_count == 0 {
+            return;
+        }
+
+        let divisor = self.instance_layout.get_divisor() as usize;
+        self.vertex_array.bind();
+
+        match &mut self.path {
+            DrawPath::Instanced => {
+                for chunk in instances.chunks(self.max_instances / divisor) {
+                    self.instance_buffer.stream_data(chunk);
+                    unsafe {
+                        gl::DrawElementsInstanced(
+                            gl::TRIANGLES,
+                            self.index_count as i32,
+                            gl::UNSIGNED_INT,
+                            ptr::null(),
+                            (chunk.len() * divisor) as i32,
+                        );
+                    }
+                    profiler::record_draw(
+                        gl::TRIANGLES,
+                        self.index_count as u32,
+                        (chunk.len() * divisor) as u32,
+                    );
+                }
+            }
+            DrawPath::Batched {
+                batch_size,
+                scratch,
+            } => {
+                let bytes = unsafe {
+                    slice::from_raw_parts(
+                        instances.as_ptr() as *const u8,
+                        mem::size_of_val(instances),
+                    )
+                };
+                let instance_count = instances.len() * divisor;
+
+                let mut first = 0;
+                while first < instance_count {
+                    let count = (*batch_size).min(instance_count - first);
+
+                    scratch.clear();
+                    for instance in first..first + count {
+                        let element = instance / divisor;
+                        let attributes = &bytes[element * stride..(element + 1) * stride];
+                        for _ in 0..self.vertex_count {
+                            scratch.extend_from_slice(attributes);
+                        }
+                    }
+                    self.instance_buffer.stream_data(scratch);
+
+                    unsafe {
+                        gl::DrawElements(
+                            gl::TRIANGLES,
+                            (count * self.index_count) as i32,
+                            gl::UNSIGNED_INT,
+                            ptr::null(),
+                        );
+                    }
+                    profiler::record_draw(gl::TRIANGLES, (count * self.index_count) as u32, 1);
+                    first += count;
+                }
+            }
+        }
+
+        self.vertex_array.unbind();
+    }
+
+    // false when drawing falls back to batched copies of the mesh
+    pub fn is_instanced(&self) -> bool {
+        matches!(self.path, DrawPath::Instanced)
+    }
+
+    pub fn get_instance_layout(&self) -> &BufferLayout {
+        &self.instance_layout
+    }
+
+    // instances per draw call
+    pub fn get_max_instances(&self) -> usize {
+        match self.path {
+            DrawPath::Instanced => self.max_instances,
+            DrawPath::Batched { batch_size, .. } => batch_size,
+        }
+    }
+}
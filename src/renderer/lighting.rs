@@ -0,0 +1,727 @@
+use std::{rc::Rc, slice};
+
+use crate::math::{Vec3, Vec4};
+use crate::renderer::{
+    buffer::UniformBuffer,
+    environment::ENVIRONMENT_TEXTURE_SLOT,
+    material::{Material, MaterialInstance},
+    model::{AlphaMode, MaterialData},
+    picking::encode_entity_id,
+    shader::{OpenGLShader, Shader},
+    shadow::{ShadowBlock, MAX_CASCADES, MAX_SPOT_SHADOWS, SHADOW_TEXTURE_SLOT},
+    texture::{Texture, Texture2D},
+};
+
+pub const MAX_LIGHTS: usize = 16;
+// uniform buffer binding points of the `Lights` and `Shadows` blocks
+pub const LIGHTS_BINDING: u32 = 0;
+pub const SHADOWS_BINDING: u32 = 1;
+
+// Vertex shader shared by the lit shaders, expects `MeshVertex` attributes
+// and the engine uniforms uploaded by materials.
+static LIT_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_Position;
+    layout(location = 1) in vec3 a_Normal;
+    layout(location = 2) in vec2 a_TexCoord;
+    layout(location = 3) in vec4 a_Tangent;
+
+    uniform mat4 u_ViewProjection;
+    uniform mat4 u_Model;
+    uniform mat3 u_NormalMatrix;
+
+    out vec3 v_WorldPosition;
+    out vec3 v_Normal;
+    out vec2 v_TexCoord;
+    out vec4 v_Tangent;
+
+    void main() {
+        vec4 world_position = u_Model * vec4(a_Position, 1.0);
+        v_WorldPosition = world_position.xyz;
+        v_Normal = u_NormalMatrix * a_Normal;
+        v_TexCoord = a_TexCoord;
+        v_Tangent = vec4(mat3(u_Model) * a_Tangent.xyz, a_Tangent.w);
+        gl_Position = u_ViewProjection * world_position;
+    }
+";
+
+// Light and shadow blocks, normal mapping and output encoding shared by the
+// lit fragment shaders. Mirrors `LightBlock` and `ShadowBlock`.
+static LIT_FRAGMENT_COMMON: &'static str = "
+    #version 330 core
+
+    #define MAX_LIGHTS 16
+    #define LIGHT_DIRECTIONAL 0
+    #define LIGHT_POINT 1
+    #define LIGHT_SPOT 2
+    #define MAX_CASCADES 4
+    #define MAX_SPOT_SHADOWS 4
+
+    struct Light {
+        vec4 position;  // xyz position, w type
+        vec4 direction; // xyz direction the light travels, w range
+        vec4 color;     // rgb color times intensity
+        vec4 cone;      // x cos inner angle, y cos outer angle, z shadow slot or -1
+    };
+
+    layout(std140) uniform Lights {
+        vec4 u_Ambient;
+        ivec4 u_LightCount;
+        Light u_Lights[MAX_LIGHTS];
+    };
+
+    layout(std140) uniform Shadows {
+        mat4 u_CascadeMatrices[MAX_CASCADES];
+        mat4 u_SpotShadowMatrices[MAX_SPOT_SHADOWS];
+        vec4 u_CascadeSplits;   // view depth at which each cascade ends
+        vec4 u_CameraForward;   // view depth is dot(p, xyz) + w
+        vec4 u_ShadowParams;    // x cascade count, y pcf radius, z depth bias, w normal bias
+        vec4 u_ShadowTexelSize; // x one over the shadow map resolution
+    };
+
+    uniform sampler2DShadow u_CascadeMaps[MAX_CASCADES];
+    uniform sampler2DShadow u_SpotShadowMaps[MAX_SPOT_SHADOWS];
+    uniform int u_ReceiveShadows;
+
+    in vec3 v_WorldPosition;
+    in vec3 v_Normal;
+    in vec2 v_TexCoord;
+    in vec4 v_Tangent;
+
+    uniform vec3 u_CameraPosition;
+    uniform sampler2D u_NormalMap;
+    uniform int u_HasNormalMap;
+    // set when a later pass does tone mapping and gamma
+    uniform int u_LinearOutput;
+    // written to the entity ID attachment, see `picking`
+    uniform int u_EntityId;
+
+    layout(location = 0) out vec4 color;
+    layout(location = 1) out int entity_id;
+
+    vec3 get_normal() {
+        vec3 n = normalize(v_Normal);
+        if (!gl_FrontFacing) {
+            n = -n;
+        }
+        if (u_HasNormalMap == 0) {
+            return n;
+        }
+
+        vec3 t = normalize(v_Tangent.xyz - n * dot(n, v_Tangent.xyz));
+        vec3 b = cross(n, t) * v_Tangent.w;
+        vec3 m = texture(u_NormalMap, v_TexCoord).xyz * 2.0 - 1.0;
+        return normalize(mat3(t, b, n) * m);
+    }
+
+    // radiance arriving from `light`, `l` is the direction towards it
+    vec3 get_radiance(Light light, out vec3 l) {
+        int light_type = int(light.position.w);
+        if (light_type == LIGHT_DIRECTIONAL) {
+            l = -normalize(light.direction.xyz);
+            return light.color.rgb;
+        }
+
+        vec3 to_light = light.position.xyz - v_WorldPosition;
+        float dist = length(to_light);
+        l = to_light / max(dist, 0.0001);
+
+        // inverse square with a smooth cutoff at the range
+        float ratio = dist / light.direction.w;
+        float window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+        float attenuation = window * window / (dist * dist + 1.0);
+
+        if (light_type == LIGHT_SPOT) {
+            float cos_angle = dot(-l, normalize(light.direction.xyz));
+            attenuation *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+        }
+        return light.color.rgb * attenuation;
+    }
+
+    // sampler arrays only take constant indices in GLSL 3.30
+    float sample_shadow_map(int map, vec3 coords) {
+        if (map == 0) return textureLod(u_CascadeMaps[0], coords, 0.0);
+        if (map == 1) return textureLod(u_CascadeMaps[1], coords, 0.0);
+        if (map == 2) return textureLod(u_CascadeMaps[2], coords, 0.0);
+        if (map == 3) return textureLod(u_CascadeMaps[3], coords, 0.0);
+        if (map == 4) return textureLod(u_SpotShadowMaps[0], coords, 0.0);
+        if (map == 5) return textureLod(u_SpotShadowMaps[1], coords, 0.0);
+        if (map == 6) return textureLod(u_SpotShadowMaps[2], coords, 0.0);
+        return textureLod(u_SpotShadowMaps[3], coords, 0.0);
+    }
+
+    // percentage closer filtering over a square kernel of compared taps
+    float filter_shadow(int map, mat4 light_matrix, vec3 position) {
+        vec4 clip = light_matrix * vec4(position, 1.0);
+        vec3 coords = clip.xyz / clip.w * 0.5 + 0.5;
+        if (coords.z > 1.0 || any(lessThan(coords.xy, vec2(0.0))) || any(greaterThan(coords.xy, vec2(1.0)))) {
+            return 1.0;
+        }
+        coords.z -= u_ShadowParams.z;
+
+        int radius = int(u_ShadowParams.y);
+        float lit = 0.0;
+        for (int x = -radius; x <= radius; x++) {
+            for (int y = -radius; y <= radius; y++) {
+                vec2 offset = vec2(x, y) * u_ShadowTexelSize.x;
+                lit += sample_shadow_map(map, vec3(coords.xy + offset, coords.z));
+            }
+        }
+        float size = float(2 * radius + 1);
+        return lit / (size * size);
+    }
+
+    // 1 where `light` is not blocked
+    float get_shadow(Light light, vec3 n) {
+        int slot = int(light.cone.z);
+        if (u_ReceiveShadows == 0 || slot < 0) {
+            return 1.0;
+        }
+
+        vec3 position = v_WorldPosition + n * u_ShadowParams.w;
+        if (int(light.position.w) == LIGHT_DIRECTIONAL) {
+            float depth = dot(v_WorldPosition, u_CameraForward.xyz) + u_CameraForward.w;
+            int cascade_count = int(u_ShadowParams.x);
+            for (int i = 0; i < cascade_count; i++) {
+                if (depth <= u_CascadeSplits[i]) {
+                    return filter_shadow(i, u_CascadeMatrices[i], position);
+                }
+            }
+            return 1.0;
+        }
+        return filter_shadow(MAX_CASCADES + slot, u_SpotShadowMatrices[slot], position);
+    }
+
+    vec4 encode_output(vec3 linear, float alpha) {
+        if (u_LinearOutput != 0) {
+            return vec4(linear, alpha);
+        }
+        return vec4(pow(clamp(linear, 0.0, 1.0), vec3(1.0 / 2.2)), alpha);
+    }
+";
+
+static BLINN_PHONG_FRAGMENT_SHADER: &'static str = "
+    uniform vec4 u_DiffuseColor;
+    uniform sampler2D u_DiffuseMap;
+    uniform int u_HasDiffuseMap;
+    uniform vec3 u_SpecularColor;
+    uniform float u_Shininess;
+
+    void main() {
+        vec4 diffuse = u_DiffuseColor;
+        if (u_HasDiffuseMap != 0) {
+            diffuse *= texture(u_DiffuseMap, v_TexCoord);
+        }
+
+        vec3 n = get_normal();
+        vec3 v = normalize(u_CameraPosition - v_WorldPosition);
+        vec3 result = u_Ambient.rgb * diffuse.rgb;
+
+        for (int i = 0; i < u_LightCount.x; i++) {
+            vec3 l;
+            vec3 radiance = get_radiance(u_Lights[i], l) * get_shadow(u_Lights[i], n);
+            vec3 h = normalize(l + v);
+            float n_dot_l = max(dot(n, l), 0.0);
+            float specular = n_dot_l > 0.0 ? pow(max(dot(n, h), 0.0), u_Shininess) : 0.0;
+            result += radiance * (diffuse.rgb * n_dot_l + u_SpecularColor * specular);
+        }
+
+        color = encode_output(result, diffuse.a);
+        entity_id = u_EntityId;
+    }
+";
+
+// metallic-roughness model as in glTF, GGX distribution with Smith geometry
+static PBR_FRAGMENT_SHADER: &'static str = "
+    #define PI 3.14159265
+
+    uniform vec4 u_BaseColor;
+    uniform sampler2D u_BaseColorMap;
+    uniform int u_HasBaseColorMap;
+    uniform float u_Metallic;
+    uniform float u_Roughness;
+    // green channel roughness, blue channel metallic
+    uniform sampler2D u_MetallicRoughnessMap;
+    uniform int u_HasMetallicRoughnessMap;
+    uniform vec3 u_Emissive;
+    uniform sampler2D u_EmissiveMap;
+    uniform int u_HasEmissiveMap;
+    // fragments with a lower alpha are discarded
+    uniform float u_AlphaCutoff;
+
+    // image based lighting, replaces the flat ambient term when set
+    uniform int u_HasEnvironment;
+    uniform samplerCube u_IrradianceMap;
+    uniform samplerCube u_PrefilteredMap;
+    uniform sampler2D u_BrdfLut;
+    // mip level of the prefiltered map at roughness 1
+    uniform float u_PrefilteredMaxLod;
+    uniform float u_EnvironmentIntensity;
+
+    float distribution_ggx(float n_dot_h, float roughness) {
+        float a = roughness * roughness;
+        float a2 = a * a;
+        float d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+        return a2 / (PI * d * d);
+    }
+
+    float geometry_smith(float n_dot_v, float n_dot_l, float roughness) {
+        float k = (roughness + 1.0) * (roughness + 1.0) / 8.0;
+        float gv = n_dot_v / (n_dot_v * (1.0 - k) + k);
+        float gl = n_dot_l / (n_dot_l * (1.0 - k) + k);
+        return gv * gl;
+    }
+
+    vec3 fresnel_schlick(float cos_theta, vec3 f0) {
+        return f0 + (1.0 - f0) * pow(1.0 - cos_theta, 5.0);
+    }
+
+    // rough surfaces reflect less at grazing angles
+    vec3 fresnel_schlick_roughness(float cos_theta, vec3 f0, float roughness) {
+        return f0 + (max(vec3(1.0 - roughness), f0) - f0) * pow(1.0 - cos_theta, 5.0);
+    }
+
+    // split sum approximation of the environment lighting
+    vec3 get_environment_light(vec3 n, vec3 v, float n_dot_v, vec3 f0, vec3 albedo, float metallic, float roughness) {
+        vec3 f = fresnel_schlick_roughness(n_dot_v, f0, roughness);
+        vec3 diffuse = (1.0 - f) * (1.0 - metallic) * albedo * texture(u_IrradianceMap, n).rgb;
+
+        vec3 prefiltered = textureLod(u_PrefilteredMap, reflect(-v, n), roughness * u_PrefilteredMaxLod).rgb;
+        vec2 brdf = texture(u_BrdfLut, vec2(n_dot_v, roughness)).rg;
+        vec3 specular = prefiltered * (f * brdf.x + brdf.y);
+
+        return (diffuse + specular) * u_EnvironmentIntensity;
+    }
+
+    void main() {
+        vec4 base_color = u_BaseColor;
+        if (u_HasBaseColorMap != 0) {
+            base_color *= texture(u_BaseColorMap, v_TexCoord);
+        }
+        if (base_color.a < u_AlphaCutoff) {
+            discard;
+        }
+
+        float metallic = u_Metallic;
+        float roughness = u_Roughness;
+        if (u_HasMetallicRoughnessMap != 0) {
+            vec4 mr = texture(u_MetallicRoughnessMap, v_TexCoord);
+            roughness *= mr.g;
+            metallic *= mr.b;
+        }
+        roughness = clamp(roughness, 0.04, 1.0);
+
+        vec3 n = get_normal();
+        vec3 v = normalize(u_CameraPosition - v_WorldPosition);
+        float n_dot_v = max(dot(n, v), 0.0001);
+        vec3 f0 = mix(vec3(0.04), base_color.rgb, metallic);
+
+        vec3 result = vec3(0.0);
+        for (int i = 0; i < u_LightCount.x; i++) {
+            vec3 l;
+            vec3 radiance = get_radiance(u_Lights[i], l) * get_shadow(u_Lights[i], n);
+            vec3 h = normalize(l + v);
+            float n_dot_l = max(dot(n, l), 0.0);
+            if (n_dot_l <= 0.0) {
+                continue;
+            }
+
+            float d = distribution_ggx(max(dot(n, h), 0.0), roughness);
+            float g = geometry_smith(n_dot_v, n_dot_l, roughness);
+            vec3 f = fresnel_schlick(max(dot(h, v), 0.0), f0);
+
+            vec3 specular = d * g * f / (4.0 * n_dot_v * n_dot_l);
+            vec3 diffuse = (1.0 - f) * (1.0 - metallic) * base_color.rgb / PI;
+            result += (diffuse + specular) * radiance * n_dot_l;
+        }
+
+        if (u_HasEnvironment != 0) {
+            result += get_environment_light(n, v, n_dot_v, f0, base_color.rgb, metallic, roughness);
+        } else {
+            result += u_Ambient.rgb * base_color.rgb * (1.0 - metallic * 0.5);
+        }
+
+        vec3 emissive = u_Emissive;
+        if (u_HasEmissiveMap != 0) {
+            emissive *= texture(u_EmissiveMap, v_TexCoord).rgb;
+        }
+        result += emissive;
+
+        color = encode_output(result, base_color.a);
+        entity_id = u_EntityId;
+    }
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DirectionalLight {
+    // direction the light travels
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub cast_shadows: bool,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Vec3, color: Vec3, intensity: f32) -> Self {
+        Self {
+            direction: direction.normalize(),
+            color,
+            intensity,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn with_shadows(mut self, cast_shadows: bool) -> Self {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    // no light reaches past this distance
+    pub range: f32,
+}
+
+impl PointLight {
+    pub fn new(position: Vec3, color: Vec3, intensity: f32, range: f32) -> Self {
+        Self {
+            position,
+            color,
+            intensity,
+            range,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Vec3,
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub range: f32,
+    // half angles of the cone in radians, full intensity inside the inner one
+    pub inner_angle: f32,
+    pub outer_angle: f32,
+    pub cast_shadows: bool,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        inner_angle: f32,
+        outer_angle: f32,
+    ) -> Self {
+        Self {
+            position,
+            direction: direction.normalize(),
+            color,
+            intensity,
+            range,
+            inner_angle,
+            outer_angle,
+            cast_shadows: false,
+        }
+    }
+
+    pub fn with_shadows(mut self, cast_shadows: bool) -> Self {
+        self.cast_shadows = cast_shadows;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Light {
+    Directional(DirectionalLight),
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+impl From<DirectionalLight> for Light {
+    fn from(light: DirectionalLight) -> Self {
+        Light::Directional(light)
+    }
+}
+
+impl From<PointLight> for Light {
+    fn from(light: PointLight) -> Self {
+        Light::Point(light)
+    }
+}
+
+impl From<SpotLight> for Light {
+    fn from(light: SpotLight) -> Self {
+        Light::Spot(light)
+    }
+}
+
+// The lights of one frame.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LightEnvironment {
+    pub ambient: Vec3,
+    pub lights: Vec<Light>,
+}
+
+impl LightEnvironment {
+    pub fn new(ambient: Vec3) -> Self {
+        Self {
+            ambient,
+            lights: Vec::new(),
+        }
+    }
+
+    pub fn add<L: Into<Light>>(&mut self, light: L) {
+        self.lights.push(light.into());
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+    }
+
+    // Shadow map slot of every light: the cascades for the first shadow
+    // casting directional light, a spot shadow map for each of the first
+    // `MAX_SPOT_SHADOWS` shadow casting spot lights.
+    pub fn get_shadow_slots(&self) -> Vec<Option<usize>> {
+        let mut has_cascades = false;
+        let mut spot_count = 0;
+
+        self.lights
+            .iter()
+            .take(MAX_LIGHTS)
+            .map(|light| match light {
+                Light::Directional(l) if l.cast_shadows && !has_cascades => {
+                    has_cascades = true;
+                    Some(0)
+                }
+                Light::Spot(l) if l.cast_shadows && spot_count < MAX_SPOT_SHADOWS => {
+                    spot_count += 1;
+                    Some(spot_count - 1)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+// std140 layout of the `Lights` block
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct GpuLight {
+    position: Vec4,
+    direction: Vec4,
+    color: Vec4,
+    cone: Vec4,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct LightBlock {
+    ambient: Vec4,
+    light_count: [i32; 4],
+    lights: [GpuLight; MAX_LIGHTS],
+}
+
+impl GpuLight {
+    fn new(light: &Light, shadow_slot: Option<usize>) -> Self {
+        let shadow_slot = shadow_slot.map_or(-1.0, |slot| slot as f32);
+
+        match light {
+            Light::Directional(l) => Self {
+                position: Vec4::new(0.0, 0.0, 0.0, 0.0),
+                direction: l.direction.extend(0.0),
+                color: (l.color * l.intensity).extend(1.0),
+                cone: Vec4::new(0.0, 0.0, shadow_slot, 0.0),
+            },
+            Light::Point(l) => Self {
+                position: l.position.extend(1.0),
+                direction: Vec4::new(0.0, 0.0, 0.0, l.range),
+                color: (l.color * l.intensity).extend(1.0),
+                cone: Vec4::new(0.0, 0.0, -1.0, 0.0),
+            },
+            Light::Spot(l) => Self {
+                position: l.position.extend(2.0),
+                direction: l.direction.extend(l.range),
+                color: (l.color * l.intensity).extend(1.0),
+                cone: Vec4::new(l.inner_angle.cos(), l.outer_angle.cos(), shadow_slot, 0.0),
+            },
+        }
+    }
+}
+
+// Uniform buffers with the active lights and their shadow data, shared by
+// every lit shader. The shadow buffer is filled by `ShadowRenderer`.
+pub struct LightBuffer {
+    uniform_buffer: UniformBuffer,
+    shadow_buffer: UniformBuffer,
+}
+
+impl LightBuffer {
+    // requires a current OpenGL context
+    pub fn new() -> Self {
+        let shadow_buffer = UniformBuffer::new(std::mem::size_of::<ShadowBlock>(), SHADOWS_BINDING);
+        shadow_buffer.set_data(slice::from_ref(&ShadowBlock::default()));
+
+        Self {
+            uniform_buffer: UniformBuffer::new(std::mem::size_of::<LightBlock>(), LIGHTS_BINDING),
+            shadow_buffer,
+        }
+    }
+
+    pub fn get_shadow_buffer(&self) -> &UniformBuffer {
+        &self.shadow_buffer
+    }
+
+    // call once per frame before drawing, lights past `MAX_LIGHTS` are dropped
+    pub fn upload(&self, environment: &LightEnvironment) {
+        let mut block = LightBlock {
+            ambient: environment.ambient.extend(1.0),
+            light_count: [0; 4],
+            lights: [GpuLight::default(); MAX_LIGHTS],
+        };
+
+        let shadow_slots = environment.get_shadow_slots();
+        let lights = environment.lights.iter().zip(shadow_slots);
+        for (gpu_light, (light, shadow_slot)) in block.lights.iter_mut().zip(lights) {
+            *gpu_light = GpuLight::new(light, shadow_slot);
+            block.light_count[0] += 1;
+        }
+
+        self.uniform_buffer.bind();
+        self.uniform_buffer.set_data(slice::from_ref(&block));
+        self.shadow_buffer.bind();
+    }
+}
+
+// requires a current OpenGL context
+pub fn blinn_phong_shader() -> OpenGLShader {
+    lit_shader(BLINN_PHONG_FRAGMENT_SHADER)
+}
+
+// requires a current OpenGL context
+pub fn pbr_shader() -> OpenGLShader {
+    lit_shader(PBR_FRAGMENT_SHADER)
+}
+
+fn lit_shader(fragment_shader: &str) -> OpenGLShader {
+    let fragment_shader = format!("{}{}", LIT_FRAGMENT_COMMON, fragment_shader);
+    let shader = OpenGLShader::new(LIT_VERTEX_SHADER, &fragment_shader);
+    shader.set_uniform_block_binding("Lights", LIGHTS_BINDING);
+    shader.set_uniform_block_binding("Shadows", SHADOWS_BINDING);
+
+    // shadow and environment samplers use fixed units past the ones materials
+    // hand out, unset they would share unit 0 with the material's 2D samplers
+    let first_slot = SHADOW_TEXTURE_SLOT as i32;
+    let cascade_slots: Vec<i32> = (0..MAX_CASCADES as i32).map(|i| first_slot + i).collect();
+    let spot_slots: Vec<i32> = (0..MAX_SPOT_SHADOWS as i32)
+        .map(|i| first_slot + MAX_CASCADES as i32 + i)
+        .collect();
+    shader.bind();
+    shader.set_int_array("u_CascadeMaps", &cascade_slots);
+    shader.set_int_array("u_SpotShadowMaps", &spot_slots);
+    let environment_slot = ENVIRONMENT_TEXTURE_SLOT as i32;
+    shader.set_int("u_IrradianceMap", environment_slot);
+    shader.set_int("u_PrefilteredMap", environment_slot + 1);
+    shader.set_int("u_BrdfLut", environment_slot + 2);
+    shader.unbind();
+
+    shader
+}
+
+// Material for `blinn_phong_shader`. Maps are optional, set them with
+// `set_diffuse_map` and `set_normal_map`.
+pub fn blinn_phong_material(
+    shader: Rc<OpenGLShader>,
+    diffuse: Vec4,
+    specular: Vec3,
+    shininess: f32,
+) -> Material {
+    let mut material = Material::new("Blinn-Phong", shader);
+    material.set_param("u_DiffuseColor", diffuse);
+    material.set_param("u_HasDiffuseMap", 0);
+    material.set_param("u_SpecularColor", specular);
+    material.set_param("u_Shininess", shininess);
+    material.set_param("u_HasNormalMap", 0);
+    material.set_param("u_ReceiveShadows", 1);
+    material.set_param("u_EntityId", 0);
+    material
+}
+
+// lit materials receive shadows unless turned off here or in an instance
+pub fn set_receive_shadows(material: &mut Material, receive_shadows: bool) {
+    material.set_param("u_ReceiveShadows", receive_shadows as i32);
+}
+
+// entity written to the ID attachment by lit draws of the instance, None for
+// ones that cannot be picked
+pub fn set_entity_id(instance: &mut MaterialInstance, entity: Option<u32>) {
+    instance.set_param("u_EntityId", encode_entity_id(entity));
+}
+
+pub fn set_diffuse_map(material: &mut Material, texture: Rc<dyn Texture>) {
+    material.set_texture("u_DiffuseMap", texture);
+    material.set_param("u_HasDiffuseMap", 1);
+}
+
+// tangent space normal map, meshes need tangents
+pub fn set_normal_map(material: &mut Material, texture: Rc<dyn Texture>) {
+    material.set_texture("u_NormalMap", texture);
+    material.set_param("u_HasNormalMap", 1);
+}
+
+// Material for `pbr_shader` from a loaded material description, `textures`
+// are the textures of the same model as returned by `Model::get_textures`.
+pub fn pbr_material(
+    shader: Rc<OpenGLShader>,
+    data: &MaterialData,
+    textures: &[Rc<Texture2D>],
+) -> Material {
+    let mut material = Material::new(&data.name, shader);
+    material.set_param("u_ReceiveShadows", 1);
+    material.set_param("u_EntityId", 0);
+    material.set_param("u_BaseColor", data.base_color);
+    material.set_param("u_Metallic", data.metallic);
+    material.set_param("u_Roughness", data.roughness);
+    material.set_param("u_Emissive", data.emissive);
+    material.set_param(
+        "u_AlphaCutoff",
+        match data.alpha_mode {
+            AlphaMode::Mask(cutoff) => cutoff,
+            AlphaMode::Opaque | AlphaMode::Blend => 0.0,
+        },
+    );
+
+    let maps = [
+        (
+            "u_BaseColorMap",
+            "u_HasBaseColorMap",
+            data.base_color_texture,
+        ),
+        (
+            "u_MetallicRoughnessMap",
+            "u_HasMetallicRoughnessMap",
+            data.metallic_roughness_texture,
+        ),
+        ("u_NormalMap", "u_HasNormalMap", data.normal_texture),
+        ("u_EmissiveMap", "u_HasEmissiveMap", data.emissive_texture),
+    ];
+    for (map, flag, texture) in maps.iter() {
+        match texture.and_then(|i| textures.get(i)) {
+            Some(texture) => {
+                material.set_texture(map, texture.clone());
+                material.set_param(flag, 1);
+            }
+            None => material.set_param(flag, 0),
+        }
+    }
+
+    material
+}
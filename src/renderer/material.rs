@@ -0,0 +1,257 @@
+use std::{collections::BTreeMap, rc::Rc};
+
+use crate::math::{Mat3, Mat4, Vec2, Vec3, Vec4};
+use crate::renderer::{
+    camera::Camera,
+    shader::{OpenGLShader, Shader},
+    texture::Texture,
+};
+
+// texture units every OpenGL 3.3 implementation provides to fragment shaders
+const MAX_TEXTURE_SLOTS: usize = 16;
+
+#[derive(Clone)]
+pub enum MaterialParam {
+    Int(i32),
+    Float(f32),
+    Float2(Vec2),
+    Float3(Vec3),
+    Float4(Vec4),
+    Mat3(Mat3),
+    Mat4(Mat4),
+    // bound to the next free texture unit, the sampler uniform gets the unit
+    Texture(Rc<dyn Texture>),
+}
+
+impl From<i32> for MaterialParam {
+    fn from(value: i32) -> Self {
+        MaterialParam::Int(value)
+    }
+}
+
+impl From<f32> for MaterialParam {
+    fn from(value: f32) -> Self {
+        MaterialParam::Float(value)
+    }
+}
+
+impl From<Vec2> for MaterialParam {
+    fn from(value: Vec2) -> Self {
+        MaterialParam::Float2(value)
+    }
+}
+
+impl From<Vec3> for MaterialParam {
+    fn from(value: Vec3) -> Self {
+        MaterialParam::Float3(value)
+    }
+}
+
+impl From<Vec4> for MaterialParam {
+    fn from(value: Vec4) -> Self {
+        MaterialParam::Float4(value)
+    }
+}
+
+impl From<Mat3> for MaterialParam {
+    fn from(value: Mat3) -> Self {
+        MaterialParam::Mat3(value)
+    }
+}
+
+impl From<Mat4> for MaterialParam {
+    fn from(value: Mat4) -> Self {
+        MaterialParam::Mat4(value)
+    }
+}
+
+impl From<Rc<dyn Texture>> for MaterialParam {
+    fn from(value: Rc<dyn Texture>) -> Self {
+        MaterialParam::Texture(value)
+    }
+}
+
+// Engine provided uniforms, uploaded on every bind. Shaders declare the ones
+// they use: u_ViewProjection, u_View, u_Projection, u_CameraPosition, u_Time,
+// u_Model and u_NormalMatrix (mat3).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneUniforms {
+    pub view_projection: Mat4,
+    pub view: Mat4,
+    pub projection: Mat4,
+    pub camera_position: Vec3,
+    // seconds
+    pub time: f32,
+}
+
+impl SceneUniforms {
+    pub fn new(camera: &dyn Camera, time: f32) -> Self {
+        Self {
+            view_projection: *camera.get_view_projection(),
+            view: *camera.get_view(),
+            projection: *camera.get_projection(),
+            camera_position: camera.get_position(),
+            time,
+        }
+    }
+}
+
+impl Default for SceneUniforms {
+    fn default() -> Self {
+        Self {
+            view_projection: Mat4::IDENTITY,
+            view: Mat4::IDENTITY,
+            projection: Mat4::IDENTITY,
+            camera_position: Vec3::ZERO,
+            time: 0.0,
+        }
+    }
+}
+
+// A shader together with the values of its uniforms.
+pub struct Material {
+    name: String,
+    shader: Rc<OpenGLShader>,
+    params: BTreeMap<String, MaterialParam>,
+}
+
+impl Material {
+    pub fn new(name: &str, shader: Rc<OpenGLShader>) -> Self {
+        Self {
+            name: name.to_string(),
+            shader,
+            params: BTreeMap::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_shader(&self) -> &Rc<OpenGLShader> {
+        &self.shader
+    }
+
+    // `name` is the uniform name in the shader
+    pub fn set_param<T: Into<MaterialParam>>(&mut self, name: &str, value: T) {
+        self.params.insert(name.to_string(), value.into());
+    }
+
+    pub fn set_texture(&mut self, name: &str, texture: Rc<dyn Texture>) {
+        self.set_param(name, texture);
+    }
+
+    pub fn remove_param(&mut self, name: &str) -> Option<MaterialParam> {
+        self.params.remove(name)
+    }
+
+    pub fn get_param(&self, name: &str) -> Option<&MaterialParam> {
+        self.params.get(name)
+    }
+
+    pub fn get_params(&self) -> &BTreeMap<String, MaterialParam> {
+        &self.params
+    }
+
+    // binds the shader and uploads the engine uniforms and all parameters
+    pub fn bind(&self, scene: &SceneUniforms, model: &Mat4) {
+        bind_params(&self.shader, scene, model, self.params.iter());
+    }
+}
+
+// Shares the shader and parameters of a `Material`, overriding some of them.
+pub struct MaterialInstance {
+    parent: Rc<Material>,
+    overrides: BTreeMap<String, MaterialParam>,
+}
+
+impl MaterialInstance {
+    pub fn new(parent: Rc<Material>) -> Self {
+        Self {
+            parent,
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    pub fn get_parent(&self) -> &Rc<Material> {
+        &self.parent
+    }
+
+    pub fn get_shader(&self) -> &Rc<OpenGLShader> {
+        self.parent.get_shader()
+    }
+
+    pub fn set_param<T: Into<MaterialParam>>(&mut self, name: &str, value: T) {
+        self.overrides.insert(name.to_string(), value.into());
+    }
+
+    pub fn set_texture(&mut self, name: &str, texture: Rc<dyn Texture>) {
+        self.set_param(name, texture);
+    }
+
+    // falls back to the parent value again
+    pub fn clear_override(&mut self, name: &str) -> Option<MaterialParam> {
+        self.overrides.remove(name)
+    }
+
+    pub fn is_overridden(&self, name: &str) -> bool {
+        self.overrides.contains_key(name)
+    }
+
+    pub fn get_overrides(&self) -> &BTreeMap<String, MaterialParam> {
+        &self.overrides
+    }
+
+    pub fn get_param(&self, name: &str) -> Option<&MaterialParam> {
+        self.overrides
+            .get(name)
+            .or_else(|| self.parent.get_param(name))
+    }
+
+    pub fn bind(&self, scene: &SceneUniforms, model: &Mat4) {
+        let mut params: BTreeMap<&String, &MaterialParam> = self.parent.params.iter().collect();
+        params.extend(self.overrides.iter());
+        bind_params(&self.parent.shader, scene, model, params.into_iter());
+    }
+}
+
+// Texture units are handed out in parameter name order, so a material and
+// its instances use the same unit for the same sampler.
+fn bind_params<'p, I>(shader: &OpenGLShader, scene: &SceneUniforms, model: &Mat4, params: I)
+where
+    I: Iterator<Item = (&'p String, &'p MaterialParam)>,
+{
+    shader.bind();
+
+    shader.set_mat4("u_ViewProjection", &scene.view_projection);
+    shader.set_mat4("u_View", &scene.view);
+    shader.set_mat4("u_Projection", &scene.projection);
+    shader.set_float3("u_CameraPosition", scene.camera_position);
+    shader.set_float("u_Time", scene.time);
+    shader.set_mat4("u_Model", model);
+    shader.set_mat3("u_NormalMatrix", &Mat3::normal_matrix(model));
+
+    let mut slot = 0;
+    for (name, param) in params {
+        match param {
+            MaterialParam::Int(value) => shader.set_int(name, *value),
+            MaterialParam::Float(value) => shader.set_float(name, *value),
+            MaterialParam::Float2(value) => shader.set_float2(name, *value),
+            MaterialParam::Float3(value) => shader.set_float3(name, *value),
+            MaterialParam::Float4(value) => shader.set_float4(name, *value),
+            MaterialParam::Mat3(value) => shader.set_mat3(name, value),
+            MaterialParam::Mat4(value) => shader.set_mat4(name, value),
+            MaterialParam::Texture(texture) => {
+                if slot as usize == MAX_TEXTURE_SLOTS {
+                    panic!(
+                        "Material uses more than {} textures, {} has no texture unit left",
+                        MAX_TEXTURE_SLOTS, name
+                    );
+                }
+                texture.bind(slot);
+                shader.set_int(name, slot as i32);
+                slot += 1;
+            }
+        }
+    }
+}
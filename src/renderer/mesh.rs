@@ -0,0 +1,337 @@
+use std::rc::Rc;
+
+use crate::math::{Aabb, Vec2, Vec3, Vec4};
+use crate::renderer::buffer::{
+    BufferElement, BufferLayout, IndexBuffer, ShaderDataType, VertexArray, VertexBuffer,
+};
+use crate::renderer::profiler;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub tex_coord: Vec2,
+    // xyz tangent, w the bitangent sign
+    pub tangent: Vec4,
+}
+
+impl MeshVertex {
+    pub fn new(position: Vec3, normal: Vec3, tex_coord: Vec2) -> Self {
+        Self {
+            position,
+            normal,
+            tex_coord,
+            tangent: Vec4::new(1.0, 0.0, 0.0, 1.0),
+        }
+    }
+
+    // attribute locations 0 to 3
+    pub fn get_layout() -> BufferLayout {
+        BufferLayout::new(vec![
+            BufferElement::new("a_Position", ShaderDataType::Float3),
+            BufferElement::new("a_Normal", ShaderDataType::Float3),
+            BufferElement::new("a_TexCoord", ShaderDataType::Float2),
+            BufferElement::new("a_Tangent", ShaderDataType::Float4),
+        ])
+    }
+}
+
+// Indexed triangle list on the CPU, counter-clockwise front faces.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub vertices: Vec<MeshVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn new(vertices: Vec<MeshVertex>, indices: Vec<u32>) -> Self {
+        Self { vertices, indices }
+    }
+
+    pub fn get_bounds(&self) -> Aabb {
+        Aabb::from_points(self.vertices.iter().map(|v| v.position))
+    }
+
+    pub fn get_triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // smooth normals, each face weighted by its area
+    pub fn compute_normals(&mut self) {
+        for vertex in self.vertices.iter_mut() {
+            vertex.normal = Vec3::ZERO;
+        }
+
+        for tri in self.indices.chunks_exact(3) {
+            let (a, b, c) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+            let p0 = self.vertices[a].position;
+            // the cross product length is twice the area
+            let n = (self.vertices[b].position - p0).cross(self.vertices[c].position - p0);
+            self.vertices[a].normal += n;
+            self.vertices[b].normal += n;
+            self.vertices[c].normal += n;
+        }
+
+        for vertex in self.vertices.iter_mut() {
+            vertex.normal = vertex.normal.normalize();
+        }
+    }
+
+    // Per vertex tangents from texture coordinate gradients, orthogonalized
+    // against the normal. Needs normals, vertices without usable texture
+    // coordinates get an arbitrary tangent.
+    pub fn compute_tangents(&mut self) {
+        let mut tangents = vec![Vec3::ZERO; self.vertices.len()];
+        let mut bitangents = vec![Vec3::ZERO; self.vertices.len()];
+
+        for tri in self.indices.chunks_exact(3) {
+            let (a, b, c) = (tri[0] as usize, tri[1] as usize, tri[2] as usize);
+            let (v0, v1, v2) = (&self.vertices[a], &self.vertices[b], &self.vertices[c]);
+
+            let e1 = v1.position - v0.position;
+            let e2 = v2.position - v0.position;
+            let d1 = v1.tex_coord - v0.tex_coord;
+            let d2 = v2.tex_coord - v0.tex_coord;
+
+            let det = d1.x * d2.y - d2.x * d1.y;
+            if det.abs() < 1e-12 {
+                continue;
+            }
+            let r = 1.0 / det;
+            let t = (e1 * d2.y - e2 * d1.y) * r;
+            let bt = (e2 * d1.x - e1 * d2.x) * r;
+
+            for &i in [a, b, c].iter() {
+                tangents[i] += t;
+                bitangents[i] += bt;
+            }
+        }
+
+        for (i, vertex) in self.vertices.iter_mut().enumerate() {
+            let n = vertex.normal;
+            let t = tangents[i] - n * n.dot(tangents[i]);
+            let t = if t.length_squared() > 1e-12 {
+                t.normalize()
+            } else {
+                n.any_orthogonal().normalize()
+            };
+            let w = if n.cross(t).dot(bitangents[i]) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            vertex.tangent = t.extend(w);
+        }
+    }
+}
+
+// GPU copy of a `MeshData`.
+pub struct Mesh {
+    vertex_array: VertexArray,
+    index_count: usize,
+    bounds: Aabb,
+    cast_shadows: bool,
+}
+
+impl Mesh {
+    // requires a current OpenGL context
+    pub fn new(data: &MeshData) -> Self {
+        let vertex_buffer = Rc::new(VertexBuffer::from_data(
+            &data.vertices,
+            MeshVertex::get_layout(),
+        ));
+        let index_buffer = Rc::new(IndexBuffer::new(&data.indices));
+
+        let mut vertex_array = VertexArray::new();
+        vertex_array.add_vertex_buffer(vertex_buffer);
+        vertex_array.set_index_buffer(index_buffer);
+
+        Self {
+            vertex_array,
+            index_count: data.indices.len(),
+            bounds: data.get_bounds(),
+            cast_shadows: true,
+        }
+    }
+
+    // draws with the currently bound shader
+    pub fn draw(&self) {
+        self.vertex_array.bind();
+        unsafe {
+            gl::DrawElements(
+                gl::TRIANGLES,
+                self.index_count as i32,
+                gl::UNSIGNED_INT,
+                std::ptr::null(),
+            );
+        }
+        profiler::record_draw(gl::TRIANGLES, self.index_count as u32, 1);
+        self.vertex_array.unbind();
+    }
+
+    pub fn get_vertex_array(&self) -> &VertexArray {
+        &self.vertex_array
+    }
+
+    pub fn get_index_count(&self) -> usize {
+        self.index_count
+    }
+
+    // object space
+    pub fn get_bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    // skipped by the shadow pass when off
+    pub fn set_cast_shadows(&mut self, cast_shadows: bool) {
+        self.cast_shadows = cast_shadows;
+    }
+
+    pub fn is_shadow_caster(&self) -> bool {
+        self.cast_shadows
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn assert_vec4_eq(a: Vec4, b: Vec4) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    // 2x2 quad in the XY plane facing +Z, corners counter-clockwise from the
+    // bottom left
+    fn quad(tex_coords: [Vec2; 4]) -> MeshData {
+        let positions = [
+            Vec3::new(-1.0, -1.0, 0.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(-1.0, 1.0, 0.0),
+        ];
+        let vertices = positions
+            .iter()
+            .zip(tex_coords.iter())
+            .map(|(p, t)| MeshVertex::new(*p, Vec3::Z, *t))
+            .collect();
+        MeshData::new(vertices, vec![0, 1, 2, 2, 3, 0])
+    }
+
+    fn uv(coords: [(f32, f32); 4]) -> [Vec2; 4] {
+        let mut uv = [Vec2::ZERO; 4];
+        for (uv, (u, v)) in uv.iter_mut().zip(coords.iter()) {
+            *uv = Vec2::new(*u, *v);
+        }
+        uv
+    }
+
+    // v up, the OpenGL convention
+    const GL_UV: [(f32, f32); 4] = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+
+    #[test]
+    fn normals_follow_the_winding() {
+        let mut mesh = quad(uv(GL_UV));
+        mesh.compute_normals();
+        for vertex in mesh.vertices.iter() {
+            assert_vec3_eq(vertex.normal, Vec3::Z);
+        }
+
+        mesh.indices = vec![0, 2, 1, 2, 0, 3];
+        mesh.compute_normals();
+        for vertex in mesh.vertices.iter() {
+            assert_vec3_eq(vertex.normal, -Vec3::Z);
+        }
+    }
+
+    #[test]
+    fn normals_are_weighted_by_area() {
+        let vertices = [
+            Vec3::ZERO,
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.0, 1.0),
+        ]
+        .iter()
+        .map(|p| MeshVertex::new(*p, Vec3::ZERO, Vec2::ZERO))
+        .collect();
+        // an area 2 triangle facing +Z and an area 0.5 one facing +X
+        let mut mesh = MeshData::new(vertices, vec![0, 1, 2, 0, 3, 4]);
+        mesh.compute_normals();
+
+        assert_vec3_eq(
+            mesh.vertices[0].normal,
+            Vec3::new(1.0, 0.0, 4.0).normalize(),
+        );
+        assert_vec3_eq(mesh.vertices[1].normal, Vec3::Z);
+        assert_vec3_eq(mesh.vertices[4].normal, Vec3::X);
+    }
+
+    #[test]
+    fn tangents_point_along_u() {
+        let mut mesh = quad(uv(GL_UV));
+        mesh.compute_tangents();
+        for vertex in mesh.vertices.iter() {
+            assert_vec4_eq(vertex.tangent, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        }
+
+        // rotated texture coordinates, u grows along +Y
+        let mut mesh = quad(uv([(0.0, 1.0), (0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]));
+        mesh.compute_tangents();
+        assert_vec4_eq(mesh.vertices[0].tangent, Vec4::new(0.0, 1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn mirrored_tex_coords_flip_the_handedness() {
+        let mut mesh = quad(uv([(1.0, 0.0), (0.0, 0.0), (0.0, 1.0), (1.0, 1.0)]));
+        mesh.compute_tangents();
+        for vertex in mesh.vertices.iter() {
+            assert_vec4_eq(vertex.tangent, Vec4::new(-1.0, 0.0, 0.0, -1.0));
+        }
+    }
+
+    #[test]
+    fn flipping_v_negates_the_bitangent_sign() {
+        // v down, as in glTF
+        let top_down = [(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)];
+        let mut mesh = quad(uv(top_down));
+        mesh.compute_tangents();
+        let before = mesh.vertices[0].tangent;
+        assert_vec4_eq(before, Vec4::new(1.0, 0.0, 0.0, -1.0));
+
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.tex_coord.y = 1.0 - vertex.tex_coord.y;
+        }
+        mesh.compute_tangents();
+        let after = mesh.vertices[0].tangent;
+        assert_vec3_eq(after.truncate(), before.truncate());
+        assert_eq!(after.w, -before.w);
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_the_normal() {
+        let mut mesh = quad(uv(GL_UV));
+        let tilted = Vec3::new(1.0, 0.0, 1.0).normalize();
+        for vertex in mesh.vertices.iter_mut() {
+            vertex.normal = tilted;
+        }
+        mesh.compute_tangents();
+        let tangent = mesh.vertices[0].tangent.truncate();
+        assert!(tangent.dot(tilted).abs() < 1e-5);
+        assert_vec3_eq(tangent, Vec3::new(1.0, 0.0, -1.0).normalize());
+
+        // without usable texture coordinates any orthogonal unit vector does
+        let mut mesh = quad([Vec2::ZERO; 4]);
+        mesh.compute_tangents();
+        for vertex in mesh.vertices.iter() {
+            let tangent = vertex.tangent.truncate();
+            assert!((tangent.length() - 1.0).abs() < 1e-5);
+            assert!(tangent.dot(Vec3::Z).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,734 @@
+use std::{path::Path, rc::Rc};
+
+use crate::log_warn;
+use crate::math::{Aabb, Mat4, Quat, Transform, Vec2, Vec3, Vec4};
+use crate::renderer::{
+    bitmap::Bitmap,
+    mesh::{Mesh, MeshData, MeshVertex},
+    texture::{ColorSpace, SamplerSettings, Texture2D},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlphaMode {
+    Opaque,
+    // discard below the cutoff
+    Mask(f32),
+    Blend,
+}
+
+// Material description as found in the file, textures index
+// `ModelData::textures`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialData {
+    pub name: String,
+    pub base_color: Vec4,
+    pub base_color_texture: Option<usize>,
+    pub metallic: f32,
+    pub roughness: f32,
+    // roughness in green, metallic in blue
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub emissive: Vec3,
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl Default for MaterialData {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: Vec4::ONE,
+            base_color_texture: None,
+            metallic: 0.0,
+            roughness: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            emissive: Vec3::ZERO,
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshPrimitive {
+    pub data: MeshData,
+    pub material: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelMesh {
+    pub name: String,
+    pub primitives: Vec<MeshPrimitive>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelNode {
+    pub name: String,
+    // relative to the parent
+    pub transform: Transform,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+// Imported scene on the CPU. Texture images are stored top row first and
+// texture coordinates follow the OpenGL convention with v up, matching
+// `Texture2D`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ModelData {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<MaterialData>,
+    pub textures: Vec<Bitmap>,
+    pub nodes: Vec<ModelNode>,
+    pub roots: Vec<usize>,
+}
+
+impl ModelData {
+    // picks the loader from the extension: obj, gltf or glb
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("obj") => Self::load_obj(path),
+            Some("gltf") | Some("glb") => Self::load_gltf(path),
+            _ => Err(format!("Unsupported model format {:?}", path)),
+        }
+    }
+
+    // Wavefront OBJ with its MTL library. Every object becomes a mesh with a
+    // root node, Phong materials are mapped to metallic-roughness.
+    pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let options = tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ignore_points: true,
+            ignore_lines: true,
+        };
+        let (models, materials) = tobj::load_obj(path, &options)
+            .map_err(|e| format!("Failed to load {:?}: {}", path, e))?;
+
+        let mut data = ModelData::default();
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        match materials {
+            Ok(materials) => {
+                for material in materials {
+                    let mut load_texture = |file: &str| {
+                        if file.is_empty() {
+                            return None;
+                        }
+                        match Bitmap::load(base_dir.join(file)) {
+                            Ok(bitmap) => {
+                                data.textures.push(bitmap);
+                                Some(data.textures.len() - 1)
+                            }
+                            Err(e) => {
+                                log_warn!("{}", e);
+                                None
+                            }
+                        }
+                    };
+
+                    let base_color_texture = load_texture(&material.diffuse_texture);
+                    let normal_texture = load_texture(&material.normal_texture);
+                    let d = material.diffuse;
+
+                    data.materials.push(MaterialData {
+                        name: material.name.clone(),
+                        base_color: Vec4::new(d[0], d[1], d[2], material.dissolve),
+                        base_color_texture,
+                        // Blinn-Phong exponent to perceptual roughness
+                        roughness: (2.0 / (material.shininess + 2.0)).sqrt().sqrt(),
+                        normal_texture,
+                        alpha_mode: if material.dissolve < 1.0 {
+                            AlphaMode::Blend
+                        } else {
+                            AlphaMode::Opaque
+                        },
+                        ..MaterialData::default()
+                    });
+                }
+            }
+            Err(e) => {
+                log_warn!("Failed to load materials for {:?}: {}", path, e);
+            }
+        }
+
+        for model in models {
+            let mesh = &model.mesh;
+            let vertex_count = mesh.positions.len() / 3;
+            let has_normals = mesh.normals.len() == vertex_count * 3;
+            let has_tex_coords = mesh.texcoords.len() == vertex_count * 2;
+
+            let vertices = (0..vertex_count)
+                .map(|i| {
+                    let p = &mesh.positions[i * 3..i * 3 + 3];
+                    let normal = if has_normals {
+                        let n = &mesh.normals[i * 3..i * 3 + 3];
+                        Vec3::new(n[0], n[1], n[2])
+                    } else {
+                        Vec3::ZERO
+                    };
+                    let tex_coord = if has_tex_coords {
+                        Vec2::new(mesh.texcoords[i * 2], mesh.texcoords[i * 2 + 1])
+                    } else {
+                        Vec2::ZERO
+                    };
+                    MeshVertex::new(Vec3::new(p[0], p[1], p[2]), normal, tex_coord)
+                })
+                .collect();
+
+            let mut mesh_data = MeshData::new(vertices, mesh.indices.clone());
+            if !has_normals {
+                mesh_data.compute_normals();
+            }
+            mesh_data.compute_tangents();
+
+            data.meshes.push(ModelMesh {
+                name: model.name.clone(),
+                primitives: vec![MeshPrimitive {
+                    data: mesh_data,
+                    material: mesh.material_id.filter(|&m| m < data.materials.len()),
+                }],
+            });
+            data.nodes.push(ModelNode {
+                name: model.name,
+                transform: Transform::IDENTITY,
+                mesh: Some(data.meshes.len() - 1),
+                children: Vec::new(),
+            });
+            data.roots.push(data.nodes.len() - 1);
+        }
+
+        Ok(data)
+    }
+
+    // glTF 2.0, both JSON with external or embedded buffers and binary.
+    // Only triangle primitives are imported and sampler settings are ignored.
+    pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let (document, buffers, images) =
+            gltf::import(path).map_err(|e| format!("Failed to load {:?}: {}", path, e))?;
+
+        let mut data = ModelData {
+            textures: images.iter().map(bitmap_from_gltf_image).collect(),
+            ..ModelData::default()
+        };
+
+        // material textures reference images through texture objects
+        let texture_image = |texture: gltf::Texture| texture.source().index();
+
+        for material in document.materials() {
+            let pbr = material.pbr_metallic_roughness();
+            let c = pbr.base_color_factor();
+            let e = material.emissive_factor();
+
+            data.materials.push(MaterialData {
+                name: material.name().unwrap_or_default().to_string(),
+                base_color: Vec4::new(c[0], c[1], c[2], c[3]),
+                base_color_texture: pbr.base_color_texture().map(|t| texture_image(t.texture())),
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+                metallic_roughness_texture: pbr
+                    .metallic_roughness_texture()
+                    .map(|t| texture_image(t.texture())),
+                normal_texture: material
+                    .normal_texture()
+                    .map(|t| texture_image(t.texture())),
+                emissive: Vec3::new(e[0], e[1], e[2]),
+                emissive_texture: material
+                    .emissive_texture()
+                    .map(|t| texture_image(t.texture())),
+                alpha_mode: match material.alpha_mode() {
+                    gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                    gltf::material::AlphaMode::Mask => {
+                        AlphaMode::Mask(material.alpha_cutoff().unwrap_or(0.5))
+                    }
+                    gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+                },
+                double_sided: material.double_sided(),
+            });
+        }
+
+        for mesh in document.meshes() {
+            let mut primitives = Vec::new();
+
+            for primitive in mesh.primitives() {
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    log_warn!(
+                        "Skipping {:?} primitive in mesh {:?}",
+                        primitive.mode(),
+                        mesh.name()
+                    );
+                    continue;
+                }
+
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()].0[..]));
+                let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                    Some(positions) => positions.collect(),
+                    None => continue,
+                };
+                let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|n| n.collect());
+                let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|t| t.collect());
+                let tex_coords: Option<Vec<[f32; 2]>> =
+                    reader.read_tex_coords(0).map(|t| t.into_f32().collect());
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+
+                let vertices = positions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, p)| {
+                        let mut vertex = MeshVertex::new(Vec3::from(*p), Vec3::ZERO, Vec2::ZERO);
+                        if let Some(normals) = &normals {
+                            vertex.normal = Vec3::from(normals[i]);
+                        }
+                        if let Some(tex_coords) = &tex_coords {
+                            // glTF puts the texture origin at the top left
+                            vertex.tex_coord = Vec2::new(tex_coords[i][0], 1.0 - tex_coords[i][1]);
+                        }
+                        if let Some(tangents) = &tangents {
+                            // flipping v mirrors the bitangent
+                            let t = tangents[i];
+                            vertex.tangent = Vec4::new(t[0], t[1], t[2], -t[3]);
+                        }
+                        vertex
+                    })
+                    .collect();
+
+                let mut mesh_data = MeshData::new(vertices, indices);
+                if normals.is_none() {
+                    mesh_data.compute_normals();
+                }
+                if tangents.is_none() {
+                    mesh_data.compute_tangents();
+                }
+
+                primitives.push(MeshPrimitive {
+                    data: mesh_data,
+                    material: primitive.material().index(),
+                });
+            }
+
+            data.meshes.push(ModelMesh {
+                name: mesh.name().unwrap_or_default().to_string(),
+                primitives,
+            });
+        }
+
+        for node in document.nodes() {
+            let (t, r, s) = node.transform().decomposed();
+            data.nodes.push(ModelNode {
+                name: node.name().unwrap_or_default().to_string(),
+                transform: Transform::new(
+                    Vec3::from(t),
+                    Quat::from_xyzw(r[0], r[1], r[2], r[3]),
+                    Vec3::from(s),
+                ),
+                mesh: node.mesh().map(|m| m.index()),
+                children: node.children().map(|c| c.index()).collect(),
+            });
+        }
+
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next());
+        data.roots = match scene {
+            Some(scene) => scene.nodes().map(|n| n.index()).collect(),
+            // no scene, every node that is not a child is a root
+            None => (0..data.nodes.len())
+                .filter(|i| !data.nodes.iter().any(|n| n.children.contains(i)))
+                .collect(),
+        };
+
+        Ok(data)
+    }
+
+    // world transform of every node reachable from the roots, identity for
+    // the others
+    pub fn get_world_transforms(&self) -> Vec<Mat4> {
+        let mut world = vec![Mat4::IDENTITY; self.nodes.len()];
+        let mut stack: Vec<(usize, Mat4)> =
+            self.roots.iter().map(|&r| (r, Mat4::IDENTITY)).collect();
+
+        while let Some((index, parent)) = stack.pop() {
+            let node = &self.nodes[index];
+            world[index] = parent * node.transform.to_mat4();
+            for &child in node.children.iter() {
+                stack.push((child, world[index]));
+            }
+        }
+
+        world
+    }
+
+    pub fn get_bounds(&self) -> Aabb {
+        let world = self.get_world_transforms();
+        let mut bounds = Aabb::EMPTY;
+
+        for (node, transform) in self.nodes.iter().zip(world.iter()) {
+            if let Some(mesh) = node.mesh {
+                for primitive in self.meshes[mesh].primitives.iter() {
+                    bounds = bounds.union(&primitive.data.get_bounds().transformed(transform));
+                }
+            }
+        }
+
+        bounds
+    }
+
+    // base color and emissive textures hold sRGB colors, the rest is data
+    fn get_texture_color_space(&self, texture: usize) -> ColorSpace {
+        let is_color = self
+            .materials
+            .iter()
+            .any(|m| m.base_color_texture == Some(texture) || m.emissive_texture == Some(texture));
+        if is_color {
+            ColorSpace::Srgb
+        } else {
+            ColorSpace::Linear
+        }
+    }
+}
+
+// A `ModelData` uploaded to the GPU.
+pub struct Model {
+    meshes: Vec<Vec<(Mesh, Option<usize>)>>,
+    materials: Vec<MaterialData>,
+    textures: Vec<Rc<Texture2D>>,
+    nodes: Vec<ModelNode>,
+    world_transforms: Vec<Mat4>,
+    bounds: Aabb,
+}
+
+impl Model {
+    // requires a current OpenGL context
+    pub fn new(data: &ModelData) -> Self {
+        let meshes = data
+            .meshes
+            .iter()
+            .map(|mesh| {
+                mesh.primitives
+                    .iter()
+                    .map(|p| (Mesh::new(&p.data), p.material))
+                    .collect()
+            })
+            .collect();
+
+        let textures = data
+            .textures
+            .iter()
+            .enumerate()
+            .map(|(i, bitmap)| {
+                Rc::new(Texture2D::from_bitmap(
+                    bitmap,
+                    data.get_texture_color_space(i),
+                    SamplerSettings::default(),
+                ))
+            })
+            .collect();
+
+        Self {
+            meshes,
+            materials: data.materials.clone(),
+            textures,
+            nodes: data.nodes.clone(),
+            world_transforms: data.get_world_transforms(),
+            bounds: data.get_bounds(),
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        Ok(Self::new(&ModelData::load(path)?))
+    }
+
+    pub fn get_materials(&self) -> &[MaterialData] {
+        &self.materials
+    }
+
+    // shared so that materials can reference them
+    pub fn get_textures(&self) -> &[Rc<Texture2D>] {
+        &self.textures
+    }
+
+    pub fn get_nodes(&self) -> &[ModelNode] {
+        &self.nodes
+    }
+
+    pub fn get_bounds(&self) -> &Aabb {
+        &self.bounds
+    }
+
+    // calls `f` with the world transform, mesh and material of every
+    // primitive, `transform` places the whole model
+    pub fn for_each_primitive<F>(&self, transform: &Mat4, mut f: F)
+    where
+        F: FnMut(&Mat4, &Mesh, Option<&MaterialData>),
+    {
+        for (node, world) in self.nodes.iter().zip(self.world_transforms.iter()) {
+            if let Some(mesh) = node.mesh {
+                let world = *transform * *world;
+                for (primitive, material) in self.meshes[mesh].iter() {
+                    f(&world, primitive, material.map(|m| &self.materials[m]));
+                }
+            }
+        }
+    }
+}
+
+fn bitmap_from_gltf_image(image: &gltf::image::Data) -> Bitmap {
+    use gltf::image::Format;
+
+    let pixel_count = (image.width * image.height) as usize;
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    // 16 bit channels are stored native endian, keep the high byte
+    let high = |p: &[u8], i: usize| u16::from_ne_bytes([p[i * 2], p[i * 2 + 1]]) >> 8;
+
+    for i in 0..pixel_count {
+        let p = &image.pixels;
+        let pixel = match image.format {
+            Format::R8 => [p[i], p[i], p[i], 255],
+            Format::R8G8 => [p[i * 2], p[i * 2 + 1], 0, 255],
+            Format::R8G8B8 => [p[i * 3], p[i * 3 + 1], p[i * 3 + 2], 255],
+            Format::R8G8B8A8 => [p[i * 4], p[i * 4 + 1], p[i * 4 + 2], p[i * 4 + 3]],
+            Format::B8G8R8 => [p[i * 3 + 2], p[i * 3 + 1], p[i * 3], 255],
+            Format::B8G8R8A8 => [p[i * 4 + 2], p[i * 4 + 1], p[i * 4], p[i * 4 + 3]],
+            Format::R16 => {
+                let v = high(p, i) as u8;
+                [v, v, v, 255]
+            }
+            Format::R16G16 => [high(p, i * 2) as u8, high(p, i * 2 + 1) as u8, 0, 255],
+            Format::R16G16B16 => [
+                high(p, i * 3) as u8,
+                high(p, i * 3 + 1) as u8,
+                high(p, i * 3 + 2) as u8,
+                255,
+            ],
+            Format::R16G16B16A16 => [
+                high(p, i * 4) as u8,
+                high(p, i * 4 + 1) as u8,
+                high(p, i * 4 + 2) as u8,
+                high(p, i * 4 + 3) as u8,
+            ],
+        };
+        rgba.extend_from_slice(&pixel);
+    }
+
+    Bitmap::from_raw(image.width, image.height, rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::PathBuf};
+
+    fn assert_vec4_eq(a: Vec4, b: Vec4) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    // fresh directory per test so parallel runs don't share fixtures
+    fn fixture_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("simulacra_model_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // uncompressed 32 bit TGA stored top row first
+    fn write_tga(path: &Path, width: u16, height: u16, rgba: &[[u8; 4]]) {
+        let mut bytes = vec![0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&[32, 0x28]);
+        for p in rgba {
+            bytes.extend_from_slice(&[p[2], p[1], p[0], p[3]]);
+        }
+        fs::write(path, bytes).unwrap();
+    }
+
+    const OBJ: &str = "\
+mtllib quad.mtl
+o Quad
+v -1 -1 0
+v 1 -1 0
+v 1 1 0
+v -1 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+usemtl Glass
+f 1/1 2/2 3/3 4/4
+o Triangle
+v 0 0 1
+v 1 0 1
+v 0 1 1
+f 5 6 7
+";
+
+    const MTL: &str = "\
+newmtl Glass
+Kd 0.5 0.25 1.0
+Ns 98
+d 0.5
+map_Kd albedo.tga
+";
+
+    #[test]
+    fn loads_obj_with_materials() {
+        let dir = fixture_dir("obj");
+        fs::write(dir.join("quad.obj"), OBJ).unwrap();
+        fs::write(dir.join("quad.mtl"), MTL).unwrap();
+        write_tga(
+            &dir.join("albedo.tga"),
+            2,
+            1,
+            &[[255, 0, 0, 255], [0, 0, 255, 128]],
+        );
+
+        let data = ModelData::load(dir.join("quad.obj")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(data.materials.len(), 1);
+        let material = &data.materials[0];
+        assert_eq!(material.name, "Glass");
+        assert_vec4_eq(material.base_color, Vec4::new(0.5, 0.25, 1.0, 0.5));
+        assert_eq!(material.alpha_mode, AlphaMode::Blend);
+        assert!((material.roughness - 0.02f32.sqrt().sqrt()).abs() < 1e-5);
+        assert_eq!(material.base_color_texture, Some(0));
+        assert_eq!(material.normal_texture, None);
+
+        let texture = &data.textures[0];
+        assert_eq!((texture.get_width(), texture.get_height()), (2, 1));
+        assert_eq!(texture.get_pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(texture.get_pixel(1, 0), [0, 0, 255, 128]);
+
+        assert_eq!(data.meshes.len(), 2);
+        assert_eq!(data.roots, vec![0, 1]);
+        assert_eq!(data.nodes[0].mesh, Some(0));
+        assert_eq!(data.nodes[1].name, "Triangle");
+
+        let quad = &data.meshes[0].primitives[0];
+        assert_eq!(quad.material, Some(0));
+        assert_eq!(quad.data.indices.len(), 6);
+        for vertex in quad.data.vertices.iter() {
+            // no normals in the file, they come from the winding
+            assert!((vertex.normal - Vec3::Z).length() < 1e-4);
+            assert_vec4_eq(vertex.tangent, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        }
+
+        let triangle = &data.meshes[1].primitives[0];
+        // usemtl carries over to the following objects
+        assert_eq!(triangle.material, Some(0));
+        assert_eq!(triangle.data.indices.len(), 3);
+    }
+
+    #[test]
+    fn missing_obj_is_an_error() {
+        assert!(ModelData::load("does/not/exist.obj").is_err());
+        assert!(ModelData::load("model.fbx").is_err());
+    }
+
+    // one quad drawn twice, the second primitive leaves the tangents out
+    fn gltf_document() -> String {
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut push = |values: &[f32]| {
+            for v in values {
+                buffer.extend_from_slice(&v.to_le_bytes());
+            }
+        };
+        push(&[
+            -1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0, 1.0, 0.0, -1.0, 1.0, 0.0,
+        ]);
+        push(&[0.0, 0.0, 1.0].repeat(4));
+        // v down, bottom left corner at (0, 1)
+        push(&[0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0]);
+        // the bitangent points along -Y, where v grows
+        push(&[1.0, 0.0, 0.0, -1.0].repeat(4));
+        for i in [0u16, 1, 2, 2, 3, 0].iter() {
+            buffer.extend_from_slice(&i.to_le_bytes());
+        }
+
+        format!(
+            r#"{{
+  "asset": {{ "version": "2.0" }},
+  "scene": 0,
+  "scenes": [{{ "nodes": [0] }}],
+  "nodes": [{{ "name": "Quad", "mesh": 0, "translation": [0, 0, -2] }}],
+  "materials": [{{ "name": "Cutout", "alphaMode": "MASK", "alphaCutoff": 0.25 }}],
+  "meshes": [{{
+    "name": "Quad",
+    "primitives": [
+      {{ "attributes": {{ "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2, "TANGENT": 3 }},
+         "indices": 4, "material": 0 }},
+      {{ "attributes": {{ "POSITION": 0, "NORMAL": 1, "TEXCOORD_0": 2 }}, "indices": 4 }}
+    ]
+  }}],
+  "buffers": [{{
+    "byteLength": {},
+    "uri": "data:application/octet-stream;base64,{}"
+  }}],
+  "bufferViews": [
+    {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+    {{ "buffer": 0, "byteOffset": 48, "byteLength": 48 }},
+    {{ "buffer": 0, "byteOffset": 96, "byteLength": 32 }},
+    {{ "buffer": 0, "byteOffset": 128, "byteLength": 64 }},
+    {{ "buffer": 0, "byteOffset": 192, "byteLength": 12 }}
+  ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+       "min": [-1, -1, 0], "max": [1, 1, 0] }},
+    {{ "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC3" }},
+    {{ "bufferView": 2, "componentType": 5126, "count": 4, "type": "VEC2" }},
+    {{ "bufferView": 3, "componentType": 5126, "count": 4, "type": "VEC4" }},
+    {{ "bufferView": 4, "componentType": 5123, "count": 6, "type": "SCALAR" }}
+  ]
+}}"#,
+            buffer.len(),
+            base64::encode(&buffer)
+        )
+    }
+
+    #[test]
+    fn loads_gltf_with_flipped_tex_coords() {
+        let dir = fixture_dir("gltf");
+        let path = dir.join("quad.gltf");
+        fs::write(&path, gltf_document()).unwrap();
+
+        let data = ModelData::load(&path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(data.materials[0].alpha_mode, AlphaMode::Mask(0.25));
+        assert_eq!(data.roots, vec![0]);
+        assert_eq!(
+            data.nodes[0].transform.translation,
+            Vec3::new(0.0, 0.0, -2.0)
+        );
+
+        let primitives = &data.meshes[0].primitives;
+        assert_eq!(primitives.len(), 2);
+        assert_eq!(primitives[0].material, Some(0));
+        assert_eq!(primitives[1].material, None);
+
+        let vertices = &primitives[0].data.vertices;
+        assert_eq!(vertices[0].tex_coord, Vec2::new(0.0, 0.0));
+        assert_eq!(vertices[2].tex_coord, Vec2::new(1.0, 1.0));
+
+        // the stored tangents have their sign negated to match the flipped
+        // v, which is what generating them from the flipped coordinates gives
+        for (stored, generated) in vertices.iter().zip(primitives[1].data.vertices.iter()) {
+            assert_vec4_eq(stored.tangent, Vec4::new(1.0, 0.0, 0.0, 1.0));
+            assert_vec4_eq(generated.tangent, stored.tangent);
+        }
+    }
+}
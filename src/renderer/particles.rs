@@ -0,0 +1,1198 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::PI,
+    ptr,
+    rc::Rc,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use crate::{
+    log_warn,
+    math::{Vec2, Vec3, Vec4},
+    renderer::{
+        buffer::{
+            BufferElement, BufferLayout, IndexBuffer, ShaderDataType, VertexArray, VertexBuffer,
+        },
+        camera::Camera,
+        instancing::{supports_instancing, InstancedMesh},
+        mesh::{MeshData, MeshVertex},
+        profiler,
+        renderer2d::Renderer2D,
+        shader::{OpenGLShader, Shader},
+        state,
+        texture::Texture,
+    },
+};
+
+// forces past this count are ignored by GPU simulations
+pub const MAX_GPU_FORCES: usize = 8;
+// samples of the color and size curves uploaded for rendering
+const CURVE_SAMPLES: usize = 16;
+// instances per draw call of `ParticleRenderer` for CPU simulated emitters
+const MAX_PARTICLES_PER_DRAW: usize = 4096;
+
+static NEXT_SEED: AtomicU32 = AtomicU32::new(0x9E37_79B9);
+
+// One transform feedback step per update, instance `gl_InstanceID` reads and
+// writes particle slot `gl_InstanceID`. Mirrors `spawn_particle` and
+// `integrate_particle`.
+static SIMULATION_SHADER: &'static str = "
+    #version 330 core
+
+    #define PI 3.14159265
+    #define MAX_FORCES 8
+    #define FORCE_ACCELERATION 0
+    #define FORCE_DRAG 1
+    #define FORCE_ATTRACTOR 2
+    #define FORCE_TURBULENCE 3
+    #define SHAPE_POINT 0
+    #define SHAPE_SPHERE 1
+    #define SHAPE_BOX 2
+
+    layout(location = 0) in vec4 a_ParticlePosition; // xyz position, w age
+    layout(location = 1) in vec4 a_ParticleVelocity; // xyz velocity, w lifetime
+    layout(location = 2) in vec2 a_ParticleRotation; // x rotation, y angular velocity
+
+    out vec4 tf_Position;
+    out vec4 tf_Velocity;
+    out vec2 tf_Rotation;
+
+    uniform float u_DeltaTime;
+    uniform float u_Time;
+    uniform int u_Seed;
+    // slots u_SpawnStart to u_SpawnStart + u_SpawnCount, wrapping, are respawned
+    uniform int u_SpawnStart;
+    uniform int u_SpawnCount;
+    uniform int u_Capacity;
+
+    uniform vec3 u_EmitterPosition;
+    uniform int u_Shape;
+    uniform vec3 u_ShapeSize;
+    uniform vec3 u_Direction;
+    uniform float u_Spread;
+    // (min, max) ranges
+    uniform vec2 u_Lifetime;
+    uniform vec2 u_Speed;
+    uniform vec2 u_Rotation;
+    uniform vec2 u_AngularVelocity;
+
+    uniform int u_ForceCount;
+    uniform int u_ForceTypes[MAX_FORCES];
+    // xyz vector or position, w strength
+    uniform vec4 u_Forces[MAX_FORCES];
+
+    uint g_State;
+
+    // PCG hash, uniform in [0, 1)
+    float random() {
+        g_State = g_State * 747796405u + 2891336453u;
+        uint word = ((g_State >> ((g_State >> 28u) + 4u)) ^ g_State) * 277803737u;
+        word = (word >> 22u) ^ word;
+        return float(word >> 8u) / 16777216.0;
+    }
+
+    float random_range(vec2 range) {
+        return mix(range.x, range.y, random());
+    }
+
+    // uniform over the cap of the cone around `axis` with half angle `spread`
+    vec3 random_cone(vec3 axis, float spread) {
+        float cos_theta = 1.0 - random() * (1.0 - cos(spread));
+        float sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+        float phi = 2.0 * PI * random();
+        vec3 other = abs(axis.x) < 0.9 ? vec3(1.0, 0.0, 0.0) : vec3(0.0, 1.0, 0.0);
+        vec3 tangent = normalize(cross(axis, other));
+        vec3 bitangent = cross(axis, tangent);
+        return (tangent * cos(phi) + bitangent * sin(phi)) * sin_theta + axis * cos_theta;
+    }
+
+    vec3 turbulence(vec3 p, float frequency, float time) {
+        vec3 q = p * frequency;
+        return vec3(
+            sin(q.y + time * 1.7) + cos(q.z * 1.3 + time),
+            sin(q.z + time * 1.1) + cos(q.x * 1.7 - time),
+            sin(q.x - time * 1.3) + cos(q.y * 1.1 + time * 0.9)
+        ) * 0.5;
+    }
+
+    void spawn() {
+        g_State = uint(gl_InstanceID) * 1664525u + uint(u_Seed);
+        random();
+
+        vec3 offset = vec3(0.0);
+        if (u_Shape == SHAPE_SPHERE) {
+            offset = random_cone(vec3(0.0, 1.0, 0.0), PI) * u_ShapeSize.x * pow(random(), 1.0 / 3.0);
+        } else if (u_Shape == SHAPE_BOX) {
+            offset = (vec3(random(), random(), random()) * 2.0 - 1.0) * u_ShapeSize;
+        }
+
+        tf_Position = vec4(u_EmitterPosition + offset, 0.0);
+        tf_Velocity = vec4(random_cone(u_Direction, u_Spread) * random_range(u_Speed), random_range(u_Lifetime));
+        tf_Rotation = vec2(random_range(u_Rotation), random_range(u_AngularVelocity));
+    }
+
+    void main() {
+        int offset = (gl_InstanceID - u_SpawnStart + u_Capacity) % u_Capacity;
+        if (offset < u_SpawnCount) {
+            spawn();
+            return;
+        }
+
+        vec3 position = a_ParticlePosition.xyz;
+        float age = a_ParticlePosition.w;
+        vec3 velocity = a_ParticleVelocity.xyz;
+        float lifetime = a_ParticleVelocity.w;
+        if (age >= lifetime) {
+            tf_Position = a_ParticlePosition;
+            tf_Velocity = a_ParticleVelocity;
+            tf_Rotation = a_ParticleRotation;
+            return;
+        }
+
+        vec3 acceleration = vec3(0.0);
+        float drag = 0.0;
+        for (int i = 0; i < u_ForceCount; i++) {
+            vec4 force = u_Forces[i];
+            if (u_ForceTypes[i] == FORCE_ACCELERATION) {
+                acceleration += force.xyz;
+            } else if (u_ForceTypes[i] == FORCE_DRAG) {
+                drag += force.w;
+            } else if (u_ForceTypes[i] == FORCE_ATTRACTOR) {
+                vec3 to_attractor = force.xyz - position;
+                float distance_squared = dot(to_attractor, to_attractor);
+                acceleration += to_attractor * inversesqrt(max(distance_squared, 0.000001)) * force.w / (distance_squared + 1.0);
+            } else if (u_ForceTypes[i] == FORCE_TURBULENCE) {
+                acceleration += turbulence(position, force.x, u_Time) * force.w;
+            }
+        }
+
+        velocity = (velocity + acceleration * u_DeltaTime) * exp(-drag * u_DeltaTime);
+        position += velocity * u_DeltaTime;
+
+        tf_Position = vec4(position, age + u_DeltaTime);
+        tf_Velocity = vec4(velocity, lifetime);
+        tf_Rotation = vec2(a_ParticleRotation.x + a_ParticleRotation.y * u_DeltaTime, a_ParticleRotation.y);
+    }
+";
+
+// Camera facing quads, the instance attributes follow the `MeshVertex` ones.
+static PARTICLE_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    #define CURVE_SAMPLES 16
+
+    layout(location = 0) in vec3 a_Position;
+    layout(location = 2) in vec2 a_TexCoord;
+    layout(location = 4) in vec4 a_ParticlePosition;
+    layout(location = 5) in vec4 a_ParticleVelocity;
+    layout(location = 6) in vec2 a_ParticleRotation;
+
+    uniform mat4 u_ViewProjection;
+    uniform vec3 u_CameraRight;
+    uniform vec3 u_CameraUp;
+    // curves sampled evenly over the lifetime
+    uniform vec4 u_ColorCurve[CURVE_SAMPLES];
+    uniform float u_SizeCurve[CURVE_SAMPLES];
+
+    out vec4 v_Color;
+    out vec2 v_TexCoord;
+
+    void main() {
+        float lifetime = a_ParticleVelocity.w;
+        if (a_ParticlePosition.w >= lifetime) {
+            // dead, outside of the clip volume
+            gl_Position = vec4(2.0, 2.0, 2.0, 1.0);
+            return;
+        }
+
+        float x = clamp(a_ParticlePosition.w / lifetime, 0.0, 1.0) * float(CURVE_SAMPLES - 1);
+        int i = int(x);
+        int j = min(i + 1, CURVE_SAMPLES - 1);
+        float f = x - float(i);
+        float size = mix(u_SizeCurve[i], u_SizeCurve[j], f);
+        v_Color = mix(u_ColorCurve[i], u_ColorCurve[j], f);
+        v_TexCoord = a_TexCoord;
+
+        float c = cos(a_ParticleRotation.x);
+        float s = sin(a_ParticleRotation.x);
+        vec2 corner = mat2(c, s, -s, c) * a_Position.xy * size;
+        vec3 position = a_ParticlePosition.xyz + u_CameraRight * corner.x + u_CameraUp * corner.y;
+        gl_Position = u_ViewProjection * vec4(position, 1.0);
+    }
+";
+
+static PARTICLE_FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    in vec4 v_Color;
+    in vec2 v_TexCoord;
+
+    uniform sampler2D u_Texture;
+    uniform int u_HasTexture;
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        vec4 result = v_Color;
+        if (u_HasTexture != 0) {
+            result *= texture(u_Texture, v_TexCoord);
+        } else {
+            // soft disc
+            float d = length(v_TexCoord - 0.5) * 2.0;
+            result.a *= 1.0 - smoothstep(0.5, 1.0, d);
+        }
+        if (result.a <= 0.0) {
+            discard;
+        }
+        color = result;
+    }
+";
+
+pub trait Lerp: Copy {
+    fn lerp(self, other: Self, t: f32) -> Self;
+}
+
+impl Lerp for f32 {
+    fn lerp(self, other: f32, t: f32) -> f32 {
+        self + (other - self) * t
+    }
+}
+
+impl Lerp for Vec2 {
+    fn lerp(self, other: Vec2, t: f32) -> Vec2 {
+        Vec2::lerp(self, other, t)
+    }
+}
+
+impl Lerp for Vec3 {
+    fn lerp(self, other: Vec3, t: f32) -> Vec3 {
+        Vec3::lerp(self, other, t)
+    }
+}
+
+impl Lerp for Vec4 {
+    fn lerp(self, other: Vec4, t: f32) -> Vec4 {
+        Vec4::lerp(self, other, t)
+    }
+}
+
+// Piecewise linear over normalized time, constant before the first and after
+// the last key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve<T> {
+    keys: Vec<(f32, T)>,
+}
+
+impl<T: Lerp> Curve<T> {
+    // keys are (time, value) pairs, sorted by time here
+    pub fn new(mut keys: Vec<(f32, T)>) -> Self {
+        assert!(!keys.is_empty(), "Curve needs at least one key.");
+        keys.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        Self { keys }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(vec![(0.0, value)])
+    }
+
+    pub fn linear(start: T, end: T) -> Self {
+        Self::new(vec![(0.0, start), (1.0, end)])
+    }
+
+    pub fn with_key(mut self, time: f32, value: T) -> Self {
+        self.keys.push((time, value));
+        Self::new(self.keys)
+    }
+
+    pub fn get_keys(&self) -> &[(f32, T)] {
+        &self.keys
+    }
+
+    pub fn evaluate(&self, time: f32) -> T {
+        let (first_time, first_value) = self.keys[0];
+        if time <= first_time {
+            return first_value;
+        }
+
+        for pair in self.keys.windows(2) {
+            let ((start, a), (end, b)) = (pair[0], pair[1]);
+            if time <= end {
+                return a.lerp(b, (time - start) / (end - start).max(f32::EPSILON));
+            }
+        }
+        self.keys[self.keys.len() - 1].1
+    }
+
+    fn sample(&self, count: usize) -> Vec<T> {
+        (0..count)
+            .map(|i| self.evaluate(i as f32 / (count - 1) as f32))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParticleForce {
+    // constant acceleration, e.g. gravity
+    Acceleration(Vec3),
+    // fraction of the velocity lost per second, exponentially
+    Drag(f32),
+    // pulls towards `position`, pushes away with a negative strength
+    Attractor { position: Vec3, strength: f32 },
+    // smooth pseudo random acceleration field changing over time
+    Turbulence { strength: f32, frequency: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitterShape {
+    Point,
+    Sphere { radius: f32 },
+    Box { half_extents: Vec3 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParticleBlend {
+    Alpha,
+    // for fire, sparks and other light emitting particles
+    Additive,
+}
+
+#[derive(Clone)]
+pub struct EmitterSettings {
+    // particles per second while emitting
+    pub spawn_rate: f32,
+    // CPU emitters drop new particles past this count, GPU emitters replace
+    // the oldest ones
+    pub max_particles: usize,
+    // (min, max) ranges, values are picked uniformly in between
+    pub lifetime: (f32, f32),
+    pub speed: (f32, f32),
+    pub rotation: (f32, f32),
+    pub angular_velocity: (f32, f32),
+    pub shape: EmitterShape,
+    // initial velocities point into the cone around `direction`
+    pub direction: Vec3,
+    // half angle of the cone in radians, PI emits in all directions
+    pub spread: f32,
+    pub color_over_lifetime: Curve<Vec4>,
+    pub size_over_lifetime: Curve<f32>,
+    pub forces: Vec<ParticleForce>,
+    pub blend: ParticleBlend,
+    // soft discs when `None`
+    pub texture: Option<Rc<dyn Texture>>,
+}
+
+impl Default for EmitterSettings {
+    fn default() -> Self {
+        Self {
+            spawn_rate: 50.0,
+            max_particles: 1000,
+            lifetime: (1.0, 2.0),
+            speed: (1.0, 2.0),
+            rotation: (0.0, 0.0),
+            angular_velocity: (0.0, 0.0),
+            shape: EmitterShape::Point,
+            direction: Vec3::Y,
+            spread: 0.3,
+            color_over_lifetime: Curve::linear(Vec4::ONE, Vec4::new(1.0, 1.0, 1.0, 0.0)),
+            size_over_lifetime: Curve::constant(0.1),
+            forces: Vec::new(),
+            blend: ParticleBlend::Alpha,
+            texture: None,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Particle {
+    pub position: Vec3,
+    // seconds since spawning
+    pub age: f32,
+    pub velocity: Vec3,
+    // dead once `age` reaches it
+    pub lifetime: f32,
+    // radians, around the view direction
+    pub rotation: f32,
+    pub angular_velocity: f32,
+}
+
+impl Particle {
+    pub fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+
+    // normalized age, 0 when spawned and 1 when dying
+    pub fn get_progress(&self) -> f32 {
+        (self.age / self.lifetime).clamp(0.0, 1.0)
+    }
+
+    // per instance attributes, vectors packed with the scalar after them
+    pub fn get_layout() -> BufferLayout {
+        BufferLayout::new(vec![
+            BufferElement::new("a_ParticlePosition", ShaderDataType::Float4),
+            BufferElement::new("a_ParticleVelocity", ShaderDataType::Float4),
+            BufferElement::new("a_ParticleRotation", ShaderDataType::Float2),
+        ])
+        .with_divisor(1)
+    }
+}
+
+enum Simulation {
+    Cpu(Vec<Particle>),
+    Gpu(Box<GpuSimulation>),
+}
+
+// Spawns and simulates particles, draw them with `ParticleRenderer` or, for
+// CPU simulated emitters, with `draw_2d`.
+pub struct ParticleEmitter {
+    settings: EmitterSettings,
+    position: Vec3,
+    is_emitting: bool,
+    spawn_accumulator: f32,
+    pending_burst: usize,
+    time: f32,
+    random: Random,
+    simulation: Simulation,
+}
+
+impl ParticleEmitter {
+    pub fn new(settings: EmitterSettings) -> Self {
+        Self {
+            simulation: Simulation::Cpu(Vec::with_capacity(settings.max_particles)),
+            settings,
+            position: Vec3::ZERO,
+            is_emitting: true,
+            spawn_accumulator: 0.0,
+            pending_burst: 0,
+            time: 0.0,
+            random: Random::new(NEXT_SEED.fetch_add(0x6D2B_79F5, Ordering::Relaxed)),
+        }
+    }
+
+    // Requires a current OpenGL context. Simulates on the CPU where instanced
+    // draws, needed for the transform feedback step, are not supported.
+    pub fn new_gpu(settings: EmitterSettings) -> Self {
+        let mut emitter = Self::new(settings);
+        if supports_instancing() {
+            emitter.simulation =
+                Simulation::Gpu(Box::new(GpuSimulation::new(emitter.settings.max_particles)));
+        } else {
+            log_warn!("Instancing is not supported, simulating particles on the CPU");
+        }
+        emitter
+    }
+
+    pub fn is_gpu_simulated(&self) -> bool {
+        matches!(self.simulation, Simulation::Gpu(_))
+    }
+
+    pub fn get_settings(&self) -> &EmitterSettings {
+        &self.settings
+    }
+
+    // changing `max_particles` of a GPU emitter clears its particles
+    pub fn get_settings_mut(&mut self) -> &mut EmitterSettings {
+        &mut self.settings
+    }
+
+    pub fn get_position(&self) -> Vec3 {
+        self.position
+    }
+
+    // new particles spawn around it, existing ones are not moved
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        self.is_emitting
+    }
+
+    pub fn set_emitting(&mut self, is_emitting: bool) {
+        self.is_emitting = is_emitting;
+    }
+
+    // spawns `count` particles on the next update, also while not emitting
+    pub fn burst(&mut self, count: usize) {
+        self.pending_burst += count;
+    }
+
+    pub fn clear(&mut self) {
+        match &mut self.simulation {
+            Simulation::Cpu(particles) => particles.clear(),
+            Simulation::Gpu(gpu) => **gpu = GpuSimulation::new(self.settings.max_particles),
+        }
+        self.spawn_accumulator = 0.0;
+        self.pending_burst = 0;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        if self.is_emitting {
+            self.spawn_accumulator += self.settings.spawn_rate * dt;
+        }
+        let spawn_count = self.spawn_accumulator as usize + self.pending_burst;
+        self.spawn_accumulator = self.spawn_accumulator.fract();
+        self.pending_burst = 0;
+        self.time += dt;
+
+        match &mut self.simulation {
+            Simulation::Cpu(particles) => {
+                for particle in particles.iter_mut() {
+                    integrate_particle(particle, &self.settings.forces, self.time, dt);
+                }
+                particles.retain(|particle| particle.is_alive());
+
+                let room = self.settings.max_particles.saturating_sub(particles.len());
+                for _ in 0..spawn_count.min(room) {
+                    particles.push(spawn_particle(
+                        &self.settings,
+                        self.position,
+                        &mut self.random,
+                    ));
+                }
+            }
+            Simulation::Gpu(gpu) => {
+                if gpu.capacity != self.settings.max_particles {
+                    **gpu = GpuSimulation::new(self.settings.max_particles);
+                }
+                let seed = self.random.next_u32();
+                gpu.update(
+                    &self.settings,
+                    self.position,
+                    spawn_count,
+                    seed,
+                    self.time,
+                    dt,
+                );
+            }
+        }
+    }
+
+    // alive particles of CPU simulated emitters, empty for GPU ones
+    pub fn get_particles(&self) -> &[Particle] {
+        match &self.simulation {
+            Simulation::Cpu(particles) => particles,
+            Simulation::Gpu(_) => &[],
+        }
+    }
+
+    // Submits one quad per particle to a 2D batch, between `begin_scene` and
+    // `end_scene`. Uses the blending of the 2D renderer, rotations are around
+    // the Z axis.
+    pub fn draw_2d(&self, renderer: &mut Renderer2D) {
+        assert!(
+            !self.is_gpu_simulated(),
+            "GPU simulated particles can only be drawn by ParticleRenderer."
+        );
+
+        for particle in self.get_particles() {
+            let progress = particle.get_progress();
+            let size = Vec2::splat(self.settings.size_over_lifetime.evaluate(progress));
+            let color = self.settings.color_over_lifetime.evaluate(progress);
+            match &self.settings.texture {
+                Some(texture) => renderer.draw_rotated_textured_quad(
+                    particle.position,
+                    size,
+                    particle.rotation,
+                    texture.as_ref(),
+                    1.0,
+                    color,
+                ),
+                None => {
+                    renderer.draw_rotated_quad(particle.position, size, particle.rotation, color)
+                }
+            }
+        }
+    }
+}
+
+// Draws emitters as camera facing quads, instanced or in batches where
+// instancing is not supported.
+pub struct ParticleRenderer {
+    shader: OpenGLShader,
+    mesh: InstancedMesh,
+}
+
+impl ParticleRenderer {
+    // requires a current OpenGL context
+    pub fn new() -> Self {
+        Self {
+            shader: OpenGLShader::new(PARTICLE_VERTEX_SHADER, PARTICLE_FRAGMENT_SHADER),
+            mesh: InstancedMesh::new(&quad_data(), Particle::get_layout(), MAX_PARTICLES_PER_DRAW),
+        }
+    }
+
+    // Draw after the opaque geometry, particles are depth tested but do not
+    // write depth and are not sorted.
+    pub fn draw(&mut self, camera: &dyn Camera, emitter: &ParticleEmitter) {
+        let settings = &emitter.settings;
+        let view = camera.get_view();
+        let (x, y) = (view.get_col(0), view.get_col(1));
+        let z = view.get_col(2);
+
+        self.shader.bind();
+        self.shader
+            .set_mat4("u_ViewProjection", camera.get_view_projection());
+        self.shader
+            .set_float3("u_CameraRight", Vec3::new(x.x, y.x, z.x));
+        self.shader
+            .set_float3("u_CameraUp", Vec3::new(x.y, y.y, z.y));
+
+        let colors = settings.color_over_lifetime.sample(CURVE_SAMPLES);
+        let sizes = settings.size_over_lifetime.sample(CURVE_SAMPLES);
+        for (i, (color, size)) in colors.iter().zip(sizes.iter()).enumerate() {
+            self.shader
+                .set_float4(&format!("u_ColorCurve[{}]", i), *color);
+            self.shader.set_float(&format!("u_SizeCurve[{}]", i), *size);
+        }
+
+        match &settings.texture {
+            Some(texture) => {
+                texture.bind(0);
+                self.shader.set_int("u_Texture", 0);
+                self.shader.set_int("u_HasTexture", 1);
+            }
+            None => self.shader.set_int("u_HasTexture", 0),
+        }
+
+        let blend_enabled = state::is_enabled(gl::BLEND);
+        let depth_mask = state::get_depth_mask();
+        let blend_func = state::get_blend_func();
+
+        state::enable(gl::BLEND);
+        state::set_depth_mask(false);
+        match settings.blend {
+            ParticleBlend::Alpha => state::set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA),
+            ParticleBlend::Additive => state::set_blend_func(gl::SRC_ALPHA, gl::ONE),
+        }
+
+        match &emitter.simulation {
+            Simulation::Cpu(particles) => self.mesh.draw(particles),
+            Simulation::Gpu(gpu) => gpu.draw(),
+        }
+
+        state::set_blend_func_separate(blend_func[0], blend_func[1], blend_func[2], blend_func[3]);
+        state::set_depth_mask(depth_mask);
+        state::set_enabled(gl::BLEND, blend_enabled);
+        self.shader.unbind();
+    }
+}
+
+impl Default for ParticleRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Particles live in a ring of slots inside two buffers, every update reads
+// one and writes the other through transform feedback. Spawning overwrites
+// the next slots of the ring, which hold the oldest particles.
+struct GpuSimulation {
+    shader: OpenGLShader,
+    buffers: [Rc<VertexBuffer>; 2],
+    simulate_arrays: [VertexArray; 2],
+    render_arrays: [VertexArray; 2],
+    index_count: usize,
+    // buffer holding the latest state
+    current: usize,
+    cursor: usize,
+    capacity: usize,
+    spawns: SpawnHistory,
+}
+
+impl GpuSimulation {
+    fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Particle emitter needs room for a particle.");
+
+        let quad = quad_data();
+        let quad_vertices = Rc::new(VertexBuffer::from_data(
+            &quad.vertices,
+            MeshVertex::get_layout(),
+        ));
+        let quad_indices = Rc::new(IndexBuffer::new(&quad.indices));
+
+        let dead = vec![Particle::default(); capacity];
+        let buffers = [
+            Rc::new(VertexBuffer::from_data(&dead, Particle::get_layout())),
+            Rc::new(VertexBuffer::from_data(&dead, Particle::get_layout())),
+        ];
+
+        let simulate_array = |buffer: &Rc<VertexBuffer>| {
+            let mut vertex_array = VertexArray::new();
+            vertex_array.add_vertex_buffer(buffer.clone());
+            vertex_array
+        };
+        let render_array = |buffer: &Rc<VertexBuffer>| {
+            let mut vertex_array = VertexArray::new();
+            vertex_array.add_vertex_buffer(quad_vertices.clone());
+            vertex_array.add_vertex_buffer(buffer.clone());
+            vertex_array.set_index_buffer(quad_indices.clone());
+            vertex_array
+        };
+
+        Self {
+            shader: OpenGLShader::new_transform_feedback(
+                SIMULATION_SHADER,
+                &["tf_Position", "tf_Velocity", "tf_Rotation"],
+            ),
+            simulate_arrays: [simulate_array(&buffers[0]), simulate_array(&buffers[1])],
+            render_arrays: [render_array(&buffers[0]), render_array(&buffers[1])],
+            buffers,
+            index_count: quad.indices.len(),
+            current: 0,
+            cursor: 0,
+            capacity,
+            spawns: SpawnHistory::default(),
+        }
+    }
+
+    fn update(
+        &mut self,
+        settings: &EmitterSettings,
+        position: Vec3,
+        spawn_count: usize,
+        seed: u32,
+        time: f32,
+        dt: f32,
+    ) {
+        let spawn_count = spawn_count.min(self.capacity);
+        let shader = &self.shader;
+
+        shader.bind();
+        shader.set_float("u_DeltaTime", dt);
+        shader.set_float("u_Time", time);
+        shader.set_int("u_Seed", seed as i32);
+        shader.set_int("u_SpawnStart", self.cursor as i32);
+        shader.set_int("u_SpawnCount", spawn_count as i32);
+        shader.set_int("u_Capacity", self.capacity as i32);
+
+        shader.set_float3("u_EmitterPosition", position);
+        let (shape, shape_size) = match settings.shape {
+            EmitterShape::Point => (0, Vec3::ZERO),
+            EmitterShape::Sphere { radius } => (1, Vec3::splat(radius)),
+            EmitterShape::Box { half_extents } => (2, half_extents),
+        };
+        shader.set_int("u_Shape", shape);
+        shader.set_float3("u_ShapeSize", shape_size);
+        shader.set_float3("u_Direction", get_direction(settings));
+        shader.set_float("u_Spread", settings.spread);
+        shader.set_float2("u_Lifetime", range_to_vec2(settings.lifetime));
+        shader.set_float2("u_Speed", range_to_vec2(settings.speed));
+        shader.set_float2("u_Rotation", range_to_vec2(settings.rotation));
+        shader.set_float2(
+            "u_AngularVelocity",
+            range_to_vec2(settings.angular_velocity),
+        );
+
+        let forces = &settings.forces[..settings.forces.len().min(MAX_GPU_FORCES)];
+        shader.set_int("u_ForceCount", forces.len() as i32);
+        for (i, force) in forces.iter().enumerate() {
+            let (force_type, value) = match *force {
+                ParticleForce::Acceleration(acceleration) => (0, acceleration.extend(0.0)),
+                ParticleForce::Drag(drag) => (1, Vec4::new(0.0, 0.0, 0.0, drag)),
+                ParticleForce::Attractor { position, strength } => (2, position.extend(strength)),
+                ParticleForce::Turbulence {
+                    strength,
+                    frequency,
+                } => (3, Vec4::new(frequency, 0.0, 0.0, strength)),
+            };
+            shader.set_int(&format!("u_ForceTypes[{}]", i), force_type);
+            shader.set_float4(&format!("u_Forces[{}]", i), value);
+        }
+
+        let target = 1 - self.current;
+        state::enable(gl::RASTERIZER_DISCARD);
+        self.simulate_arrays[self.current].bind();
+        state::bind_buffer_base(
+            gl::TRANSFORM_FEEDBACK_BUFFER,
+            0,
+            self.buffers[target].get_renderer_id(),
+        );
+        unsafe {
+            gl::BeginTransformFeedback(gl::POINTS);
+            gl::DrawArraysInstanced(gl::POINTS, 0, 1, self.capacity as i32);
+            gl::EndTransformFeedback();
+        }
+        profiler::record_draw(gl::POINTS, 1, self.capacity as u32);
+        state::bind_buffer_base(gl::TRANSFORM_FEEDBACK_BUFFER, 0, 0);
+        self.simulate_arrays[self.current].unbind();
+        state::disable(gl::RASTERIZER_DISCARD);
+        shader.unbind();
+
+        self.current = target;
+        self.cursor = (self.cursor + spawn_count) % self.capacity;
+        self.spawns.record(time, spawn_count, settings.lifetime.1);
+    }
+
+    // expects the particle shader to be bound
+    fn draw(&self) {
+        let vertex_array = &self.render_arrays[self.current];
+        vertex_array.bind();
+        unsafe {
+            gl::DrawElementsInstanced(
+                gl::TRIANGLES,
+                self.index_count as i32,
+                gl::UNSIGNED_INT,
+                ptr::null(),
+                self.capacity as i32,
+            );
+        }
+        // every slot is drawn but dead ones are moved out of the clip volume,
+        // count the particles that can still be alive instead
+        profiler::record_draw(
+            gl::TRIANGLES,
+            self.index_count as u32,
+            self.spawns.get_alive_estimate(self.capacity) as u32,
+        );
+        vertex_array.unbind();
+    }
+}
+
+// Recent spawns of a GPU emitter, whose particles are never read back. Gives
+// an upper bound of the alive count from the longest lifetime.
+#[derive(Debug, Default)]
+struct SpawnHistory {
+    // (time, count), oldest first
+    spawns: VecDeque<(f32, usize)>,
+}
+
+impl SpawnHistory {
+    fn record(&mut self, time: f32, count: usize, max_lifetime: f32) {
+        if count > 0 {
+            self.spawns.push_back((time, count));
+        }
+        while let Some(&(spawn_time, _)) = self.spawns.front() {
+            if time - spawn_time < max_lifetime {
+                break;
+            }
+            self.spawns.pop_front();
+        }
+    }
+
+    fn get_alive_estimate(&self, capacity: usize) -> usize {
+        let spawned: usize = self.spawns.iter().map(|&(_, count)| count).sum();
+        spawned.min(capacity)
+    }
+}
+
+// xorshift32, deterministic per emitter
+struct Random {
+    state: u32,
+}
+
+impl Random {
+    fn new(seed: u32) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    // uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn range(&mut self, (min, max): (f32, f32)) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+
+    // uniform over the cap of the cone around `axis` with half angle `spread`
+    fn cone(&mut self, axis: Vec3, spread: f32) -> Vec3 {
+        let cos_theta = 1.0 - self.next_f32() * (1.0 - spread.cos());
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * self.next_f32();
+        let tangent = axis.any_orthogonal();
+        let bitangent = axis.cross(tangent);
+        (tangent * phi.cos() + bitangent * phi.sin()) * sin_theta + axis * cos_theta
+    }
+}
+
+fn spawn_particle(settings: &EmitterSettings, position: Vec3, random: &mut Random) -> Particle {
+    let offset = match settings.shape {
+        EmitterShape::Point => Vec3::ZERO,
+        EmitterShape::Sphere { radius } => {
+            random.cone(Vec3::Y, PI) * radius * random.next_f32().cbrt()
+        }
+        EmitterShape::Box { half_extents } => {
+            let unit = Vec3::new(random.next_f32(), random.next_f32(), random.next_f32());
+            Vec3::new(
+                (unit.x * 2.0 - 1.0) * half_extents.x,
+                (unit.y * 2.0 - 1.0) * half_extents.y,
+                (unit.z * 2.0 - 1.0) * half_extents.z,
+            )
+        }
+    };
+    let direction = random.cone(get_direction(settings), settings.spread);
+
+    Particle {
+        position: position + offset,
+        age: 0.0,
+        velocity: direction * random.range(settings.speed),
+        lifetime: random.range(settings.lifetime),
+        rotation: random.range(settings.rotation),
+        angular_velocity: random.range(settings.angular_velocity),
+    }
+}
+
+fn integrate_particle(particle: &mut Particle, forces: &[ParticleForce], time: f32, dt: f32) {
+    let mut acceleration = Vec3::ZERO;
+    let mut drag = 0.0;
+    for force in forces.iter() {
+        match *force {
+            ParticleForce::Acceleration(value) => acceleration += value,
+            ParticleForce::Drag(value) => drag += value,
+            ParticleForce::Attractor { position, strength } => {
+                let to_attractor = position - particle.position;
+                let distance_squared = to_attractor.length_squared();
+                acceleration += to_attractor * (strength / distance_squared.max(0.000001).sqrt())
+                    / (distance_squared + 1.0);
+            }
+            ParticleForce::Turbulence {
+                strength,
+                frequency,
+            } => acceleration += turbulence(particle.position, frequency, time) * strength,
+        }
+    }
+
+    particle.velocity = (particle.velocity + acceleration * dt) * (-drag * dt).exp();
+    particle.position += particle.velocity * dt;
+    particle.rotation += particle.angular_velocity * dt;
+    particle.age += dt;
+}
+
+fn turbulence(p: Vec3, frequency: f32, time: f32) -> Vec3 {
+    let q = p * frequency;
+    Vec3::new(
+        (q.y + time * 1.7).sin() + (q.z * 1.3 + time).cos(),
+        (q.z + time * 1.1).sin() + (q.x * 1.7 - time).cos(),
+        (q.x - time * 1.3).sin() + (q.y * 1.1 + time * 0.9).cos(),
+    ) * 0.5
+}
+
+fn get_direction(settings: &EmitterSettings) -> Vec3 {
+    if settings.direction.length_squared() > 0.0 {
+        settings.direction.normalize()
+    } else {
+        Vec3::Y
+    }
+}
+
+fn range_to_vec2((min, max): (f32, f32)) -> Vec2 {
+    Vec2::new(min, max)
+}
+
+// unit quad centered at the origin, facing +Z
+fn quad_data() -> MeshData {
+    let normal = Vec3::Z;
+    MeshData::new(
+        vec![
+            MeshVertex::new(Vec3::new(-0.5, -0.5, 0.0), normal, Vec2::new(0.0, 0.0)),
+            MeshVertex::new(Vec3::new(0.5, -0.5, 0.0), normal, Vec2::new(1.0, 0.0)),
+            MeshVertex::new(Vec3::new(0.5, 0.5, 0.0), normal, Vec2::new(1.0, 1.0)),
+            MeshVertex::new(Vec3::new(-0.5, 0.5, 0.0), normal, Vec2::new(0.0, 1.0)),
+        ],
+        vec![0, 1, 2, 2, 3, 0],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_vec3_eq(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    // particles that outlive the tests unless stated otherwise
+    fn settings() -> EmitterSettings {
+        EmitterSettings {
+            spawn_rate: 10.0,
+            lifetime: (10.0, 10.0),
+            ..EmitterSettings::default()
+        }
+    }
+
+    #[test]
+    fn curve_is_constant_outside_the_keys() {
+        let curve = Curve::new(vec![(0.75, 3.0), (0.25, 1.0)]);
+        assert_eq!(curve.get_keys()[0], (0.25, 1.0));
+        assert_eq!(curve.evaluate(0.0), 1.0);
+        assert_eq!(curve.evaluate(0.25), 1.0);
+        assert_eq!(curve.evaluate(0.5), 2.0);
+        assert_eq!(curve.evaluate(0.75), 3.0);
+        assert_eq!(curve.evaluate(1.0), 3.0);
+
+        assert_eq!(Curve::constant(5.0).evaluate(0.5), 5.0);
+    }
+
+    #[test]
+    fn curve_interpolates_between_neighbouring_keys() {
+        let curve = Curve::linear(0.0, 1.0).with_key(0.5, 4.0);
+        assert_eq!(curve.evaluate(0.25), 2.0);
+        assert_eq!(curve.evaluate(0.75), 2.5);
+        assert_eq!(curve.sample(3), vec![0.0, 4.0, 1.0]);
+    }
+
+    #[test]
+    fn spawn_rate_accumulates_fractions() {
+        let mut emitter = ParticleEmitter::new(settings());
+        emitter.update(0.25);
+        assert_eq!(emitter.get_particles().len(), 2);
+        emitter.update(0.25);
+        assert_eq!(emitter.get_particles().len(), 5);
+
+        emitter.set_emitting(false);
+        emitter.update(1.0);
+        assert_eq!(emitter.get_particles().len(), 5);
+    }
+
+    #[test]
+    fn bursts_spawn_once_while_not_emitting() {
+        let mut emitter = ParticleEmitter::new(settings());
+        emitter.set_emitting(false);
+        emitter.burst(3);
+        emitter.burst(1);
+        emitter.update(0.1);
+        assert_eq!(emitter.get_particles().len(), 4);
+        emitter.update(0.1);
+        assert_eq!(emitter.get_particles().len(), 4);
+
+        emitter.burst(2);
+        emitter.clear();
+        emitter.update(0.1);
+        assert!(emitter.get_particles().is_empty());
+    }
+
+    #[test]
+    fn spawning_stops_at_max_particles() {
+        let mut emitter = ParticleEmitter::new(EmitterSettings {
+            max_particles: 3,
+            lifetime: (1.0, 1.0),
+            ..settings()
+        });
+        emitter.set_emitting(false);
+        emitter.burst(10);
+        emitter.update(0.5);
+        assert_eq!(emitter.get_particles().len(), 3);
+
+        // the excess is dropped, not queued
+        emitter.update(0.5);
+        assert_eq!(emitter.get_particles().len(), 3);
+        emitter.burst(2);
+        emitter.update(0.5);
+        assert_eq!(emitter.get_particles().len(), 2);
+    }
+
+    #[test]
+    fn particles_die_at_their_lifetime() {
+        let mut emitter = ParticleEmitter::new(EmitterSettings {
+            lifetime: (1.0, 1.0),
+            ..settings()
+        });
+        emitter.set_emitting(false);
+        emitter.burst(2);
+        emitter.update(0.5);
+        emitter.update(0.5);
+        let particle = emitter.get_particles()[0];
+        assert!(particle.is_alive());
+        assert_eq!(particle.get_progress(), 0.5);
+
+        emitter.update(0.5);
+        assert!(emitter.get_particles().is_empty());
+    }
+
+    #[test]
+    fn spawns_inside_the_shape_along_the_direction() {
+        let settings = EmitterSettings {
+            lifetime: (1.0, 2.0),
+            speed: (2.0, 2.0),
+            shape: EmitterShape::Box {
+                half_extents: Vec3::new(1.0, 2.0, 0.0),
+            },
+            direction: Vec3::new(0.0, 0.0, 3.0),
+            spread: 0.0,
+            ..settings()
+        };
+        let mut random = Random::new(7);
+        for _ in 0..32 {
+            let particle = spawn_particle(&settings, Vec3::new(5.0, 0.0, 0.0), &mut random);
+            let offset = particle.position - Vec3::new(5.0, 0.0, 0.0);
+            assert!(offset.x.abs() <= 1.0 && offset.y.abs() <= 2.0 && offset.z == 0.0);
+            assert_vec3_eq(particle.velocity, Vec3::new(0.0, 0.0, 2.0));
+            assert!(particle.lifetime >= 1.0 && particle.lifetime < 2.0);
+            assert_eq!(particle.age, 0.0);
+        }
+    }
+
+    #[test]
+    fn integrates_acceleration_then_drag() {
+        let mut particle = Particle {
+            velocity: Vec3::X,
+            lifetime: 1.0,
+            ..Particle::default()
+        };
+        let gravity = [ParticleForce::Acceleration(Vec3::new(0.0, -10.0, 0.0))];
+        integrate_particle(&mut particle, &gravity, 0.0, 0.1);
+        assert_vec3_eq(particle.velocity, Vec3::new(1.0, -1.0, 0.0));
+        assert_vec3_eq(particle.position, Vec3::new(0.1, -0.1, 0.0));
+        assert!((particle.age - 0.1).abs() < 1e-6);
+
+        // drag is exponential over the step, after adding the acceleration
+        let forces = [
+            gravity[0],
+            ParticleForce::Drag(1.0),
+            ParticleForce::Drag(1.0),
+        ];
+        let mut particle = Particle {
+            velocity: Vec3::X,
+            angular_velocity: 2.0,
+            lifetime: 1.0,
+            ..Particle::default()
+        };
+        integrate_particle(&mut particle, &forces, 0.0, 0.5);
+        let expected = Vec3::new(1.0, -5.0, 0.0) * (-1.0f32).exp();
+        assert_vec3_eq(particle.velocity, expected);
+        assert_vec3_eq(particle.position, expected * 0.5);
+        assert_eq!(particle.rotation, 1.0);
+    }
+
+    #[test]
+    fn emitter_update_applies_the_forces() {
+        let mut emitter = ParticleEmitter::new(EmitterSettings {
+            speed: (0.0, 0.0),
+            forces: vec![ParticleForce::Acceleration(Vec3::new(0.0, -10.0, 0.0))],
+            ..settings()
+        });
+        emitter.set_position(Vec3::new(0.0, 5.0, 0.0));
+        emitter.set_emitting(false);
+        emitter.burst(1);
+        emitter.update(0.1);
+        emitter.update(0.1);
+        emitter.update(0.1);
+
+        let particle = emitter.get_particles()[0];
+        assert_vec3_eq(particle.velocity, Vec3::new(0.0, -2.0, 0.0));
+        assert_vec3_eq(particle.position, Vec3::new(0.0, 4.7, 0.0));
+    }
+
+    #[test]
+    fn alive_estimate_forgets_expired_spawns() {
+        let mut spawns = SpawnHistory::default();
+        spawns.record(0.0, 5, 1.0);
+        spawns.record(0.5, 5, 1.0);
+        assert_eq!(spawns.get_alive_estimate(100), 10);
+        assert_eq!(spawns.get_alive_estimate(8), 8);
+
+        spawns.record(1.0, 0, 1.0);
+        assert_eq!(spawns.get_alive_estimate(100), 5);
+        spawns.record(2.0, 0, 1.0);
+        assert_eq!(spawns.get_alive_estimate(100), 0);
+    }
+}
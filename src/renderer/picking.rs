@@ -0,0 +1,340 @@
+use crate::{
+    events::{Event, EventHandler, MouseButton},
+    math::{Aabb, Mat4, Ray, Vec2, Vec3},
+    renderer::{
+        camera::Camera,
+        framebuffer::{Framebuffer, PixelReadback},
+    },
+};
+
+// Draws write the entity under each pixel into an R32I color attachment at
+// this index, the renderer2d batches and lit materials do so through
+// `Renderer2D::set_entity_id` and `lighting::set_entity_id`. Framebuffers
+// without it discard the IDs.
+pub const ENTITY_ID_ATTACHMENT: usize = 1;
+
+// IDs are stored one higher, so that 0, the clear value and the default of
+// unset uniforms, means no entity.
+pub fn encode_entity_id(entity: Option<u32>) -> i32 {
+    entity.map_or(0, |id| id as i32 + 1)
+}
+
+pub fn decode_entity_id(value: i32) -> Option<u32> {
+    if value > 0 {
+        Some(value as u32 - 1)
+    } else {
+        None
+    }
+}
+
+// expects the framebuffer to be bound, call it after clearing its colors
+pub fn clear_entity_ids(framebuffer: &Framebuffer) {
+    framebuffer.clear_attachment(ENTITY_ID_ATTACHMENT, [0.0; 4]);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadbackMode {
+    // reads the pixel right away, waits for the GPU to finish the frame
+    Immediate,
+    // reads through a pixel buffer, results arrive a frame or two later
+    Async,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PickResult {
+    pub entity: Option<u32>,
+    // cursor position of the click in window pixels, origin at the top left
+    pub position: Vec2,
+}
+
+// Maps the cursor to the entity under it. Feed it the window events, then call
+// `update` each frame after the frame is drawn into the framebuffer, and
+// resolved when multisampled. Clicks are picked with the left mouse button,
+// the hovered entity is only tracked when enabled.
+pub struct Picker {
+    mode: ReadbackMode,
+    window_size: Vec2,
+    cursor: Option<Vec2>,
+    click_pending: bool,
+    track_hover: bool,
+    readback: Option<PixelReadback>,
+    // cursor position and whether it was a click, of the read in flight
+    in_flight: Option<(Vec2, bool)>,
+    hovered: Option<u32>,
+    picked: Option<PickResult>,
+}
+
+impl Picker {
+    // `Async` requires a current OpenGL context
+    pub fn new(window_width: u32, window_height: u32, mode: ReadbackMode) -> Self {
+        Self {
+            mode,
+            window_size: Vec2::new(window_width as f32, window_height as f32),
+            cursor: None,
+            click_pending: false,
+            track_hover: false,
+            readback: match mode {
+                ReadbackMode::Immediate => None,
+                ReadbackMode::Async => Some(PixelReadback::new()),
+            },
+            in_flight: None,
+            hovered: None,
+            picked: None,
+        }
+    }
+
+    pub fn with_hover(mut self, track_hover: bool) -> Self {
+        self.track_hover = track_hover;
+        self
+    }
+
+    pub fn get_mode(&self) -> ReadbackMode {
+        self.mode
+    }
+
+    // in window pixels, origin at the top left
+    pub fn get_cursor(&self) -> Option<Vec2> {
+        self.cursor
+    }
+
+    pub fn get_hovered(&self) -> Option<u32> {
+        self.hovered
+    }
+
+    // the last click, once
+    pub fn take_picked(&mut self) -> Option<PickResult> {
+        self.picked.take()
+    }
+
+    // reads the entity ID attachment of `framebuffer` under the cursor when
+    // there is a click or hover to resolve
+    pub fn update(&mut self, framebuffer: &Framebuffer) {
+        if let Some(readback) = &mut self.readback {
+            if let Some((position, clicked)) = self.in_flight {
+                match readback.try_take() {
+                    Some(value) => {
+                        self.in_flight = None;
+                        self.finish(decode_entity_id(value), position, clicked);
+                    }
+                    None => return,
+                }
+            }
+        }
+
+        let position = match self.cursor {
+            Some(position) => position,
+            None => return,
+        };
+        if !self.click_pending && !self.track_hover {
+            return;
+        }
+        let clicked = self.click_pending;
+        self.click_pending = false;
+
+        let (x, y) = match self.to_framebuffer(position, framebuffer) {
+            Some(pixel) => pixel,
+            None => {
+                self.finish(None, position, clicked);
+                return;
+            }
+        };
+
+        match &mut self.readback {
+            Some(readback) => {
+                framebuffer.read_pixel_async(ENTITY_ID_ATTACHMENT, x, y, readback);
+                self.in_flight = Some((position, clicked));
+            }
+            None => {
+                let value = framebuffer.read_pixel(ENTITY_ID_ATTACHMENT, x, y);
+                self.finish(decode_entity_id(value), position, clicked);
+            }
+        }
+    }
+
+    // ray from the camera through the cursor, for `raycast`
+    pub fn get_cursor_ray(&self, camera: &dyn Camera) -> Option<Ray> {
+        let position = self.cursor?;
+        if self.window_size.x <= 0.0 || self.window_size.y <= 0.0 {
+            return None;
+        }
+        let ndc_x = position.x / self.window_size.x * 2.0 - 1.0;
+        let ndc_y = 1.0 - position.y / self.window_size.y * 2.0;
+        Some(camera.get_ray(ndc_x, ndc_y))
+    }
+
+    fn finish(&mut self, entity: Option<u32>, position: Vec2, clicked: bool) {
+        if self.track_hover {
+            self.hovered = entity;
+        }
+        if clicked {
+            self.picked = Some(PickResult { entity, position });
+        }
+    }
+
+    // window coordinates to a framebuffer pixel, which has its origin at the
+    // bottom left and may differ in size, e.g. on high DPI displays
+    fn to_framebuffer(&self, position: Vec2, framebuffer: &Framebuffer) -> Option<(u32, u32)> {
+        let spec = framebuffer.get_spec();
+        if self.window_size.x <= 0.0 || self.window_size.y <= 0.0 {
+            return None;
+        }
+
+        let x = position.x / self.window_size.x * spec.width as f32;
+        let y = position.y / self.window_size.y * spec.height as f32;
+        if x < 0.0 || y < 0.0 || x >= spec.width as f32 || y >= spec.height as f32 {
+            return None;
+        }
+        Some((x as u32, spec.height - 1 - y as u32))
+    }
+}
+
+impl EventHandler for Picker {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        for e in events.iter() {
+            match e {
+                Event::MouseMoved { x, y } => {
+                    self.cursor = Some(Vec2::new(*x as f32, *y as f32));
+                }
+                Event::MouseButtonPressed {
+                    btn: MouseButton::Left,
+                } => self.click_pending = true,
+                Event::WindowResize { width, height } => {
+                    self.window_size = Vec2::new(*width as f32, *height as f32);
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Shapes for picking entities that are not drawn into the ID attachment,
+// such as colliders, by casting a ray.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Collider {
+    Aabb(Aabb),
+    Sphere { center: Vec3, radius: f32 },
+    // the unit cube centered at the origin, transformed
+    Box(Mat4),
+}
+
+impl Collider {
+    // distance along the ray to the entry point, zero when starting inside
+    pub fn intersect(&self, ray: &Ray) -> Option<f32> {
+        match self {
+            Collider::Aabb(aabb) => ray.intersect_aabb(aabb),
+            Collider::Sphere { center, radius } => ray.intersect_sphere(*center, *radius),
+            Collider::Box(transform) => {
+                let inverse = transform.inverse()?;
+                let local_ray = Ray::new(
+                    inverse.transform_point(ray.origin),
+                    inverse.transform_vector(ray.direction),
+                );
+                let unit_cube =
+                    Aabb::from_center_half_extents(Vec3::ZERO, Vec3::new(0.5, 0.5, 0.5));
+                let t = local_ray.intersect_aabb(&unit_cube)?;
+                // local distances are scaled, measure the hit in world space
+                let point = transform.transform_point(local_ray.at(t));
+                Some((point - ray.origin).length())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub entity: u32,
+    pub distance: f32,
+    pub point: Vec3,
+}
+
+// the closest collider hit by the ray, degenerate colliders giving no finite
+// distance are never hit
+pub fn raycast<'a, I>(ray: &Ray, colliders: I) -> Option<RayHit>
+where
+    I: IntoIterator<Item = (u32, &'a Collider)>,
+{
+    colliders
+        .into_iter()
+        .filter_map(|(entity, collider)| {
+            let distance = collider.intersect(ray).filter(|d| d.is_finite())?;
+            Some(RayHit {
+                entity,
+                distance,
+                point: ray.at(distance),
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_ids_round_trip() {
+        assert_eq!(encode_entity_id(None), 0);
+        assert_eq!(decode_entity_id(0), None);
+        assert_eq!(decode_entity_id(-1), None);
+        for id in [0, 1, 41, 1 << 20].iter() {
+            assert_eq!(decode_entity_id(encode_entity_id(Some(*id))), Some(*id));
+        }
+    }
+
+    #[test]
+    fn raycast_returns_closest_hit() {
+        let colliders = [
+            (
+                1,
+                Collider::Sphere {
+                    center: Vec3::new(0.0, 0.0, -10.0),
+                    radius: 1.0,
+                },
+            ),
+            (
+                2,
+                Collider::Aabb(Aabb::from_center_half_extents(
+                    Vec3::new(0.0, 0.0, -5.0),
+                    Vec3::ONE,
+                )),
+            ),
+            (
+                3,
+                Collider::Sphere {
+                    center: Vec3::new(5.0, 0.0, -2.0),
+                    radius: 1.0,
+                },
+            ),
+        ];
+        let ray = Ray::new(Vec3::ZERO, -Vec3::Z);
+        let hit = raycast(&ray, colliders.iter().map(|(id, c)| (*id, c))).unwrap();
+
+        assert_eq!(hit.entity, 2);
+        assert_eq!(hit.distance, 4.0);
+        assert_eq!(hit.point, Vec3::new(0.0, 0.0, -4.0));
+    }
+
+    #[test]
+    fn box_distance_is_in_world_units() {
+        let transform =
+            Mat4::translation(Vec3::new(0.0, 0.0, -10.0)) * Mat4::scale(Vec3::new(1.0, 1.0, 4.0));
+        let collider = Collider::Box(transform);
+        let ray = Ray::new(Vec3::ZERO, -Vec3::Z);
+
+        let distance = collider.intersect(&ray).unwrap();
+        assert!((distance - 8.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn raycast_skips_degenerate_colliders() {
+        let broken = Collider::Box(Mat4::translation(Vec3::new(f32::NAN, 0.0, 0.0)));
+        let sphere = Collider::Sphere {
+            center: Vec3::new(0.0, 0.0, -3.0),
+            radius: 1.0,
+        };
+        let ray = Ray::new(Vec3::ZERO, -Vec3::Z);
+
+        let hit = raycast(&ray, [(1, &broken), (2, &sphere)]).unwrap();
+        assert_eq!(hit.entity, 2);
+        assert!(raycast(&ray, [(1, &broken)]).is_none());
+    }
+}
@@ -0,0 +1,636 @@
+use std::time::Instant;
+
+use crate::{
+    events::{Event, EventHandler},
+    log_warn,
+    math::{Mat4, Vec2},
+    renderer::{
+        buffer::VertexArray,
+        framebuffer::{Framebuffer, FramebufferAttachment, FramebufferFormat, FramebufferSpec},
+        material::{Material, SceneUniforms},
+        profiler,
+        shader::{OpenGLShader, Shader},
+        state,
+        texture::Texture,
+    },
+};
+
+// texture units of the pass inputs, past the material and shadow map units
+const SOURCE_SLOT: u32 = 24;
+const BLOOM_SLOT: u32 = 25;
+// smallest bloom level edge in pixels
+const MIN_BLOOM_SIZE: u32 = 4;
+
+// one triangle covering the screen, positions derived from the vertex index
+pub(crate) static FULLSCREEN_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    out vec2 v_TexCoord;
+
+    void main() {
+        vec2 position = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+        v_TexCoord = position;
+        gl_Position = vec4(position * 2.0 - 1.0, 0.0, 1.0);
+    }
+";
+
+// prepended to every pass fragment shader, including custom ones
+static PASS_HEADER: &'static str = "
+    #version 330 core
+
+    in vec2 v_TexCoord;
+
+    // output of the previous pass
+    uniform sampler2D u_Source;
+    // size of one source pixel in texture coordinates
+    uniform vec2 u_TexelSize;
+
+    layout(location = 0) out vec4 color;
+";
+
+static COPY_SHADER: &'static str = "
+    void main() {
+        color = texture(u_Source, v_TexCoord);
+    }
+";
+
+static TONE_MAPPING_SHADER: &'static str = "
+    uniform float u_Exposure;
+    uniform int u_Operator;
+
+    // fit of the ACES filmic curve by Krzysztof Narkowicz
+    vec3 aces(vec3 x) {
+        return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+    }
+
+    void main() {
+        vec4 hdr = texture(u_Source, v_TexCoord);
+        vec3 x = hdr.rgb * u_Exposure;
+
+        vec3 mapped;
+        if (u_Operator == 0) {
+            mapped = x / (1.0 + x);
+        } else if (u_Operator == 1) {
+            mapped = aces(x);
+        } else {
+            mapped = clamp(x, 0.0, 1.0);
+        }
+        color = vec4(mapped, hdr.a);
+    }
+";
+
+static GAMMA_SHADER: &'static str = "
+    uniform float u_Gamma;
+
+    void main() {
+        vec4 linear = texture(u_Source, v_TexCoord);
+        color = vec4(pow(max(linear.rgb, 0.0), vec3(1.0 / u_Gamma)), linear.a);
+    }
+";
+
+static VIGNETTE_SHADER: &'static str = "
+    uniform float u_Intensity;
+    uniform float u_Radius;
+    uniform float u_Softness;
+
+    void main() {
+        vec4 source = texture(u_Source, v_TexCoord);
+        float distance_to_center = length(v_TexCoord - 0.5);
+        float falloff = smoothstep(u_Radius, u_Radius - u_Softness, distance_to_center);
+        color = vec4(source.rgb * mix(1.0, falloff, u_Intensity), source.a);
+    }
+";
+
+// FXAA after Timothy Lottes, expects gamma encoded colors
+static FXAA_SHADER: &'static str = "
+    #define FXAA_REDUCE_MIN (1.0 / 128.0)
+    #define FXAA_REDUCE_MUL (1.0 / 8.0)
+    #define FXAA_SPAN_MAX 8.0
+
+    void main() {
+        vec3 luma = vec3(0.299, 0.587, 0.114);
+        float luma_nw = dot(texture(u_Source, v_TexCoord + vec2(-1.0, -1.0) * u_TexelSize).rgb, luma);
+        float luma_ne = dot(texture(u_Source, v_TexCoord + vec2(1.0, -1.0) * u_TexelSize).rgb, luma);
+        float luma_sw = dot(texture(u_Source, v_TexCoord + vec2(-1.0, 1.0) * u_TexelSize).rgb, luma);
+        float luma_se = dot(texture(u_Source, v_TexCoord + vec2(1.0, 1.0) * u_TexelSize).rgb, luma);
+        vec4 center = texture(u_Source, v_TexCoord);
+        float luma_m = dot(center.rgb, luma);
+
+        float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+        float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+        // blur along the edge, perpendicular to the luma gradient
+        vec2 dir = vec2(
+            -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+            (luma_nw + luma_sw) - (luma_ne + luma_se)
+        );
+        float dir_reduce = max(
+            (luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL,
+            FXAA_REDUCE_MIN
+        );
+        float inverse_dir_min = 1.0 / (min(abs(dir.x), abs(dir.y)) + dir_reduce);
+        dir = clamp(dir * inverse_dir_min, vec2(-FXAA_SPAN_MAX), vec2(FXAA_SPAN_MAX)) * u_TexelSize;
+
+        vec3 rgb_a = 0.5 * (
+            texture(u_Source, v_TexCoord + dir * (1.0 / 3.0 - 0.5)).rgb +
+            texture(u_Source, v_TexCoord + dir * (2.0 / 3.0 - 0.5)).rgb
+        );
+        vec3 rgb_b = rgb_a * 0.5 + 0.25 * (
+            texture(u_Source, v_TexCoord + dir * -0.5).rgb +
+            texture(u_Source, v_TexCoord + dir * 0.5).rgb
+        );
+        float luma_b = dot(rgb_b, luma);
+
+        color = vec4((luma_b < luma_min || luma_b > luma_max) ? rgb_a : rgb_b, center.a);
+    }
+";
+
+// downsamples and keeps what is brighter than the threshold, with a soft knee
+static BLOOM_PREFILTER_SHADER: &'static str = "
+    uniform float u_Threshold;
+    uniform float u_Knee;
+
+    void main() {
+        vec2 o = u_TexelSize * 0.5;
+        vec3 c = 0.25 * (
+            texture(u_Source, v_TexCoord + vec2(-o.x, -o.y)).rgb +
+            texture(u_Source, v_TexCoord + vec2(o.x, -o.y)).rgb +
+            texture(u_Source, v_TexCoord + vec2(-o.x, o.y)).rgb +
+            texture(u_Source, v_TexCoord + vec2(o.x, o.y)).rgb
+        );
+
+        float brightness = max(c.r, max(c.g, c.b));
+        float soft = clamp(brightness - u_Threshold + u_Knee, 0.0, 2.0 * u_Knee);
+        soft = soft * soft / (4.0 * u_Knee + 0.00001);
+        float weight = max(soft, brightness - u_Threshold) / max(brightness, 0.00001);
+        color = vec4(c * weight, 1.0);
+    }
+";
+
+static BLOOM_DOWNSAMPLE_SHADER: &'static str = "
+    void main() {
+        vec2 o = u_TexelSize;
+        vec3 c = 0.25 * (
+            texture(u_Source, v_TexCoord + vec2(-o.x, -o.y)).rgb +
+            texture(u_Source, v_TexCoord + vec2(o.x, -o.y)).rgb +
+            texture(u_Source, v_TexCoord + vec2(-o.x, o.y)).rgb +
+            texture(u_Source, v_TexCoord + vec2(o.x, o.y)).rgb
+        );
+        color = vec4(c, 1.0);
+    }
+";
+
+// 3x3 tent filter, added onto the next larger level
+static BLOOM_UPSAMPLE_SHADER: &'static str = "
+    void main() {
+        vec2 o = u_TexelSize;
+        vec3 c = texture(u_Source, v_TexCoord).rgb * 4.0;
+        c += texture(u_Source, v_TexCoord + vec2(-o.x, 0.0)).rgb * 2.0;
+        c += texture(u_Source, v_TexCoord + vec2(o.x, 0.0)).rgb * 2.0;
+        c += texture(u_Source, v_TexCoord + vec2(0.0, -o.y)).rgb * 2.0;
+        c += texture(u_Source, v_TexCoord + vec2(0.0, o.y)).rgb * 2.0;
+        c += texture(u_Source, v_TexCoord + vec2(-o.x, -o.y)).rgb;
+        c += texture(u_Source, v_TexCoord + vec2(o.x, -o.y)).rgb;
+        c += texture(u_Source, v_TexCoord + vec2(-o.x, o.y)).rgb;
+        c += texture(u_Source, v_TexCoord + vec2(o.x, o.y)).rgb;
+        color = vec4(c / 16.0, 1.0);
+    }
+";
+
+static BLOOM_COMPOSITE_SHADER: &'static str = "
+    uniform sampler2D u_Bloom;
+    uniform float u_Intensity;
+
+    void main() {
+        vec4 source = texture(u_Source, v_TexCoord);
+        color = vec4(source.rgb + texture(u_Bloom, v_TexCoord).rgb * u_Intensity, source.a);
+    }
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToneMapOperator {
+    Reinhard,
+    Aces,
+    // only exposure, colors above 1 are clipped
+    Clamp,
+}
+
+pub enum PostEffect {
+    // HDR to LDR, colors stay linear
+    ToneMapping {
+        operator: ToneMapOperator,
+        exposure: f32,
+    },
+    GammaCorrection {
+        gamma: f32,
+    },
+    // run on HDR colors before tone mapping, `levels` is the number of
+    // half resolution blur steps
+    Bloom {
+        threshold: f32,
+        knee: f32,
+        intensity: f32,
+        levels: usize,
+    },
+    // run after gamma correction
+    Fxaa,
+    Vignette {
+        intensity: f32,
+        radius: f32,
+        softness: f32,
+    },
+    // a material whose shader comes from `post_process_shader`
+    Custom(Material),
+}
+
+pub struct PostProcessPass {
+    pub name: String,
+    pub effect: PostEffect,
+    pub enabled: bool,
+}
+
+impl PostProcessPass {
+    pub fn new(name: &str, effect: PostEffect) -> Self {
+        Self {
+            name: name.to_string(),
+            effect,
+            enabled: true,
+        }
+    }
+}
+
+// Compiles a custom pass. `fragment_source` is appended to a header that
+// declares `v_TexCoord`, `u_Source`, `u_TexelSize` and the `color` output,
+// so it only contains its own uniforms and `main`. Requires a current
+// OpenGL context.
+pub fn post_process_shader(fragment_source: &str) -> OpenGLShader {
+    let fragment_shader = format!("{}{}", PASS_HEADER, fragment_source);
+    OpenGLShader::new(FULLSCREEN_VERTEX_SHADER, &fragment_shader)
+}
+
+enum PassTarget<'a> {
+    Framebuffer(&'a Framebuffer),
+    Screen,
+}
+
+struct PostProcessShaders {
+    copy: OpenGLShader,
+    tone_mapping: OpenGLShader,
+    gamma: OpenGLShader,
+    vignette: OpenGLShader,
+    fxaa: OpenGLShader,
+    bloom_prefilter: OpenGLShader,
+    bloom_downsample: OpenGLShader,
+    bloom_upsample: OpenGLShader,
+    bloom_composite: OpenGLShader,
+}
+
+// The scene is drawn into an HDR target between `begin` and `end`, then the
+// enabled passes run in order, each reading the output of the previous one.
+// Lit materials should set `u_LinearOutput` so that tone mapping and gamma
+// are left to the passes:
+//
+//     let mut post = PostProcessStack::new(width, height, 1, vec![
+//         PostProcessPass::new("bloom", PostEffect::Bloom { .. }),
+//         PostProcessPass::new("tone mapping", PostEffect::ToneMapping { .. }),
+//         PostProcessPass::new("gamma", PostEffect::GammaCorrection { gamma: 2.2 }),
+//         PostProcessPass::new("fxaa", PostEffect::Fxaa),
+//     ]);
+pub struct PostProcessStack {
+    width: u32,
+    height: u32,
+    passes: Vec<PostProcessPass>,
+    scene_target: Framebuffer,
+    ping_pong: [Framebuffer; 2],
+    bloom_levels: Vec<Framebuffer>,
+    shaders: PostProcessShaders,
+    vertex_array: VertexArray,
+    start_time: Instant,
+}
+
+impl PostProcessStack {
+    // requires a current OpenGL context, `samples` applies to the scene target
+    pub fn new(width: u32, height: u32, samples: u32, passes: Vec<PostProcessPass>) -> Self {
+        let scene_target = Framebuffer::new(FramebufferSpec {
+            width,
+            height,
+            samples,
+            color_attachments: vec![FramebufferFormat::Rgba16F],
+            depth_attachment: Some(FramebufferFormat::Depth24Stencil8),
+            resize_with_window: false,
+        });
+
+        Self {
+            width,
+            height,
+            passes,
+            scene_target,
+            ping_pong: [create_target(width, height), create_target(width, height)],
+            bloom_levels: Vec::new(),
+            shaders: PostProcessShaders {
+                copy: post_process_shader(COPY_SHADER),
+                tone_mapping: post_process_shader(TONE_MAPPING_SHADER),
+                gamma: post_process_shader(GAMMA_SHADER),
+                vignette: post_process_shader(VIGNETTE_SHADER),
+                fxaa: post_process_shader(FXAA_SHADER),
+                bloom_prefilter: post_process_shader(BLOOM_PREFILTER_SHADER),
+                bloom_downsample: post_process_shader(BLOOM_DOWNSAMPLE_SHADER),
+                bloom_upsample: post_process_shader(BLOOM_UPSAMPLE_SHADER),
+                bloom_composite: post_process_shader(BLOOM_COMPOSITE_SHADER),
+            },
+            vertex_array: VertexArray::new(),
+            start_time: Instant::now(),
+        }
+    }
+
+    pub fn get_scene_target(&self) -> &Framebuffer {
+        &self.scene_target
+    }
+
+    pub fn get_passes(&self) -> &[PostProcessPass] {
+        &self.passes
+    }
+
+    // replaces the chain, passes run in the given order
+    pub fn set_passes(&mut self, passes: Vec<PostProcessPass>) {
+        self.passes = passes;
+    }
+
+    pub fn get_pass_mut(&mut self, name: &str) -> Option<&mut PostProcessPass> {
+        self.passes.iter_mut().find(|pass| pass.name == name)
+    }
+
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        match self.get_pass_mut(name) {
+            Some(pass) => pass.enabled = enabled,
+            None => {
+                log_warn!("No post-process pass named {:?}", name);
+            }
+        }
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.passes
+            .iter()
+            .any(|pass| pass.name == name && pass.enabled)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width == self.width && height == self.height) {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.scene_target.resize(width, height);
+        for target in self.ping_pong.iter_mut() {
+            target.resize(width, height);
+        }
+        self.bloom_levels.clear();
+    }
+
+    // binds the scene target, draw the scene afterwards
+    pub fn begin(&self) {
+        self.scene_target.bind();
+    }
+
+    // Runs the enabled passes and writes the result to `output`, or to the
+    // default framebuffer when `None`.
+    pub fn end(&mut self, output: Option<&Framebuffer>) {
+        self.scene_target.unbind();
+        self.scene_target.resolve();
+
+        let bloom_levels = self
+            .passes
+            .iter()
+            .filter(|pass| pass.enabled)
+            .filter_map(|pass| match pass.effect {
+                PostEffect::Bloom { levels, .. } => Some(levels),
+                _ => None,
+            })
+            .max();
+        if let Some(levels) = bloom_levels {
+            self.create_bloom_levels(levels);
+        }
+
+        let depth_test = state::is_enabled(gl::DEPTH_TEST);
+        let blend = state::is_enabled(gl::BLEND);
+        let cull_face = state::is_enabled(gl::CULL_FACE);
+        state::disable(gl::DEPTH_TEST);
+        state::disable(gl::BLEND);
+        state::disable(gl::CULL_FACE);
+        self.vertex_array.bind();
+
+        let enabled: Vec<&PostProcessPass> = self.passes.iter().filter(|p| p.enabled).collect();
+        let final_target = || match output {
+            Some(framebuffer) => PassTarget::Framebuffer(framebuffer),
+            None => PassTarget::Screen,
+        };
+
+        let mut source = self.scene_target.get_color_attachment(0);
+        if enabled.is_empty() {
+            self.begin_pass(&self.shaders.copy, source, &final_target());
+            self.draw_fullscreen();
+        }
+
+        for (i, pass) in enabled.iter().enumerate() {
+            let last = i + 1 == enabled.len();
+            let target = if last {
+                final_target()
+            } else {
+                PassTarget::Framebuffer(&self.ping_pong[i % 2])
+            };
+
+            self.run_pass(&pass.effect, source, &target);
+            if !last {
+                source = self.ping_pong[i % 2].get_color_attachment(0);
+            }
+        }
+
+        self.vertex_array.unbind();
+        state::use_program(0);
+        state::set_enabled(gl::DEPTH_TEST, depth_test);
+        state::set_enabled(gl::BLEND, blend);
+        state::set_enabled(gl::CULL_FACE, cull_face);
+    }
+
+    fn run_pass(&self, effect: &PostEffect, source: &FramebufferAttachment, target: &PassTarget) {
+        match effect {
+            PostEffect::ToneMapping { operator, exposure } => {
+                let shader = &self.shaders.tone_mapping;
+                self.begin_pass(shader, source, target);
+                shader.set_float("u_Exposure", *exposure);
+                shader.set_int(
+                    "u_Operator",
+                    match operator {
+                        ToneMapOperator::Reinhard => 0,
+                        ToneMapOperator::Aces => 1,
+                        ToneMapOperator::Clamp => 2,
+                    },
+                );
+            }
+            PostEffect::GammaCorrection { gamma } => {
+                let shader = &self.shaders.gamma;
+                self.begin_pass(shader, source, target);
+                shader.set_float("u_Gamma", *gamma);
+            }
+            PostEffect::Bloom {
+                threshold,
+                knee,
+                intensity,
+                levels,
+            } => {
+                self.render_bloom(source, *threshold, *knee, *levels);
+
+                let shader = &self.shaders.bloom_composite;
+                self.begin_pass(shader, source, target);
+                self.bloom_levels[0]
+                    .get_color_attachment(0)
+                    .bind(BLOOM_SLOT);
+                shader.set_int("u_Bloom", BLOOM_SLOT as i32);
+                shader.set_float("u_Intensity", *intensity);
+            }
+            PostEffect::Fxaa => {
+                self.begin_pass(&self.shaders.fxaa, source, target);
+            }
+            PostEffect::Vignette {
+                intensity,
+                radius,
+                softness,
+            } => {
+                let shader = &self.shaders.vignette;
+                self.begin_pass(shader, source, target);
+                shader.set_float("u_Intensity", *intensity);
+                shader.set_float("u_Radius", *radius);
+                shader.set_float("u_Softness", *softness);
+            }
+            PostEffect::Custom(material) => {
+                let scene = SceneUniforms {
+                    time: self.start_time.elapsed().as_secs_f32(),
+                    ..SceneUniforms::default()
+                };
+                bind_target(target, self.width, self.height);
+                material.bind(&scene, &Mat4::IDENTITY);
+                bind_source(material.get_shader(), source);
+            }
+        }
+
+        self.draw_fullscreen();
+    }
+
+    // leaves the blurred result in the first bloom level
+    fn render_bloom(
+        &self,
+        source: &FramebufferAttachment,
+        threshold: f32,
+        knee: f32,
+        levels: usize,
+    ) {
+        let levels = levels.max(1).min(self.bloom_levels.len());
+
+        let shader = &self.shaders.bloom_prefilter;
+        let first = PassTarget::Framebuffer(&self.bloom_levels[0]);
+        self.begin_pass(shader, source, &first);
+        shader.set_float("u_Threshold", threshold);
+        shader.set_float("u_Knee", knee.max(0.0001));
+        self.draw_fullscreen();
+
+        for i in 1..levels {
+            let target = PassTarget::Framebuffer(&self.bloom_levels[i]);
+            let source = self.bloom_levels[i - 1].get_color_attachment(0);
+            self.begin_pass(&self.shaders.bloom_downsample, source, &target);
+            self.draw_fullscreen();
+        }
+
+        state::enable(gl::BLEND);
+        state::set_blend_func(gl::ONE, gl::ONE);
+        for i in (0..levels - 1).rev() {
+            let target = PassTarget::Framebuffer(&self.bloom_levels[i]);
+            let source = self.bloom_levels[i + 1].get_color_attachment(0);
+            self.begin_pass(&self.shaders.bloom_upsample, source, &target);
+            self.draw_fullscreen();
+        }
+        state::disable(gl::BLEND);
+    }
+
+    // halves the size per level down to `MIN_BLOOM_SIZE`, at least one level
+    fn create_bloom_levels(&mut self, levels: usize) {
+        let mut sizes = Vec::new();
+        let (mut width, mut height) = ((self.width / 2).max(1), (self.height / 2).max(1));
+        while sizes.is_empty() || (sizes.len() < levels && width.min(height) >= MIN_BLOOM_SIZE) {
+            sizes.push((width, height));
+            width /= 2;
+            height /= 2;
+        }
+
+        if self.bloom_levels.len() == sizes.len() {
+            return;
+        }
+        self.bloom_levels = sizes
+            .into_iter()
+            .map(|(width, height)| create_target(width, height))
+            .collect();
+    }
+
+    fn begin_pass(
+        &self,
+        shader: &OpenGLShader,
+        source: &FramebufferAttachment,
+        target: &PassTarget,
+    ) {
+        bind_target(target, self.width, self.height);
+        shader.bind();
+        bind_source(shader, source);
+    }
+
+    fn draw_fullscreen(&self) {
+        unsafe {
+            gl::DrawArrays(gl::TRIANGLES, 0, 3);
+        }
+        profiler::record_draw(gl::TRIANGLES, 3, 1);
+    }
+}
+
+impl EventHandler for PostProcessStack {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        for e in events.iter() {
+            if let Event::FramebufferResize { width, height } = e {
+                self.resize(*width, *height);
+            }
+        }
+    }
+}
+
+fn create_target(width: u32, height: u32) -> Framebuffer {
+    Framebuffer::new(FramebufferSpec {
+        width,
+        height,
+        samples: 1,
+        color_attachments: vec![FramebufferFormat::Rgba16F],
+        depth_attachment: None,
+        resize_with_window: false,
+    })
+}
+
+fn bind_target(target: &PassTarget, width: u32, height: u32) {
+    match target {
+        PassTarget::Framebuffer(framebuffer) => framebuffer.bind(),
+        PassTarget::Screen => unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            state::set_viewport(0, 0, width as i32, height as i32);
+        },
+    }
+}
+
+// expects `shader` to be bound
+fn bind_source(shader: &OpenGLShader, source: &FramebufferAttachment) {
+    source.bind(SOURCE_SLOT);
+    shader.set_int("u_Source", SOURCE_SLOT as i32);
+    shader.set_float2(
+        "u_TexelSize",
+        Vec2::new(
+            1.0 / source.get_width() as f32,
+            1.0 / source.get_height() as f32,
+        ),
+    );
+}
@@ -0,0 +1,337 @@
+use std::{cell::RefCell, collections::VecDeque, fmt::Write, time::Instant};
+
+use gl::types::GLenum;
+
+use crate::{
+    log_warn,
+    math::{Vec2, Vec4},
+    renderer::{debug_draw, state},
+};
+
+// Frames a timer result may take to arrive before it is dropped, drivers
+// usually deliver them within two or three.
+const MAX_PENDING_FRAMES: usize = 4;
+
+// Counters are recorded by the renderer while it draws and uploads, timings
+// come from timestamp queries around named scopes and are read back a few
+// frames later, so that the CPU never waits for the GPU. Frames are delimited
+// by `begin_frame` and `end_frame`.
+thread_local! {
+    static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::default());
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FrameStats {
+    // seconds between `begin_frame` and `end_frame`
+    pub cpu_time: f32,
+    // seconds the GPU spent on the frame, from a few frames earlier
+    pub gpu_time: f32,
+    pub draw_calls: u32,
+    pub triangles: u32,
+    pub vertices: u32,
+    // program, vertex array and buffer binds and fixed function state issued
+    // to the driver, see `state`
+    pub state_changes: u32,
+    pub state_changes_skipped: u32,
+    pub texture_binds: u32,
+    pub upload_bytes: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct GpuTiming {
+    pub name: String,
+    // nesting level, scopes opened inside another one are one deeper
+    pub depth: u32,
+    // seconds
+    pub time: f32,
+}
+
+struct TimerScope {
+    name: String,
+    depth: u32,
+    start_query: u32,
+    end_query: Option<u32>,
+}
+
+#[derive(Default)]
+struct Profiler {
+    frame_start: Option<Instant>,
+    current: FrameStats,
+    last_frame: FrameStats,
+
+    timing_requested: bool,
+    timing_enabled: bool,
+    free_queries: Vec<u32>,
+    // scopes of the current frame, the first one spans the whole frame
+    scopes: Vec<TimerScope>,
+    open_scopes: Vec<usize>,
+    pending_frames: VecDeque<Vec<TimerScope>>,
+    timings: Vec<GpuTiming>,
+}
+
+impl Profiler {
+    fn begin_scope(&mut self, name: &str) {
+        if !self.timing_enabled {
+            return;
+        }
+
+        let query = self.allocate_query();
+        unsafe {
+            gl::QueryCounter(query, gl::TIMESTAMP);
+        }
+        self.open_scopes.push(self.scopes.len());
+        self.scopes.push(TimerScope {
+            name: name.to_string(),
+            depth: self.open_scopes.len() as u32 - 1,
+            start_query: query,
+            end_query: None,
+        });
+    }
+
+    fn end_scope(&mut self) {
+        if !self.timing_enabled {
+            return;
+        }
+
+        let index = match self.open_scopes.pop() {
+            Some(index) => index,
+            None => {
+                log_warn!("GPU timer scope ended without being begun");
+                return;
+            }
+        };
+        let query = self.allocate_query();
+        unsafe {
+            gl::QueryCounter(query, gl::TIMESTAMP);
+        }
+        self.scopes[index].end_query = Some(query);
+    }
+
+    fn allocate_query(&mut self) -> u32 {
+        self.free_queries.pop().unwrap_or_else(|| {
+            let mut query = 0;
+            unsafe {
+                gl::GenQueries(1, &mut query);
+            }
+            query
+        })
+    }
+
+    fn release_scopes(&mut self, scopes: Vec<TimerScope>) {
+        for scope in scopes {
+            self.free_queries.push(scope.start_query);
+            self.free_queries.extend(scope.end_query);
+        }
+    }
+
+    // reads back the oldest frames whose queries completed, timestamps finish
+    // in order and the frame scope ends last, so checking its end suffices
+    fn collect_timings(&mut self) {
+        while let Some(scopes) = self.pending_frames.front() {
+            let is_available = match scopes.first().and_then(|s| s.end_query) {
+                Some(query) => {
+                    let mut available = 0;
+                    unsafe {
+                        gl::GetQueryObjectiv(query, gl::QUERY_RESULT_AVAILABLE, &mut available);
+                    }
+                    available != 0
+                }
+                None => true,
+            };
+
+            if !is_available {
+                if self.pending_frames.len() > MAX_PENDING_FRAMES {
+                    let scopes = self.pending_frames.pop_front().unwrap();
+                    self.release_scopes(scopes);
+                    continue;
+                }
+                break;
+            }
+
+            let scopes = self.pending_frames.pop_front().unwrap();
+            self.timings = scopes
+                .iter()
+                .filter_map(|scope| {
+                    let end_query = scope.end_query?;
+                    let (mut start, mut end) = (0, 0);
+                    unsafe {
+                        gl::GetQueryObjectui64v(scope.start_query, gl::QUERY_RESULT, &mut start);
+                        gl::GetQueryObjectui64v(end_query, gl::QUERY_RESULT, &mut end);
+                    }
+                    Some(GpuTiming {
+                        name: scope.name.clone(),
+                        depth: scope.depth,
+                        time: end.saturating_sub(start) as f32 * 1e-9,
+                    })
+                })
+                .collect();
+            self.release_scopes(scopes);
+        }
+    }
+}
+
+fn with_profiler<R, F: FnOnce(&mut Profiler) -> R>(f: F) -> R {
+    PROFILER.with(|profiler| f(&mut profiler.borrow_mut()))
+}
+
+// Timestamp queries are core in OpenGL 3.3 and `ARB_timer_query`.
+pub fn supports_gpu_timing() -> bool {
+    gl::QueryCounter::is_loaded() && gl::GetQueryObjectui64v::is_loaded()
+}
+
+// Off by default, takes effect with the next frame.
+pub fn set_gpu_timing_enabled(enabled: bool) {
+    with_profiler(|profiler| profiler.timing_requested = enabled && supports_gpu_timing());
+}
+
+pub fn is_gpu_timing_enabled() -> bool {
+    with_profiler(|profiler| profiler.timing_requested)
+}
+
+pub fn begin_frame() {
+    with_profiler(|profiler| {
+        profiler.frame_start = Some(Instant::now());
+        profiler.current = FrameStats::default();
+        profiler.collect_timings();
+
+        profiler.timing_enabled = profiler.timing_requested;
+        if !profiler.timing_enabled {
+            profiler.timings.clear();
+        }
+        profiler.begin_scope("Frame");
+    });
+    state::reset_stats();
+}
+
+// Finishes the counters of the frame, call it before swapping buffers.
+pub fn end_frame() {
+    let state_stats = state::get_stats();
+    with_profiler(|profiler| {
+        if profiler.open_scopes.len() > 1 {
+            log_warn!("GPU timer scopes left open at the end of the frame");
+        }
+        while !profiler.open_scopes.is_empty() {
+            profiler.end_scope();
+        }
+        if !profiler.scopes.is_empty() {
+            let scopes = std::mem::take(&mut profiler.scopes);
+            profiler.pending_frames.push_back(scopes);
+        }
+
+        let mut stats = profiler.current;
+        if let Some(start) = profiler.frame_start.take() {
+            stats.cpu_time = start.elapsed().as_secs_f32();
+        }
+        stats.gpu_time = profiler
+            .timings
+            .first()
+            .filter(|timing| timing.depth == 0)
+            .map_or(0.0, |timing| timing.time);
+        stats.state_changes = state_stats.program_binds
+            + state_stats.vertex_array_binds
+            + state_stats.buffer_binds
+            + state_stats.state_changes;
+        stats.state_changes_skipped = state_stats.program_binds_skipped
+            + state_stats.vertex_array_binds_skipped
+            + state_stats.buffer_binds_skipped
+            + state_stats.state_changes_skipped;
+        stats.texture_binds = state_stats.texture_binds;
+        profiler.last_frame = stats;
+    });
+}
+
+// counters of the last finished frame
+pub fn get_frame_stats() -> FrameStats {
+    with_profiler(|profiler| profiler.last_frame)
+}
+
+// the latest timings read back, in the order the scopes were begun
+pub fn get_gpu_timings() -> Vec<GpuTiming> {
+    with_profiler(|profiler| profiler.timings.clone())
+}
+
+// called after each draw call, `vertex_count` per instance
+pub fn record_draw(mode: GLenum, vertex_count: u32, instance_count: u32) {
+    let triangles = match mode {
+        gl::TRIANGLES => vertex_count / 3,
+        gl::TRIANGLE_STRIP | gl::TRIANGLE_FAN => vertex_count.saturating_sub(2),
+        _ => 0,
+    };
+    with_profiler(|profiler| {
+        profiler.current.draw_calls += 1;
+        profiler.current.vertices += vertex_count * instance_count;
+        profiler.current.triangles += triangles * instance_count;
+    });
+}
+
+pub fn record_upload(bytes: usize) {
+    with_profiler(|profiler| profiler.current.upload_bytes += bytes);
+}
+
+pub fn begin_gpu_scope(name: &str) {
+    with_profiler(|profiler| profiler.begin_scope(name));
+}
+
+pub fn end_gpu_scope() {
+    with_profiler(|profiler| profiler.end_scope());
+}
+
+// Times the GPU work submitted while alive, e.g. one scope per render pass.
+// Scopes nest but must not overlap.
+pub struct GpuScope;
+
+impl GpuScope {
+    pub fn new(name: &str) -> Self {
+        begin_gpu_scope(name);
+        Self
+    }
+}
+
+impl Drop for GpuScope {
+    fn drop(&mut self) {
+        end_gpu_scope();
+    }
+}
+
+// Queues the stats of the last frame and the GPU timings as debug text at
+// `position` in pixels from the top left corner, shown by the
+// `DebugRenderer`. Call it once per frame.
+pub fn draw_overlay(position: Vec2, size: f32) {
+    let stats = get_frame_stats();
+    let mut text = String::new();
+    let _ = writeln!(
+        text,
+        "CPU {:.2} ms  GPU {:.2} ms",
+        stats.cpu_time * 1000.0,
+        stats.gpu_time * 1000.0
+    );
+    let _ = writeln!(
+        text,
+        "Draws {}  Tris {}  Verts {}",
+        stats.draw_calls, stats.triangles, stats.vertices
+    );
+    let _ = writeln!(
+        text,
+        "State {} (-{})  Tex {}  Upload {} KB",
+        stats.state_changes,
+        stats.state_changes_skipped,
+        stats.texture_binds,
+        stats.upload_bytes / 1024
+    );
+    for timing in get_gpu_timings().iter().skip(1) {
+        let _ = writeln!(
+            text,
+            "{}{} {:.2} ms",
+            "  ".repeat(timing.depth as usize),
+            timing.name,
+            timing.time * 1000.0
+        );
+    }
+
+    debug_draw::screen_text(
+        position,
+        text.trim_end(),
+        size,
+        Vec4::new(1.0, 1.0, 0.4, 1.0),
+    );
+}
@@ -0,0 +1,1137 @@
+use std::{collections::BTreeSet, fmt::Write as _, mem, path::Path, rc::Rc};
+
+use crate::{
+    events::{Event, EventHandler},
+    log_trace,
+    renderer::{
+        buffer::UniformBuffer,
+        debug::{set_object_label, DebugGroup},
+        framebuffer::{self, FramebufferAttachment, FramebufferFormat},
+        profiler::GpuScope,
+        state,
+        texture::Texture,
+    },
+};
+
+const MAX_COLOR_ATTACHMENTS: usize = 8;
+
+// Handle to a texture or buffer of one graph, only valid until `clear`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ResourceId(usize);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextureSize {
+    // scale of the backbuffer size, follows resizes
+    Relative(f32),
+    Fixed(u32, u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDesc {
+    pub size: TextureSize,
+    pub format: FramebufferFormat,
+}
+
+impl TextureDesc {
+    // backbuffer sized
+    pub fn new(format: FramebufferFormat) -> Self {
+        Self {
+            size: TextureSize::Relative(1.0),
+            format,
+        }
+    }
+
+    pub fn with_size(mut self, size: TextureSize) -> Self {
+        self.size = size;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferDesc {
+    pub size: usize,
+    pub binding: u32,
+}
+
+enum ResourceKind {
+    Texture(TextureDesc),
+    Buffer(BufferDesc),
+    ImportedTexture(Rc<dyn Texture>),
+    ImportedBuffer(Rc<UniformBuffer>),
+    // the default framebuffer, can only be written
+    Backbuffer,
+}
+
+struct ResourceNode {
+    name: String,
+    kind: ResourceKind,
+}
+
+impl ResourceNode {
+    // owned by the graph, allocated only for the passes using it
+    fn is_transient(&self) -> bool {
+        matches!(
+            self.kind,
+            ResourceKind::Texture(_) | ResourceKind::Buffer(_)
+        )
+    }
+}
+
+struct PassNode {
+    name: String,
+    reads: Vec<ResourceId>,
+    writes: Vec<ResourceId>,
+    clear_color: Option<[f32; 4]>,
+    clear_depth: Option<f32>,
+    side_effects: bool,
+}
+
+impl PassNode {
+    fn uses(&self, resource: ResourceId) -> bool {
+        self.reads.contains(&resource) || self.writes.contains(&resource)
+    }
+}
+
+type PassCallback = Box<dyn FnMut(&PassContext)>;
+
+// A pass declares what it reads and writes, the graph derives the order from
+// that: every writer of a resource runs before the passes only reading it,
+// writers of the same resource keep the order they were added in.
+pub struct GraphPass {
+    node: PassNode,
+    execute: PassCallback,
+}
+
+impl GraphPass {
+    pub fn new<F: FnMut(&PassContext) + 'static>(name: &str, execute: F) -> Self {
+        Self {
+            node: PassNode {
+                name: name.to_string(),
+                reads: Vec::new(),
+                writes: Vec::new(),
+                clear_color: None,
+                clear_depth: None,
+                side_effects: false,
+            },
+            execute: Box::new(execute),
+        }
+    }
+
+    pub fn with_read(mut self, resource: ResourceId) -> Self {
+        if !self.node.reads.contains(&resource) {
+            self.node.reads.push(resource);
+        }
+        self
+    }
+
+    // Written textures become the render target of the pass, color ones in
+    // the order they are declared here. All of them need the same size.
+    pub fn with_write(mut self, resource: ResourceId) -> Self {
+        if !self.node.writes.contains(&resource) {
+            self.node.writes.push(resource);
+        }
+        self
+    }
+
+    // Transient textures may share memory with earlier ones, so their first
+    // writer should clear them or overwrite every pixel.
+    pub fn with_clear_color(mut self, color: [f32; 4]) -> Self {
+        self.node.clear_color = Some(color);
+        self
+    }
+
+    pub fn with_clear_depth(mut self, depth: f32) -> Self {
+        self.node.clear_depth = Some(depth);
+        self
+    }
+
+    // never culled, for passes with results outside of the graph, e.g. readbacks
+    pub fn with_side_effects(mut self) -> Self {
+        self.node.side_effects = true;
+        self
+    }
+}
+
+// Resources of the running pass. Only the ones the pass declared are
+// accessible.
+pub struct PassContext<'a> {
+    pass: &'a PassNode,
+    resources: &'a [ResourceNode],
+    slots: &'a [Option<usize>],
+    textures: &'a [FramebufferAttachment],
+    buffers: &'a [UniformBuffer],
+    width: u32,
+    height: u32,
+}
+
+impl<'a> PassContext<'a> {
+    pub fn get_texture(&self, resource: ResourceId) -> &dyn Texture {
+        self.check_declared(resource);
+        match &self.resources[resource.0].kind {
+            ResourceKind::Texture(_) => &self.textures[self.slots[resource.0].unwrap()],
+            ResourceKind::ImportedTexture(texture) => texture.as_ref(),
+            _ => panic!("{:?} is not a texture.", self.resources[resource.0].name),
+        }
+    }
+
+    pub fn get_buffer(&self, resource: ResourceId) -> &UniformBuffer {
+        self.check_declared(resource);
+        match &self.resources[resource.0].kind {
+            ResourceKind::Buffer(_) => &self.buffers[self.slots[resource.0].unwrap()],
+            ResourceKind::ImportedBuffer(buffer) => buffer,
+            _ => panic!("{:?} is not a buffer.", self.resources[resource.0].name),
+        }
+    }
+
+    // size of the render target, the backbuffer size for passes without one
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    fn check_declared(&self, resource: ResourceId) {
+        assert!(
+            self.pass.uses(resource),
+            "Pass {:?} did not declare {:?}.",
+            self.pass.name,
+            self.resources[resource.0].name
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct TextureKey {
+    width: u32,
+    height: u32,
+    format: FramebufferFormat,
+}
+
+enum PassTarget {
+    // leaves the current framebuffer bound
+    None,
+    Backbuffer,
+    Textures {
+        colors: Vec<ResourceId>,
+        depth: Option<ResourceId>,
+        width: u32,
+        height: u32,
+    },
+}
+
+// Result of the dependency analysis, independent of any GL objects.
+struct Schedule {
+    // indices of the passes that run, in execution order
+    order: Vec<usize>,
+    targets: Vec<PassTarget>,
+    // physical resource of every transient resource used by a running pass,
+    // resources with disjoint lifetimes and matching descriptions share one
+    slots: Vec<Option<usize>>,
+    texture_slots: Vec<TextureKey>,
+    buffer_slots: Vec<BufferDesc>,
+}
+
+struct CompiledGraph {
+    schedule: Schedule,
+    textures: Vec<FramebufferAttachment>,
+    buffers: Vec<UniformBuffer>,
+    // one per scheduled pass rendering into textures
+    framebuffers: Vec<Option<u32>>,
+}
+
+impl Drop for CompiledGraph {
+    fn drop(&mut self) {
+        for id in self.framebuffers.iter().flatten() {
+            unsafe {
+                gl::DeleteFramebuffers(1, id);
+            }
+        }
+    }
+}
+
+// Orders the frame's passes from the resources they declare, culls the ones
+// nothing visible depends on and allocates transient render targets, reusing
+// memory between resources that are never alive at the same time:
+//
+//     let mut graph = RenderGraph::new(width, height);
+//     let color = graph.create_texture("scene color", TextureDesc::new(FramebufferFormat::Rgba16F));
+//     let backbuffer = graph.get_backbuffer();
+//     graph.add_pass(GraphPass::new("scene", draw_scene).with_write(color).with_clear_color(..));
+//     graph.add_pass(GraphPass::new("present", present).with_read(color).with_write(backbuffer));
+//     graph.execute();
+//
+// The graph compiles on first use and again after it changed, so a graph
+// built once can be executed every frame.
+pub struct RenderGraph {
+    width: u32,
+    height: u32,
+    resources: Vec<ResourceNode>,
+    passes: Vec<PassNode>,
+    callbacks: Vec<PassCallback>,
+    // kept across recompiles so that textures and buffers can be reused
+    compiled: Option<CompiledGraph>,
+    is_dirty: bool,
+}
+
+impl RenderGraph {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            resources: vec![ResourceNode {
+                name: "backbuffer".to_string(),
+                kind: ResourceKind::Backbuffer,
+            }],
+            passes: Vec::new(),
+            callbacks: Vec::new(),
+            compiled: None,
+            is_dirty: true,
+        }
+    }
+
+    pub fn get_backbuffer(&self) -> ResourceId {
+        ResourceId(0)
+    }
+
+    pub fn create_texture(&mut self, name: &str, desc: TextureDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::Texture(desc))
+    }
+
+    pub fn create_buffer(&mut self, name: &str, desc: BufferDesc) -> ResourceId {
+        self.add_resource(name, ResourceKind::Buffer(desc))
+    }
+
+    // imported textures can only be read
+    pub fn import_texture(&mut self, name: &str, texture: Rc<dyn Texture>) -> ResourceId {
+        self.add_resource(name, ResourceKind::ImportedTexture(texture))
+    }
+
+    pub fn import_buffer(&mut self, name: &str, buffer: Rc<UniformBuffer>) -> ResourceId {
+        self.add_resource(name, ResourceKind::ImportedBuffer(buffer))
+    }
+
+    pub fn add_pass(&mut self, pass: GraphPass) {
+        self.passes.push(pass.node);
+        self.callbacks.push(pass.execute);
+        self.is_dirty = true;
+    }
+
+    // removes all passes and resources, the allocated memory is kept for reuse
+    pub fn clear(&mut self) {
+        self.passes.clear();
+        self.callbacks.clear();
+        self.resources.truncate(1);
+        self.is_dirty = true;
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 || (width == self.width && height == self.height) {
+            return;
+        }
+
+        self.width = width;
+        self.height = height;
+        self.is_dirty = true;
+    }
+
+    // names of the passes that run, in order
+    pub fn get_pass_order(&self) -> Result<Vec<&str>, String> {
+        let schedule = self.analyze()?;
+        Ok(schedule
+            .order
+            .iter()
+            .map(|&index| self.passes[index].name.as_str())
+            .collect())
+    }
+
+    // Requires a current OpenGL context. Panics on cycles and on resources
+    // that are read before anything writes them.
+    pub fn compile(&mut self) {
+        let schedule = self.analyze().unwrap_or_else(|e| panic!("{}", e));
+
+        let (mut old_textures, mut old_buffers) = match &mut self.compiled {
+            Some(compiled) => (
+                mem::take(&mut compiled.textures),
+                mem::take(&mut compiled.buffers),
+            ),
+            None => (Vec::new(), Vec::new()),
+        };
+        // deletes the old framebuffers
+        self.compiled = None;
+
+        let textures: Vec<FramebufferAttachment> = schedule
+            .texture_slots
+            .iter()
+            .map(|key| {
+                let reusable = old_textures.iter().position(|texture| {
+                    texture.get_width() == key.width
+                        && texture.get_height() == key.height
+                        && texture.get_format() == key.format
+                });
+                match reusable {
+                    Some(i) => old_textures.swap_remove(i),
+                    None => FramebufferAttachment::new(key.width, key.height, key.format),
+                }
+            })
+            .collect();
+
+        // named after the resources aliasing them
+        for (slot, texture) in textures.iter().enumerate() {
+            let names: Vec<&str> = self
+                .resources
+                .iter()
+                .zip(schedule.slots.iter())
+                .filter(|(resource, resource_slot)| {
+                    matches!(resource.kind, ResourceKind::Texture(_))
+                        && **resource_slot == Some(slot)
+                })
+                .map(|(resource, _)| resource.name.as_str())
+                .collect();
+            texture.set_label(&names.join(", "));
+        }
+
+        let buffers: Vec<UniformBuffer> = schedule
+            .buffer_slots
+            .iter()
+            .map(|desc| {
+                let reusable = old_buffers.iter().position(|buffer| {
+                    buffer.get_size() == desc.size && buffer.get_binding() == desc.binding
+                });
+                match reusable {
+                    Some(i) => old_buffers.swap_remove(i),
+                    None => UniformBuffer::new(desc.size, desc.binding),
+                }
+            })
+            .collect();
+
+        let framebuffers = schedule
+            .order
+            .iter()
+            .zip(schedule.targets.iter())
+            .map(|(&index, target)| match target {
+                PassTarget::Textures { colors, depth, .. } => {
+                    let attachment =
+                        |resource: &ResourceId| &textures[schedule.slots[resource.0].unwrap()];
+                    let colors: Vec<_> = colors.iter().map(attachment).collect();
+                    let depth = depth.as_ref().map(attachment);
+                    Some(create_framebuffer(&self.passes[index].name, &colors, depth))
+                }
+                _ => None,
+            })
+            .collect();
+
+        log_trace!(
+            "Compiled render graph: {} of {} passes, {} textures, {} buffers",
+            schedule.order.len(),
+            self.passes.len(),
+            textures.len(),
+            buffers.len()
+        );
+
+        self.compiled = Some(CompiledGraph {
+            schedule,
+            textures,
+            buffers,
+            framebuffers,
+        });
+        self.is_dirty = false;
+    }
+
+    // runs the passes, compiling first if the graph changed
+    pub fn execute(&mut self) {
+        if self.is_dirty || self.compiled.is_none() {
+            self.compile();
+        }
+        let compiled = self.compiled.as_ref().unwrap();
+        let schedule = &compiled.schedule;
+
+        for (position, &index) in schedule.order.iter().enumerate() {
+            let pass = &self.passes[index];
+
+            let (width, height) = match &schedule.targets[position] {
+                PassTarget::None => (self.width, self.height),
+                PassTarget::Backbuffer => {
+                    unsafe {
+                        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+                        state::set_viewport(0, 0, self.width as i32, self.height as i32);
+                        clear_backbuffer(pass.clear_color, pass.clear_depth);
+                    }
+                    (self.width, self.height)
+                }
+                PassTarget::Textures {
+                    colors,
+                    depth,
+                    width,
+                    height,
+                } => {
+                    let format = |resource: &ResourceId| {
+                        compiled.textures[schedule.slots[resource.0].unwrap()].get_format()
+                    };
+                    unsafe {
+                        gl::BindFramebuffer(
+                            gl::FRAMEBUFFER,
+                            compiled.framebuffers[position].unwrap(),
+                        );
+                        state::set_viewport(0, 0, *width as i32, *height as i32);
+                        if let Some(color) = pass.clear_color {
+                            for (i, resource) in colors.iter().enumerate() {
+                                clear_color_attachment(i, format(resource), color);
+                            }
+                        }
+                        if let (Some(value), Some(resource)) = (pass.clear_depth, depth) {
+                            clear_depth_attachment(format(resource), value);
+                        }
+                    }
+                    (*width, *height)
+                }
+            };
+
+            // aliased buffers share binding points, attach the ones of this pass
+            for resource in pass.reads.iter().chain(pass.writes.iter()) {
+                match &self.resources[resource.0].kind {
+                    ResourceKind::Buffer(_) => {
+                        compiled.buffers[schedule.slots[resource.0].unwrap()].bind()
+                    }
+                    ResourceKind::ImportedBuffer(buffer) => buffer.bind(),
+                    _ => (),
+                }
+            }
+
+            let context = PassContext {
+                pass,
+                resources: &self.resources,
+                slots: &schedule.slots,
+                textures: &compiled.textures,
+                buffers: &compiled.buffers,
+                width,
+                height,
+            };
+            let _group = DebugGroup::new(&pass.name);
+            let _timer = GpuScope::new(&pass.name);
+            (self.callbacks[index])(&context);
+        }
+
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        }
+    }
+
+    // Graphviz description of the passes and resources. Culled passes are
+    // dashed, transient textures list the physical texture they were given.
+    pub fn to_dot(&self) -> String {
+        let schedule = self.analyze().ok();
+        let mut dot = String::from("digraph RenderGraph {\n    rankdir=LR;\n");
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            let position = schedule
+                .as_ref()
+                .and_then(|s| s.order.iter().position(|&i| i == index));
+            let (label, style) = match position {
+                Some(position) => (format!("{}: {}", position, pass.name), "solid"),
+                None => (format!("{}\\n(culled)", pass.name), "dashed"),
+            };
+            let _ = writeln!(
+                dot,
+                "    p{} [shape=box, style={}, label=\"{}\"];",
+                index,
+                style,
+                escape(&label)
+            );
+        }
+
+        for (index, resource) in self.resources.iter().enumerate() {
+            let slot = schedule.as_ref().and_then(|s| s.slots[index]);
+            let (details, shape) = match &resource.kind {
+                ResourceKind::Texture(desc) => {
+                    let (width, height) = self.resolve_size(desc.size);
+                    let mut details = format!("{:?} {}x{}", desc.format, width, height);
+                    if let Some(slot) = slot {
+                        let _ = write!(details, "\\ntexture {}", slot);
+                    }
+                    (details, "ellipse")
+                }
+                ResourceKind::Buffer(desc) => {
+                    let mut details = format!("{} bytes, binding {}", desc.size, desc.binding);
+                    if let Some(slot) = slot {
+                        let _ = write!(details, "\\nbuffer {}", slot);
+                    }
+                    (details, "ellipse")
+                }
+                ResourceKind::ImportedTexture(texture) => (
+                    format!("imported {}x{}", texture.get_width(), texture.get_height()),
+                    "doubleoctagon",
+                ),
+                ResourceKind::ImportedBuffer(buffer) => (
+                    format!("imported, binding {}", buffer.get_binding()),
+                    "doubleoctagon",
+                ),
+                ResourceKind::Backbuffer => (format!("{}x{}", self.width, self.height), "house"),
+            };
+            let _ = writeln!(
+                dot,
+                "    r{} [shape={}, label=\"{}\\n{}\"];",
+                index,
+                shape,
+                escape(&resource.name),
+                details
+            );
+        }
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            for resource in pass.reads.iter() {
+                let _ = writeln!(dot, "    r{} -> p{};", resource.0, index);
+            }
+            for resource in pass.writes.iter() {
+                let _ = writeln!(dot, "    p{} -> r{};", index, resource.0);
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn write_dot<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
+        std::fs::write(path.as_ref(), self.to_dot())
+            .map_err(|e| format!("Failed to write {:?}: {}", path.as_ref(), e))
+    }
+
+    fn add_resource(&mut self, name: &str, kind: ResourceKind) -> ResourceId {
+        self.resources.push(ResourceNode {
+            name: name.to_string(),
+            kind,
+        });
+        self.is_dirty = true;
+        ResourceId(self.resources.len() - 1)
+    }
+
+    fn resolve_size(&self, size: TextureSize) -> (u32, u32) {
+        match size {
+            TextureSize::Relative(scale) => (
+                ((self.width as f32 * scale).round() as u32).max(1),
+                ((self.height as f32 * scale).round() as u32).max(1),
+            ),
+            TextureSize::Fixed(width, height) => (width, height),
+        }
+    }
+
+    fn analyze(&self) -> Result<Schedule, String> {
+        let resource_count = self.resources.len();
+
+        let mut writers = vec![Vec::new(); resource_count];
+        for (index, pass) in self.passes.iter().enumerate() {
+            for resource in pass.reads.iter().chain(pass.writes.iter()) {
+                if resource.0 >= resource_count {
+                    return Err(format!(
+                        "Pass {:?} uses a resource that is not part of the graph",
+                        pass.name
+                    ));
+                }
+            }
+            for resource in pass.writes.iter() {
+                writers[resource.0].push(index);
+            }
+        }
+
+        // Passes writing outside of the graph are kept, and with them the
+        // writers of everything they read. A pass that modifies a resource
+        // only depends on the writers added before it.
+        let mut is_needed = vec![false; self.passes.len()];
+        let mut stack: Vec<usize> = (0..self.passes.len())
+            .filter(|&index| self.is_root(index))
+            .collect();
+        while let Some(index) = stack.pop() {
+            if is_needed[index] {
+                continue;
+            }
+            is_needed[index] = true;
+
+            let pass = &self.passes[index];
+            for resource in pass.reads.iter() {
+                let modifies = pass.writes.contains(resource);
+                for &writer in writers[resource.0].iter() {
+                    if !(modifies && writer >= index) {
+                        stack.push(writer);
+                    }
+                }
+            }
+        }
+
+        for (index, pass) in self.passes.iter().enumerate() {
+            if !is_needed[index] {
+                continue;
+            }
+
+            for resource in pass.reads.iter() {
+                let node = &self.resources[resource.0];
+                match node.kind {
+                    ResourceKind::Backbuffer => {
+                        return Err(format!(
+                            "Pass {:?} reads the backbuffer, which can only be written",
+                            pass.name
+                        ));
+                    }
+                    ResourceKind::Texture(_) | ResourceKind::Buffer(_) => {
+                        let modifies = pass.writes.contains(resource);
+                        let is_written = writers[resource.0]
+                            .iter()
+                            .any(|&writer| writer != index && !(modifies && writer > index));
+                        if !is_written {
+                            return Err(format!(
+                                "Pass {:?} reads {:?} before anything writes it",
+                                pass.name, node.name
+                            ));
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            for resource in pass.writes.iter() {
+                let node = &self.resources[resource.0];
+                if let ResourceKind::ImportedTexture(_) = node.kind {
+                    return Err(format!(
+                        "Pass {:?} writes the imported texture {:?}, imported textures are read only",
+                        pass.name, node.name
+                    ));
+                }
+            }
+        }
+
+        // writers of a resource form a chain, readers follow the last one
+        let mut successors = vec![Vec::new(); self.passes.len()];
+        let mut in_degree = vec![0; self.passes.len()];
+        for (resource, resource_writers) in writers.iter().enumerate() {
+            let resource_writers: Vec<usize> = resource_writers
+                .iter()
+                .copied()
+                .filter(|&writer| is_needed[writer])
+                .collect();
+            for pair in resource_writers.windows(2) {
+                successors[pair[0]].push(pair[1]);
+                in_degree[pair[1]] += 1;
+            }
+
+            if let Some(&last) = resource_writers.last() {
+                for (index, pass) in self.passes.iter().enumerate() {
+                    let id = ResourceId(resource);
+                    if is_needed[index] && pass.reads.contains(&id) && !pass.writes.contains(&id) {
+                        successors[last].push(index);
+                        in_degree[index] += 1;
+                    }
+                }
+            }
+        }
+
+        // ties are broken by the order the passes were added in
+        let mut order = Vec::new();
+        let mut ready: BTreeSet<usize> = (0..self.passes.len())
+            .filter(|&index| is_needed[index] && in_degree[index] == 0)
+            .collect();
+        while let Some(index) = ready.pop_first() {
+            order.push(index);
+            for &next in successors[index].iter() {
+                in_degree[next] -= 1;
+                if in_degree[next] == 0 {
+                    ready.insert(next);
+                }
+            }
+        }
+
+        let needed_count = is_needed.iter().filter(|&&needed| needed).count();
+        if order.len() < needed_count {
+            let cycle: Vec<&str> = (0..self.passes.len())
+                .filter(|&index| is_needed[index] && !order.contains(&index))
+                .map(|index| self.passes[index].name.as_str())
+                .collect();
+            return Err(format!(
+                "Render graph has a dependency cycle between {:?}",
+                cycle
+            ));
+        }
+
+        // lifetimes of the transient resources, as positions in `order`
+        let mut first_use = vec![usize::MAX; resource_count];
+        let mut last_use = vec![0; resource_count];
+        for (position, &index) in order.iter().enumerate() {
+            let pass = &self.passes[index];
+            for resource in pass.reads.iter().chain(pass.writes.iter()) {
+                if self.resources[resource.0].is_transient() {
+                    first_use[resource.0] = first_use[resource.0].min(position);
+                    last_use[resource.0] = last_use[resource.0].max(position);
+                }
+            }
+        }
+
+        let mut slots = vec![None; resource_count];
+        let mut texture_slots: Vec<TextureKey> = Vec::new();
+        let mut buffer_slots: Vec<BufferDesc> = Vec::new();
+        let mut free_textures: Vec<usize> = Vec::new();
+        let mut free_buffers: Vec<usize> = Vec::new();
+        for position in 0..order.len() {
+            for (resource, node) in self.resources.iter().enumerate() {
+                if first_use[resource] != position {
+                    continue;
+                }
+
+                slots[resource] = match node.kind {
+                    ResourceKind::Texture(desc) => {
+                        let (width, height) = self.resolve_size(desc.size);
+                        let key = TextureKey {
+                            width,
+                            height,
+                            format: desc.format,
+                        };
+                        Some(acquire_slot(&mut texture_slots, &mut free_textures, key))
+                    }
+                    ResourceKind::Buffer(desc) => {
+                        Some(acquire_slot(&mut buffer_slots, &mut free_buffers, desc))
+                    }
+                    _ => None,
+                };
+            }
+
+            for (resource, node) in self.resources.iter().enumerate() {
+                if first_use[resource] == usize::MAX || last_use[resource] != position {
+                    continue;
+                }
+
+                match node.kind {
+                    ResourceKind::Texture(_) => free_textures.push(slots[resource].unwrap()),
+                    ResourceKind::Buffer(_) => free_buffers.push(slots[resource].unwrap()),
+                    _ => (),
+                }
+            }
+        }
+
+        let targets = order
+            .iter()
+            .map(|&index| self.get_target(&self.passes[index]))
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Schedule {
+            order,
+            targets,
+            slots,
+            texture_slots,
+            buffer_slots,
+        })
+    }
+
+    fn is_root(&self, index: usize) -> bool {
+        let pass = &self.passes[index];
+        pass.side_effects
+            || pass
+                .writes
+                .iter()
+                .any(|resource| !self.resources[resource.0].is_transient())
+    }
+
+    fn get_target(&self, pass: &PassNode) -> Result<PassTarget, String> {
+        let mut writes_backbuffer = false;
+        let mut colors = Vec::new();
+        let mut depth = None;
+        let mut size = None;
+
+        for resource in pass.writes.iter() {
+            let desc = match self.resources[resource.0].kind {
+                ResourceKind::Texture(desc) => desc,
+                ResourceKind::Backbuffer => {
+                    writes_backbuffer = true;
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let resource_size = self.resolve_size(desc.size);
+            if size.get_or_insert(resource_size) != &resource_size {
+                return Err(format!(
+                    "Pass {:?} writes textures of different sizes",
+                    pass.name
+                ));
+            }
+
+            if !desc.format.is_depth() {
+                colors.push(*resource);
+            } else if depth.replace(*resource).is_some() {
+                return Err(format!(
+                    "Pass {:?} writes more than one depth texture",
+                    pass.name
+                ));
+            }
+        }
+
+        if colors.len() > MAX_COLOR_ATTACHMENTS {
+            return Err(format!(
+                "Pass {:?} writes more than {} color textures",
+                pass.name, MAX_COLOR_ATTACHMENTS
+            ));
+        }
+
+        match (size, writes_backbuffer) {
+            (Some(_), true) => Err(format!(
+                "Pass {:?} writes the backbuffer together with textures",
+                pass.name
+            )),
+            (Some((width, height)), false) => Ok(PassTarget::Textures {
+                colors,
+                depth,
+                width,
+                height,
+            }),
+            (None, true) => Ok(PassTarget::Backbuffer),
+            (None, false) => Ok(PassTarget::None),
+        }
+    }
+}
+
+impl EventHandler for RenderGraph {
+    fn handle_events(&mut self, events: &mut Vec<Event>) {
+        for e in events.iter() {
+            if let Event::FramebufferResize { width, height } = e {
+                self.set_size(*width, *height);
+            }
+        }
+    }
+}
+
+// reuses a released slot with the same description, or adds one
+fn acquire_slot<T: PartialEq>(slots: &mut Vec<T>, free: &mut Vec<usize>, key: T) -> usize {
+    match free.iter().position(|&slot| slots[slot] == key) {
+        Some(i) => free.swap_remove(i),
+        None => {
+            slots.push(key);
+            slots.len() - 1
+        }
+    }
+}
+
+fn create_framebuffer(
+    name: &str,
+    colors: &[&FramebufferAttachment],
+    depth: Option<&FramebufferAttachment>,
+) -> u32 {
+    let mut renderer_id = 0;
+
+    unsafe {
+        gl::GenFramebuffers(1, &mut renderer_id);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, renderer_id);
+
+        let attachments = colors
+            .iter()
+            .copied()
+            .enumerate()
+            .chain(depth.into_iter().map(|a| (0, a)));
+        for (index, attachment) in attachments {
+            gl::FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                attachment.get_format().get_gl_attachment_point(index),
+                gl::TEXTURE_2D,
+                attachment.get_renderer_id(),
+                0,
+            );
+        }
+
+        framebuffer::set_draw_buffers(colors.len());
+        framebuffer::check_status(&format!("Render target of pass {:?}", name));
+        set_object_label(gl::FRAMEBUFFER, renderer_id, name);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    }
+
+    renderer_id
+}
+
+// clears respect the current color and depth write masks
+unsafe fn clear_backbuffer(color: Option<[f32; 4]>, depth: Option<f32>) {
+    let mut mask = 0;
+    if let Some([r, g, b, a]) = color {
+        gl::ClearColor(r, g, b, a);
+        mask |= gl::COLOR_BUFFER_BIT;
+    }
+    if let Some(depth) = depth {
+        gl::ClearDepth(depth as f64);
+        mask |= gl::DEPTH_BUFFER_BIT;
+    }
+    if mask != 0 {
+        gl::Clear(mask);
+    }
+}
+
+unsafe fn clear_color_attachment(index: usize, format: FramebufferFormat, value: [f32; 4]) {
+    if format.is_integer() {
+        let int_value = value.map(|v| v as i32);
+        gl::ClearBufferiv(gl::COLOR, index as i32, int_value.as_ptr());
+    } else {
+        gl::ClearBufferfv(gl::COLOR, index as i32, value.as_ptr());
+    }
+}
+
+unsafe fn clear_depth_attachment(format: FramebufferFormat, value: f32) {
+    match format {
+        FramebufferFormat::Depth24Stencil8 => gl::ClearBufferfi(gl::DEPTH_STENCIL, 0, value, 0),
+        _ => gl::ClearBufferfv(gl::DEPTH, 0, &value),
+    }
+}
+
+// quotes inside labels
+fn escape(label: &str) -> String {
+    label.replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pass(name: &str) -> GraphPass {
+        GraphPass::new(name, |_| {})
+    }
+
+    fn color(graph: &mut RenderGraph, name: &str) -> ResourceId {
+        graph.create_texture(name, TextureDesc::new(FramebufferFormat::Rgba8))
+    }
+
+    #[test]
+    fn passes_run_after_the_writers_of_their_reads() {
+        let mut graph = RenderGraph::new(64, 64);
+        let backbuffer = graph.get_backbuffer();
+        let scene = color(&mut graph, "scene");
+        let bloom = color(&mut graph, "bloom");
+
+        graph.add_pass(
+            pass("present")
+                .with_read(scene)
+                .with_read(bloom)
+                .with_write(backbuffer),
+        );
+        graph.add_pass(pass("bloom").with_read(scene).with_write(bloom));
+        graph.add_pass(pass("scene").with_write(scene));
+
+        assert_eq!(
+            graph.get_pass_order().unwrap(),
+            vec!["scene", "bloom", "present"]
+        );
+    }
+
+    #[test]
+    fn writers_of_a_resource_keep_their_order() {
+        let mut graph = RenderGraph::new(64, 64);
+        let backbuffer = graph.get_backbuffer();
+        let scene = color(&mut graph, "scene");
+
+        graph.add_pass(pass("opaque").with_write(scene));
+        graph.add_pass(pass("transparent").with_read(scene).with_write(scene));
+        graph.add_pass(pass("present").with_read(scene).with_write(backbuffer));
+
+        assert_eq!(
+            graph.get_pass_order().unwrap(),
+            vec!["opaque", "transparent", "present"]
+        );
+    }
+
+    #[test]
+    fn unused_passes_are_culled() {
+        let mut graph = RenderGraph::new(64, 64);
+        let backbuffer = graph.get_backbuffer();
+        let scene = color(&mut graph, "scene");
+        let unused = color(&mut graph, "unused");
+        let readback = color(&mut graph, "readback");
+
+        graph.add_pass(pass("scene").with_write(scene));
+        graph.add_pass(pass("unused").with_read(scene).with_write(unused));
+        graph.add_pass(pass("readback").with_write(readback).with_side_effects());
+        graph.add_pass(pass("present").with_read(scene).with_write(backbuffer));
+
+        assert_eq!(
+            graph.get_pass_order().unwrap(),
+            vec!["scene", "readback", "present"]
+        );
+    }
+
+    #[test]
+    fn cycles_are_errors() {
+        let mut graph = RenderGraph::new(64, 64);
+        let backbuffer = graph.get_backbuffer();
+        let a = color(&mut graph, "a");
+        let b = color(&mut graph, "b");
+
+        graph.add_pass(pass("first").with_read(a).with_write(b));
+        graph.add_pass(
+            pass("second")
+                .with_read(b)
+                .with_write(a)
+                .with_write(backbuffer),
+        );
+
+        let error = graph.get_pass_order().unwrap_err();
+        assert!(error.contains("cycle"), "{}", error);
+    }
+
+    #[test]
+    fn reading_unwritten_resources_is_an_error() {
+        let mut graph = RenderGraph::new(64, 64);
+        let backbuffer = graph.get_backbuffer();
+        let scene = color(&mut graph, "scene");
+
+        graph.add_pass(pass("present").with_read(scene).with_write(backbuffer));
+
+        assert!(graph.get_pass_order().is_err());
+    }
+
+    #[test]
+    fn disjoint_lifetimes_share_memory() {
+        let mut graph = RenderGraph::new(64, 64);
+        let backbuffer = graph.get_backbuffer();
+        let a = color(&mut graph, "a");
+        let b = color(&mut graph, "b");
+        let c = color(&mut graph, "c");
+        let half = graph.create_texture(
+            "half",
+            TextureDesc::new(FramebufferFormat::Rgba8).with_size(TextureSize::Relative(0.5)),
+        );
+
+        graph.add_pass(pass("a").with_write(a));
+        graph.add_pass(pass("b").with_read(a).with_write(b));
+        graph.add_pass(pass("c").with_read(b).with_write(c));
+        graph.add_pass(pass("half").with_read(c).with_write(half));
+        graph.add_pass(pass("present").with_read(half).with_write(backbuffer));
+
+        let schedule = graph.analyze().unwrap();
+        // a is released once b is written, c takes its place
+        assert_eq!(schedule.slots[a.0], schedule.slots[c.0]);
+        assert_ne!(schedule.slots[a.0], schedule.slots[b.0]);
+        // b is free again by then, but has a different size
+        assert_ne!(schedule.slots[half.0], schedule.slots[b.0]);
+        assert_eq!(schedule.texture_slots.len(), 3);
+        assert_eq!(schedule.slots[backbuffer.0], None);
+    }
+
+    #[test]
+    fn overlapping_lifetimes_do_not_share_memory() {
+        let mut graph = RenderGraph::new(64, 64);
+        let backbuffer = graph.get_backbuffer();
+        let a = color(&mut graph, "a");
+        let b = color(&mut graph, "b");
+
+        graph.add_pass(pass("a").with_write(a));
+        graph.add_pass(pass("b").with_write(b));
+        graph.add_pass(
+            pass("present")
+                .with_read(a)
+                .with_read(b)
+                .with_write(backbuffer),
+        );
+
+        let schedule = graph.analyze().unwrap();
+        assert_ne!(schedule.slots[a.0], schedule.slots[b.0]);
+        assert_eq!(schedule.texture_slots.len(), 2);
+    }
+}
@@ -0,0 +1,947 @@
+use std::rc::Rc;
+
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::renderer::{
+    buffer::{BufferElement, BufferLayout, IndexBuffer, ShaderDataType, VertexArray, VertexBuffer},
+    camera::Camera,
+    font::{Font, FontAtlasMode, TextLayout, TextOptions},
+    picking::{decode_entity_id, encode_entity_id},
+    profiler,
+    shader::{OpenGLShader, Shader},
+    state,
+    texture::{SamplerSettings, Texture, Texture2D, TextureFormat, TextureSpec},
+};
+
+const MAX_QUADS: usize = 20000;
+const MAX_VERTICES: usize = MAX_QUADS * 4;
+const MAX_INDICES: usize = MAX_QUADS * 6;
+const MAX_LINES: usize = 20000;
+// the minimum every OpenGL 3.3 implementation supports
+const MAX_TEXTURE_SLOTS: usize = 16;
+
+// corners of the unit quad centered at the origin, counter-clockwise
+const QUAD_POSITIONS: [Vec4; 4] = [
+    Vec4 {
+        x: -0.5,
+        y: -0.5,
+        z: 0.0,
+        w: 1.0,
+    },
+    Vec4 {
+        x: 0.5,
+        y: -0.5,
+        z: 0.0,
+        w: 1.0,
+    },
+    Vec4 {
+        x: 0.5,
+        y: 0.5,
+        z: 0.0,
+        w: 1.0,
+    },
+    Vec4 {
+        x: -0.5,
+        y: 0.5,
+        z: 0.0,
+        w: 1.0,
+    },
+];
+
+const QUAD_TEX_COORDS: [Vec2; 4] = [
+    Vec2 { x: 0.0, y: 0.0 },
+    Vec2 { x: 1.0, y: 0.0 },
+    Vec2 { x: 1.0, y: 1.0 },
+    Vec2 { x: 0.0, y: 1.0 },
+];
+
+static QUAD_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_Position;
+    layout(location = 1) in vec4 a_Color;
+    layout(location = 2) in vec2 a_TexCoord;
+    layout(location = 3) in float a_TexIndex;
+    layout(location = 4) in float a_TilingFactor;
+    layout(location = 5) in int a_EntityId;
+
+    uniform mat4 u_ViewProjection;
+
+    out vec4 v_Color;
+    out vec2 v_TexCoord;
+    flat out int v_TexIndex;
+    flat out int v_EntityId;
+
+    void main() {
+        v_Color = a_Color;
+        v_TexCoord = a_TexCoord * a_TilingFactor;
+        v_TexIndex = int(a_TexIndex);
+        v_EntityId = a_EntityId;
+        gl_Position = u_ViewProjection * vec4(a_Position, 1.0);
+    }
+";
+
+// Indexing a sampler array with a non-constant expression is undefined in
+// GLSL 3.30, so the lookup is a switch over every slot.
+static QUAD_FRAGMENT_SHADER_HEAD: &'static str = "
+    #version 330 core
+
+    in vec4 v_Color;
+    in vec2 v_TexCoord;
+    flat in int v_TexIndex;
+    flat in int v_EntityId;
+
+    uniform sampler2D u_Textures[16];
+
+    layout(location = 0) out vec4 color;
+    layout(location = 1) out int entity_id;
+
+    void main() {
+        vec4 tex_color = vec4(1.0);
+        switch (v_TexIndex) {
+";
+
+static QUAD_FRAGMENT_SHADER_TAIL: &'static str = "
+        }
+        color = tex_color * v_Color;
+        if (color.a == 0.0)
+            discard;
+        entity_id = v_EntityId;
+    }
+";
+
+static CIRCLE_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_WorldPosition;
+    layout(location = 1) in vec2 a_LocalPosition;
+    layout(location = 2) in vec4 a_Color;
+    layout(location = 3) in float a_Thickness;
+    layout(location = 4) in float a_Fade;
+    layout(location = 5) in int a_EntityId;
+
+    uniform mat4 u_ViewProjection;
+
+    out vec2 v_LocalPosition;
+    out vec4 v_Color;
+    out float v_Thickness;
+    out float v_Fade;
+    flat out int v_EntityId;
+
+    void main() {
+        v_LocalPosition = a_LocalPosition;
+        v_Color = a_Color;
+        v_Thickness = a_Thickness;
+        v_Fade = a_Fade;
+        v_EntityId = a_EntityId;
+        gl_Position = u_ViewProjection * vec4(a_WorldPosition, 1.0);
+    }
+";
+
+static CIRCLE_FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    in vec2 v_LocalPosition;
+    in vec4 v_Color;
+    in float v_Thickness;
+    in float v_Fade;
+    flat in int v_EntityId;
+
+    layout(location = 0) out vec4 color;
+    layout(location = 1) out int entity_id;
+
+    void main() {
+        // 1 at the center, 0 on the rim
+        float distance = 1.0 - length(v_LocalPosition);
+        float alpha = smoothstep(0.0, v_Fade, distance);
+        alpha *= smoothstep(v_Thickness + v_Fade, v_Thickness, distance);
+        if (alpha == 0.0)
+            discard;
+
+        color = vec4(v_Color.rgb, v_Color.a * alpha);
+        entity_id = v_EntityId;
+    }
+";
+
+static TEXT_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_Position;
+    layout(location = 1) in vec4 a_Color;
+    layout(location = 2) in vec2 a_TexCoord;
+    layout(location = 3) in float a_Sdf;
+
+    uniform mat4 u_ViewProjection;
+
+    out vec4 v_Color;
+    out vec2 v_TexCoord;
+    flat out float v_Sdf;
+
+    void main() {
+        v_Color = a_Color;
+        v_TexCoord = a_TexCoord;
+        v_Sdf = a_Sdf;
+        gl_Position = u_ViewProjection * vec4(a_Position, 1.0);
+    }
+";
+
+// The atlas holds coverage, or a distance field with the outline at 0.5
+// which is antialiased over one screen pixel.
+static TEXT_FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    in vec4 v_Color;
+    in vec2 v_TexCoord;
+    flat in float v_Sdf;
+
+    uniform sampler2D u_Atlas;
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        float value = texture(u_Atlas, v_TexCoord).r;
+        float alpha = value;
+        if (v_Sdf != 0.0) {
+            float width = max(fwidth(value), 1e-4) * 0.5;
+            alpha = smoothstep(0.5 - width, 0.5 + width, value);
+        }
+        if (alpha == 0.0)
+            discard;
+
+        color = vec4(v_Color.rgb, v_Color.a * alpha);
+    }
+";
+
+// Lines are quads, each end moved half the width in pixels to either side,
+// since line widths above 1 are not part of the core profile.
+static LINE_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_Position;
+    layout(location = 1) in vec3 a_Other;
+    layout(location = 2) in vec4 a_Color;
+    layout(location = 3) in float a_Side;
+
+    uniform mat4 u_ViewProjection;
+    uniform vec2 u_ViewportSize;
+    uniform float u_LineWidth;
+
+    out vec4 v_Color;
+
+    void main() {
+        v_Color = a_Color;
+
+        vec4 position = u_ViewProjection * vec4(a_Position, 1.0);
+        vec4 other = u_ViewProjection * vec4(a_Other, 1.0);
+        vec2 half_viewport = u_ViewportSize * 0.5;
+        vec2 direction = (other.xy / other.w - position.xy / position.w) * half_viewport;
+        if (dot(direction, direction) > 0.0) {
+            direction = normalize(direction);
+        } else {
+            direction = vec2(1.0, 0.0);
+        }
+
+        vec2 offset = vec2(-direction.y, direction.x) * a_Side * u_LineWidth * 0.5;
+        position.xy += offset / half_viewport * position.w;
+        gl_Position = position;
+    }
+";
+
+static LINE_FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    in vec4 v_Color;
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        color = v_Color;
+    }
+";
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct QuadVertex {
+    position: Vec3,
+    color: Vec4,
+    tex_coord: Vec2,
+    tex_index: f32,
+    tiling_factor: f32,
+    entity_id: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct CircleVertex {
+    world_position: Vec3,
+    local_position: Vec2,
+    color: Vec4,
+    thickness: f32,
+    fade: f32,
+    entity_id: i32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct LineVertex {
+    position: Vec3,
+    // the other end of the line
+    other: Vec3,
+    color: Vec4,
+    // -1 or 1, the side of the line the vertex is moved to
+    side: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct TextVertex {
+    position: Vec3,
+    color: Vec4,
+    tex_coord: Vec2,
+    sdf: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Renderer2DStats {
+    pub draw_calls: u32,
+    pub quad_count: u32,
+    pub circle_count: u32,
+    pub line_count: u32,
+    pub glyph_count: u32,
+}
+
+impl Renderer2DStats {
+    pub fn get_vertex_count(&self) -> u32 {
+        (self.quad_count + self.circle_count + self.glyph_count + self.line_count) * 4
+    }
+
+    pub fn get_index_count(&self) -> u32 {
+        (self.quad_count + self.circle_count + self.glyph_count + self.line_count) * 6
+    }
+}
+
+// Batches quads, circles, text and lines between `begin_scene` and `end_scene`.
+// A batch is flushed when its vertex buffer or the texture slots run out,
+// so the number of primitives per scene is unbounded. Each kind of primitive
+// has its own batch and a flush draws quads, then circles, text and lines, so
+// order is only kept within a kind. Call `flush` in between, or use depth,
+// where overlapping primitives of different kinds need to blend in
+// submission order. Stats cover the current scene, `begin_scene` resets them.
+pub struct Renderer2D {
+    quad_shader: OpenGLShader,
+    quad_vertex_array: VertexArray,
+    quad_vertex_buffer: Rc<VertexBuffer>,
+    quad_vertices: Vec<QuadVertex>,
+
+    circle_shader: OpenGLShader,
+    circle_vertex_array: VertexArray,
+    circle_vertex_buffer: Rc<VertexBuffer>,
+    circle_vertices: Vec<CircleVertex>,
+
+    text_shader: OpenGLShader,
+    text_vertex_array: VertexArray,
+    text_vertex_buffer: Rc<VertexBuffer>,
+    text_vertices: Vec<TextVertex>,
+    // atlas of the current text batch, one font per batch
+    text_atlas: Option<u32>,
+
+    line_shader: OpenGLShader,
+    line_vertex_array: VertexArray,
+    line_vertex_buffer: Rc<VertexBuffer>,
+    line_vertices: Vec<LineVertex>,
+    // in pixels
+    line_width: f32,
+
+    white_texture: Texture2D,
+    // renderer ids, slot 0 is the white texture
+    texture_slots: Vec<u32>,
+
+    view_projection: Mat4,
+    in_scene: bool,
+    // written to the entity ID attachment, see `picking`
+    entity_id: i32,
+    stats: Renderer2DStats,
+}
+
+impl Renderer2D {
+    // requires a current OpenGL context
+    pub fn new() -> Self {
+        let mut indices = Vec::with_capacity(MAX_INDICES);
+        for i in 0..MAX_QUADS as u32 {
+            let base = i * 4;
+            indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+        }
+        let quad_index_buffer = Rc::new(IndexBuffer::new(&indices));
+
+        let quad_vertex_buffer = Rc::new(VertexBuffer::new(
+            MAX_VERTICES * std::mem::size_of::<QuadVertex>(),
+            BufferLayout::new(vec![
+                BufferElement::new("a_Position", ShaderDataType::Float3),
+                BufferElement::new("a_Color", ShaderDataType::Float4),
+                BufferElement::new("a_TexCoord", ShaderDataType::Float2),
+                BufferElement::new("a_TexIndex", ShaderDataType::Float),
+                BufferElement::new("a_TilingFactor", ShaderDataType::Float),
+                BufferElement::new("a_EntityId", ShaderDataType::Int),
+            ]),
+        ));
+        let mut quad_vertex_array = VertexArray::new();
+        quad_vertex_array.add_vertex_buffer(quad_vertex_buffer.clone());
+        quad_vertex_array.set_index_buffer(quad_index_buffer.clone());
+
+        let circle_vertex_buffer = Rc::new(VertexBuffer::new(
+            MAX_VERTICES * std::mem::size_of::<CircleVertex>(),
+            BufferLayout::new(vec![
+                BufferElement::new("a_WorldPosition", ShaderDataType::Float3),
+                BufferElement::new("a_LocalPosition", ShaderDataType::Float2),
+                BufferElement::new("a_Color", ShaderDataType::Float4),
+                BufferElement::new("a_Thickness", ShaderDataType::Float),
+                BufferElement::new("a_Fade", ShaderDataType::Float),
+                BufferElement::new("a_EntityId", ShaderDataType::Int),
+            ]),
+        ));
+        let mut circle_vertex_array = VertexArray::new();
+        circle_vertex_array.add_vertex_buffer(circle_vertex_buffer.clone());
+        circle_vertex_array.set_index_buffer(quad_index_buffer.clone());
+
+        let text_vertex_buffer = Rc::new(VertexBuffer::new(
+            MAX_VERTICES * std::mem::size_of::<TextVertex>(),
+            BufferLayout::new(vec![
+                BufferElement::new("a_Position", ShaderDataType::Float3),
+                BufferElement::new("a_Color", ShaderDataType::Float4),
+                BufferElement::new("a_TexCoord", ShaderDataType::Float2),
+                BufferElement::new("a_Sdf", ShaderDataType::Float),
+            ]),
+        ));
+        let mut text_vertex_array = VertexArray::new();
+        text_vertex_array.add_vertex_buffer(text_vertex_buffer.clone());
+        text_vertex_array.set_index_buffer(quad_index_buffer.clone());
+
+        let line_vertex_buffer = Rc::new(VertexBuffer::new(
+            MAX_LINES * 4 * std::mem::size_of::<LineVertex>(),
+            BufferLayout::new(vec![
+                BufferElement::new("a_Position", ShaderDataType::Float3),
+                BufferElement::new("a_Other", ShaderDataType::Float3),
+                BufferElement::new("a_Color", ShaderDataType::Float4),
+                BufferElement::new("a_Side", ShaderDataType::Float),
+            ]),
+        ));
+        let mut line_vertex_array = VertexArray::new();
+        line_vertex_array.add_vertex_buffer(line_vertex_buffer.clone());
+        line_vertex_array.set_index_buffer(quad_index_buffer);
+
+        let mut quad_fragment_shader = QUAD_FRAGMENT_SHADER_HEAD.to_string();
+        for slot in 0..MAX_TEXTURE_SLOTS {
+            quad_fragment_shader.push_str(&format!(
+                "            case {0}: tex_color = texture(u_Textures[{0}], v_TexCoord); break;\n",
+                slot
+            ));
+        }
+        quad_fragment_shader.push_str(QUAD_FRAGMENT_SHADER_TAIL);
+
+        let quad_shader = OpenGLShader::new(QUAD_VERTEX_SHADER, &quad_fragment_shader);
+        let samplers: Vec<i32> = (0..MAX_TEXTURE_SLOTS as i32).collect();
+        quad_shader.bind();
+        quad_shader.set_int_array("u_Textures", &samplers);
+
+        let text_shader = OpenGLShader::new(TEXT_VERTEX_SHADER, TEXT_FRAGMENT_SHADER);
+        text_shader.bind();
+        text_shader.set_int("u_Atlas", 0);
+
+        let mut white_texture = Texture2D::new(TextureSpec {
+            sampler: SamplerSettings::nearest(),
+            ..TextureSpec::new(1, 1, TextureFormat::Rgba8)
+        });
+        white_texture.set_data(&[255, 255, 255, 255]);
+        let white_id = white_texture.get_renderer_id();
+
+        let renderer = Self {
+            quad_shader,
+            quad_vertex_array,
+            quad_vertex_buffer,
+            quad_vertices: Vec::with_capacity(MAX_VERTICES),
+
+            circle_shader: OpenGLShader::new(CIRCLE_VERTEX_SHADER, CIRCLE_FRAGMENT_SHADER),
+            circle_vertex_array,
+            circle_vertex_buffer,
+            circle_vertices: Vec::with_capacity(MAX_VERTICES),
+
+            text_shader,
+            text_vertex_array,
+            text_vertex_buffer,
+            text_vertices: Vec::with_capacity(MAX_VERTICES),
+            text_atlas: None,
+
+            line_shader: OpenGLShader::new(LINE_VERTEX_SHADER, LINE_FRAGMENT_SHADER),
+            line_vertex_array,
+            line_vertex_buffer,
+            line_vertices: Vec::with_capacity(MAX_LINES * 4),
+            line_width: 1.0,
+
+            white_texture,
+            texture_slots: vec![white_id],
+
+            view_projection: Mat4::IDENTITY,
+            in_scene: false,
+            entity_id: 0,
+            stats: Renderer2DStats::default(),
+        };
+        renderer.set_labels();
+        renderer
+    }
+
+    // names the GL objects for debug output and frame captures
+    fn set_labels(&self) {
+        let batches = [
+            (
+                "quad",
+                &self.quad_shader,
+                &self.quad_vertex_array,
+                &self.quad_vertex_buffer,
+            ),
+            (
+                "circle",
+                &self.circle_shader,
+                &self.circle_vertex_array,
+                &self.circle_vertex_buffer,
+            ),
+            (
+                "text",
+                &self.text_shader,
+                &self.text_vertex_array,
+                &self.text_vertex_buffer,
+            ),
+            (
+                "line",
+                &self.line_shader,
+                &self.line_vertex_array,
+                &self.line_vertex_buffer,
+            ),
+        ];
+        for (name, shader, vertex_array, vertex_buffer) in batches.iter() {
+            shader.set_label(&format!("Renderer2D {} shader", name));
+            vertex_array.set_label(&format!("Renderer2D {} vertex array", name));
+            vertex_buffer.set_label(&format!("Renderer2D {} vertices", name));
+        }
+        if let Some(index_buffer) = self.quad_vertex_array.get_index_buffer() {
+            index_buffer.set_label("Renderer2D quad indices");
+        }
+        self.white_texture.set_label("Renderer2D white texture");
+    }
+
+    pub fn begin_scene(&mut self, camera: &dyn Camera) {
+        self.begin_scene_with(camera.get_view_projection());
+    }
+
+    pub fn begin_scene_with(&mut self, view_projection: &Mat4) {
+        assert!(!self.in_scene, "Renderer2D scene already begun.");
+        self.view_projection = *view_projection;
+        self.in_scene = true;
+        self.stats = Renderer2DStats::default();
+        self.start_batch();
+    }
+
+    pub fn end_scene(&mut self) {
+        assert!(self.in_scene, "Renderer2D scene was not begun.");
+        self.flush();
+        self.in_scene = false;
+    }
+
+    // draws everything submitted so far
+    pub fn flush(&mut self) {
+        state::enable(gl::BLEND);
+        state::set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        if !self.quad_vertices.is_empty() {
+            self.quad_vertex_buffer.set_data(&self.quad_vertices);
+
+            for (slot, id) in self.texture_slots.iter().enumerate() {
+                state::bind_texture(slot as u32, gl::TEXTURE_2D, *id);
+            }
+
+            self.quad_shader.bind();
+            self.quad_shader
+                .set_mat4("u_ViewProjection", &self.view_projection);
+            draw_indexed(&self.quad_vertex_array, self.quad_vertices.len() / 4 * 6);
+            self.stats.draw_calls += 1;
+        }
+
+        if !self.circle_vertices.is_empty() {
+            self.circle_vertex_buffer.set_data(&self.circle_vertices);
+
+            self.circle_shader.bind();
+            self.circle_shader
+                .set_mat4("u_ViewProjection", &self.view_projection);
+            draw_indexed(
+                &self.circle_vertex_array,
+                self.circle_vertices.len() / 4 * 6,
+            );
+            self.stats.draw_calls += 1;
+        }
+
+        if let (false, Some(atlas)) = (self.text_vertices.is_empty(), self.text_atlas) {
+            self.text_vertex_buffer.set_data(&self.text_vertices);
+
+            state::bind_texture(0, gl::TEXTURE_2D, atlas);
+
+            self.text_shader.bind();
+            self.text_shader
+                .set_mat4("u_ViewProjection", &self.view_projection);
+            draw_indexed(&self.text_vertex_array, self.text_vertices.len() / 4 * 6);
+            self.stats.draw_calls += 1;
+        }
+
+        if !self.line_vertices.is_empty() {
+            self.line_vertex_buffer.set_data(&self.line_vertices);
+
+            let [_, _, width, height] = state::get_viewport();
+            self.line_shader.bind();
+            self.line_shader
+                .set_mat4("u_ViewProjection", &self.view_projection);
+            self.line_shader
+                .set_float2("u_ViewportSize", Vec2::new(width as f32, height as f32));
+            self.line_shader.set_float("u_LineWidth", self.line_width);
+            draw_indexed(&self.line_vertex_array, self.line_vertices.len() / 4 * 6);
+            self.stats.draw_calls += 1;
+        }
+
+        self.start_batch();
+    }
+
+    pub fn draw_quad(&mut self, position: Vec3, size: Vec2, color: Vec4) {
+        self.draw_quad_transform(&quad_transform(position, size, 0.0), color);
+    }
+
+    // `rotation` in radians around the Z axis
+    pub fn draw_rotated_quad(&mut self, position: Vec3, size: Vec2, rotation: f32, color: Vec4) {
+        self.draw_quad_transform(&quad_transform(position, size, rotation), color);
+    }
+
+    pub fn draw_textured_quad(
+        &mut self,
+        position: Vec3,
+        size: Vec2,
+        texture: &dyn Texture,
+        tiling_factor: f32,
+        tint: Vec4,
+    ) {
+        self.draw_textured_quad_transform(
+            &quad_transform(position, size, 0.0),
+            texture,
+            &QUAD_TEX_COORDS,
+            tiling_factor,
+            tint,
+        );
+    }
+
+    pub fn draw_rotated_textured_quad(
+        &mut self,
+        position: Vec3,
+        size: Vec2,
+        rotation: f32,
+        texture: &dyn Texture,
+        tiling_factor: f32,
+        tint: Vec4,
+    ) {
+        self.draw_textured_quad_transform(
+            &quad_transform(position, size, rotation),
+            texture,
+            &QUAD_TEX_COORDS,
+            tiling_factor,
+            tint,
+        );
+    }
+
+    // `transform` maps the unit quad centered at the origin
+    pub fn draw_quad_transform(&mut self, transform: &Mat4, color: Vec4) {
+        self.submit_quad(transform, color, 0.0, &QUAD_TEX_COORDS, 1.0);
+    }
+
+    // `tex_coords` for the corners counter-clockwise from the bottom left
+    pub fn draw_textured_quad_transform(
+        &mut self,
+        transform: &Mat4,
+        texture: &dyn Texture,
+        tex_coords: &[Vec2; 4],
+        tiling_factor: f32,
+        tint: Vec4,
+    ) {
+        let tex_index = self.get_texture_slot(texture.get_renderer_id());
+        self.submit_quad(transform, tint, tex_index, tex_coords, tiling_factor);
+    }
+
+    // `thickness` 1 fills the circle, `fade` blurs the edges, both relative
+    // to the radius
+    pub fn draw_circle(
+        &mut self,
+        center: Vec3,
+        radius: f32,
+        color: Vec4,
+        thickness: f32,
+        fade: f32,
+    ) {
+        let transform =
+            Mat4::translation(center) * Mat4::scale(Vec3::new(radius * 2.0, radius * 2.0, 1.0));
+        self.draw_circle_transform(&transform, color, thickness, fade);
+    }
+
+    pub fn draw_circle_transform(
+        &mut self,
+        transform: &Mat4,
+        color: Vec4,
+        thickness: f32,
+        fade: f32,
+    ) {
+        self.assert_in_scene();
+        if self.circle_vertices.len() >= MAX_VERTICES {
+            self.flush();
+        }
+
+        for corner in QUAD_POSITIONS.iter() {
+            self.circle_vertices.push(CircleVertex {
+                world_position: (*transform * *corner).truncate(),
+                local_position: Vec2::new(corner.x * 2.0, corner.y * 2.0),
+                color,
+                thickness,
+                fade,
+                entity_id: self.entity_id,
+            });
+        }
+        self.stats.circle_count += 1;
+    }
+
+    // `position` is the top left corner of the text block and `size` the
+    // font size in world units. For screen space text begin a scene with
+    // `screen_projection`, where units are pixels.
+    pub fn draw_text(
+        &mut self,
+        text: &str,
+        font: &Font,
+        position: Vec3,
+        size: f32,
+        options: &TextOptions,
+        color: Vec4,
+    ) {
+        let layout = font.layout(text, options);
+        let scale = size / font.get_size();
+        let transform = Mat4::translation(position) * Mat4::scale(Vec3::new(scale, scale, 1.0));
+        self.draw_text_layout(&layout, font, &transform, color);
+    }
+
+    // `transform` maps layout pixels, lets a layout be reused across frames
+    // or drawn rotated
+    pub fn draw_text_layout(
+        &mut self,
+        layout: &TextLayout,
+        font: &Font,
+        transform: &Mat4,
+        color: Vec4,
+    ) {
+        self.assert_in_scene();
+
+        let atlas = font.get_atlas().get_renderer_id();
+        if self.text_atlas != Some(atlas) {
+            if !self.text_vertices.is_empty() {
+                self.flush();
+            }
+            self.text_atlas = Some(atlas);
+        }
+        let sdf = match font.get_mode() {
+            FontAtlasMode::Bitmap => 0.0,
+            FontAtlasMode::Sdf { .. } => 1.0,
+        };
+
+        for positioned in layout.glyphs.iter() {
+            if self.text_vertices.len() >= MAX_VERTICES {
+                self.flush();
+                self.text_atlas = Some(atlas);
+            }
+
+            let glyph = &positioned.glyph;
+            let (p0, p1) = (positioned.position, positioned.position + glyph.size);
+            let corners = [
+                (
+                    Vec2::new(p0.x, p0.y),
+                    Vec2::new(glyph.uv_min.x, glyph.uv_min.y),
+                ),
+                (
+                    Vec2::new(p1.x, p0.y),
+                    Vec2::new(glyph.uv_max.x, glyph.uv_min.y),
+                ),
+                (
+                    Vec2::new(p1.x, p1.y),
+                    Vec2::new(glyph.uv_max.x, glyph.uv_max.y),
+                ),
+                (
+                    Vec2::new(p0.x, p1.y),
+                    Vec2::new(glyph.uv_min.x, glyph.uv_max.y),
+                ),
+            ];
+            for (position, tex_coord) in corners.iter() {
+                self.text_vertices.push(TextVertex {
+                    position: transform.transform_point(position.extend(0.0)),
+                    color,
+                    tex_coord: *tex_coord,
+                    sdf,
+                });
+            }
+            self.stats.glyph_count += 1;
+        }
+    }
+
+    pub fn draw_line(&mut self, start: Vec3, end: Vec3, color: Vec4) {
+        self.assert_in_scene();
+        if self.line_vertices.len() >= MAX_LINES * 4 {
+            self.flush();
+        }
+
+        // the direction flips at the end, so does the side
+        let corners = [
+            (start, end, -1.0),
+            (start, end, 1.0),
+            (end, start, -1.0),
+            (end, start, 1.0),
+        ];
+        for (position, other, side) in corners.iter() {
+            self.line_vertices.push(LineVertex {
+                position: *position,
+                other: *other,
+                color,
+                side: *side,
+            });
+        }
+        self.stats.line_count += 1;
+    }
+
+    // outline of an axis-aligned rectangle
+    pub fn draw_rect(&mut self, position: Vec3, size: Vec2, color: Vec4) {
+        self.draw_rect_transform(&quad_transform(position, size, 0.0), color);
+    }
+
+    pub fn draw_rect_transform(&mut self, transform: &Mat4, color: Vec4) {
+        let corners: Vec<Vec3> = QUAD_POSITIONS
+            .iter()
+            .map(|c| (*transform * *c).truncate())
+            .collect();
+        for i in 0..4 {
+            self.draw_line(corners[i], corners[(i + 1) % 4], color);
+        }
+    }
+
+    // in pixels of the viewport at the time of the flush
+    pub fn set_line_width(&mut self, width: f32) {
+        if width != self.line_width && !self.line_vertices.is_empty() {
+            self.flush();
+        }
+        self.line_width = width;
+    }
+
+    pub fn get_line_width(&self) -> f32 {
+        self.line_width
+    }
+
+    // entity of the quads and circles drawn from now on, None for ones that
+    // cannot be picked
+    pub fn set_entity_id(&mut self, entity: Option<u32>) {
+        self.entity_id = encode_entity_id(entity);
+    }
+
+    pub fn get_entity_id(&self) -> Option<u32> {
+        decode_entity_id(self.entity_id)
+    }
+
+    pub fn get_stats(&self) -> Renderer2DStats {
+        self.stats
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.stats = Renderer2DStats::default();
+    }
+
+    pub fn get_white_texture(&self) -> &Texture2D {
+        &self.white_texture
+    }
+
+    fn submit_quad(
+        &mut self,
+        transform: &Mat4,
+        color: Vec4,
+        tex_index: f32,
+        tex_coords: &[Vec2; 4],
+        tiling_factor: f32,
+    ) {
+        self.assert_in_scene();
+        if self.quad_vertices.len() >= MAX_VERTICES {
+            // keep the texture bound for the current quad
+            let texture_id = self.texture_slots[tex_index as usize];
+            self.flush();
+            let tex_index = self.get_texture_slot(texture_id);
+            return self.submit_quad(transform, color, tex_index, tex_coords, tiling_factor);
+        }
+
+        for (corner, tex_coord) in QUAD_POSITIONS.iter().zip(tex_coords.iter()) {
+            self.quad_vertices.push(QuadVertex {
+                position: (*transform * *corner).truncate(),
+                color,
+                tex_coord: *tex_coord,
+                tex_index,
+                tiling_factor,
+                entity_id: self.entity_id,
+            });
+        }
+        self.stats.quad_count += 1;
+    }
+
+    // slot of the texture in the current batch, flushing when all are taken
+    fn get_texture_slot(&mut self, renderer_id: u32) -> f32 {
+        if let Some(slot) = self.texture_slots.iter().position(|id| *id == renderer_id) {
+            return slot as f32;
+        }
+
+        if self.texture_slots.len() >= MAX_TEXTURE_SLOTS {
+            self.flush();
+        }
+        self.texture_slots.push(renderer_id);
+        (self.texture_slots.len() - 1) as f32
+    }
+
+    fn start_batch(&mut self) {
+        self.quad_vertices.clear();
+        self.circle_vertices.clear();
+        self.text_vertices.clear();
+        self.line_vertices.clear();
+        self.texture_slots.truncate(1);
+    }
+
+    fn assert_in_scene(&self) {
+        assert!(
+            self.in_scene,
+            "Renderer2D draw outside of begin_scene/end_scene."
+        );
+    }
+}
+
+// pixel coordinates with the origin at the bottom left of the viewport
+pub fn screen_projection(width: u32, height: u32) -> Mat4 {
+    Mat4::orthographic(0.0, width as f32, 0.0, height as f32, -1.0, 1.0)
+}
+
+fn quad_transform(position: Vec3, size: Vec2, rotation: f32) -> Mat4 {
+    let mut transform = Mat4::translation(position);
+    if rotation != 0.0 {
+        transform = transform * Mat4::rotation_z(rotation);
+    }
+    transform * Mat4::scale(Vec3::new(size.x, size.y, 1.0))
+}
+
+fn draw_indexed(vertex_array: &VertexArray, index_count: usize) {
+    vertex_array.bind();
+    unsafe {
+        gl::DrawElements(
+            gl::TRIANGLES,
+            index_count as i32,
+            gl::UNSIGNED_INT,
+            std::ptr::null(),
+        );
+    }
+    profiler::record_draw(gl::TRIANGLES, index_count as u32, 1);
+    vertex_array.unbind();
+}
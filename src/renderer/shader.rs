@@ -1,39 +1,124 @@
-use std::{ffi::CString, ptr, str};
+use std::{cell::RefCell, collections::HashMap, ffi::CString, ptr};
+
+use crate::{
+    log_error,
+    math::{Mat3, Mat4, Vec2, Vec3, Vec4},
+    renderer::{debug::set_object_label, state},
+};
 
 pub trait Shader<'a> {
     fn new(vs: &'a str, fs: &'a str) -> Self;
     fn bind(&self);
     fn unbind(&self);
+
+    // uniform setters expect the shader to be bound
+    fn set_int(&self, name: &str, value: i32);
+    fn set_int_array(&self, name: &str, values: &[i32]);
+    fn set_float(&self, name: &str, value: f32);
+    fn set_float2(&self, name: &str, value: Vec2);
+    fn set_float3(&self, name: &str, value: Vec3);
+    fn set_float4(&self, name: &str, value: Vec4);
+    fn set_mat3(&self, name: &str, value: &Mat3);
+    fn set_mat4(&self, name: &str, value: &Mat4);
 }
 
 pub struct OpenGLShader {
     program_id: u32,
+    uniform_locations: RefCell<HashMap<String, i32>>,
 }
 
 impl<'a> Shader<'a> for OpenGLShader {
     fn new(vs: &'a str, fs: &'a str) -> Self {
         let vs_id = OpenGLShader::compile_shader(vs, gl::VERTEX_SHADER);
         let fs_id = OpenGLShader::compile_shader(fs, gl::FRAGMENT_SHADER);
-        let program_id = OpenGLShader::link_program(vs_id, fs_id);
+        let program_id = OpenGLShader::link_program(&[vs_id, fs_id], &[]);
 
-        Self { program_id }
+        Self {
+            program_id,
+            uniform_locations: RefCell::new(HashMap::new()),
+        }
     }
 
     fn bind(&self) {
+        state::use_program(self.program_id);
+    }
+
+    fn unbind(&self) {
+        state::use_program(0);
+    }
+
+    fn set_int(&self, name: &str, value: i32) {
         unsafe {
-            gl::UseProgram(self.program_id);
+            gl::Uniform1i(self.get_uniform_location(name), value);
         }
     }
 
-    fn unbind(&self) {
+    fn set_int_array(&self, name: &str, values: &[i32]) {
+        unsafe {
+            gl::Uniform1iv(
+                self.get_uniform_location(name),
+                values.len() as i32,
+                values.as_ptr(),
+            );
+        }
+    }
+
+    fn set_float(&self, name: &str, value: f32) {
+        unsafe {
+            gl::Uniform1f(self.get_uniform_location(name), value);
+        }
+    }
+
+    fn set_float2(&self, name: &str, value: Vec2) {
+        unsafe {
+            gl::Uniform2f(self.get_uniform_location(name), value.x, value.y);
+        }
+    }
+
+    fn set_float3(&self, name: &str, value: Vec3) {
+        unsafe {
+            gl::Uniform3f(self.get_uniform_location(name), value.x, value.y, value.z);
+        }
+    }
+
+    fn set_float4(&self, name: &str, value: Vec4) {
+        unsafe {
+            gl::Uniform4f(
+                self.get_uniform_location(name),
+                value.x,
+                value.y,
+                value.z,
+                value.w,
+            );
+        }
+    }
+
+    fn set_mat3(&self, name: &str, value: &Mat3) {
+        unsafe {
+            gl::UniformMatrix3fv(
+                self.get_uniform_location(name),
+                1,
+                gl::FALSE,
+                value.as_ptr(),
+            );
+        }
+    }
+
+    fn set_mat4(&self, name: &str, value: &Mat4) {
         unsafe {
-            gl::UseProgram(0);
+            gl::UniformMatrix4fv(
+                self.get_uniform_location(name),
+                1,
+                gl::FALSE,
+                value.as_ptr(),
+            );
         }
     }
 }
 
 impl Drop for OpenGLShader {
     fn drop(&mut self) {
+        state::forget_program(self.program_id);
         unsafe {
             gl::DeleteProgram(self.program_id);
         }
@@ -41,10 +126,54 @@ impl Drop for OpenGLShader {
 }
 
 impl OpenGLShader {
+    // Vertex only program for GPU simulations, the outputs named in
+    // `varyings` are captured interleaved into one transform feedback buffer.
+    pub fn new_transform_feedback(vs: &str, varyings: &[&str]) -> Self {
+        let vs_id = OpenGLShader::compile_shader(vs, gl::VERTEX_SHADER);
+        let program_id = OpenGLShader::link_program(&[vs_id], varyings);
+
+        Self {
+            program_id,
+            uniform_locations: RefCell::new(HashMap::new()),
+        }
+    }
+
     pub fn get_program_id(&self) -> u32 {
         self.program_id
     }
 
+    // names the program in debug output and frame captures
+    pub fn set_label(&self, label: &str) {
+        set_object_label(gl::PROGRAM, self.program_id, label);
+    }
+
+    // links the uniform block `name` to a buffer binding point, blocks the
+    // program does not declare are ignored
+    pub fn set_uniform_block_binding(&self, name: &str, binding: u32) {
+        let c_name = CString::new(name).unwrap();
+        unsafe {
+            let index = gl::GetUniformBlockIndex(self.program_id, c_name.as_ptr());
+            if index != gl::INVALID_INDEX {
+                gl::UniformBlockBinding(self.program_id, index, binding);
+            }
+        }
+    }
+
+    // -1 for uniforms that do not exist or were optimized away, GL ignores those
+    fn get_uniform_location(&self, name: &str) -> i32 {
+        if let Some(location) = self.uniform_locations.borrow().get(name) {
+            return *location;
+        }
+
+        let c_name = CString::new(name).unwrap();
+        let location = unsafe { gl::GetUniformLocation(self.program_id, c_name.as_ptr()) };
+        self.uniform_locations
+            .borrow_mut()
+            .insert(name.to_string(), location);
+
+        location
+    }
+
     fn compile_shader(src: &str, ty: u32) -> u32 {
         use gl::types::*;
 
@@ -64,58 +193,68 @@ impl OpenGLShader {
             if status != (gl::TRUE as GLint) {
                 let mut len = 0;
                 gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
-                let mut buf = Vec::with_capacity(len as usize);
-                buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
-                gl::GetShaderInfoLog(
-                    shader,
-                    len,
-                    ptr::null_mut(),
-                    buf.as_mut_ptr() as *mut GLchar,
-                );
-                panic!(
-                    "{}",
-                    str::from_utf8(&buf)
-                        .ok()
-                        .expect("ShaderInfoLog not valid utf8")
-                );
+                let mut buf = vec![0u8; len.max(1) as usize];
+                let mut written = 0;
+                gl::GetShaderInfoLog(shader, len, &mut written, buf.as_mut_ptr() as *mut GLchar);
+                buf.truncate(written as usize);
+
+                let stage = match ty {
+                    gl::VERTEX_SHADER => "vertex",
+                    gl::FRAGMENT_SHADER => "fragment",
+                    gl::GEOMETRY_SHADER => "geometry",
+                    _ => "unknown",
+                };
+                let log = String::from_utf8_lossy(&buf);
+                log_error!("Failed to compile {} shader:\n{}", stage, log);
+                panic!("Failed to compile {} shader: {}", stage, log);
             }
         }
         shader
     }
 
-    fn link_program(vs: u32, fs: u32) -> u32 {
+    fn link_program(shaders: &[u32], varyings: &[&str]) -> u32 {
         use gl::types::*;
 
         unsafe {
             let program = gl::CreateProgram();
-            gl::AttachShader(program, vs);
-            gl::AttachShader(program, fs);
+            for &shader in shaders.iter() {
+                gl::AttachShader(program, shader);
+            }
+            if !varyings.is_empty() {
+                let c_varyings: Vec<CString> = varyings
+                    .iter()
+                    .map(|varying| CString::new(*varying).unwrap())
+                    .collect();
+                let pointers: Vec<*const GLchar> =
+                    c_varyings.iter().map(|varying| varying.as_ptr()).collect();
+                gl::TransformFeedbackVaryings(
+                    program,
+                    pointers.len() as i32,
+                    pointers.as_ptr(),
+                    gl::INTERLEAVED_ATTRIBS,
+                );
+            }
             gl::LinkProgram(program);
             // Get the link status
             let mut status = gl::FALSE as GLint;
             gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
 
-            gl::DetachShader(program, vs);
-            gl::DetachShader(program, fs);
+            for &shader in shaders.iter() {
+                gl::DetachShader(program, shader);
+            }
 
             // Fail on error
             if status != (gl::TRUE as GLint) {
                 let mut len: GLint = 0;
                 gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
-                let mut buf = Vec::with_capacity(len as usize);
-                buf.set_len((len as usize) - 1); // subtract 1 to skip the trailing null character
-                gl::GetProgramInfoLog(
-                    program,
-                    len,
-                    ptr::null_mut(),
-                    buf.as_mut_ptr() as *mut GLchar,
-                );
-                panic!(
-                    "{}",
-                    str::from_utf8(&buf)
-                        .ok()
-                        .expect("ProgramInfoLog not valid utf8")
-                );
+                let mut buf = vec![0u8; len.max(1) as usize];
+                let mut written = 0;
+                gl::GetProgramInfoLog(program, len, &mut written, buf.as_mut_ptr() as *mut GLchar);
+                buf.truncate(written as usize);
+
+                let log = String::from_utf8_lossy(&buf);
+                log_error!("Failed to link shader program:\n{}", log);
+                panic!("Failed to link shader program: {}", log);
             }
             program
         }
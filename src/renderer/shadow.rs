@@ -0,0 +1,390 @@
+use std::slice;
+
+use crate::math::{Mat4, Vec3, Vec4};
+use crate::renderer::{
+    camera::Camera,
+    framebuffer::{Framebuffer, FramebufferAttachment, FramebufferFormat, FramebufferSpec},
+    lighting::{Light, LightBuffer, LightEnvironment},
+    mesh::Mesh,
+    shader::{OpenGLShader, Shader},
+    state,
+    texture::Texture,
+};
+
+pub const MAX_CASCADES: usize = 4;
+pub const MAX_SPOT_SHADOWS: usize = 4;
+// first texture unit of the shadow maps, past the units materials use
+pub const SHADOW_TEXTURE_SLOT: u32 = 16;
+
+const SPOT_NEAR_PLANE: f32 = 0.05;
+
+static DEPTH_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_Position;
+
+    uniform mat4 u_LightViewProjection;
+    uniform mat4 u_Model;
+
+    void main() {
+        gl_Position = u_LightViewProjection * u_Model * vec4(a_Position, 1.0);
+    }
+";
+
+static DEPTH_FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    void main() {
+    }
+";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShadowSettings {
+    // width and height of every shadow map
+    pub resolution: u32,
+    // directional light cascades, 1 to `MAX_CASCADES`
+    pub cascade_count: usize,
+    // PCF kernel of (2 * radius + 1)^2 taps, 0 for a single filtered tap
+    pub pcf_radius: u32,
+    // subtracted from the compared depth, in shadow map depth units
+    pub depth_bias: f32,
+    // receivers are offset along their normal by this world distance
+    pub normal_bias: f32,
+    // directional shadows end this far from the camera
+    pub max_distance: f32,
+    // 0 splits the cascades evenly, 1 logarithmically
+    pub split_lambda: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self {
+            resolution: 2048,
+            cascade_count: 4,
+            pcf_radius: 1,
+            depth_bias: 0.0005,
+            normal_bias: 0.02,
+            max_distance: 100.0,
+            split_lambda: 0.75,
+        }
+    }
+}
+
+// std140 layout of the `Shadows` block in the lit shaders
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct ShadowBlock {
+    cascade_matrices: [[[f32; 4]; 4]; MAX_CASCADES],
+    spot_matrices: [[[f32; 4]; 4]; MAX_SPOT_SHADOWS],
+    cascade_splits: [f32; 4],
+    camera_forward: Vec4,
+    params: Vec4,
+    texel_size: Vec4,
+}
+
+impl Default for ShadowBlock {
+    fn default() -> Self {
+        Self {
+            cascade_matrices: [Mat4::IDENTITY.cols; MAX_CASCADES],
+            spot_matrices: [Mat4::IDENTITY.cols; MAX_SPOT_SHADOWS],
+            cascade_splits: [0.0; 4],
+            camera_forward: Vec4::ZERO,
+            params: Vec4::ZERO,
+            texel_size: Vec4::ZERO,
+        }
+    }
+}
+
+// Renders the shadow maps of the shadow casting lights in a
+// `LightEnvironment` and hands them to the lit shaders:
+//
+//     light_buffer.upload(&lights);
+//     shadow_renderer.render(&camera, &lights, &casters, &light_buffer);
+//     // draw with lit materials
+pub struct ShadowRenderer {
+    settings: ShadowSettings,
+    cascade_maps: Vec<Framebuffer>,
+    // created on first use
+    spot_maps: Vec<Framebuffer>,
+    depth_shader: OpenGLShader,
+}
+
+impl ShadowRenderer {
+    // requires a current OpenGL context
+    pub fn new(settings: ShadowSettings) -> Self {
+        let mut renderer = Self {
+            settings,
+            cascade_maps: Vec::new(),
+            spot_maps: Vec::new(),
+            depth_shader: OpenGLShader::new(DEPTH_VERTEX_SHADER, DEPTH_FRAGMENT_SHADER),
+        };
+        renderer.set_settings(settings);
+        renderer
+    }
+
+    pub fn get_settings(&self) -> &ShadowSettings {
+        &self.settings
+    }
+
+    // recreates the shadow maps when the resolution or cascade count changes
+    pub fn set_settings(&mut self, settings: ShadowSettings) {
+        assert!(
+            settings.cascade_count >= 1 && settings.cascade_count <= MAX_CASCADES,
+            "Cascade count must be between 1 and {}.",
+            MAX_CASCADES
+        );
+
+        let resolution_changed = settings.resolution != self.settings.resolution;
+        if resolution_changed || settings.cascade_count != self.cascade_maps.len() {
+            self.cascade_maps = (0..settings.cascade_count)
+                .map(|_| create_shadow_map(settings.resolution))
+                .collect();
+        }
+        if resolution_changed {
+            self.spot_maps.clear();
+        }
+        self.settings = settings;
+    }
+
+    pub fn get_cascade_map(&self, index: usize) -> &FramebufferAttachment {
+        get_depth(&self.cascade_maps[index])
+    }
+
+    pub fn get_spot_map(&self, index: usize) -> Option<&FramebufferAttachment> {
+        self.spot_maps.get(index).map(get_depth)
+    }
+
+    // Renders every shadow map, stores the light matrices in the shadow
+    // buffer of `light_buffer` and binds the maps to their texture units.
+    // `casters` are meshes with their world transform, meshes that do not
+    // cast shadows are skipped. Restores the framebuffer and viewport.
+    pub fn render(
+        &mut self,
+        camera: &dyn Camera,
+        lights: &LightEnvironment,
+        casters: &[(&Mesh, Mat4)],
+        light_buffer: &LightBuffer,
+    ) {
+        let settings = self.settings;
+        let mut block = ShadowBlock {
+            params: Vec4::new(
+                0.0,
+                settings.pcf_radius as f32,
+                settings.depth_bias,
+                settings.normal_bias,
+            ),
+            texel_size: Vec4::new(1.0 / settings.resolution as f32, 0.0, 0.0, 0.0),
+            ..ShadowBlock::default()
+        };
+
+        let mut previous_framebuffer = 0;
+        unsafe {
+            gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut previous_framebuffer);
+        }
+        let previous_viewport = state::get_viewport();
+        let depth_test = state::is_enabled(gl::DEPTH_TEST);
+
+        state::enable(gl::DEPTH_TEST);
+        state::enable(gl::POLYGON_OFFSET_FILL);
+        unsafe {
+            gl::PolygonOffset(2.0, 4.0);
+        }
+        self.depth_shader.bind();
+
+        let shadow_slots = lights.get_shadow_slots();
+        for (light, slot) in lights.lights.iter().zip(shadow_slots) {
+            match (light, slot) {
+                (Light::Directional(l), Some(_)) => {
+                    let cascades = compute_cascades(camera, l.direction, &settings);
+                    let (splits, matrices, forward) = match cascades {
+                        Some(cascades) => cascades,
+                        None => continue,
+                    };
+
+                    for (i, matrix) in matrices.iter().enumerate() {
+                        self.draw_casters(&self.cascade_maps[i], matrix, casters);
+                        block.cascade_matrices[i] = matrix.cols;
+                        block.cascade_splits[i] = splits[i];
+                    }
+                    block.camera_forward = forward;
+                    block.params.x = matrices.len() as f32;
+                }
+                (Light::Spot(l), Some(slot)) => {
+                    while self.spot_maps.len() <= slot {
+                        self.spot_maps.push(create_shadow_map(settings.resolution));
+                    }
+
+                    let up = get_up_vector(l.direction);
+                    let view = Mat4::look_at(l.position, l.position + l.direction, up);
+                    let projection = Mat4::perspective(
+                        l.outer_angle * 2.0,
+                        1.0,
+                        SPOT_NEAR_PLANE,
+                        l.range.max(SPOT_NEAR_PLANE * 2.0),
+                    );
+                    let matrix = projection * view;
+
+                    self.draw_casters(&self.spot_maps[slot], &matrix, casters);
+                    block.spot_matrices[slot] = matrix.cols;
+                }
+                _ => {}
+            }
+        }
+
+        self.depth_shader.unbind();
+        state::disable(gl::POLYGON_OFFSET_FILL);
+        state::set_enabled(gl::DEPTH_TEST, depth_test);
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous_framebuffer as u32);
+        }
+        state::set_viewport(
+            previous_viewport[0],
+            previous_viewport[1],
+            previous_viewport[2],
+            previous_viewport[3],
+        );
+
+        light_buffer
+            .get_shadow_buffer()
+            .set_data(slice::from_ref(&block));
+        light_buffer.get_shadow_buffer().bind();
+        self.bind_maps();
+    }
+
+    // binds the maps to their texture units again, e.g. after other code
+    // used those units
+    pub fn bind_maps(&self) {
+        for (i, map) in self.cascade_maps.iter().enumerate() {
+            get_depth(map).bind(SHADOW_TEXTURE_SLOT + i as u32);
+        }
+        for (i, map) in self.spot_maps.iter().enumerate() {
+            get_depth(map).bind(SHADOW_TEXTURE_SLOT + (MAX_CASCADES + i) as u32);
+        }
+    }
+
+    fn draw_casters(&self, target: &Framebuffer, light_matrix: &Mat4, casters: &[(&Mesh, Mat4)]) {
+        target.bind();
+        unsafe {
+            gl::Clear(gl::DEPTH_BUFFER_BIT);
+        }
+
+        self.depth_shader
+            .set_mat4("u_LightViewProjection", light_matrix);
+        for (mesh, transform) in casters.iter() {
+            if mesh.is_shadow_caster() {
+                self.depth_shader.set_mat4("u_Model", transform);
+                mesh.draw();
+            }
+        }
+    }
+}
+
+fn create_shadow_map(resolution: u32) -> Framebuffer {
+    let framebuffer = Framebuffer::new(FramebufferSpec {
+        width: resolution,
+        height: resolution,
+        samples: 1,
+        color_attachments: Vec::new(),
+        depth_attachment: Some(FramebufferFormat::Depth32F),
+        resize_with_window: false,
+    });
+    get_depth(&framebuffer).set_depth_compare(true);
+    framebuffer
+}
+
+fn get_depth(framebuffer: &Framebuffer) -> &FramebufferAttachment {
+    framebuffer.get_depth_attachment().unwrap()
+}
+
+fn get_up_vector(direction: Vec3) -> Vec3 {
+    if direction.normalize().y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    }
+}
+
+// Splits the camera frustum up to `max_distance` into cascades and fits an
+// orthographic light projection around each slice. Returns the view depth
+// at which each cascade ends, the light matrices and the camera forward
+// vector with the offset that turns a dot product into view depth.
+fn compute_cascades(
+    camera: &dyn Camera,
+    direction: Vec3,
+    settings: &ShadowSettings,
+) -> Option<([f32; MAX_CASCADES], Vec<Mat4>, Vec4)> {
+    let inverse = camera.get_view_projection().inverse()?;
+    let ndc = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let near_corners: Vec<Vec3> = ndc
+        .iter()
+        .map(|&(x, y)| inverse.transform_point(Vec3::new(x, y, -1.0)))
+        .collect();
+    let far_corners: Vec<Vec3> = ndc
+        .iter()
+        .map(|&(x, y)| inverse.transform_point(Vec3::new(x, y, 1.0)))
+        .collect();
+
+    let near_center = near_corners.iter().fold(Vec3::ZERO, |a, &c| a + c) * 0.25;
+    let far_center = far_corners.iter().fold(Vec3::ZERO, |a, &c| a + c) * 0.25;
+    let forward = (far_center - near_center).normalize();
+    let eye = camera.get_position();
+    let near = forward.dot(near_center - eye);
+    let far = forward.dot(far_center - eye);
+    let shadow_far = far.min(settings.max_distance).max(near + 0.001);
+
+    let count = settings.cascade_count;
+    let mut splits = [0.0; MAX_CASCADES];
+    for (i, split) in splits.iter_mut().enumerate().take(count) {
+        let t = (i + 1) as f32 / count as f32;
+        let uniform = near + (shadow_far - near) * t;
+        *split = if near > 0.0 {
+            let logarithmic = near * (shadow_far / near).powf(t);
+            settings.split_lambda * logarithmic + (1.0 - settings.split_lambda) * uniform
+        } else {
+            uniform
+        };
+    }
+
+    let direction = direction.normalize();
+    let up = get_up_vector(direction);
+    let texels = settings.resolution as f32;
+    let mut matrices = Vec::with_capacity(count);
+    let mut slice_near = near;
+
+    for &slice_far in splits.iter().take(count) {
+        // corners of the slice along the frustum edges
+        let a = (slice_near - near) / (far - near);
+        let b = (slice_far - near) / (far - near);
+        let corners: Vec<Vec3> = near_corners
+            .iter()
+            .zip(far_corners.iter())
+            .flat_map(|(&n, &f)| vec![n.lerp(f, a), n.lerp(f, b)])
+            .collect();
+
+        // a bounding sphere keeps the projection size constant while the
+        // camera turns, rounded to avoid jitter from float noise
+        let center = corners.iter().fold(Vec3::ZERO, |acc, &c| acc + c) / 8.0;
+        let radius = corners
+            .iter()
+            .map(|c| c.distance(center))
+            .fold(0.0, f32::max);
+        let radius = (radius * 16.0).ceil() / 16.0;
+
+        // casters up to `max_distance` in front of the slice are included
+        let back_off = radius + settings.max_distance;
+        let view = Mat4::look_at(center - direction * back_off, center, up);
+        let mut projection =
+            Mat4::orthographic(-radius, radius, -radius, radius, 0.0, back_off + radius);
+
+        // snap to whole texels so that moving the camera does not shimmer
+        let origin = (projection * view).transform_point(Vec3::ZERO);
+        let scale = texels * 0.5;
+        projection.cols[3][0] += (origin.x * scale).round() / scale - origin.x;
+        projection.cols[3][1] += (origin.y * scale).round() / scale - origin.y;
+
+        matrices.push(projection * view);
+        slice_near = slice_far;
+    }
+
+    Some((splits, matrices, forward.extend(-forward.dot(eye))))
+}
@@ -0,0 +1,415 @@
+use crate::math::{Vec2, Vec4};
+use crate::renderer::{
+    backend::RenderBackend,
+    bitmap::Bitmap,
+    command::{CommandBuffer, RenderCommand, Shading, Vertex},
+};
+
+// CPU reference rasterizer, follows OpenGL conventions (viewport origin at the
+// bottom left, depth range [0, 1], GL_LESS depth test) so that its output can be
+// compared with the OpenGL backend.
+pub struct SoftwareBackend {
+    color_buffer: Bitmap,
+    depth_buffer: Vec<f32>,
+    viewport: (i32, i32, u32, u32),
+    depth_test: bool,
+}
+
+#[derive(Clone, Copy)]
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    color: Vec4,
+}
+
+impl SoftwareBackend {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            color_buffer: Bitmap::new(width, height),
+            depth_buffer: vec![1.0; (width * height) as usize],
+            viewport: (0, 0, width, height),
+            depth_test: false,
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        *self = Self::new(width, height);
+    }
+
+    pub fn get_color_buffer(&self) -> &Bitmap {
+        &self.color_buffer
+    }
+
+    pub fn get_depth(&self, x: u32, y: u32) -> f32 {
+        self.depth_buffer[(y * self.color_buffer.get_width() + x) as usize]
+    }
+
+    fn clear(&mut self, color: Option<Vec4>, depth: Option<f32>) {
+        if let Some(c) = color {
+            self.color_buffer.fill(to_rgba8(c));
+        }
+        if let Some(d) = depth {
+            let d = d.clamp(0.0, 1.0);
+            self.depth_buffer.iter_mut().for_each(|v| *v = d);
+        }
+    }
+
+    fn draw_triangles(&mut self, vertices: &[Vertex], indices: Option<&[u32]>, shading: Shading) {
+        let screen: Vec<ScreenVertex> = vertices
+            .iter()
+            .map(|v| self.to_screen(v, shading))
+            .collect();
+
+        match indices {
+            Some(indices) => {
+                for tri in indices.chunks_exact(3) {
+                    self.rasterize(
+                        screen[tri[0] as usize],
+                        screen[tri[1] as usize],
+                        screen[tri[2] as usize],
+                    );
+                }
+            }
+            None => {
+                for tri in screen.chunks_exact(3) {
+                    self.rasterize(tri[0], tri[1], tri[2]);
+                }
+            }
+        }
+    }
+
+    fn to_screen(&self, v: &Vertex, shading: Shading) -> ScreenVertex {
+        let (vx, vy, vw, vh) = self.viewport;
+        let height = self.color_buffer.get_height() as f32;
+
+        // window coordinates have y pointing up, bitmap rows go top down
+        let x = vx as f32 + (v.position.x + 1.0) * 0.5 * vw as f32;
+        let y = vy as f32 + (v.position.y + 1.0) * 0.5 * vh as f32;
+
+        ScreenVertex {
+            x,
+            y: height - y,
+            z: (v.position.z + 1.0) * 0.5,
+            color: match shading {
+                Shading::Flat(color) => color,
+                Shading::VertexColor => v.color,
+            },
+        }
+    }
+
+    fn rasterize(&mut self, a: ScreenVertex, b: ScreenVertex, c: ScreenVertex) {
+        // both windings are drawn, normalize to a positive area
+        let (b, c) = if edge(&a, &b, &c.xy()) < 0.0 {
+            (c, b)
+        } else {
+            (b, c)
+        };
+        let area = edge(&a, &b, &c.xy());
+        if area <= 0.0 {
+            return;
+        }
+
+        // viewport and bitmap bounds, in bitmap rows
+        let (vx, vy, vw, vh) = self.viewport;
+        let width = self.color_buffer.get_width() as i32;
+        let height = self.color_buffer.get_height() as i32;
+        let clip_min_x = vx.max(0);
+        let clip_max_x = (vx + vw as i32).min(width);
+        let clip_min_y = (height - (vy + vh as i32)).max(0);
+        let clip_max_y = (height - vy).min(height);
+
+        let min_x = (a.x.min(b.x).min(c.x).floor() as i32).max(clip_min_x);
+        let max_x = (a.x.max(b.x).max(c.x).ceil() as i32).min(clip_max_x);
+        let min_y = (a.y.min(b.y).min(c.y).floor() as i32).max(clip_min_y);
+        let max_y = (a.y.max(b.y).max(c.y).ceil() as i32).min(clip_max_y);
+
+        let tl0 = is_top_left(&b, &c);
+        let tl1 = is_top_left(&c, &a);
+        let tl2 = is_top_left(&a, &b);
+
+        for py in min_y..max_y {
+            for px in min_x..max_x {
+                let p = Vec2::new(px as f32 + 0.5, py as f32 + 0.5);
+
+                let w0 = edge(&b, &c, &p);
+                let w1 = edge(&c, &a, &p);
+                let w2 = edge(&a, &b, &p);
+
+                if !covers(w0, tl0) || !covers(w1, tl1) || !covers(w2, tl2) {
+                    continue;
+                }
+
+                let (l0, l1, l2) = (w0 / area, w1 / area, w2 / area);
+
+                let z = l0 * a.z + l1 * b.z + l2 * c.z;
+                if !(0.0..=1.0).contains(&z) {
+                    continue;
+                }
+
+                let depth_index = (py * width + px) as usize;
+                if self.depth_test {
+                    if z >= self.depth_buffer[depth_index] {
+                        continue;
+                    }
+                    self.depth_buffer[depth_index] = z;
+                }
+
+                let color = a.color * l0 + b.color * l1 + c.color * l2;
+
+                self.color_buffer
+                    .set_pixel(px as u32, py as u32, to_rgba8(color));
+            }
+        }
+    }
+}
+
+impl RenderBackend for SoftwareBackend {
+    fn execute(&mut self, commands: &CommandBuffer) {
+        for command in commands.get_commands() {
+            match command {
+                RenderCommand::SetViewport {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => self.viewport = (*x, *y, *width, *height),
+
+                RenderCommand::SetDepthTest(enabled) => self.depth_test = *enabled,
+
+                RenderCommand::Clear { color, depth } => self.clear(*color, *depth),
+
+                RenderCommand::DrawTriangles {
+                    vertices,
+                    indices,
+                    shading,
+                } => self.draw_triangles(vertices, indices.as_deref(), *shading),
+            }
+        }
+    }
+}
+
+impl ScreenVertex {
+    fn xy(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+}
+
+fn edge(a: &ScreenVertex, b: &ScreenVertex, p: &Vec2) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+// top-left fill rule, pixels on a shared edge belong to exactly one triangle
+fn is_top_left(a: &ScreenVertex, b: &ScreenVertex) -> bool {
+    (a.y == b.y && b.x > a.x) || b.y < a.y
+}
+
+fn covers(w: f32, top_left: bool) -> bool {
+    w > 0.0 || (w == 0.0 && top_left)
+}
+
+fn to_rgba8(color: Vec4) -> [u8; 4] {
+    let mut out = [0; 4];
+    for (o, c) in out.iter_mut().zip(color.to_array().iter()) {
+        *o = (c.clamp(0.0, 1.0) * 255.0 + 0.5) as u8;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec3;
+
+    const RED: Vec4 = Vec4 {
+        x: 1.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+    const GREEN: Vec4 = Vec4 {
+        x: 0.0,
+        y: 1.0,
+        z: 0.0,
+        w: 1.0,
+    };
+    const BLUE: Vec4 = Vec4 {
+        x: 0.0,
+        y: 0.0,
+        z: 1.0,
+        w: 1.0,
+    };
+    const BLACK: Vec4 = Vec4 {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    fn triangle(points: [(f32, f32); 3], z: f32) -> Vec<Vertex> {
+        points
+            .iter()
+            .map(|&(x, y)| Vertex::new(Vec3::new(x, y, z), Vec4::ZERO))
+            .collect()
+    }
+
+    // covers the whole viewport
+    fn fullscreen(z: f32) -> Vec<Vertex> {
+        triangle([(-1.0, -1.0), (3.0, -1.0), (-1.0, 3.0)], z)
+    }
+
+    fn render(width: u32, height: u32, commands: &CommandBuffer) -> SoftwareBackend {
+        let mut backend = SoftwareBackend::new(width, height);
+        backend.execute(commands);
+        backend
+    }
+
+    fn covered_pixels(bitmap: &Bitmap, color: [u8; 4]) -> Vec<(u32, u32)> {
+        let mut pixels = Vec::new();
+        for y in 0..bitmap.get_height() {
+            for x in 0..bitmap.get_width() {
+                if bitmap.get_pixel(x, y) == color {
+                    pixels.push((x, y));
+                }
+            }
+        }
+        pixels
+    }
+
+    #[test]
+    fn triangle_covers_pixel_centers_inside() {
+        let mut commands = CommandBuffer::new();
+        commands.clear(Some(BLACK), None);
+        // lower left half of the viewport, the diagonal runs through the
+        // centers of the pixels on it
+        commands.draw_triangles(
+            &triangle([(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0)], 0.0),
+            Shading::Flat(RED),
+        );
+        let bitmap = render(4, 4, &commands);
+        let covered = covered_pixels(bitmap.get_color_buffer(), to_rgba8(RED));
+
+        // rows are top down, so the lower left half is below the diagonal
+        for y in 0..4 {
+            for x in 0..4 {
+                if x < y {
+                    assert!(covered.contains(&(x, y)), "({}, {}) not covered", x, y);
+                } else if x > y {
+                    assert!(!covered.contains(&(x, y)), "({}, {}) covered", x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn shared_edge_pixels_are_drawn_once() {
+        let quad = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        let halves = [[quad[0], quad[1], quad[2]], [quad[2], quad[3], quad[0]]];
+
+        let covered: Vec<Vec<(u32, u32)>> = halves
+            .iter()
+            .map(|half| {
+                let mut commands = CommandBuffer::new();
+                commands.clear(Some(BLACK), None);
+                commands.draw_triangles(&triangle(*half, 0.0), Shading::Flat(RED));
+                let backend = render(4, 4, &commands);
+                covered_pixels(backend.get_color_buffer(), to_rgba8(RED))
+            })
+            .collect();
+
+        assert_eq!(covered[0].len() + covered[1].len(), 16);
+        assert!(covered[0].iter().all(|p| !covered[1].contains(p)));
+    }
+
+    #[test]
+    fn both_windings_are_drawn() {
+        let mut commands = CommandBuffer::new();
+        commands.clear(Some(BLACK), None);
+        commands.draw_triangles(
+            &triangle([(-1.0, -1.0), (-1.0, 3.0), (3.0, -1.0)], 0.0),
+            Shading::Flat(RED),
+        );
+        let backend = render(4, 4, &commands);
+
+        assert_eq!(
+            covered_pixels(backend.get_color_buffer(), to_rgba8(RED)).len(),
+            16
+        );
+    }
+
+    #[test]
+    fn depth_test_keeps_nearest() {
+        let mut commands = CommandBuffer::new();
+        commands.set_depth_test(true);
+        commands.clear(Some(BLACK), Some(1.0));
+        commands.draw_triangles(&fullscreen(0.5), Shading::Flat(RED));
+        commands.draw_triangles(&fullscreen(0.0), Shading::Flat(GREEN));
+        commands.draw_triangles(&fullscreen(0.8), Shading::Flat(BLUE));
+        let backend = render(2, 2, &commands);
+
+        assert_eq!(backend.get_color_buffer().get_pixel(1, 1), to_rgba8(GREEN));
+        assert!((backend.get_depth(1, 1) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn without_depth_test_last_draw_wins() {
+        let mut commands = CommandBuffer::new();
+        commands.clear(Some(BLACK), Some(1.0));
+        commands.draw_triangles(&fullscreen(0.0), Shading::Flat(GREEN));
+        commands.draw_triangles(&fullscreen(0.8), Shading::Flat(BLUE));
+        let backend = render(2, 2, &commands);
+
+        assert_eq!(backend.get_color_buffer().get_pixel(0, 0), to_rgba8(BLUE));
+        assert_eq!(backend.get_depth(0, 0), 1.0);
+    }
+
+    #[test]
+    fn viewport_origin_is_bottom_left() {
+        let mut commands = CommandBuffer::new();
+        commands.clear(Some(BLACK), None);
+        commands.set_viewport(0, 0, 2, 2);
+        commands.draw_triangles(&fullscreen(0.0), Shading::Flat(RED));
+        let backend = render(4, 4, &commands);
+
+        let mut covered = covered_pixels(backend.get_color_buffer(), to_rgba8(RED));
+        covered.sort();
+        assert_eq!(covered, vec![(0, 2), (0, 3), (1, 2), (1, 3)]);
+    }
+
+    #[test]
+    fn vertex_colors_are_interpolated() {
+        let vertices: Vec<Vertex> = fullscreen(0.0)
+            .into_iter()
+            .map(|v| Vertex::new(v.position, GREEN))
+            .collect();
+        let mut commands = CommandBuffer::new();
+        commands.draw_indexed(&vertices, &[0, 1, 2], Shading::VertexColor);
+        let backend = render(2, 2, &commands);
+
+        assert_eq!(backend.get_color_buffer().get_pixel(0, 0), to_rgba8(GREEN));
+    }
+
+    #[test]
+    fn matches_golden_image() {
+        // red lower left half over blue, the diagonal pixels belong to blue
+        let mut commands = CommandBuffer::new();
+        commands.clear(Some(BLUE), None);
+        commands.draw_triangles(
+            &triangle([(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0)], 0.0),
+            Shading::Flat(RED),
+        );
+        let backend = render(4, 4, &commands);
+
+        let mut golden = Bitmap::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                let color = if x < y { RED } else { BLUE };
+                golden.set_pixel(x, y, to_rgba8(color));
+            }
+        }
+
+        let image = backend.get_color_buffer();
+        assert_eq!(image.count_mismatches(&golden, 0), 0);
+        assert!(image.matches(&golden, 1));
+    }
+}
@@ -0,0 +1,1108 @@
+use std::{cmp::Ordering, collections::HashMap, fs, path::Path, rc::Rc};
+
+use serde_json::Value;
+
+use crate::log_warn;
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::renderer::{
+    renderer2d::Renderer2D,
+    texture::{ColorSpace, SamplerSettings, Texture2D},
+};
+
+// frame duration of clips listing no durations
+const DEFAULT_FRAME_DURATION: f32 = 0.1;
+
+// Rectangle of an atlas holding one sprite. Pixel coordinates have their
+// origin at the top left of the image, as in atlas files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpriteRegion {
+    name: String,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    // corners counter-clockwise from the bottom left, as `Renderer2D` takes
+    tex_coords: [Vec2; 4],
+    // untrimmed size and the position of the region inside it, in pixels
+    // from the top left
+    source_size: Vec2,
+    trim_offset: Vec2,
+    // seconds, from the atlas file
+    duration: Option<f32>,
+}
+
+impl SpriteRegion {
+    fn new(name: &str, x: u32, y: u32, width: u32, height: u32, texture_size: (u32, u32)) -> Self {
+        assert!(
+            x + width <= texture_size.0 && y + height <= texture_size.1,
+            "Sprite region {} is outside of the texture.",
+            name
+        );
+
+        let (texture_width, texture_height) = (texture_size.0 as f32, texture_size.1 as f32);
+        let left = x as f32 / texture_width;
+        let right = (x + width) as f32 / texture_width;
+        // rows are flipped on upload
+        let top = 1.0 - y as f32 / texture_height;
+        let bottom = 1.0 - (y + height) as f32 / texture_height;
+
+        Self {
+            name: name.to_string(),
+            x,
+            y,
+            width,
+            height,
+            tex_coords: [
+                Vec2::new(left, bottom),
+                Vec2::new(right, bottom),
+                Vec2::new(right, top),
+                Vec2::new(left, top),
+            ],
+            source_size: Vec2::new(width as f32, height as f32),
+            trim_offset: Vec2::ZERO,
+            duration: None,
+        }
+    }
+
+    // TexturePacker stores rotated sprites turned 90 degrees clockwise, turns
+    // the texture coordinates of a region created with the rotated size back
+    fn rotate(&mut self) {
+        let [bottom_left, bottom_right, top_right, top_left] = self.tex_coords;
+        self.tex_coords = [top_left, bottom_left, bottom_right, top_right];
+        std::mem::swap(&mut self.width, &mut self.height);
+        self.source_size = self.get_size();
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // (x, y, width, height) in pixels, width and height before any rotation
+    pub fn get_rect(&self) -> (u32, u32, u32, u32) {
+        (self.x, self.y, self.width, self.height)
+    }
+
+    pub fn get_tex_coords(&self) -> &[Vec2; 4] {
+        &self.tex_coords
+    }
+
+    pub fn get_size(&self) -> Vec2 {
+        Vec2::new(self.width as f32, self.height as f32)
+    }
+
+    pub fn get_source_size(&self) -> Vec2 {
+        self.source_size
+    }
+
+    pub fn get_trim_offset(&self) -> Vec2 {
+        self.trim_offset
+    }
+
+    pub fn get_duration(&self) -> Option<f32> {
+        self.duration
+    }
+
+    pub fn is_trimmed(&self) -> bool {
+        self.trim_offset != Vec2::ZERO || self.source_size != self.get_size()
+    }
+}
+
+// A texture split into named sprite regions, sliced from a grid or read from
+// an Aseprite or TexturePacker JSON file.
+pub struct TextureAtlas {
+    texture: Rc<Texture2D>,
+    regions: Vec<SpriteRegion>,
+    names: HashMap<String, usize>,
+    // from the frame tags or animations of the atlas file
+    clips: Vec<AnimationClip>,
+}
+
+impl TextureAtlas {
+    pub fn new(texture: Rc<Texture2D>) -> Self {
+        Self {
+            texture,
+            regions: Vec::new(),
+            names: HashMap::new(),
+            clips: Vec::new(),
+        }
+    }
+
+    // Cells of `cell_size` pixels, `margin` pixels from the image border and
+    // `spacing` pixels apart. Regions are named by their index, row by row
+    // from the top left.
+    pub fn from_grid(
+        texture: Rc<Texture2D>,
+        cell_size: (u32, u32),
+        spacing: u32,
+        margin: u32,
+    ) -> Self {
+        assert!(
+            cell_size.0 > 0 && cell_size.1 > 0,
+            "Sprite cells need a non-zero size."
+        );
+
+        let spec = texture.get_spec();
+        let columns = (spec.width + spacing).saturating_sub(margin * 2) / (cell_size.0 + spacing);
+        let rows = (spec.height + spacing).saturating_sub(margin * 2) / (cell_size.1 + spacing);
+
+        let mut atlas = Self::new(texture);
+        for row in 0..rows {
+            for column in 0..columns {
+                let index = atlas.regions.len();
+                atlas.add_region(
+                    &index.to_string(),
+                    margin + column * (cell_size.0 + spacing),
+                    margin + row * (cell_size.1 + spacing),
+                    cell_size.0,
+                    cell_size.1,
+                );
+            }
+        }
+        atlas
+    }
+
+    // Aseprite or TexturePacker JSON, in the hash or the array format. The
+    // image is looked up relative to the JSON file.
+    pub fn from_json<P: AsRef<Path>>(
+        path: P,
+        color_space: ColorSpace,
+        sampler: SamplerSettings,
+    ) -> Result<Self, String> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read atlas {:?}: {}", path, e))?;
+        let json: Value = serde_json::from_str(&source)
+            .map_err(|e| format!("Failed to parse atlas {:?}: {}", path, e))?;
+
+        let image = json["meta"]["image"]
+            .as_str()
+            .ok_or_else(|| format!("Atlas {:?} names no image", path))?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let texture = Texture2D::from_file(base_dir.join(image), color_space, sampler)?;
+
+        Self::from_json_value(Rc::new(texture), &json)
+            .map_err(|e| format!("Invalid atlas {:?}: {}", path, e))
+    }
+
+    // parses an atlas file for an already loaded texture
+    pub fn from_json_str(texture: Rc<Texture2D>, source: &str) -> Result<Self, String> {
+        let json: Value =
+            serde_json::from_str(source).map_err(|e| format!("Failed to parse atlas: {}", e))?;
+        Self::from_json_value(texture, &json)
+    }
+
+    fn from_json_value(texture: Rc<Texture2D>, json: &Value) -> Result<Self, String> {
+        let spec = texture.get_spec();
+        let (regions, clips) = parse_atlas(json, (spec.width, spec.height))?;
+
+        let mut atlas = Self::new(texture);
+        for region in regions {
+            atlas.push_region(region);
+        }
+        atlas.clips = clips;
+        Ok(atlas)
+    }
+
+    // Returns the index of the new region. Names are unique, adding a region
+    // under a taken name makes it refer to the new one.
+    pub fn add_region(&mut self, name: &str, x: u32, y: u32, width: u32, height: u32) -> usize {
+        let spec = self.texture.get_spec();
+        let region = SpriteRegion::new(name, x, y, width, height, (spec.width, spec.height));
+        self.push_region(region)
+    }
+
+    fn push_region(&mut self, region: SpriteRegion) -> usize {
+        let index = self.regions.len();
+        self.names.insert(region.name.clone(), index);
+        self.regions.push(region);
+        index
+    }
+
+    pub fn get_texture(&self) -> &Rc<Texture2D> {
+        &self.texture
+    }
+
+    pub fn get_regions(&self) -> &[SpriteRegion] {
+        &self.regions
+    }
+
+    pub fn get_region(&self, index: usize) -> &SpriteRegion {
+        &self.regions[index]
+    }
+
+    pub fn find_region(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    pub fn get_clips(&self) -> &[AnimationClip] {
+        &self.clips
+    }
+
+    pub fn find_clip(&self, name: &str) -> Option<&AnimationClip> {
+        self.clips.iter().find(|clip| clip.name == name)
+    }
+
+    // Draws a region through a 2D batch, between `begin_scene` and
+    // `end_scene`. `size` is the untrimmed size in world units, a negative
+    // width or height mirrors the sprite.
+    pub fn draw(
+        &self,
+        renderer: &mut Renderer2D,
+        region: usize,
+        position: Vec3,
+        size: Vec2,
+        rotation: f32,
+        tint: Vec4,
+    ) {
+        let region = &self.regions[region];
+        let region_size = region.get_size();
+
+        // center of the trimmed rectangle relative to the untrimmed one
+        let center = Vec2::new(
+            (region.trim_offset.x + region_size.x * 0.5) / region.source_size.x - 0.5,
+            0.5 - (region.trim_offset.y + region_size.y * 0.5) / region.source_size.y,
+        );
+        let scale = Vec2::new(
+            size.x * region_size.x / region.source_size.x,
+            size.y * region_size.y / region.source_size.y,
+        );
+
+        let mut transform = Mat4::translation(position);
+        if rotation != 0.0 {
+            transform = transform * Mat4::rotation_z(rotation);
+        }
+        if region.is_trimmed() {
+            transform =
+                transform * Mat4::translation(Vec3::new(center.x * size.x, center.y * size.y, 0.0));
+        }
+        transform = transform * Mat4::scale(Vec3::new(scale.x, scale.y, 1.0));
+
+        renderer.draw_textured_quad_transform(
+            &transform,
+            self.texture.as_ref(),
+            &region.tex_coords,
+            1.0,
+            tint,
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopMode {
+    // stops on the last frame
+    Once,
+    Loop,
+    // forward then backward, endlessly
+    PingPong,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnimationFrame {
+    // region of the atlas shown
+    pub region: usize,
+    // seconds
+    pub duration: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationClip {
+    name: String,
+    frames: Vec<AnimationFrame>,
+    loop_mode: LoopMode,
+    // (frame, name) pairs, reported whenever the frame is entered
+    events: Vec<(usize, String)>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, frames: Vec<AnimationFrame>, loop_mode: LoopMode) -> Self {
+        assert!(!frames.is_empty(), "Animation clip {} has no frames.", name);
+        assert!(
+            frames.iter().all(|frame| frame.duration > 0.0),
+            "Animation clip {} has frames without duration.",
+            name
+        );
+
+        Self {
+            name: name.to_string(),
+            frames,
+            loop_mode,
+            events: Vec::new(),
+        }
+    }
+
+    // frames of the regions, each lasting its atlas duration if any
+    pub fn from_regions(
+        name: &str,
+        atlas: &TextureAtlas,
+        regions: &[usize],
+        loop_mode: LoopMode,
+    ) -> Self {
+        Self::from_atlas_regions(name, &atlas.regions, regions, loop_mode)
+    }
+
+    fn from_atlas_regions(
+        name: &str,
+        atlas_regions: &[SpriteRegion],
+        regions: &[usize],
+        loop_mode: LoopMode,
+    ) -> Self {
+        let frames = regions
+            .iter()
+            .map(|region| AnimationFrame {
+                region: *region,
+                duration: atlas_regions[*region]
+                    .duration
+                    .filter(|duration| *duration > 0.0)
+                    .unwrap_or(DEFAULT_FRAME_DURATION),
+            })
+            .collect();
+        Self::new(name, frames, loop_mode)
+    }
+
+    // `frame_rate` frames per second
+    pub fn with_frame_rate(mut self, frame_rate: f32) -> Self {
+        assert!(frame_rate > 0.0, "Animation frame rate must be positive.");
+        for frame in self.frames.iter_mut() {
+            frame.duration = 1.0 / frame_rate;
+        }
+        self
+    }
+
+    pub fn with_loop_mode(mut self, loop_mode: LoopMode) -> Self {
+        self.loop_mode = loop_mode;
+        self
+    }
+
+    pub fn with_event(mut self, frame: usize, name: &str) -> Self {
+        assert!(
+            frame < self.frames.len(),
+            "Animation clip {} has no frame {}.",
+            self.name,
+            frame
+        );
+        self.events.push((frame, name.to_string()));
+        self
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_frames(&self) -> &[AnimationFrame] {
+        &self.frames
+    }
+
+    pub fn get_loop_mode(&self) -> LoopMode {
+        self.loop_mode
+    }
+
+    pub fn get_events(&self) -> &[(usize, String)] {
+        &self.events
+    }
+
+    // of a single pass over the frames
+    pub fn get_duration(&self) -> f32 {
+        self.frames.iter().map(|frame| frame.duration).sum()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationEvent {
+    // an event of the clip, its frame was entered
+    Frame {
+        clip: String,
+        frame: usize,
+        name: String,
+    },
+    // a looping clip started over, ping-pong clips after going back and forth
+    Looped {
+        clip: String,
+    },
+    // a `LoopMode::Once` clip reached the end of its last frame
+    Finished {
+        clip: String,
+    },
+}
+
+// Plays the clips of one atlas, one at a time. Events collect until taken
+// with `drain_events`.
+pub struct AnimationPlayer {
+    atlas: Rc<TextureAtlas>,
+    clips: HashMap<String, Rc<AnimationClip>>,
+    playback: Playback,
+}
+
+impl AnimationPlayer {
+    // starts out with the clips of the atlas file
+    pub fn new(atlas: Rc<TextureAtlas>) -> Self {
+        let clips = atlas
+            .clips
+            .iter()
+            .map(|clip| (clip.name.clone(), Rc::new(clip.clone())))
+            .collect();
+
+        Self {
+            atlas,
+            clips,
+            playback: Playback::new(),
+        }
+    }
+
+    // replaces a clip of the same name
+    pub fn add_clip(&mut self, clip: AnimationClip) {
+        assert!(
+            clip.frames
+                .iter()
+                .all(|frame| frame.region < self.atlas.regions.len()),
+            "Animation clip {} shows regions missing from the atlas.",
+            clip.name
+        );
+        self.clips.insert(clip.name.clone(), Rc::new(clip));
+    }
+
+    pub fn with_clip(mut self, clip: AnimationClip) -> Self {
+        self.add_clip(clip);
+        self
+    }
+
+    pub fn get_atlas(&self) -> &Rc<TextureAtlas> {
+        &self.atlas
+    }
+
+    pub fn has_clip(&self, name: &str) -> bool {
+        self.clips.contains_key(name)
+    }
+
+    // Only resumes when the clip is already the current one, so that it can
+    // be called every frame with the state of an agent.
+    pub fn play(&mut self, name: &str) {
+        if self.get_clip_name() == Some(name) {
+            self.resume();
+        } else {
+            self.play_from_start(name);
+        }
+    }
+
+    pub fn play_from_start(&mut self, name: &str) {
+        let clip = match self.clips.get(name) {
+            Some(clip) => clip.clone(),
+            None => panic!("Animation player has no clip {}.", name),
+        };
+        self.playback.start(clip);
+    }
+
+    // keeps showing the current frame
+    pub fn pause(&mut self) {
+        self.playback.is_playing = false;
+    }
+
+    pub fn resume(&mut self) {
+        self.playback.resume();
+    }
+
+    pub fn stop(&mut self) {
+        self.playback.stop();
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_playing
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.playback.is_finished
+    }
+
+    pub fn get_speed(&self) -> f32 {
+        self.playback.speed
+    }
+
+    // multiplies the frame durations' pace, 2 plays twice as fast
+    pub fn set_speed(&mut self, speed: f32) {
+        assert!(speed >= 0.0, "Animation speed must not be negative.");
+        self.playback.speed = speed;
+    }
+
+    pub fn get_clip_name(&self) -> Option<&str> {
+        self.playback
+            .current
+            .as_ref()
+            .map(|clip| clip.name.as_str())
+    }
+
+    pub fn get_frame_index(&self) -> usize {
+        self.playback.frame
+    }
+
+    // region shown, none while no clip is played
+    pub fn get_region(&self) -> Option<usize> {
+        self.playback.get_region()
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.playback.update(dt);
+    }
+
+    pub fn drain_events(&mut self) -> Vec<AnimationEvent> {
+        std::mem::take(&mut self.playback.events)
+    }
+
+    // draws the current frame, see `TextureAtlas::draw`
+    pub fn draw(
+        &self,
+        renderer: &mut Renderer2D,
+        position: Vec3,
+        size: Vec2,
+        rotation: f32,
+        tint: Vec4,
+    ) {
+        if let Some(region) = self.get_region() {
+            self.atlas
+                .draw(renderer, region, position, size, rotation, tint);
+        }
+    }
+}
+
+// position in the current clip of a player, independent of the atlas
+struct Playback {
+    current: Option<Rc<AnimationClip>>,
+    frame: usize,
+    frame_time: f32,
+    is_reversing: bool,
+    speed: f32,
+    is_playing: bool,
+    is_finished: bool,
+    events: Vec<AnimationEvent>,
+}
+
+impl Playback {
+    fn new() -> Self {
+        Self {
+            current: None,
+            frame: 0,
+            frame_time: 0.0,
+            is_reversing: false,
+            speed: 1.0,
+            is_playing: false,
+            is_finished: false,
+            events: Vec::new(),
+        }
+    }
+
+    fn start(&mut self, clip: Rc<AnimationClip>) {
+        self.current = Some(clip);
+        self.frame = 0;
+        self.frame_time = 0.0;
+        self.is_reversing = false;
+        self.is_playing = true;
+        self.is_finished = false;
+        self.enter_frame();
+    }
+
+    fn resume(&mut self) {
+        if self.current.is_some() && !self.is_finished {
+            self.is_playing = true;
+        }
+    }
+
+    fn stop(&mut self) {
+        self.current = None;
+        self.is_playing = false;
+        self.is_finished = false;
+    }
+
+    fn get_region(&self) -> Option<usize> {
+        self.current
+            .as_ref()
+            .map(|clip| clip.frames[self.frame].region)
+    }
+
+    fn update(&mut self, dt: f32) {
+        let clip = match (&self.current, self.is_playing) {
+            (Some(clip), true) => clip.clone(),
+            _ => return,
+        };
+
+        self.frame_time += dt * self.speed;
+        while self.is_playing && self.frame_time >= clip.frames[self.frame].duration {
+            self.frame_time -= clip.frames[self.frame].duration;
+            self.advance(&clip);
+        }
+    }
+
+    fn advance(&mut self, clip: &AnimationClip) {
+        let last = clip.frames.len() - 1;
+        match clip.loop_mode {
+            LoopMode::Once => {
+                if self.frame == last {
+                    self.frame_time = 0.0;
+                    self.is_playing = false;
+                    self.is_finished = true;
+                    self.events.push(AnimationEvent::Finished {
+                        clip: clip.name.clone(),
+                    });
+                    return;
+                }
+                self.frame += 1;
+            }
+            LoopMode::Loop => {
+                if self.frame == last {
+                    self.frame = 0;
+                    self.events.push(AnimationEvent::Looped {
+                        clip: clip.name.clone(),
+                    });
+                } else {
+                    self.frame += 1;
+                }
+            }
+            LoopMode::PingPong => {
+                if last == 0 {
+                    self.events.push(AnimationEvent::Looped {
+                        clip: clip.name.clone(),
+                    });
+                } else if self.is_reversing {
+                    self.frame -= 1;
+                    if self.frame == 0 {
+                        self.is_reversing = false;
+                        self.events.push(AnimationEvent::Looped {
+                            clip: clip.name.clone(),
+                        });
+                    }
+                } else {
+                    self.frame += 1;
+                    self.is_reversing = self.frame == last;
+                }
+            }
+        }
+        self.enter_frame();
+    }
+
+    fn enter_frame(&mut self) {
+        let clip = match &self.current {
+            Some(clip) => clip,
+            None => return,
+        };
+        for (frame, name) in clip.events.iter() {
+            if *frame == self.frame {
+                self.events.push(AnimationEvent::Frame {
+                    clip: clip.name.clone(),
+                    frame: *frame,
+                    name: name.clone(),
+                });
+            }
+        }
+    }
+}
+
+// Regions and clips of an Aseprite or TexturePacker atlas file, for a texture
+// of `texture_size` pixels. Objects are unordered, so frames of the hash
+// format are ordered by name with numbers compared by value, which keeps
+// Aseprite frame tags pointing at the right frames.
+fn parse_atlas(
+    json: &Value,
+    texture_size: (u32, u32),
+) -> Result<(Vec<SpriteRegion>, Vec<AnimationClip>), String> {
+    let mut frames: Vec<(String, &Value)> = match &json["frames"] {
+        Value::Array(frames) => frames
+            .iter()
+            .map(|frame| {
+                let name = frame["filename"].as_str().unwrap_or_default().to_string();
+                (name, frame)
+            })
+            .collect(),
+        Value::Object(frames) => frames
+            .iter()
+            .map(|(name, frame)| (name.clone(), frame))
+            .collect(),
+        _ => return Err("no frames".to_string()),
+    };
+    if json["frames"].is_object() {
+        frames.sort_by(|a, b| natural_cmp(&a.0, &b.0));
+    }
+
+    let mut regions = Vec::new();
+    for (i, (name, frame)) in frames.iter().enumerate() {
+        let rect = &frame["frame"];
+        let (x, y, width, height) = (
+            get_u32(rect, "x")?,
+            get_u32(rect, "y")?,
+            get_u32(rect, "w")?,
+            get_u32(rect, "h")?,
+        );
+        let rotated = frame["rotated"].as_bool().unwrap_or(false);
+        let name = if name.is_empty() {
+            i.to_string()
+        } else {
+            name.clone()
+        };
+
+        // TexturePacker keeps the unrotated size in the frame rect
+        let mut region = if rotated {
+            let mut region = SpriteRegion::new(&name, x, y, height, width, texture_size);
+            region.rotate();
+            region
+        } else {
+            SpriteRegion::new(&name, x, y, width, height, texture_size)
+        };
+
+        if let Some(size) = frame.get("sourceSize") {
+            region.source_size = Vec2::new(get_u32(size, "w")? as f32, get_u32(size, "h")? as f32);
+        }
+        if let Some(offset) = frame.get("spriteSourceSize") {
+            region.trim_offset =
+                Vec2::new(get_u32(offset, "x")? as f32, get_u32(offset, "y")? as f32);
+        }
+        // Aseprite durations are in milliseconds
+        region.duration = frame["duration"].as_f64().map(|ms| ms as f32 / 1000.0);
+        regions.push(region);
+    }
+
+    let mut clips = Vec::new();
+
+    // Aseprite frame tags
+    if let Some(tags) = json["meta"]["frameTags"].as_array() {
+        for tag in tags {
+            let name = tag["name"].as_str().unwrap_or_default();
+            let (from, to) = (get_u32(tag, "from")? as usize, get_u32(tag, "to")? as usize);
+            if from > to || to >= regions.len() {
+                log_warn!("Frame tag {} is out of range, skipping it", name);
+                continue;
+            }
+
+            let mut indices: Vec<usize> = (from..=to).collect();
+            let loop_mode = match tag["direction"].as_str().unwrap_or("forward") {
+                "pingpong" => LoopMode::PingPong,
+                "reverse" => {
+                    indices.reverse();
+                    LoopMode::Loop
+                }
+                "pingpong_reverse" => {
+                    indices.reverse();
+                    LoopMode::PingPong
+                }
+                _ => LoopMode::Loop,
+            };
+            let loop_mode = match tag["repeat"].as_str() {
+                Some("1") => LoopMode::Once,
+                _ => loop_mode,
+            };
+
+            clips.push(AnimationClip::from_atlas_regions(
+                name, &regions, &indices, loop_mode,
+            ));
+        }
+    }
+
+    // TexturePacker animations, lists of frame names, later regions win
+    // like the names of the atlas
+    if let Some(animations) = json["animations"].as_object() {
+        for (name, frames) in animations {
+            let indices: Option<Vec<usize>> = frames
+                .as_array()
+                .map(|frames| {
+                    frames
+                        .iter()
+                        .map(|frame| {
+                            let frame = frame.as_str()?;
+                            regions.iter().rposition(|region| region.name == frame)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            match indices {
+                Some(indices) if !indices.is_empty() => {
+                    clips.push(AnimationClip::from_atlas_regions(
+                        name,
+                        &regions,
+                        &indices,
+                        LoopMode::Loop,
+                    ));
+                }
+                _ => {
+                    log_warn!("Animation {} names unknown frames, skipping it", name);
+                }
+            }
+        }
+    }
+
+    Ok((regions, clips))
+}
+
+fn get_u32(value: &Value, key: &str) -> Result<u32, String> {
+    value[key]
+        .as_u64()
+        .map(|n| n as u32)
+        .ok_or_else(|| format!("missing or invalid {}", key))
+}
+
+// compares runs of digits by value, "frame 2" before "frame 10"
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        match (a.chars().next(), b.chars().next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let a_len = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+                let b_len = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+                let (a_digits, b_digits) = (
+                    a[..a_len].trim_start_matches('0'),
+                    b[..b_len].trim_start_matches('0'),
+                );
+                let ordering = a_digits
+                    .len()
+                    .cmp(&b_digits.len())
+                    .then_with(|| a_digits.cmp(b_digits));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a = &a[a_len..];
+                b = &b[b_len..];
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a = &a[x.len_utf8()..];
+                b = &b[y.len_utf8()..];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXTURE_SIZE: (u32, u32) = (64, 32);
+
+    fn parse(source: &str) -> (Vec<SpriteRegion>, Vec<AnimationClip>) {
+        parse_atlas(&serde_json::from_str(source).unwrap(), TEXTURE_SIZE).unwrap()
+    }
+
+    fn clip_regions(clip: &AnimationClip) -> Vec<usize> {
+        clip.get_frames().iter().map(|frame| frame.region).collect()
+    }
+
+    fn playback(loop_mode: LoopMode) -> Playback {
+        let frames = (0..3)
+            .map(|region| AnimationFrame {
+                region,
+                duration: 0.25,
+            })
+            .collect();
+        let clip = AnimationClip::new("walk", frames, loop_mode).with_event(1, "step");
+        let mut playback = Playback::new();
+        playback.start(Rc::new(clip));
+        playback
+    }
+
+    // frame shown after each update of `dt`
+    fn step(playback: &mut Playback, dt: f32, count: usize) -> Vec<usize> {
+        (0..count)
+            .map(|_| {
+                playback.update(dt);
+                playback.frame
+            })
+            .collect()
+    }
+
+    #[test]
+    fn aseprite_hash_frames_are_ordered_by_number() {
+        let (regions, clips) = parse(
+            r#"{
+                "frames": {
+                    "run 10.png": { "frame": { "x": 32, "y": 0, "w": 16, "h": 16 }, "duration": 50 },
+                    "run 2.png": { "frame": { "x": 16, "y": 0, "w": 16, "h": 16 }, "duration": 100 },
+                    "run 1.png": { "frame": { "x": 0, "y": 0, "w": 16, "h": 16 }, "duration": 100 }
+                },
+                "meta": {
+                    "image": "run.png",
+                    "frameTags": [
+                        { "name": "run", "from": 0, "to": 2, "direction": "forward" },
+                        { "name": "back", "from": 0, "to": 2, "direction": "reverse" },
+                        { "name": "bounce", "from": 1, "to": 2, "direction": "pingpong" },
+                        { "name": "once", "from": 0, "to": 1, "direction": "forward", "repeat": "1" },
+                        { "name": "broken", "from": 1, "to": 5, "direction": "forward" }
+                    ]
+                }
+            }"#,
+        );
+
+        let names: Vec<&str> = regions.iter().map(|region| region.get_name()).collect();
+        assert_eq!(names, vec!["run 1.png", "run 2.png", "run 10.png"]);
+        assert_eq!(regions[2].get_rect(), (32, 0, 16, 16));
+        assert_eq!(regions[2].get_duration(), Some(0.05));
+        assert!(!regions[0].is_trimmed());
+
+        let clip_names: Vec<&str> = clips.iter().map(|clip| clip.get_name()).collect();
+        assert_eq!(clip_names, vec!["run", "back", "bounce", "once"]);
+        assert_eq!(clip_regions(&clips[0]), vec![0, 1, 2]);
+        assert_eq!(clips[0].get_loop_mode(), LoopMode::Loop);
+        assert_eq!(clips[0].get_frames()[2].duration, 0.05);
+        assert_eq!(clip_regions(&clips[1]), vec![2, 1, 0]);
+        assert_eq!(clips[1].get_loop_mode(), LoopMode::Loop);
+        assert_eq!(clip_regions(&clips[2]), vec![1, 2]);
+        assert_eq!(clips[2].get_loop_mode(), LoopMode::PingPong);
+        assert_eq!(clips[3].get_loop_mode(), LoopMode::Once);
+    }
+
+    #[test]
+    fn texture_packer_array_frames_keep_their_order() {
+        let (regions, clips) = parse(
+            r#"{
+                "frames": [
+                    {
+                        "filename": "idle",
+                        "frame": { "x": 0, "y": 0, "w": 16, "h": 8 },
+                        "rotated": true,
+                        "trimmed": false
+                    },
+                    {
+                        "filename": "jump",
+                        "frame": { "x": 16, "y": 16, "w": 12, "h": 10 },
+                        "rotated": false,
+                        "trimmed": true,
+                        "spriteSourceSize": { "x": 2, "y": 4, "w": 12, "h": 10 },
+                        "sourceSize": { "w": 16, "h": 16 }
+                    }
+                ],
+                "animations": {
+                    "hop": ["idle", "jump", "idle"],
+                    "missing": ["idle", "fly"]
+                },
+                "meta": { "image": "sheet.png" }
+            }"#,
+        );
+
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[0].get_name(), "idle");
+
+        // stored turned clockwise in an 8x16 rectangle
+        let rotated = &regions[0];
+        assert_eq!(rotated.get_rect(), (0, 0, 16, 8));
+        assert_eq!(
+            rotated.get_tex_coords(),
+            &[
+                Vec2::new(0.0, 1.0),
+                Vec2::new(0.0, 0.5),
+                Vec2::new(0.125, 0.5),
+                Vec2::new(0.125, 1.0),
+            ]
+        );
+        assert_eq!(rotated.get_source_size(), Vec2::new(16.0, 8.0));
+
+        let trimmed = &regions[1];
+        assert!(trimmed.is_trimmed());
+        assert_eq!(trimmed.get_source_size(), Vec2::new(16.0, 16.0));
+        assert_eq!(trimmed.get_trim_offset(), Vec2::new(2.0, 4.0));
+        assert_eq!(trimmed.get_duration(), None);
+
+        assert_eq!(clips.len(), 1);
+        assert_eq!(clips[0].get_name(), "hop");
+        assert_eq!(clip_regions(&clips[0]), vec![0, 1, 0]);
+        assert_eq!(clips[0].get_frames()[0].duration, DEFAULT_FRAME_DURATION);
+    }
+
+    #[test]
+    fn atlas_without_frames_is_an_error() {
+        let json = serde_json::from_str(r#"{ "meta": {} }"#).unwrap();
+        assert!(parse_atlas(&json, TEXTURE_SIZE).is_err());
+
+        let json = serde_json::from_str(r#"{ "frames": [{ "frame": { "x": 0 } }] }"#).unwrap();
+        assert!(parse_atlas(&json, TEXTURE_SIZE).is_err());
+    }
+
+    #[test]
+    fn regions_flip_rows_into_tex_coords() {
+        let region = SpriteRegion::new("a", 16, 8, 16, 8, TEXTURE_SIZE);
+        assert_eq!(
+            region.get_tex_coords(),
+            &[
+                Vec2::new(0.25, 0.5),
+                Vec2::new(0.5, 0.5),
+                Vec2::new(0.5, 0.75),
+                Vec2::new(0.25, 0.75),
+            ]
+        );
+    }
+
+    #[test]
+    fn natural_order_compares_numbers_by_value() {
+        assert_eq!(natural_cmp("frame 2", "frame 10"), Ordering::Less);
+        assert_eq!(natural_cmp("frame 010", "frame 9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a1", "b0"), Ordering::Less);
+        assert_eq!(natural_cmp("walk", "walk 1"), Ordering::Less);
+        assert_eq!(natural_cmp("007", "7"), Ordering::Equal);
+    }
+
+    #[test]
+    fn loop_starts_over() {
+        let mut playback = playback(LoopMode::Loop);
+        assert_eq!(step(&mut playback, 0.25, 4), vec![1, 2, 0, 1]);
+        assert!(playback.is_playing);
+        assert!(playback.events.contains(&AnimationEvent::Looped {
+            clip: "walk".to_string()
+        }));
+    }
+
+    #[test]
+    fn once_stops_on_the_last_frame() {
+        let mut playback = playback(LoopMode::Once);
+        assert_eq!(step(&mut playback, 0.25, 4), vec![1, 2, 2, 2]);
+        assert!(!playback.is_playing);
+        assert!(playback.is_finished);
+        assert_eq!(
+            playback.events.last(),
+            Some(&AnimationEvent::Finished {
+                clip: "walk".to_string()
+            })
+        );
+
+        playback.resume();
+        assert!(!playback.is_playing);
+    }
+
+    #[test]
+    fn ping_pong_turns_around_at_both_ends() {
+        let mut playback = playback(LoopMode::PingPong);
+        assert_eq!(step(&mut playback, 0.25, 6), vec![1, 2, 1, 0, 1, 2]);
+        let loops = playback
+            .events
+            .iter()
+            .filter(|event| matches!(event, AnimationEvent::Looped { .. }))
+            .count();
+        assert_eq!(loops, 1);
+    }
+
+    #[test]
+    fn long_updates_skip_frames() {
+        let mut playback = playback(LoopMode::Loop);
+        playback.update(0.6);
+        assert_eq!(playback.frame, 2);
+        assert!((playback.frame_time - 0.1).abs() < 1e-5);
+
+        playback.speed = 0.5;
+        assert_eq!(step(&mut playback, 0.2, 2), vec![2, 0]);
+    }
+
+    #[test]
+    fn entering_a_frame_reports_its_events() {
+        let mut playback = playback(LoopMode::Loop);
+        step(&mut playback, 0.25, 4);
+
+        let steps = playback
+            .events
+            .iter()
+            .filter(|event| {
+                **event
+                    == AnimationEvent::Frame {
+                        clip: "walk".to_string(),
+                        frame: 1,
+                        name: "step".to_string(),
+                    }
+            })
+            .count();
+        assert_eq!(steps, 2);
+    }
+}
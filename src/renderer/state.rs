@@ -0,0 +1,371 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use gl::types::{GLboolean, GLenum};
+
+// Mirrors the GL state that changes most often so that redundant binds and
+// toggles never reach the driver. Every change to the tracked state has to go
+// through here, code calling GL directly must call `invalidate` afterwards.
+// State that was never set is unknown and always issued, like all state after
+// `invalidate`.
+thread_local! {
+    static STATE: RefCell<StateCache> = RefCell::new(StateCache::default());
+}
+
+// Accumulates until `reset_stats`, call it once per frame for per-frame counts.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StateCacheStats {
+    pub program_binds: u32,
+    pub program_binds_skipped: u32,
+    pub vertex_array_binds: u32,
+    pub vertex_array_binds_skipped: u32,
+    pub buffer_binds: u32,
+    pub buffer_binds_skipped: u32,
+    pub texture_binds: u32,
+    pub texture_binds_skipped: u32,
+    // capabilities, blend, depth, cull face and viewport
+    pub state_changes: u32,
+    pub state_changes_skipped: u32,
+}
+
+impl StateCacheStats {
+    pub fn get_issued_count(&self) -> u32 {
+        self.program_binds
+            + self.vertex_array_binds
+            + self.buffer_binds
+            + self.texture_binds
+            + self.state_changes
+    }
+
+    pub fn get_skipped_count(&self) -> u32 {
+        self.program_binds_skipped
+            + self.vertex_array_binds_skipped
+            + self.buffer_binds_skipped
+            + self.texture_binds_skipped
+            + self.state_changes_skipped
+    }
+}
+
+#[derive(Default)]
+struct StateCache {
+    program: Option<u32>,
+    vertex_array: Option<u32>,
+    buffers: HashMap<GLenum, u32>,
+    active_texture: Option<u32>,
+    // keyed by unit and target, a unit holds one texture per target
+    textures: HashMap<(u32, GLenum), u32>,
+    capabilities: HashMap<GLenum, bool>,
+    blend_func: Option<[GLenum; 4]>,
+    depth_func: Option<GLenum>,
+    depth_mask: Option<bool>,
+    cull_face: Option<GLenum>,
+    viewport: Option<[i32; 4]>,
+    stats: StateCacheStats,
+}
+
+// returns whether `value` differs from the cached one, and caches it
+fn update<T: PartialEq>(cached: &mut Option<T>, value: T) -> bool {
+    if cached.as_ref() == Some(&value) {
+        return false;
+    }
+    *cached = Some(value);
+    true
+}
+
+fn with_state<R, F: FnOnce(&mut StateCache) -> R>(f: F) -> R {
+    STATE.with(|state| f(&mut state.borrow_mut()))
+}
+
+pub fn use_program(program: u32) {
+    with_state(|state| {
+        if update(&mut state.program, program) {
+            state.stats.program_binds += 1;
+            unsafe {
+                gl::UseProgram(program);
+            }
+        } else {
+            state.stats.program_binds_skipped += 1;
+        }
+    });
+}
+
+pub fn bind_vertex_array(vertex_array: u32) {
+    with_state(|state| {
+        if update(&mut state.vertex_array, vertex_array) {
+            state.stats.vertex_array_binds += 1;
+            unsafe {
+                gl::BindVertexArray(vertex_array);
+            }
+        } else {
+            state.stats.vertex_array_binds_skipped += 1;
+        }
+    });
+}
+
+// The element array binding belongs to the bound vertex array and is always
+// issued.
+pub fn bind_buffer(target: GLenum, buffer: u32) {
+    with_state(|state| {
+        if target != gl::ELEMENT_ARRAY_BUFFER && state.buffers.get(&target) == Some(&buffer) {
+            state.stats.buffer_binds_skipped += 1;
+            return;
+        }
+        if target != gl::ELEMENT_ARRAY_BUFFER {
+            state.buffers.insert(target, buffer);
+        }
+        state.stats.buffer_binds += 1;
+        unsafe {
+            gl::BindBuffer(target, buffer);
+        }
+    });
+}
+
+// Indexed bindings are not cached, but binding one also binds `buffer` to the
+// generic `target`.
+pub fn bind_buffer_base(target: GLenum, index: u32, buffer: u32) {
+    with_state(|state| {
+        state.buffers.insert(target, buffer);
+        state.stats.buffer_binds += 1;
+        unsafe {
+            gl::BindBufferBase(target, index, buffer);
+        }
+    });
+}
+
+// Also leaves `unit` active, so that calls on `target` which follow, such as
+// uploads, apply to `texture`.
+pub fn bind_texture(unit: u32, target: GLenum, texture: u32) {
+    with_state(|state| {
+        if update(&mut state.active_texture, unit) {
+            unsafe {
+                gl::ActiveTexture(gl::TEXTURE0 + unit);
+            }
+        }
+        if state.textures.get(&(unit, target)) == Some(&texture) {
+            state.stats.texture_binds_skipped += 1;
+            return;
+        }
+        state.textures.insert((unit, target), texture);
+        state.stats.texture_binds += 1;
+        unsafe {
+            gl::BindTexture(target, texture);
+        }
+    });
+}
+
+pub fn set_enabled(capability: GLenum, enabled: bool) {
+    with_state(|state| {
+        if state.capabilities.get(&capability) == Some(&enabled) {
+            state.stats.state_changes_skipped += 1;
+            return;
+        }
+        state.capabilities.insert(capability, enabled);
+        state.stats.state_changes += 1;
+        unsafe {
+            if enabled {
+                gl::Enable(capability);
+            } else {
+                gl::Disable(capability);
+            }
+        }
+    });
+}
+
+pub fn enable(capability: GLenum) {
+    set_enabled(capability, true);
+}
+
+pub fn disable(capability: GLenum) {
+    set_enabled(capability, false);
+}
+
+// queries GL when the capability is unknown
+pub fn is_enabled(capability: GLenum) -> bool {
+    with_state(|state| {
+        *state
+            .capabilities
+            .entry(capability)
+            .or_insert_with(|| unsafe { gl::IsEnabled(capability) == gl::TRUE })
+    })
+}
+
+pub fn set_blend_func(source: GLenum, destination: GLenum) {
+    set_blend_func_separate(source, destination, source, destination);
+}
+
+pub fn set_blend_func_separate(
+    source_rgb: GLenum,
+    destination_rgb: GLenum,
+    source_alpha: GLenum,
+    destination_alpha: GLenum,
+) {
+    let blend_func = [source_rgb, destination_rgb, source_alpha, destination_alpha];
+    set_state(
+        |state| &mut state.blend_func,
+        blend_func,
+        || unsafe {
+            gl::BlendFuncSeparate(source_rgb, destination_rgb, source_alpha, destination_alpha);
+        },
+    );
+}
+
+// as source and destination factors of color and alpha
+pub fn get_blend_func() -> [GLenum; 4] {
+    get_state(
+        |state| &mut state.blend_func,
+        || unsafe {
+            let mut blend_func = [0; 4];
+            gl::GetIntegerv(gl::BLEND_SRC_RGB, &mut blend_func[0]);
+            gl::GetIntegerv(gl::BLEND_DST_RGB, &mut blend_func[1]);
+            gl::GetIntegerv(gl::BLEND_SRC_ALPHA, &mut blend_func[2]);
+            gl::GetIntegerv(gl::BLEND_DST_ALPHA, &mut blend_func[3]);
+            [
+                blend_func[0] as GLenum,
+                blend_func[1] as GLenum,
+                blend_func[2] as GLenum,
+                blend_func[3] as GLenum,
+            ]
+        },
+    )
+}
+
+pub fn set_depth_func(func: GLenum) {
+    set_state(
+        |state| &mut state.depth_func,
+        func,
+        || unsafe {
+            gl::DepthFunc(func);
+        },
+    );
+}
+
+pub fn get_depth_func() -> GLenum {
+    get_state(
+        |state| &mut state.depth_func,
+        || unsafe {
+            let mut func = 0;
+            gl::GetIntegerv(gl::DEPTH_FUNC, &mut func);
+            func as GLenum
+        },
+    )
+}
+
+pub fn set_depth_mask(write: bool) {
+    set_state(
+        |state| &mut state.depth_mask,
+        write,
+        || unsafe {
+            gl::DepthMask(write as GLboolean);
+        },
+    );
+}
+
+pub fn get_depth_mask() -> bool {
+    get_state(
+        |state| &mut state.depth_mask,
+        || unsafe {
+            let mut write = gl::FALSE;
+            gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut write);
+            write == gl::TRUE
+        },
+    )
+}
+
+pub fn set_cull_face(face: GLenum) {
+    set_state(
+        |state| &mut state.cull_face,
+        face,
+        || unsafe {
+            gl::CullFace(face);
+        },
+    );
+}
+
+pub fn set_viewport(x: i32, y: i32, width: i32, height: i32) {
+    set_state(
+        |state| &mut state.viewport,
+        [x, y, width, height],
+        || unsafe {
+            gl::Viewport(x, y, width, height);
+        },
+    );
+}
+
+// as x, y, width and height
+pub fn get_viewport() -> [i32; 4] {
+    get_state(
+        |state| &mut state.viewport,
+        || unsafe {
+            let mut viewport = [0; 4];
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+            viewport
+        },
+    )
+}
+
+fn set_state<T: PartialEq, S, F>(select: S, value: T, apply: F)
+where
+    S: FnOnce(&mut StateCache) -> &mut Option<T>,
+    F: FnOnce(),
+{
+    with_state(|state| {
+        if update(select(state), value) {
+            state.stats.state_changes += 1;
+            apply();
+        } else {
+            state.stats.state_changes_skipped += 1;
+        }
+    });
+}
+
+fn get_state<T: Copy, S, F>(select: S, query: F) -> T
+where
+    S: FnOnce(&mut StateCache) -> &mut Option<T>,
+    F: FnOnce() -> T,
+{
+    with_state(|state| *select(state).get_or_insert_with(query))
+}
+
+// Deleting an object unbinds it, and its name may be reused right away, so the
+// deleting code has to forget it.
+pub fn forget_program(program: u32) {
+    with_state(|state| {
+        if state.program == Some(program) {
+            state.program = None;
+        }
+    });
+}
+
+pub fn forget_vertex_array(vertex_array: u32) {
+    with_state(|state| {
+        if state.vertex_array == Some(vertex_array) {
+            state.vertex_array = None;
+        }
+    });
+}
+
+pub fn forget_buffer(buffer: u32) {
+    with_state(|state| state.buffers.retain(|_, bound| *bound != buffer));
+}
+
+pub fn forget_texture(texture: u32) {
+    with_state(|state| state.textures.retain(|_, bound| *bound != texture));
+}
+
+// marks all state as unknown, e.g. after GL calls made around the cache
+pub fn invalidate() {
+    with_state(|state| {
+        let stats = state.stats;
+        *state = StateCache {
+            stats,
+            ..StateCache::default()
+        };
+    });
+}
+
+pub fn get_stats() -> StateCacheStats {
+    with_state(|state| state.stats)
+}
+
+pub fn reset_stats() {
+    with_state(|state| state.stats = StateCacheStats::default());
+}
@@ -0,0 +1,654 @@
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::renderer::{bitmap::Bitmap, debug::set_object_label, state};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFormat {
+    R8,
+    Rg8,
+    Rgb8,
+    Rgba8,
+    R32F,
+    Rgba16F,
+    Rgba32F,
+}
+
+impl TextureFormat {
+    pub fn get_channel_count(&self) -> usize {
+        match self {
+            TextureFormat::R8 | TextureFormat::R32F => 1,
+            TextureFormat::Rg8 => 2,
+            TextureFormat::Rgb8 => 3,
+            TextureFormat::Rgba8 | TextureFormat::Rgba16F | TextureFormat::Rgba32F => 4,
+        }
+    }
+
+    // size in bytes of one pixel as passed to `set_data`
+    pub fn get_pixel_size(&self) -> usize {
+        match self {
+            TextureFormat::R8 | TextureFormat::Rg8 | TextureFormat::Rgb8 | TextureFormat::Rgba8 => {
+                self.get_channel_count()
+            }
+            // half float textures are uploaded from f32 data
+            TextureFormat::R32F | TextureFormat::Rgba16F | TextureFormat::Rgba32F => {
+                self.get_channel_count() * 4
+            }
+        }
+    }
+
+    fn get_gl_formats(&self, color_space: ColorSpace) -> (u32, u32, u32) {
+        let srgb = color_space == ColorSpace::Srgb;
+
+        // (internal format, pixel format, pixel type)
+        match self {
+            TextureFormat::R8 => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+            TextureFormat::Rg8 => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgb8 if srgb => (gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgb8 => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgba8 if srgb => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::Rgba8 => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+            TextureFormat::R32F => (gl::R32F, gl::RED, gl::FLOAT),
+            TextureFormat::Rgba16F => (gl::RGBA16F, gl::RGBA, gl::FLOAT),
+            TextureFormat::Rgba32F => (gl::RGBA32F, gl::RGBA, gl::FLOAT),
+        }
+    }
+}
+
+// sRGB only affects 8-bit color formats, everything else is always linear
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Linear,
+    Srgb,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureFilter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureWrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl TextureWrap {
+    fn to_gl(self) -> u32 {
+        match self {
+            TextureWrap::Repeat => gl::REPEAT,
+            TextureWrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            TextureWrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            TextureWrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerSettings {
+    pub min_filter: TextureFilter,
+    pub mag_filter: TextureFilter,
+    // ignored for textures without mipmaps
+    pub mipmap_filter: Option<TextureFilter>,
+    pub wrap_s: TextureWrap,
+    pub wrap_t: TextureWrap,
+    pub border_color: [f32; 4],
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        Self {
+            min_filter: TextureFilter::Linear,
+            mag_filter: TextureFilter::Linear,
+            mipmap_filter: Some(TextureFilter::Linear),
+            wrap_s: TextureWrap::Repeat,
+            wrap_t: TextureWrap::Repeat,
+            border_color: [0.0, 0.0, 0.0, 0.0],
+        }
+    }
+}
+
+impl SamplerSettings {
+    pub fn nearest() -> Self {
+        Self {
+            min_filter: TextureFilter::Nearest,
+            mag_filter: TextureFilter::Nearest,
+            mipmap_filter: None,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_wrap(mut self, wrap: TextureWrap) -> Self {
+        self.wrap_s = wrap;
+        self.wrap_t = wrap;
+        self
+    }
+
+    fn get_gl_min_filter(&self, mipmaps: bool) -> u32 {
+        match (
+            self.min_filter,
+            if mipmaps { self.mipmap_filter } else { None },
+        ) {
+            (TextureFilter::Nearest, None) => gl::NEAREST,
+            (TextureFilter::Linear, None) => gl::LINEAR,
+            (TextureFilter::Nearest, Some(TextureFilter::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+            (TextureFilter::Nearest, Some(TextureFilter::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+            (TextureFilter::Linear, Some(TextureFilter::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+            (TextureFilter::Linear, Some(TextureFilter::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+
+    fn get_gl_mag_filter(&self) -> u32 {
+        match self.mag_filter {
+            TextureFilter::Nearest => gl::NEAREST,
+            TextureFilter::Linear => gl::LINEAR,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureSpec {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub color_space: ColorSpace,
+    pub mipmaps: bool,
+    pub sampler: SamplerSettings,
+}
+
+impl TextureSpec {
+    pub fn new(width: u32, height: u32, format: TextureFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            color_space: ColorSpace::Linear,
+            mipmaps: false,
+            sampler: SamplerSettings::default(),
+        }
+    }
+}
+
+pub trait Texture {
+    fn get_width(&self) -> u32;
+    fn get_height(&self) -> u32;
+    fn get_renderer_id(&self) -> u32;
+
+    // binds to texture unit `slot`, pass the same slot to the sampler uniform
+    fn bind(&self, slot: u32);
+
+    // names the texture in debug output and frame captures
+    fn set_label(&self, label: &str) {
+        set_object_label(gl::TEXTURE, self.get_renderer_id(), label);
+    }
+}
+
+// Rows are stored bottom to top as in OpenGL, so that (0, 0) texture
+// coordinates address the bottom left corner. Images loaded from disk and
+// bitmaps are flipped on upload, raw buffers passed to `set_data` are not.
+pub struct Texture2D {
+    renderer_id: u32,
+    spec: TextureSpec,
+}
+
+impl Texture2D {
+    pub fn new(spec: TextureSpec) -> Self {
+        let mut renderer_id = 0;
+        let (internal_format, format, ty) = spec.format.get_gl_formats(spec.color_space);
+
+        unsafe {
+            gl::GenTextures(1, &mut renderer_id);
+            state::bind_texture(0, gl::TEXTURE_2D, renderer_id);
+            gl::TexImage2D(
+                gl::TEXTURE_2D,
+                0,
+                internal_format as i32,
+                spec.width as i32,
+                spec.height as i32,
+                0,
+                format,
+                ty,
+                std::ptr::null(),
+            );
+        }
+
+        let texture = Self { renderer_id, spec };
+        texture.apply_sampler();
+        texture
+    }
+
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        color_space: ColorSpace,
+        sampler: SamplerSettings,
+    ) -> Result<Self, String> {
+        let img = image::open(path.as_ref())
+            .map_err(|e| format!("Failed to load texture {:?}: {}", path.as_ref(), e))?;
+
+        let texture = Self::from_image(img, color_space, sampler);
+        texture.set_label(&path.as_ref().to_string_lossy());
+        Ok(texture)
+    }
+
+    pub fn from_memory(
+        bytes: &[u8],
+        color_space: ColorSpace,
+        sampler: SamplerSettings,
+    ) -> Result<Self, String> {
+        let img = image::load_from_memory(bytes)
+            .map_err(|e| format!("Failed to decode texture: {}", e))?;
+
+        Ok(Self::from_image(img, color_space, sampler))
+    }
+
+    pub fn from_bitmap(bitmap: &Bitmap, color_space: ColorSpace, sampler: SamplerSettings) -> Self {
+        let mut texture = Self::new(TextureSpec {
+            width: bitmap.get_width(),
+            height: bitmap.get_height(),
+            format: TextureFormat::Rgba8,
+            color_space,
+            mipmaps: true,
+            sampler,
+        });
+        texture.set_data(&flip_rows(
+            bitmap.get_data(),
+            bitmap.get_width() as usize * 4,
+        ));
+        texture
+    }
+
+    // Radiance HDR image as a linear half float texture, e.g. an
+    // equirectangular environment
+    pub fn from_hdr_file<P: AsRef<Path>>(
+        path: P,
+        sampler: SamplerSettings,
+    ) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| {
+            format!("Failed to load HDR image {:?}: {}", path.as_ref(), e)
+        };
+        let file = File::open(path.as_ref()).map_err(|e| error(&e))?;
+        let decoder =
+            image::codecs::hdr::HdrDecoder::new(BufReader::new(file)).map_err(|e| error(&e))?;
+        let metadata = decoder.metadata();
+        let pixels = decoder.read_image_hdr().map_err(|e| error(&e))?;
+
+        // decoded top row first
+        let row_size = metadata.width as usize;
+        let data: Vec<f32> = pixels
+            .chunks_exact(row_size)
+            .rev()
+            .flatten()
+            .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 1.0])
+            .collect();
+
+        let mut texture = Self::new(TextureSpec {
+            width: metadata.width,
+            height: metadata.height,
+            format: TextureFormat::Rgba16F,
+            color_space: ColorSpace::Linear,
+            mipmaps: false,
+            sampler,
+        });
+        texture.set_float_data(&data);
+        texture.set_label(&path.as_ref().to_string_lossy());
+        Ok(texture)
+    }
+
+    fn from_image(
+        img: image::DynamicImage,
+        color_space: ColorSpace,
+        sampler: SamplerSettings,
+    ) -> Self {
+        let img = img.flipv();
+        let (format, width, height, data) = match img {
+            image::DynamicImage::ImageRgb8(buf) => (
+                TextureFormat::Rgb8,
+                buf.width(),
+                buf.height(),
+                buf.into_raw(),
+            ),
+            other => {
+                let buf = other.into_rgba8();
+                (
+                    TextureFormat::Rgba8,
+                    buf.width(),
+                    buf.height(),
+                    buf.into_raw(),
+                )
+            }
+        };
+
+        let mut texture = Self::new(TextureSpec {
+            width,
+            height,
+            format,
+            color_space,
+            mipmaps: true,
+            sampler,
+        });
+        texture.set_data(&data);
+        texture
+    }
+
+    pub fn get_spec(&self) -> &TextureSpec {
+        &self.spec
+    }
+
+    pub fn set_data(&mut self, data: &[u8]) {
+        let (width, height) = (self.spec.width, self.spec.height);
+        self.set_sub_data(0, 0, width, height, data);
+    }
+
+    pub fn set_float_data(&mut self, data: &[f32]) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(data.as_ptr() as *const u8, std::mem::size_of_val(data))
+        };
+        self.set_data(bytes);
+    }
+
+    // `data` is tightly packed in the texture format, see `TextureFormat::get_pixel_size`
+    pub fn set_sub_data(&mut self, x: u32, y: u32, width: u32, height: u32, data: &[u8]) {
+        assert!(
+            x + width <= self.spec.width && y + height <= self.spec.height,
+            "Texture region out of bounds."
+        );
+        assert_eq!(
+            data.len(),
+            width as usize * height as usize * self.spec.format.get_pixel_size(),
+            "Data size does not match the texture region."
+        );
+
+        let (_, format, ty) = self.spec.format.get_gl_formats(self.spec.color_space);
+
+        unsafe {
+            state::bind_texture(0, gl::TEXTURE_2D, self.renderer_id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_2D,
+                0,
+                x as i32,
+                y as i32,
+                width as i32,
+                height as i32,
+                format,
+                ty,
+                data.as_ptr() as *const _,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+
+        if self.spec.mipmaps {
+            self.generate_mipmaps();
+        }
+    }
+
+    pub fn set_sampler(&mut self, sampler: SamplerSettings) {
+        self.spec.sampler = sampler;
+        self.apply_sampler();
+    }
+
+    pub fn generate_mipmaps(&self) {
+        unsafe {
+            state::bind_texture(0, gl::TEXTURE_2D, self.renderer_id);
+            gl::GenerateMipmap(gl::TEXTURE_2D);
+        }
+    }
+
+    fn apply_sampler(&self) {
+        let sampler = &self.spec.sampler;
+
+        unsafe {
+            state::bind_texture(0, gl::TEXTURE_2D, self.renderer_id);
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MIN_FILTER,
+                sampler.get_gl_min_filter(self.spec.mipmaps) as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_MAG_FILTER,
+                sampler.get_gl_mag_filter() as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_S,
+                sampler.wrap_s.to_gl() as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_WRAP_T,
+                sampler.wrap_t.to_gl() as i32,
+            );
+            gl::TexParameterfv(
+                gl::TEXTURE_2D,
+                gl::TEXTURE_BORDER_COLOR,
+                sampler.border_color.as_ptr(),
+            );
+        }
+    }
+}
+
+impl Texture for Texture2D {
+    fn get_width(&self) -> u32 {
+        self.spec.width
+    }
+
+    fn get_height(&self) -> u32 {
+        self.spec.height
+    }
+
+    fn get_renderer_id(&self) -> u32 {
+        self.renderer_id
+    }
+
+    fn bind(&self, slot: u32) {
+        state::bind_texture(slot, gl::TEXTURE_2D, self.renderer_id);
+    }
+}
+
+impl Drop for Texture2D {
+    fn drop(&mut self) {
+        state::forget_texture(self.renderer_id);
+        unsafe {
+            gl::DeleteTextures(1, &self.renderer_id);
+        }
+    }
+}
+
+// Faces are in the OpenGL order +X, -X, +Y, -Y, +Z, -Z and are not flipped
+// on upload, cubemap lookups expect the top row of each face first.
+pub struct TextureCube {
+    renderer_id: u32,
+    size: u32,
+    format: TextureFormat,
+    color_space: ColorSpace,
+    mip_levels: u32,
+}
+
+impl TextureCube {
+    // Requires a current OpenGL context. Allocates `mip_levels` levels, see
+    // `get_mip_level_count` for a full chain.
+    pub fn new(size: u32, format: TextureFormat, color_space: ColorSpace, mip_levels: u32) -> Self {
+        assert!(
+            mip_levels >= 1 && mip_levels <= get_mip_level_count(size),
+            "Invalid cubemap mip level count."
+        );
+
+        let mut renderer_id = 0;
+        let (internal_format, pixel_format, ty) = format.get_gl_formats(color_space);
+        let min_filter = if mip_levels > 1 {
+            gl::LINEAR_MIPMAP_LINEAR
+        } else {
+            gl::LINEAR
+        };
+
+        unsafe {
+            // global state, filters across face edges for every cubemap
+            state::enable(gl::TEXTURE_CUBE_MAP_SEAMLESS);
+
+            gl::GenTextures(1, &mut renderer_id);
+            state::bind_texture(0, gl::TEXTURE_CUBE_MAP, renderer_id);
+            for level in 0..mip_levels {
+                let level_size = (size >> level).max(1) as i32;
+                for face in 0..6 {
+                    gl::TexImage2D(
+                        gl::TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                        level as i32,
+                        internal_format as i32,
+                        level_size,
+                        level_size,
+                        0,
+                        pixel_format,
+                        ty,
+                        std::ptr::null(),
+                    );
+                }
+            }
+
+            let target = gl::TEXTURE_CUBE_MAP;
+            gl::TexParameteri(target, gl::TEXTURE_MIN_FILTER, min_filter as i32);
+            gl::TexParameteri(target, gl::TEXTURE_MAG_FILTER, gl::LINEAR as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_WRAP_R, gl::CLAMP_TO_EDGE as i32);
+            gl::TexParameteri(target, gl::TEXTURE_MAX_LEVEL, mip_levels as i32 - 1);
+        }
+
+        Self {
+            renderer_id,
+            size,
+            format,
+            color_space,
+            mip_levels,
+        }
+    }
+
+    // six square images of the same size, with a full mip chain
+    pub fn from_faces<P: AsRef<Path>>(
+        paths: &[P; 6],
+        color_space: ColorSpace,
+    ) -> Result<Self, String> {
+        let mut faces = Vec::with_capacity(6);
+        for path in paths.iter() {
+            let img = image::open(path.as_ref())
+                .map_err(|e| format!("Failed to load cubemap face {:?}: {}", path.as_ref(), e))?;
+            faces.push(img.into_rgba8());
+        }
+
+        let size = faces[0].width();
+        if faces
+            .iter()
+            .any(|face| face.width() != size || face.height() != size)
+        {
+            return Err(format!(
+                "Cubemap faces must be square and of the same size, the first one is {}x{}",
+                size,
+                faces[0].height()
+            ));
+        }
+
+        let mut cube = Self::new(
+            size,
+            TextureFormat::Rgba8,
+            color_space,
+            get_mip_level_count(size),
+        );
+        for (face, img) in faces.iter().enumerate() {
+            cube.set_face_data(face, img.as_raw());
+        }
+        cube.generate_mipmaps();
+        cube.set_label(&paths[0].as_ref().to_string_lossy());
+        Ok(cube)
+    }
+
+    pub fn get_size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn get_format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn get_mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    // level 0 of `face`, tightly packed in the texture format
+    pub fn set_face_data(&mut self, face: usize, data: &[u8]) {
+        assert!(face < 6, "Cubemap face out of range.");
+        assert_eq!(
+            data.len(),
+            self.size as usize * self.size as usize * self.format.get_pixel_size(),
+            "Data size does not match the cubemap face."
+        );
+
+        let (_, pixel_format, ty) = self.format.get_gl_formats(self.color_space);
+
+        unsafe {
+            state::bind_texture(0, gl::TEXTURE_CUBE_MAP, self.renderer_id);
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage2D(
+                gl::TEXTURE_CUBE_MAP_POSITIVE_X + face as u32,
+                0,
+                0,
+                0,
+                self.size as i32,
+                self.size as i32,
+                pixel_format,
+                ty,
+                data.as_ptr() as *const _,
+            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+        }
+    }
+
+    // fills the allocated levels from level 0
+    pub fn generate_mipmaps(&self) {
+        unsafe {
+            state::bind_texture(0, gl::TEXTURE_CUBE_MAP, self.renderer_id);
+            gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+        }
+    }
+}
+
+impl Texture for TextureCube {
+    fn get_width(&self) -> u32 {
+        self.size
+    }
+
+    fn get_height(&self) -> u32 {
+        self.size
+    }
+
+    fn get_renderer_id(&self) -> u32 {
+        self.renderer_id
+    }
+
+    fn bind(&self, slot: u32) {
+        state::bind_texture(slot, gl::TEXTURE_CUBE_MAP, self.renderer_id);
+    }
+}
+
+impl Drop for TextureCube {
+    fn drop(&mut self) {
+        state::forget_texture(self.renderer_id);
+        unsafe {
+            gl::DeleteTextures(1, &self.renderer_id);
+        }
+    }
+}
+
+// levels down to 1x1 for a texture of `size` pixels
+pub fn get_mip_level_count(size: u32) -> u32 {
+    32 - size.max(1).leading_zeros()
+}
+
+fn flip_rows(data: &[u8], row_size: usize) -> Vec<u8> {
+    data.chunks_exact(row_size)
+        .rev()
+        .flatten()
+        .copied()
+        .collect()
+}
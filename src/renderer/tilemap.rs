@@ -0,0 +1,1721 @@
+use std::{collections::HashMap, fs, path::Path, ptr, rc::Rc};
+
+use serde_json::{Map, Value};
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::log_warn;
+use crate::math::{Mat4, Vec2, Vec3, Vec4};
+use crate::renderer::{
+    buffer::{BufferElement, BufferLayout, IndexBuffer, ShaderDataType, VertexArray, VertexBuffer},
+    camera::Camera,
+    profiler,
+    shader::{OpenGLShader, Shader},
+    sprite::TextureAtlas,
+    state,
+    texture::{ColorSpace, SamplerSettings, Texture, Texture2D},
+};
+
+// tiles per chunk side, chunks are meshed and culled as a whole
+pub const CHUNK_SIZE: u32 = 16;
+
+// TMX attributes kept as strings, all others are read as numbers when they
+// parse as one, matching the Tiled JSON format
+const STRING_ATTRIBUTES: [&str; 14] = [
+    "name",
+    "type",
+    "class",
+    "source",
+    "orientation",
+    "renderorder",
+    "encoding",
+    "compression",
+    "propertytype",
+    "version",
+    "tiledversion",
+    "backgroundcolor",
+    "trans",
+    "color",
+];
+
+static TILEMAP_VERTEX_SHADER: &'static str = "
+    #version 330 core
+
+    layout(location = 0) in vec3 a_Position;
+    layout(location = 1) in vec2 a_TexCoord;
+
+    uniform mat4 u_ViewProjection;
+    uniform mat4 u_Transform;
+
+    out vec2 v_TexCoord;
+
+    void main() {
+        v_TexCoord = a_TexCoord;
+        gl_Position = u_ViewProjection * u_Transform * vec4(a_Position, 1.0);
+    }
+";
+
+static TILEMAP_FRAGMENT_SHADER: &'static str = "
+    #version 330 core
+
+    in vec2 v_TexCoord;
+
+    uniform sampler2D u_Texture;
+    uniform vec4 u_Tint;
+
+    layout(location = 0) out vec4 color;
+
+    void main() {
+        color = texture(u_Texture, v_TexCoord) * u_Tint;
+        if (color.a <= 0.0) {
+            discard;
+        }
+    }
+";
+
+// Global tile id as stored by Tiled, with the flip flags in the high bits.
+// Gid 0 is the empty tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Tile(u32);
+
+impl Tile {
+    pub const EMPTY: Tile = Tile(0);
+
+    const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+    const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+    const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+    // also includes the hexagonal rotation flag, meaningless for orthogonal maps
+    const FLAGS: u32 = 0xF000_0000;
+
+    pub fn new(gid: u32) -> Self {
+        assert!(gid & Self::FLAGS == 0, "Tile gid {} is out of range.", gid);
+        Self(gid)
+    }
+
+    pub fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    pub fn with_flips(self, horizontal: bool, vertical: bool, diagonal: bool) -> Self {
+        let mut raw = self.get_gid();
+        if horizontal {
+            raw |= Self::FLIPPED_HORIZONTALLY;
+        }
+        if vertical {
+            raw |= Self::FLIPPED_VERTICALLY;
+        }
+        if diagonal {
+            raw |= Self::FLIPPED_DIAGONALLY;
+        }
+        Self(raw)
+    }
+
+    pub fn get_raw(&self) -> u32 {
+        self.0
+    }
+
+    pub fn get_gid(&self) -> u32 {
+        self.0 & !Self::FLAGS
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.get_gid() == 0
+    }
+
+    pub fn is_flipped_horizontally(&self) -> bool {
+        self.0 & Self::FLIPPED_HORIZONTALLY != 0
+    }
+
+    pub fn is_flipped_vertically(&self) -> bool {
+        self.0 & Self::FLIPPED_VERTICALLY != 0
+    }
+
+    // x and y swapped, applied before the other flips
+    pub fn is_flipped_diagonally(&self) -> bool {
+        self.0 & Self::FLIPPED_DIAGONALLY != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropertyValue {
+    String(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Color(Vec4),
+    // path as written in the map
+    File(String),
+    // id of a map object, 0 for none
+    Object(u32),
+}
+
+pub type Properties = HashMap<String, PropertyValue>;
+
+// class and custom properties of one tile of a tileset
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TileData {
+    pub class: String,
+    pub properties: Properties,
+}
+
+// An image sliced into tiles, which the map addresses by global ids starting
+// at `first_gid`.
+pub struct Tileset {
+    name: String,
+    first_gid: u32,
+    tile_size: (u32, u32),
+    tile_count: u32,
+    // drawing offset in pixels, y down
+    offset: Vec2,
+    atlas: Rc<TextureAtlas>,
+    tiles: HashMap<u32, TileData>,
+    properties: Properties,
+}
+
+impl Tileset {
+    // `first_gid` is assigned when added to a map
+    pub fn new(
+        name: &str,
+        texture: Rc<Texture2D>,
+        tile_size: (u32, u32),
+        spacing: u32,
+        margin: u32,
+    ) -> Self {
+        let atlas = TextureAtlas::from_grid(texture, tile_size, spacing, margin);
+        Self {
+            name: name.to_string(),
+            first_gid: 1,
+            tile_size,
+            tile_count: atlas.get_regions().len() as u32,
+            offset: Vec2::ZERO,
+            atlas: Rc::new(atlas),
+            tiles: HashMap::new(),
+            properties: Properties::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_first_gid(&self) -> u32 {
+        self.first_gid
+    }
+
+    pub fn get_tile_size(&self) -> (u32, u32) {
+        self.tile_size
+    }
+
+    pub fn get_tile_count(&self) -> u32 {
+        self.tile_count
+    }
+
+    pub fn get_atlas(&self) -> &Rc<TextureAtlas> {
+        &self.atlas
+    }
+
+    pub fn contains(&self, gid: u32) -> bool {
+        gid >= self.first_gid && gid - self.first_gid < self.tile_count
+    }
+
+    // `id` is local to the tileset, the gid minus `first_gid`
+    pub fn get_tile_data(&self, id: u32) -> Option<&TileData> {
+        self.tiles.get(&id)
+    }
+
+    pub fn set_tile_data(&mut self, id: u32, data: TileData) {
+        assert!(
+            id < self.tile_count,
+            "Tileset {} has no tile {}.",
+            self.name,
+            id
+        );
+        self.tiles.insert(id, data);
+    }
+
+    pub fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+}
+
+// GPU geometry of one chunk for one tileset
+struct ChunkMesh {
+    tileset: usize,
+    vertex_array: VertexArray,
+    index_count: usize,
+}
+
+#[derive(Default)]
+struct Chunk {
+    meshes: Vec<ChunkMesh>,
+    is_dirty: bool,
+}
+
+// Grid of tiles, row by row from the top left as in Tiled. Meshes of the
+// chunks are built when first drawn and after their tiles change.
+pub struct TileLayer {
+    name: String,
+    width: u32,
+    height: u32,
+    tiles: Vec<Tile>,
+    is_visible: bool,
+    opacity: f32,
+    // in pixels, y down
+    offset: Vec2,
+    properties: Properties,
+    chunks: Vec<Chunk>,
+}
+
+impl TileLayer {
+    pub fn new(name: &str, width: u32, height: u32) -> Self {
+        Self::from_tiles(
+            name,
+            width,
+            height,
+            vec![Tile::EMPTY; (width * height) as usize],
+        )
+    }
+
+    fn from_tiles(name: &str, width: u32, height: u32, tiles: Vec<Tile>) -> Self {
+        let chunk_count = width.div_ceil(CHUNK_SIZE) * height.div_ceil(CHUNK_SIZE);
+        Self {
+            name: name.to_string(),
+            width,
+            height,
+            tiles,
+            is_visible: true,
+            opacity: 1.0,
+            offset: Vec2::ZERO,
+            properties: Properties::new(),
+            chunks: (0..chunk_count)
+                .map(|_| Chunk {
+                    meshes: Vec::new(),
+                    is_dirty: true,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    // row 0 is the top row
+    pub fn get_tile(&self, x: u32, y: u32) -> Tile {
+        assert!(
+            x < self.width && y < self.height,
+            "Tile ({}, {}) is outside of layer {}.",
+            x,
+            y,
+            self.name
+        );
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Tile) {
+        assert!(
+            x < self.width && y < self.height,
+            "Tile ({}, {}) is outside of layer {}.",
+            x,
+            y,
+            self.name
+        );
+        let index = (y * self.width + x) as usize;
+        if self.tiles[index] != tile {
+            self.tiles[index] = tile;
+            let chunk = (y / CHUNK_SIZE) * self.width.div_ceil(CHUNK_SIZE) + x / CHUNK_SIZE;
+            self.chunks[chunk as usize].is_dirty = true;
+        }
+    }
+
+    pub fn get_tiles(&self) -> &[Tile] {
+        &self.tiles
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.is_visible
+    }
+
+    pub fn set_visible(&mut self, is_visible: bool) {
+        self.is_visible = is_visible;
+    }
+
+    pub fn get_opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity;
+    }
+
+    pub fn get_offset(&self) -> Vec2 {
+        self.offset
+    }
+
+    pub fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    fn mark_dirty(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            chunk.is_dirty = true;
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectShape {
+    Rectangle,
+    Ellipse,
+    Point,
+    // points relative to the object position
+    Polygon(Vec<Vec2>),
+    Polyline(Vec<Vec2>),
+    Text(String),
+    // tile objects have their position at the bottom left
+    Tile(Tile),
+}
+
+// Object as placed in Tiled, in map pixels with y down. `Tilemap::map_to_world`
+// converts positions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub position: Vec2,
+    pub size: Vec2,
+    // degrees clockwise
+    pub rotation: f32,
+    pub is_visible: bool,
+    pub shape: ObjectShape,
+    pub properties: Properties,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectLayer {
+    pub name: String,
+    pub objects: Vec<MapObject>,
+    pub is_visible: bool,
+    pub opacity: f32,
+    // in pixels, y down, already added to the object positions
+    pub offset: Vec2,
+    pub properties: Properties,
+}
+
+impl ObjectLayer {
+    pub fn find_object(&self, name: &str) -> Option<&MapObject> {
+        self.objects.iter().find(|object| object.name == name)
+    }
+}
+
+pub enum MapLayer {
+    Tiles(TileLayer),
+    Objects(ObjectLayer),
+}
+
+impl MapLayer {
+    pub fn get_name(&self) -> &str {
+        match self {
+            MapLayer::Tiles(layer) => &layer.name,
+            MapLayer::Objects(layer) => &layer.name,
+        }
+    }
+}
+
+// Orthogonal map in drawing order. One tile covers `tile_size` world units
+// before `transform`, the map spans from the origin to the top right.
+pub struct Tilemap {
+    width: u32,
+    height: u32,
+    tile_size: (u32, u32),
+    tilesets: Vec<Tileset>,
+    layers: Vec<MapLayer>,
+    properties: Properties,
+    transform: Mat4,
+}
+
+impl Tilemap {
+    pub fn new(width: u32, height: u32, tile_size: (u32, u32)) -> Self {
+        assert!(
+            tile_size.0 > 0 && tile_size.1 > 0,
+            "Tilemap tiles need a non-zero size."
+        );
+
+        Self {
+            width,
+            height,
+            tile_size,
+            tilesets: Vec::new(),
+            layers: Vec::new(),
+            properties: Properties::new(),
+            transform: Mat4::IDENTITY,
+        }
+    }
+
+    // Requires a current OpenGL context. Tiled maps as TMX or JSON, chosen by
+    // the extension, with embedded or external tilesets. Group layers are
+    // flattened, image layers and infinite maps are not supported.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, String> {
+        let path = path.as_ref();
+        let json = read_tiled_file(path)?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        parse_map(&json, base_dir).map_err(|e| format!("Invalid map {:?}: {}", path, e))
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get_tile_size(&self) -> (u32, u32) {
+        self.tile_size
+    }
+
+    pub fn get_properties(&self) -> &Properties {
+        &self.properties
+    }
+
+    pub fn get_transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: Mat4) {
+        self.transform = transform;
+    }
+
+    // returns the first gid of the tileset, following the ones added before
+    pub fn add_tileset(&mut self, mut tileset: Tileset) -> u32 {
+        tileset.first_gid = self
+            .tilesets
+            .last()
+            .map(|last| last.first_gid + last.tile_count)
+            .unwrap_or(1);
+        let first_gid = tileset.first_gid;
+        self.tilesets.push(tileset);
+
+        for layer in self.layers.iter_mut() {
+            if let MapLayer::Tiles(layer) = layer {
+                layer.mark_dirty();
+            }
+        }
+        first_gid
+    }
+
+    pub fn get_tilesets(&self) -> &[Tileset] {
+        &self.tilesets
+    }
+
+    pub fn find_tileset(&self, gid: u32) -> Option<&Tileset> {
+        find_tileset_index(&self.tilesets, gid).map(|index| &self.tilesets[index])
+    }
+
+    pub fn get_tile_data(&self, tile: Tile) -> Option<&TileData> {
+        self.find_tileset(tile.get_gid())
+            .and_then(|tileset| tileset.get_tile_data(tile.get_gid() - tileset.first_gid))
+    }
+
+    // custom properties of the tile in its tileset
+    pub fn get_tile_properties(&self, tile: Tile) -> Option<&Properties> {
+        self.get_tile_data(tile).map(|data| &data.properties)
+    }
+
+    // returns the index of the new layer, drawn above the others
+    pub fn add_tile_layer(&mut self, name: &str) -> usize {
+        self.layers.push(MapLayer::Tiles(TileLayer::new(
+            name,
+            self.width,
+            self.height,
+        )));
+        self.layers.len() - 1
+    }
+
+    pub fn add_object_layer(&mut self, layer: ObjectLayer) -> usize {
+        self.layers.push(MapLayer::Objects(layer));
+        self.layers.len() - 1
+    }
+
+    pub fn get_layers(&self) -> &[MapLayer] {
+        &self.layers
+    }
+
+    pub fn get_layer(&self, index: usize) -> &MapLayer {
+        &self.layers[index]
+    }
+
+    pub fn get_layer_mut(&mut self, index: usize) -> &mut MapLayer {
+        &mut self.layers[index]
+    }
+
+    pub fn find_layer(&self, name: &str) -> Option<usize> {
+        self.layers
+            .iter()
+            .position(|layer| layer.get_name() == name)
+    }
+
+    pub fn get_tile_layer(&self, name: &str) -> Option<&TileLayer> {
+        self.layers.iter().find_map(|layer| match layer {
+            MapLayer::Tiles(layer) if layer.name == name => Some(layer),
+            _ => None,
+        })
+    }
+
+    pub fn get_tile_layer_mut(&mut self, name: &str) -> Option<&mut TileLayer> {
+        self.layers.iter_mut().find_map(|layer| match layer {
+            MapLayer::Tiles(layer) if layer.name == name => Some(layer),
+            _ => None,
+        })
+    }
+
+    pub fn get_object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.layers.iter().find_map(|layer| match layer {
+            MapLayer::Objects(layer) if layer.name == name => Some(layer),
+            _ => None,
+        })
+    }
+
+    pub fn get_object_layers(&self) -> impl Iterator<Item = &ObjectLayer> {
+        self.layers.iter().filter_map(|layer| match layer {
+            MapLayer::Objects(layer) => Some(layer),
+            _ => None,
+        })
+    }
+
+    // center of a tile in world space, row 0 is the top row
+    pub fn tile_to_world(&self, x: u32, y: u32) -> Vec3 {
+        let (tile_width, tile_height) = (self.tile_size.0 as f32, self.tile_size.1 as f32);
+        self.transform.transform_point(Vec3::new(
+            (x as f32 + 0.5) * tile_width,
+            (self.height - y) as f32 * tile_height - tile_height * 0.5,
+            0.0,
+        ))
+    }
+
+    // tile under a world position, ignoring depth
+    pub fn world_to_tile(&self, position: Vec3) -> Option<(u32, u32)> {
+        let local = self.transform.inverse()?.transform_point(position);
+        let x = (local.x / self.tile_size.0 as f32).floor();
+        let y = self.height as f32 - 1.0 - (local.y / self.tile_size.1 as f32).floor();
+        if x < 0.0 || y < 0.0 || x >= self.width as f32 || y >= self.height as f32 {
+            return None;
+        }
+        Some((x as u32, y as u32))
+    }
+
+    // map pixels with y down, as object positions, to world space
+    pub fn map_to_world(&self, position: Vec2) -> Vec3 {
+        self.transform.transform_point(Vec3::new(
+            position.x,
+            (self.height * self.tile_size.1) as f32 - position.y,
+            0.0,
+        ))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct TileVertex {
+    position: Vec3,
+    tex_coord: Vec2,
+}
+
+// Draws the tile layers of maps, only meshing and drawing the chunks in view
+// of the camera. Culling expects an orthographic camera looking along -Z.
+pub struct TilemapRenderer {
+    shader: OpenGLShader,
+    chunk_count: u32,
+    draw_calls: u32,
+}
+
+impl TilemapRenderer {
+    // requires a current OpenGL context
+    pub fn new() -> Self {
+        let shader = OpenGLShader::new(TILEMAP_VERTEX_SHADER, TILEMAP_FRAGMENT_SHADER);
+        shader.bind();
+        shader.set_int("u_Texture", 0);
+        shader.unbind();
+
+        Self {
+            shader,
+            chunk_count: 0,
+            draw_calls: 0,
+        }
+    }
+
+    // visible tile layers in order
+    pub fn draw(&mut self, camera: &dyn Camera, tilemap: &mut Tilemap) {
+        for layer in 0..tilemap.layers.len() {
+            self.draw_layer(camera, tilemap, layer);
+        }
+    }
+
+    // one layer, e.g. to draw agents between the ground and the roofs
+    pub fn draw_layer(&mut self, camera: &dyn Camera, tilemap: &mut Tilemap, layer: usize) {
+        let Tilemap {
+            tile_size,
+            tilesets,
+            layers,
+            transform,
+            ..
+        } = tilemap;
+        let layer = match &mut layers[layer] {
+            MapLayer::Tiles(layer) if layer.is_visible => layer,
+            _ => return,
+        };
+
+        let transform =
+            *transform * Mat4::translation(Vec3::new(layer.offset.x, -layer.offset.y, 0.0));
+        let view_projection = camera.get_view_projection();
+        let visible = match get_visible_rect(&(*view_projection * transform)) {
+            Some(visible) => visible,
+            None => return,
+        };
+
+        // tiles larger than the grid reach into the chunks above and right
+        let reach = tilesets.iter().fold((0.0f32, 0.0f32), |reach, tileset| {
+            let (width, height) = tileset.tile_size;
+            (
+                reach.0.max(width as f32 + tileset.offset.x.abs()),
+                reach.1.max(height as f32 + tileset.offset.y.abs()),
+            )
+        });
+        let chunk_width = (CHUNK_SIZE * tile_size.0) as f32;
+        let chunk_height = (CHUNK_SIZE * tile_size.1) as f32;
+        let layer_height = (layer.height * tile_size.1) as f32;
+        let columns = layer.width.div_ceil(CHUNK_SIZE);
+
+        self.shader.bind();
+        self.shader.set_mat4("u_ViewProjection", view_projection);
+        self.shader.set_mat4("u_Transform", &transform);
+        self.shader
+            .set_float4("u_Tint", Vec4::new(1.0, 1.0, 1.0, layer.opacity));
+        state::enable(gl::BLEND);
+        state::set_blend_func(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        for index in 0..layer.chunks.len() {
+            let (column, row) = (index as u32 % columns, index as u32 / columns);
+            let left = column as f32 * chunk_width;
+            let top = layer_height - row as f32 * chunk_height;
+            if left > visible.1.x
+                || left + chunk_width + reach.0 < visible.0.x
+                || top - chunk_height > visible.1.y
+                || top + reach.1 < visible.0.y
+            {
+                continue;
+            }
+
+            if layer.chunks[index].is_dirty {
+                let meshes = build_chunk(layer, column, row, *tile_size, tilesets);
+                layer.chunks[index] = Chunk {
+                    meshes,
+                    is_dirty: false,
+                };
+            }
+
+            for mesh in layer.chunks[index].meshes.iter() {
+                tilesets[mesh.tileset].atlas.get_texture().bind(0);
+                mesh.vertex_array.bind();
+                unsafe {
+                    gl::DrawElements(
+                        gl::TRIANGLES,
+                        mesh.index_count as i32,
+                        gl::UNSIGNED_INT,
+                        ptr::null(),
+                    );
+                }
+                profiler::record_draw(gl::TRIANGLES, mesh.index_count as u32, 1);
+                mesh.vertex_array.unbind();
+                self.draw_calls += 1;
+            }
+            self.chunk_count += 1;
+        }
+        self.shader.unbind();
+    }
+
+    // chunks drawn since the last reset, to check the culling
+    pub fn get_chunk_count(&self) -> u32 {
+        self.chunk_count
+    }
+
+    pub fn get_draw_calls(&self) -> u32 {
+        self.draw_calls
+    }
+
+    pub fn reset_stats(&mut self) {
+        self.chunk_count = 0;
+        self.draw_calls = 0;
+    }
+}
+
+impl Default for TilemapRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn find_tileset_index(tilesets: &[Tileset], gid: u32) -> Option<usize> {
+    tilesets.iter().rposition(|tileset| tileset.contains(gid))
+}
+
+// (min, max) corners of the view in layer space
+fn get_visible_rect(model_view_projection: &Mat4) -> Option<(Vec2, Vec2)> {
+    let inverse = model_view_projection.inverse()?;
+    let mut min = Vec2::splat(f32::MAX);
+    let mut max = Vec2::splat(f32::MIN);
+    for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter() {
+        let corner = inverse.transform_point(Vec3::new(*x, *y, 0.0));
+        min = Vec2::new(min.x.min(corner.x), min.y.min(corner.y));
+        max = Vec2::new(max.x.max(corner.x), max.y.max(corner.y));
+    }
+    Some((min, max))
+}
+
+fn build_chunk(
+    layer: &TileLayer,
+    column: u32,
+    row: u32,
+    tile_size: (u32, u32),
+    tilesets: &[Tileset],
+) -> Vec<ChunkMesh> {
+    let mut geometry: Vec<(Vec<TileVertex>, Vec<u32>)> =
+        vec![(Vec::new(), Vec::new()); tilesets.len()];
+
+    for y in row * CHUNK_SIZE..((row + 1) * CHUNK_SIZE).min(layer.height) {
+        for x in column * CHUNK_SIZE..((column + 1) * CHUNK_SIZE).min(layer.width) {
+            let tile = layer.tiles[(y * layer.width + x) as usize];
+            let index = match find_tileset_index(tilesets, tile.get_gid()) {
+                Some(index) => index,
+                None => continue,
+            };
+            let tileset = &tilesets[index];
+            let tex_coords = tileset
+                .atlas
+                .get_region((tile.get_gid() - tileset.first_gid) as usize)
+                .get_tex_coords();
+            let (top_left, bottom_right) = (tex_coords[3], tex_coords[1]);
+
+            // anchored at the bottom left of the cell
+            let origin = Vec2::new(
+                (x * tile_size.0) as f32 + tileset.offset.x,
+                ((layer.height - 1 - y) * tile_size.1) as f32 - tileset.offset.y,
+            );
+            let size = Vec2::new(tileset.tile_size.0 as f32, tileset.tile_size.1 as f32);
+
+            let (vertices, indices) = &mut geometry[index];
+            let base = vertices.len() as u32;
+            // corners counter-clockwise from the bottom left, with their
+            // image position y down
+            for (corner, image) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+                .iter()
+                .zip([(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)].iter())
+            {
+                let (mut u, mut v) = *image;
+                if tile.is_flipped_vertically() {
+                    v = 1.0 - v;
+                }
+                if tile.is_flipped_horizontally() {
+                    u = 1.0 - u;
+                }
+                if tile.is_flipped_diagonally() {
+                    std::mem::swap(&mut u, &mut v);
+                }
+
+                vertices.push(TileVertex {
+                    position: Vec3::new(
+                        origin.x + corner.0 * size.x,
+                        origin.y + corner.1 * size.y,
+                        0.0,
+                    ),
+                    tex_coord: Vec2::new(
+                        top_left.x + (bottom_right.x - top_left.x) * u,
+                        top_left.y + (bottom_right.y - top_left.y) * v,
+                    ),
+                });
+            }
+            indices.extend_from_slice(&[base, base + 1, base + 2, base + 2, base + 3, base]);
+        }
+    }
+
+    geometry
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (_, indices))| !indices.is_empty())
+        .map(|(tileset, (vertices, indices))| {
+            let layout = BufferLayout::new(vec![
+                BufferElement::new("a_Position", ShaderDataType::Float3),
+                BufferElement::new("a_TexCoord", ShaderDataType::Float2),
+            ]);
+            let mut vertex_array = VertexArray::new();
+            vertex_array.add_vertex_buffer(Rc::new(VertexBuffer::from_data(&vertices, layout)));
+            vertex_array.set_index_buffer(Rc::new(IndexBuffer::new(&indices)));
+            ChunkMesh {
+                tileset,
+                vertex_array,
+                index_count: indices.len(),
+            }
+        })
+        .collect()
+}
+
+// Tiled JSON as is, TMX converted to the same structure
+fn read_tiled_file(path: &Path) -> Result<Value, String> {
+    let source =
+        fs::read_to_string(path).map_err(|e| format!("Failed to read {:?}: {}", path, e))?;
+    let is_xml = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => {
+            extension.eq_ignore_ascii_case("tmx") || extension.eq_ignore_ascii_case("tsx")
+        }
+        None => false,
+    };
+
+    if is_xml {
+        let root = parse_xml(&source).map_err(|e| format!("Failed to parse {:?}: {}", path, e))?;
+        Ok(xml_to_json(&root))
+    } else {
+        serde_json::from_str(&source).map_err(|e| format!("Failed to parse {:?}: {}", path, e))
+    }
+}
+
+fn parse_map(json: &Value, base_dir: &Path) -> Result<Tilemap, String> {
+    match json["orientation"].as_str() {
+        Some("orthogonal") | None => {}
+        Some(orientation) => return Err(format!("{} maps are not supported", orientation)),
+    }
+    if get_bool(json, "infinite", false) {
+        return Err("infinite maps are not supported".to_string());
+    }
+
+    let mut map = Tilemap::new(
+        get_u32(json, "width")?,
+        get_u32(json, "height")?,
+        (get_u32(json, "tilewidth")?, get_u32(json, "tileheight")?),
+    );
+    map.properties = parse_properties(&json["properties"]);
+
+    for tileset in json["tilesets"].as_array().into_iter().flatten() {
+        let first_gid = get_u32(tileset, "firstgid")?;
+        let mut tileset = match tileset["source"].as_str() {
+            Some(source) => {
+                let path = base_dir.join(source);
+                let json = read_tiled_file(&path)?;
+                let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+                parse_tileset(&json, base_dir)
+                    .map_err(|e| format!("invalid tileset {:?}: {}", path, e))?
+            }
+            None => parse_tileset(tileset, base_dir)?,
+        };
+        tileset.first_gid = first_gid;
+        map.tilesets.push(tileset);
+    }
+    map.tilesets.sort_by_key(|tileset| tileset.first_gid);
+
+    let (width, height) = (map.width, map.height);
+    parse_layers(
+        &json["layers"],
+        (width, height),
+        (Vec2::ZERO, 1.0, true),
+        &mut map.layers,
+    )?;
+    Ok(map)
+}
+
+fn parse_tileset(json: &Value, base_dir: &Path) -> Result<Tileset, String> {
+    let name = json["name"].as_str().unwrap_or_default();
+    let image = json["image"].as_str().ok_or_else(|| {
+        format!(
+            "tileset {} has no single image, image collections are not supported",
+            name
+        )
+    })?;
+    let texture = Texture2D::from_file(
+        base_dir.join(image),
+        ColorSpace::Srgb,
+        SamplerSettings::nearest(),
+    )?;
+
+    let mut tileset = Tileset::new(
+        name,
+        Rc::new(texture),
+        (get_u32(json, "tilewidth")?, get_u32(json, "tileheight")?),
+        get_u32(json, "spacing").unwrap_or(0),
+        get_u32(json, "margin").unwrap_or(0),
+    );
+    if let Some(tile_count) = json["tilecount"].as_u64() {
+        tileset.tile_count = tileset.tile_count.min(tile_count as u32);
+    }
+    tileset.offset = Vec2::new(
+        get_f32(&json["tileoffset"], "x", 0.0),
+        get_f32(&json["tileoffset"], "y", 0.0),
+    );
+    tileset.properties = parse_properties(&json["properties"]);
+
+    for tile in json["tiles"].as_array().into_iter().flatten() {
+        let id = get_u32(tile, "id")?;
+        if id >= tileset.tile_count {
+            log_warn!("Tileset {} has data for missing tile {}", name, id);
+            continue;
+        }
+        // `type` before Tiled 1.9
+        let class = tile["class"]
+            .as_str()
+            .or_else(|| tile["type"].as_str())
+            .unwrap_or_default();
+        tileset.set_tile_data(
+            id,
+            TileData {
+                class: class.to_string(),
+                properties: parse_properties(&tile["properties"]),
+            },
+        );
+    }
+    Ok(tileset)
+}
+
+// flattens groups, their offset, opacity and visibility carry over to the
+// layers inside
+fn parse_layers(
+    json: &Value,
+    size: (u32, u32),
+    (offset, opacity, is_visible): (Vec2, f32, bool),
+    layers: &mut Vec<MapLayer>,
+) -> Result<(), String> {
+    for layer in json.as_array().into_iter().flatten() {
+        let name = layer["name"].as_str().unwrap_or_default();
+        let offset = offset
+            + Vec2::new(
+                get_f32(layer, "offsetx", 0.0),
+                get_f32(layer, "offsety", 0.0),
+            );
+        let opacity = opacity * get_f32(layer, "opacity", 1.0);
+        let is_visible = is_visible && get_bool(layer, "visible", true);
+        let properties = parse_properties(&layer["properties"]);
+
+        match layer["type"].as_str().unwrap_or_default() {
+            "tilelayer" => {
+                let (width, height) = (
+                    get_u32(layer, "width").unwrap_or(size.0),
+                    get_u32(layer, "height").unwrap_or(size.1),
+                );
+                let tiles = parse_tile_data(layer, (width * height) as usize)
+                    .map_err(|e| format!("layer {}: {}", name, e))?;
+                let mut tile_layer = TileLayer::from_tiles(name, width, height, tiles);
+                tile_layer.is_visible = is_visible;
+                tile_layer.opacity = opacity;
+                tile_layer.offset = offset;
+                tile_layer.properties = properties;
+                layers.push(MapLayer::Tiles(tile_layer));
+            }
+            "objectgroup" => {
+                let objects = layer["objects"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|object| parse_object(object, offset))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| format!("layer {}: {}", name, e))?;
+                layers.push(MapLayer::Objects(ObjectLayer {
+                    name: name.to_string(),
+                    objects,
+                    is_visible,
+                    opacity,
+                    offset,
+                    properties,
+                }));
+            }
+            "group" => parse_layers(
+                &layer["layers"],
+                size,
+                (offset, opacity, is_visible),
+                layers,
+            )?,
+            other => {
+                log_warn!(
+                    "Skipping {} layer {}, the type is not supported",
+                    other,
+                    name
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn parse_tile_data(layer: &Value, count: usize) -> Result<Vec<Tile>, String> {
+    let gids = match (&layer["data"], layer["encoding"].as_str()) {
+        (Value::Array(data), _) => data
+            .iter()
+            .map(|gid| {
+                gid.as_u64()
+                    .map(|gid| gid as u32)
+                    .ok_or_else(|| "invalid tile".to_string())
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        (Value::String(data), Some("base64")) => {
+            let bytes =
+                base64::decode(data.trim()).map_err(|e| format!("invalid base64 data: {}", e))?;
+            let bytes = match layer["compression"].as_str().unwrap_or_default() {
+                "" => bytes,
+                "zlib" => miniz_oxide::inflate::decompress_to_vec_zlib(&bytes)
+                    .map_err(|e| format!("invalid zlib data: {:?}", e))?,
+                "gzip" => gunzip(&bytes)?,
+                other => return Err(format!("{} compression is not supported", other)),
+            };
+            bytes
+                .chunks_exact(4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect()
+        }
+        _ => return Err("no tile data".to_string()),
+    };
+
+    if gids.len() != count {
+        return Err(format!("expected {} tiles, found {}", count, gids.len()));
+    }
+    Ok(gids.into_iter().map(Tile::from_raw).collect())
+}
+
+fn parse_object(json: &Value, offset: Vec2) -> Result<MapObject, String> {
+    let points = |key: &str| -> Vec<Vec2> {
+        json[key]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|point| Vec2::new(get_f32(point, "x", 0.0), get_f32(point, "y", 0.0)))
+            .collect()
+    };
+
+    let shape = if let Some(gid) = json["gid"].as_u64() {
+        ObjectShape::Tile(Tile::from_raw(gid as u32))
+    } else if get_bool(json, "point", false) {
+        ObjectShape::Point
+    } else if get_bool(json, "ellipse", false) {
+        ObjectShape::Ellipse
+    } else if json["polygon"].is_array() {
+        ObjectShape::Polygon(points("polygon"))
+    } else if json["polyline"].is_array() {
+        ObjectShape::Polyline(points("polyline"))
+    } else if json["text"].is_object() {
+        ObjectShape::Text(
+            json["text"]["text"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+        )
+    } else {
+        ObjectShape::Rectangle
+    };
+
+    Ok(MapObject {
+        id: get_u32(json, "id")?,
+        name: json["name"].as_str().unwrap_or_default().to_string(),
+        class: json["class"]
+            .as_str()
+            .or_else(|| json["type"].as_str())
+            .unwrap_or_default()
+            .to_string(),
+        position: offset + Vec2::new(get_f32(json, "x", 0.0), get_f32(json, "y", 0.0)),
+        size: Vec2::new(get_f32(json, "width", 0.0), get_f32(json, "height", 0.0)),
+        rotation: get_f32(json, "rotation", 0.0),
+        is_visible: get_bool(json, "visible", true),
+        shape,
+        properties: parse_properties(&json["properties"]),
+    })
+}
+
+fn parse_properties(json: &Value) -> Properties {
+    let mut properties = Properties::new();
+    for property in json.as_array().into_iter().flatten() {
+        let name = match property["name"].as_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        let value = &property["value"];
+        let parsed = match property["type"].as_str().unwrap_or("string") {
+            "int" => value.as_i64().map(PropertyValue::Int),
+            "float" => value.as_f64().map(PropertyValue::Float),
+            "bool" => value.as_bool().map(PropertyValue::Bool),
+            "color" => value
+                .as_str()
+                .and_then(parse_color)
+                .map(PropertyValue::Color),
+            "file" => value
+                .as_str()
+                .map(|path| PropertyValue::File(path.to_string())),
+            "object" => value.as_u64().map(|id| PropertyValue::Object(id as u32)),
+            "string" => value
+                .as_str()
+                .map(|value| PropertyValue::String(value.to_string())),
+            _ => None,
+        };
+        match parsed {
+            Some(parsed) => {
+                properties.insert(name.to_string(), parsed);
+            }
+            None => {
+                log_warn!(
+                    "Skipping property {}, the type or value is not supported",
+                    name
+                );
+            }
+        }
+    }
+    properties
+}
+
+// #AARRGGBB or #RRGGBB, empty for transparent black
+fn parse_color(color: &str) -> Option<Vec4> {
+    let hex = color.trim_start_matches('#');
+    if hex.is_empty() {
+        return Some(Vec4::ZERO);
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    let channel = |shift: u32| ((value >> shift) & 0xff) as f32 / 255.0;
+    match hex.len() {
+        6 => Some(Vec4::new(channel(16), channel(8), channel(0), 1.0)),
+        8 => Some(Vec4::new(channel(16), channel(8), channel(0), channel(24))),
+        _ => None,
+    }
+}
+
+fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    const FHCRC: u8 = 2;
+    const FEXTRA: u8 = 4;
+    const FNAME: u8 = 8;
+    const FCOMMENT: u8 = 16;
+
+    if bytes.len() < 18 || bytes[0] != 0x1f || bytes[1] != 0x8b || bytes[2] != 8 {
+        return Err("invalid gzip data".to_string());
+    }
+    let flags = bytes[3];
+    let mut start = 10;
+    if flags & FEXTRA != 0 {
+        start += 2 + u16::from_le_bytes([bytes[start], bytes[start + 1]]) as usize;
+    }
+    for flag in [FNAME, FCOMMENT].iter() {
+        if flags & flag != 0 {
+            start += bytes[start.min(bytes.len())..]
+                .iter()
+                .position(|b| *b == 0)
+                .unwrap_or(0)
+                + 1;
+        }
+    }
+    if flags & FHCRC != 0 {
+        start += 2;
+    }
+
+    let deflated = bytes
+        .get(start..)
+        .ok_or_else(|| "invalid gzip data".to_string())?;
+    miniz_oxide::inflate::decompress_to_vec(deflated)
+        .map_err(|e| format!("invalid gzip data: {:?}", e))
+}
+
+fn get_u32(json: &Value, key: &str) -> Result<u32, String> {
+    json[key]
+        .as_u64()
+        .map(|value| value as u32)
+        .ok_or_else(|| format!("missing or invalid {}", key))
+}
+
+fn get_f32(json: &Value, key: &str, default: f32) -> f32 {
+    json[key]
+        .as_f64()
+        .map(|value| value as f32)
+        .unwrap_or(default)
+}
+
+// TMX stores booleans as 0 and 1
+fn get_bool(json: &Value, key: &str, default: bool) -> bool {
+    match &json[key] {
+        Value::Bool(value) => *value,
+        Value::Number(value) => value.as_f64() != Some(0.0),
+        _ => default,
+    }
+}
+
+struct XmlElement {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<XmlElement>,
+    text: String,
+}
+
+fn parse_xml(source: &str) -> Result<XmlElement, String> {
+    let mut stack: Vec<XmlElement> = Vec::new();
+    for event in EventReader::from_str(source) {
+        match event.map_err(|e| e.to_string())? {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } => stack.push(XmlElement {
+                name: name.local_name,
+                attributes: attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.name.local_name, attribute.value))
+                    .collect(),
+                children: Vec::new(),
+                text: String::new(),
+            }),
+            XmlEvent::EndElement { .. } => {
+                let element = stack
+                    .pop()
+                    .ok_or_else(|| "unbalanced elements".to_string())?;
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element),
+                }
+            }
+            XmlEvent::Characters(text) | XmlEvent::CData(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err("no root element".to_string())
+}
+
+// Converts TMX and TSX elements to their Tiled JSON counterparts, as far as
+// the parser above reads them.
+fn xml_to_json(element: &XmlElement) -> Value {
+    let mut object: Map<String, Value> = element
+        .attributes
+        .iter()
+        .map(|(key, value)| (key.clone(), attribute_to_json(key, value)))
+        .collect();
+
+    let mut push = |key: &str, value: Value| {
+        object
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Array(Vec::new()))
+            .as_array_mut()
+            .unwrap()
+            .push(value)
+    };
+    let mut fields = Vec::new();
+    for child in element.children.iter() {
+        match child.name.as_str() {
+            "properties" => {
+                for property in child.children.iter() {
+                    push("properties", property_to_json(property));
+                }
+            }
+            "tileset" => push("tilesets", xml_to_json(child)),
+            "tile" => push("tiles", xml_to_json(child)),
+            "object" => push("objects", xml_to_json(child)),
+            "layer" | "objectgroup" | "imagelayer" | "group" => {
+                let mut layer = xml_to_json(child);
+                let kind = match child.name.as_str() {
+                    "layer" => "tilelayer",
+                    name => name,
+                };
+                layer["type"] = Value::from(kind);
+                push("layers", layer);
+            }
+            "image" => {
+                let image = xml_to_json(child);
+                fields.push(("image".to_string(), image["source"].clone()));
+                fields.push(("imagewidth".to_string(), image["width"].clone()));
+                fields.push(("imageheight".to_string(), image["height"].clone()));
+            }
+            "tileoffset" => fields.push(("tileoffset".to_string(), xml_to_json(child))),
+            "data" => fields.extend(data_to_json(child)),
+            "ellipse" | "point" => fields.push((child.name.clone(), Value::Bool(true))),
+            "polygon" | "polyline" => {
+                let points = child
+                    .attributes
+                    .iter()
+                    .find(|(key, _)| key == "points")
+                    .map(|(_, points)| points.as_str())
+                    .unwrap_or_default()
+                    .split_whitespace()
+                    .filter_map(|point| {
+                        let (x, y) = point.split_once(',')?;
+                        Some(serde_json::json!({
+                            "x": x.parse::<f64>().ok()?,
+                            "y": y.parse::<f64>().ok()?,
+                        }))
+                    })
+                    .collect();
+                fields.push((child.name.clone(), Value::Array(points)));
+            }
+            "text" => fields.push((
+                "text".to_string(),
+                serde_json::json!({ "text": child.text }),
+            )),
+            _ => {}
+        }
+    }
+    object.extend(fields);
+    Value::Object(object)
+}
+
+fn attribute_to_json(key: &str, value: &str) -> Value {
+    if STRING_ATTRIBUTES.contains(&key) {
+        return Value::from(value);
+    }
+    if let Ok(number) = value.parse::<u64>() {
+        Value::from(number)
+    } else if let Ok(number) = value.parse::<i64>() {
+        Value::from(number)
+    } else if let Ok(number) = value.parse::<f64>() {
+        Value::from(number)
+    } else {
+        Value::from(value)
+    }
+}
+
+fn property_to_json(property: &XmlElement) -> Value {
+    let attribute = |key: &str| {
+        property
+            .attributes
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    };
+    let kind = attribute("type").unwrap_or("string");
+    // multiline strings are stored as text
+    let text = attribute("value").unwrap_or(&property.text);
+
+    let value = match kind {
+        "int" | "object" => text.parse::<i64>().map(Value::from).unwrap_or(Value::Null),
+        "float" => text.parse::<f64>().map(Value::from).unwrap_or(Value::Null),
+        "bool" => Value::Bool(text == "true"),
+        _ => Value::from(text),
+    };
+    serde_json::json!({
+        "name": attribute("name").unwrap_or_default(),
+        "type": kind,
+        "value": value,
+    })
+}
+
+// the layer fields of the JSON format for a `data` element
+fn data_to_json(data: &XmlElement) -> Vec<(String, Value)> {
+    let attribute = |key: &str| {
+        data.attributes
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.clone())
+    };
+
+    match attribute("encoding").as_deref() {
+        Some("csv") => {
+            let gids = data
+                .text
+                .split(',')
+                .filter_map(|gid| gid.trim().parse::<u64>().ok())
+                .map(Value::from)
+                .collect();
+            vec![("data".to_string(), Value::Array(gids))]
+        }
+        Some("base64") => vec![
+            ("data".to_string(), Value::from(data.text.trim())),
+            ("encoding".to_string(), Value::from("base64")),
+            (
+                "compression".to_string(),
+                Value::from(attribute("compression").unwrap_or_default()),
+            ),
+        ],
+        _ => {
+            let gids = data
+                .children
+                .iter()
+                .map(|tile| {
+                    let gid = tile
+                        .attributes
+                        .iter()
+                        .find(|(name, _)| name == "gid")
+                        .and_then(|(_, gid)| gid.parse::<u64>().ok())
+                        .unwrap_or(0);
+                    Value::from(gid)
+                })
+                .collect();
+            vec![("data".to_string(), Value::Array(gids))]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // gids 1, 2, 3 and 1 flipped horizontally, little endian
+    const BASE64: &str = "AQAAAAIAAAADAAAAAQAAgA==";
+    const BASE64_ZLIB: &str = "eJxjZGBgYAJiZiBmZGBoAAAA1ACI";
+    // with a file name in the header
+    const BASE64_GZIP: &str = "H4sICAAAAAAC/2xheWVyLmJpbgBjZGBgYAJiZiBmZGBoAAD9p2N1EAAAAA==";
+
+    const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+        <map version="1.10" orientation="orthogonal" renderorder="right-down"
+             width="2" height="2" tilewidth="16" tileheight="16" infinite="0">
+            <properties>
+                <property name="music" value="cave.ogg"/>
+                <property name="gravity" type="float" value="9.5"/>
+                <property name="level" type="int" value="3"/>
+                <property name="dark" type="bool" value="true"/>
+                <property name="intro" type="string">line one
+line two</property>
+            </properties>
+            <layer id="1" name="ground" width="2" height="2">
+                <data encoding="csv">
+                    1,2,
+                    3,2147483649
+                </data>
+            </layer>
+            <group name="details" offsetx="4" opacity="0.5">
+                <layer id="2" name="decals" width="2" height="2" visible="0">
+                    <data encoding="base64" compression="zlib">
+                        eJxjZGBgYAJiZiBmZGBoAAAA1ACI
+                    </data>
+                </layer>
+                <objectgroup id="3" name="spawns" offsety="-2">
+                    <object id="1" name="player" type="spawn" x="8" y="24"/>
+                    <object id="2" name="zone" x="0" y="0" width="32" height="16">
+                        <ellipse/>
+                    </object>
+                    <object id="3" name="path" x="1" y="2">
+                        <polyline points="0,0 16,0 16,-8.5"/>
+                    </object>
+                    <object id="4" name="sign" x="0" y="0">
+                        <text wrap="1">Hello</text>
+                    </object>
+                </objectgroup>
+            </group>
+        </map>"#;
+
+    fn element(name: &str, attributes: &[(&str, &str)], text: &str) -> XmlElement {
+        XmlElement {
+            name: name.to_string(),
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            children: Vec::new(),
+            text: text.to_string(),
+        }
+    }
+
+    fn gids(tiles: &[Tile]) -> Vec<u32> {
+        tiles.iter().map(|tile| tile.get_raw()).collect()
+    }
+
+    #[test]
+    fn xml_elements_nest() {
+        let root = parse_xml("<a x=\"1\"><b>text</b><c/><![CDATA[<raw>]]></a>").unwrap();
+        assert_eq!(root.name, "a");
+        assert_eq!(root.attributes, vec![("x".to_string(), "1".to_string())]);
+        assert_eq!(root.children.len(), 2);
+        assert_eq!(root.children[0].name, "b");
+        assert_eq!(root.children[0].text, "text");
+        assert_eq!(root.text, "<raw>");
+
+        assert!(parse_xml("<a><b></a>").is_err());
+        assert!(parse_xml("").is_err());
+    }
+
+    #[test]
+    fn attributes_become_numbers_unless_they_are_strings() {
+        let json = xml_to_json(&element(
+            "map",
+            &[
+                ("width", "10"),
+                ("offsetx", "-4"),
+                ("opacity", "0.5"),
+                ("version", "1.10"),
+                ("name", "42"),
+                ("class", "door"),
+            ],
+            "",
+        ));
+        assert_eq!(json["width"], Value::from(10));
+        assert_eq!(json["offsetx"], Value::from(-4));
+        assert_eq!(json["opacity"], Value::from(0.5));
+        assert_eq!(json["version"], Value::from("1.10"));
+        assert_eq!(json["name"], Value::from("42"));
+        assert_eq!(json["class"], Value::from("door"));
+    }
+
+    #[test]
+    fn csv_data_becomes_a_gid_array() {
+        let data = element("data", &[("encoding", "csv")], "\n1,2,\n3,2147483649\n");
+        assert_eq!(
+            data_to_json(&data),
+            vec![(
+                "data".to_string(),
+                serde_json::json!([1, 2, 3, 2147483649u64])
+            )]
+        );
+    }
+
+    #[test]
+    fn xml_tile_data_becomes_a_gid_array() {
+        let mut data = element("data", &[], "");
+        data.children = vec![
+            element("tile", &[("gid", "5")], ""),
+            element("tile", &[], ""),
+            element("tile", &[("gid", "7")], ""),
+        ];
+        assert_eq!(
+            data_to_json(&data),
+            vec![("data".to_string(), serde_json::json!([5, 0, 7]))]
+        );
+    }
+
+    #[test]
+    fn base64_data_keeps_its_encoding() {
+        let data = element(
+            "data",
+            &[("encoding", "base64"), ("compression", "zlib")],
+            "\n   eJxjZGBgYAJiZiBmZGBoAAAA1ACI\n",
+        );
+        let json: Map<String, Value> = data_to_json(&data).into_iter().collect();
+        assert_eq!(json["data"], Value::from(BASE64_ZLIB));
+        assert_eq!(json["encoding"], Value::from("base64"));
+        assert_eq!(json["compression"], Value::from("zlib"));
+    }
+
+    #[test]
+    fn tile_data_decodes_every_compression() {
+        let expected = vec![1, 2, 3, 0x8000_0001];
+        for (data, compression) in
+            [(BASE64, ""), (BASE64_ZLIB, "zlib"), (BASE64_GZIP, "gzip")].iter()
+        {
+            let layer = serde_json::json!({
+                "data": data,
+                "encoding": "base64",
+                "compression": compression,
+            });
+            let tiles = parse_tile_data(&layer, 4).unwrap();
+            assert_eq!(gids(&tiles), expected, "{} compression", compression);
+        }
+
+        let tiles = parse_tile_data(
+            &serde_json::json!({ "data": BASE64, "encoding": "base64" }),
+            4,
+        )
+        .unwrap();
+        assert!(tiles[3].is_flipped_horizontally());
+        assert_eq!(tiles[3].get_gid(), 1);
+    }
+
+    #[test]
+    fn tile_data_errors() {
+        let layer = serde_json::json!({ "data": [1, 2, 3] });
+        assert!(parse_tile_data(&layer, 4).is_err());
+
+        let layer = serde_json::json!({
+            "data": BASE64,
+            "encoding": "base64",
+            "compression": "zstd",
+        });
+        assert!(parse_tile_data(&layer, 4).is_err());
+
+        let layer = serde_json::json!({
+            "data": BASE64,
+            "encoding": "base64",
+            "compression": "gzip",
+        });
+        assert!(parse_tile_data(&layer, 4).is_err());
+
+        assert!(parse_tile_data(&serde_json::json!({}), 4).is_err());
+    }
+
+    #[test]
+    fn tmx_maps_convert_to_tiled_json() {
+        let json = xml_to_json(&parse_xml(MAP).unwrap());
+        assert_eq!(json["orientation"], Value::from("orthogonal"));
+        assert_eq!(json["width"], Value::from(2));
+        assert_eq!(json["infinite"], Value::from(0));
+
+        let layers = json["layers"].as_array().unwrap();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers[0]["type"], Value::from("tilelayer"));
+        assert_eq!(
+            layers[0]["data"],
+            serde_json::json!([1, 2, 3, 2147483649u64])
+        );
+        assert_eq!(layers[1]["type"], Value::from("group"));
+        assert_eq!(layers[1]["layers"][1]["type"], Value::from("objectgroup"));
+
+        let property = &json["properties"][1];
+        assert_eq!(property["name"], Value::from("gravity"));
+        assert_eq!(property["type"], Value::from("float"));
+        assert_eq!(property["value"], Value::from(9.5));
+        assert_eq!(
+            json["properties"][4]["value"],
+            Value::from("line one\nline two")
+        );
+    }
+
+    #[test]
+    fn tmx_maps_parse_without_tilesets() {
+        let json = xml_to_json(&parse_xml(MAP).unwrap());
+        let map = parse_map(&json, Path::new("")).unwrap();
+        assert_eq!(map.get_size(), (2, 2));
+        assert_eq!(map.get_tile_size(), (16, 16));
+
+        let properties = map.get_properties();
+        assert_eq!(
+            properties["music"],
+            PropertyValue::String("cave.ogg".to_string())
+        );
+        assert_eq!(properties["gravity"], PropertyValue::Float(9.5));
+        assert_eq!(properties["level"], PropertyValue::Int(3));
+        assert_eq!(properties["dark"], PropertyValue::Bool(true));
+
+        let names: Vec<&str> = map
+            .get_layers()
+            .iter()
+            .map(|layer| layer.get_name())
+            .collect();
+        assert_eq!(names, vec!["ground", "decals", "spawns"]);
+
+        let ground = map.get_tile_layer("ground").unwrap();
+        assert_eq!(gids(ground.get_tiles()), vec![1, 2, 3, 0x8000_0001]);
+
+        // group offset, opacity and visibility carry over
+        let decals = map.get_tile_layer("decals").unwrap();
+        assert_eq!(gids(decals.get_tiles()), gids(ground.get_tiles()));
+        assert!(!decals.is_visible());
+        assert_eq!(decals.get_opacity(), 0.5);
+        assert_eq!(decals.get_offset(), Vec2::new(4.0, 0.0));
+
+        let spawns = map.get_object_layer("spawns").unwrap();
+        assert_eq!(spawns.offset, Vec2::new(4.0, -2.0));
+        let player = spawns.find_object("player").unwrap();
+        assert_eq!(player.class, "spawn");
+        assert_eq!(player.position, Vec2::new(12.0, 22.0));
+        assert_eq!(player.shape, ObjectShape::Rectangle);
+        assert_eq!(
+            spawns.find_object("zone").unwrap().shape,
+            ObjectShape::Ellipse
+        );
+        assert_eq!(
+            spawns.find_object("path").unwrap().shape,
+            ObjectShape::Polyline(vec![
+                Vec2::new(0.0, 0.0),
+                Vec2::new(16.0, 0.0),
+                Vec2::new(16.0, -8.5),
+            ])
+        );
+        assert_eq!(
+            spawns.find_object("sign").unwrap().shape,
+            ObjectShape::Text("Hello".to_string())
+        );
+    }
+
+    #[test]
+    fn colors_parse_with_and_without_alpha() {
+        assert_eq!(parse_color("#ff0000"), Some(Vec4::new(1.0, 0.0, 0.0, 1.0)));
+        assert_eq!(
+            parse_color("#00ff0000"),
+            Some(Vec4::new(1.0, 0.0, 0.0, 0.0))
+        );
+        assert_eq!(parse_color(""), Some(Vec4::ZERO));
+        assert_eq!(parse_color("#fff"), None);
+    }
+}
@@ -1,4 +1,4 @@
-use crate::events::Event;
+use crate::{events::Event, renderer::context::ContextConfig};
 
 #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
 pub mod glfw;
@@ -7,6 +7,7 @@ pub struct WindowProps {
     pub title: String,
     pub width: u32,
     pub height: u32,
+    pub context: ContextConfig,
 }
 
 pub trait Window {
@@ -17,6 +18,8 @@ pub trait Window {
     fn get_title(&self) -> &str;
     fn get_width(&self) -> u32;
     fn get_height(&self) -> u32;
+    // in pixels, differs from the window size on high DPI displays
+    fn get_framebuffer_size(&self) -> (u32, u32);
 }
 
 #[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
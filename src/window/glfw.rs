@@ -2,7 +2,7 @@ use std::sync::mpsc::Receiver;
 
 use crate::{
     events::{Event, Key, MouseButton},
-    renderer::context::RenderingContext,
+    renderer::context::{ContextConfig, GlProfile, RenderingContext},
     window::{Window, WindowProps},
 };
 use glfw;
@@ -16,7 +16,9 @@ pub struct GLFWWindow {
 
 impl Window for GLFWWindow {
     fn new(props: WindowProps) -> Self {
-        let glfw = glfw::init(glfw::FAIL_ON_ERRORS).expect("Failed to initialize GLFW.");
+        props.context.validate();
+        let mut glfw = glfw::init(glfw::FAIL_ON_ERRORS).expect("Failed to initialize GLFW.");
+        apply_context_hints(&mut glfw, &props.context);
 
         let (mut window, event_receiver) = glfw
             .create_window(
@@ -28,6 +30,7 @@ impl Window for GLFWWindow {
             .expect("Failed to create GLFW window.");
 
         window.set_all_polling(true);
+        window.set_framebuffer_size_polling(true);
 
         GLFWWindow {
             glfw,
@@ -49,6 +52,11 @@ impl Window for GLFWWindow {
         self.props.height
     }
 
+    fn get_framebuffer_size(&self) -> (u32, u32) {
+        let (width, height) = self.window.get_framebuffer_size();
+        (width.max(0) as u32, height.max(0) as u32)
+    }
+
     fn on_update(&mut self, event_queue: &mut Vec<Event>) {
         if self.window.should_close() {
             event_queue.push(Event::WindowClose);
@@ -65,48 +73,51 @@ impl Window for GLFWWindow {
                     y: y as u32,
                 }),
 
-                glfw::WindowEvent::Size(width, height) => Some(Event::WindowResize {
-                    width: width as u32,
-                    height: height as u32,
-                }),
+                glfw::WindowEvent::Size(width, height) => {
+                    self.props.width = width as u32;
+                    self.props.height = height as u32;
+                    Some(Event::WindowResize {
+                        width: width as u32,
+                        height: height as u32,
+                    })
+                }
+
+                glfw::WindowEvent::FramebufferSize(width, height) => {
+                    Some(Event::FramebufferResize {
+                        width: width.max(0) as u32,
+                        height: height.max(0) as u32,
+                    })
+                }
 
                 glfw::WindowEvent::Close => Some(Event::WindowClose),
 
                 glfw::WindowEvent::Focus(true) => Some(Event::WindowFocus),
                 glfw::WindowEvent::Focus(false) => Some(Event::WindowLostFocus),
 
-                glfw::WindowEvent::CursorPos(x, y) => Some(Event::MouseMoved {
-                    x: x as u64,
-                    y: y as u64,
-                }),
+                glfw::WindowEvent::CursorPos(x, y) => Some(Event::MouseMoved { x, y }),
 
-                glfw::WindowEvent::Scroll(x, y) => Some(Event::MouseScrolled {
-                    x: x as u64,
-                    y: y as u64,
-                }),
+                glfw::WindowEvent::Scroll(x, y) => Some(Event::MouseScrolled { x, y }),
 
                 glfw::WindowEvent::Key(key, _, action, _) => {
                     let internal_key = map_glfw_key_to_internal(key);
-                    if let Some(ik) = internal_key {
-                        match action {
-                            glfw::Action::Press => Some(Event::KeyPressed { key: ik }),
-                            glfw::Action::Release => Some(Event::KeyReleased { key: ik }),
-                            _ => None,
-                        };
-                    };
-                    None
+                    match (internal_key, action) {
+                        (Some(ik), glfw::Action::Press) => Some(Event::KeyPressed { key: ik }),
+                        (Some(ik), glfw::Action::Release) => Some(Event::KeyReleased { key: ik }),
+                        _ => None,
+                    }
                 }
 
                 glfw::WindowEvent::MouseButton(btn, action, _) => {
                     let internal_btn = map_glfw_btn_to_internal(btn);
-                    if let Some(ib) = internal_btn {
-                        match action {
-                            glfw::Action::Press => Some(Event::MouseButtonPressed { btn: ib }),
-                            glfw::Action::Release => Some(Event::MouseButtonReleased { btn: ib }),
-                            _ => None,
-                        };
-                    };
-                    None
+                    match (internal_btn, action) {
+                        (Some(ib), glfw::Action::Press) => {
+                            Some(Event::MouseButtonPressed { btn: ib })
+                        }
+                        (Some(ib), glfw::Action::Release) => {
+                            Some(Event::MouseButtonReleased { btn: ib })
+                        }
+                        _ => None,
+                    }
                 }
 
                 _ => None,
@@ -119,6 +130,35 @@ impl Window for GLFWWindow {
     }
 }
 
+impl GLFWWindow {
+    pub fn get_context_config(&self) -> &ContextConfig {
+        &self.props.context
+    }
+}
+
+fn apply_context_hints(glfw: &mut glfw::Glfw, config: &ContextConfig) {
+    let (major, minor) = config.version;
+    glfw.window_hint(glfw::WindowHint::ContextVersion(major, minor));
+
+    let profile = match config.profile {
+        GlProfile::Core => glfw::OpenGlProfileHint::Core,
+        GlProfile::Compatibility => glfw::OpenGlProfileHint::Compat,
+        GlProfile::Any => glfw::OpenGlProfileHint::Any,
+    };
+    glfw.window_hint(glfw::WindowHint::OpenGlProfile(profile));
+    glfw.window_hint(glfw::WindowHint::OpenGlForwardCompat(
+        config.forward_compatible,
+    ));
+
+    glfw.window_hint(glfw::WindowHint::Samples(Some(config.samples)));
+    glfw.window_hint(glfw::WindowHint::SRgbCapable(config.srgb));
+    glfw.window_hint(glfw::WindowHint::DepthBits(Some(config.depth_bits)));
+    glfw.window_hint(glfw::WindowHint::StencilBits(Some(config.stencil_bits)));
+
+    // lets the driver report errors and warnings through the debug output
+    glfw.window_hint(glfw::WindowHint::OpenGlDebugContext(config.debug));
+}
+
 fn map_glfw_key_to_internal(key: glfw::Key) -> Option<Key> {
     match key {
         glfw::Key::Space => Some(Key::Space),